@@ -0,0 +1,155 @@
+//! Compares choosing an agent's action by allocating the scores and
+//! probabilities for every agent against reusing a [ScoreScratch], with 50
+//! beliefs and 20 behaviours.
+//!
+//! The number of allocations made by each is counted and printed before
+//! timing.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use belief_spread::{
+    Agent, AgentPtr, BasicAgent, BasicBehaviour, BasicBelief, BehaviourPtr, BeliefPtr,
+};
+use concept::{
+    performance_relationships::{PerformanceRelationships, PrsMatrix},
+    scoring::{compute_behaviour_scores_dense, compute_behaviour_scores_into, ScoreScratch},
+    selection::{ActionSelection, LinearSelection},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const N_BELIEFS: usize = 50;
+const N_BEHAVIOURS: usize = 20;
+const N_CALLS: usize = 1000;
+
+fn setup() -> (AgentPtr, Vec<BeliefPtr>, Vec<BehaviourPtr>, PrsMatrix) {
+    let beliefs: Vec<BeliefPtr> = (0..N_BELIEFS)
+        .map(|i| BasicBelief::new(format!("belief {i}")).into())
+        .collect();
+    let behaviours: Vec<BehaviourPtr> = (0..N_BEHAVIOURS)
+        .map(|i| BasicBehaviour::new(format!("behaviour {i}")).into())
+        .collect();
+    let prs: PerformanceRelationships = beliefs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, belief)| {
+            behaviours.iter().enumerate().map(move |(j, behaviour)| {
+                (
+                    (*belief.borrow().uuid(), *behaviour.borrow().uuid()),
+                    ((i * 7 + j * 3) % 11) as f64 / 5.5 - 1.0,
+                )
+            })
+        })
+        .collect();
+    let matrix = PrsMatrix::new(&prs, &beliefs, &behaviours);
+
+    let mut agent = BasicAgent::new();
+    for (i, belief) in beliefs.iter().enumerate() {
+        agent
+            .set_activation(1, belief.clone(), Some((i % 10) as f64 / 10.0))
+            .unwrap();
+    }
+    (agent.into(), beliefs, behaviours, matrix)
+}
+
+fn allocating(
+    agent: &AgentPtr,
+    beliefs: &[BeliefPtr],
+    behaviours: &[BehaviourPtr],
+    prs: &PrsMatrix,
+    rng: &mut ChaCha8Rng,
+) -> Option<BehaviourPtr> {
+    let scores = compute_behaviour_scores_dense(agent, 1, beliefs, behaviours, prs);
+    let values: Vec<f64> = scores.iter().map(|(_, v)| *v).collect();
+    LinearSelection
+        .select(agent, 1, &values, rng)
+        .map(|i| scores[i].0.clone())
+}
+
+fn reusing(
+    agent: &AgentPtr,
+    beliefs: &[BeliefPtr],
+    behaviours: &[BehaviourPtr],
+    prs: &PrsMatrix,
+    rng: &mut ChaCha8Rng,
+    scratch: &mut ScoreScratch,
+) -> Option<BehaviourPtr> {
+    compute_behaviour_scores_into(agent, 1, beliefs, behaviours.len(), prs, scratch);
+    LinearSelection
+        .select(agent, 1, &scratch.scores, rng)
+        .map(|i| behaviours[i].clone())
+}
+
+fn count_allocations(mut f: impl FnMut()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..N_CALLS {
+        f();
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_actions(c: &mut Criterion) {
+    let (agent, beliefs, behaviours, prs) = setup();
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut scratch = ScoreScratch::default();
+
+    let allocations = count_allocations(|| {
+        black_box(allocating(&agent, &beliefs, &behaviours, &prs, &mut rng));
+    });
+    println!(
+        "allocating: {} allocations per agent",
+        allocations / N_CALLS
+    );
+    let allocations = count_allocations(|| {
+        black_box(reusing(
+            &agent,
+            &beliefs,
+            &behaviours,
+            &prs,
+            &mut rng,
+            &mut scratch,
+        ));
+    });
+    println!("reusing: {} allocations per agent", allocations / N_CALLS);
+
+    c.bench_function("choose action allocating 50x20", |b| {
+        b.iter(|| black_box(allocating(&agent, &beliefs, &behaviours, &prs, &mut rng)))
+    });
+    c.bench_function("choose action reusing scratch 50x20", |b| {
+        b.iter(|| {
+            black_box(reusing(
+                &agent,
+                &beliefs,
+                &behaviours,
+                &prs,
+                &mut rng,
+                &mut scratch,
+            ))
+        })
+    });
+}
+
+criterion_group!(benches, bench_actions);
+criterion_main!(benches);
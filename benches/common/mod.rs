@@ -0,0 +1,143 @@
+//! Seeded synthetic populations shared by the benchmarks, so that they run
+//! without fixture files and measure the same model every time.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use belief_spread::{BehaviourPtr, BeliefPtr};
+use concept::{
+    configuration::ConfigurationBuilder,
+    json::{AgentSpec, BehaviourSpec, BeliefSpec, PerformanceRelationshipSpec},
+};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use uuid::Uuid;
+
+/// The specs of a synthetic model.
+pub struct Population {
+    pub behaviours: Vec<BehaviourSpec>,
+    pub beliefs: Vec<BeliefSpec>,
+    pub agents: Vec<AgentSpec>,
+    pub prs: Vec<PerformanceRelationshipSpec>,
+}
+
+fn uuid(rng: &mut ChaCha8Rng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.gen()).into_uuid()
+}
+
+impl Population {
+    /// Generate a population whose agents have an action, activation and
+    /// delta for every belief at time 0, and `n_friends` random friends.
+    pub fn generate(
+        n_agents: usize,
+        n_beliefs: usize,
+        n_behaviours: usize,
+        n_friends: usize,
+        seed: u64,
+    ) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        let behaviours: Vec<BehaviourSpec> = (0..n_behaviours)
+            .map(|i| BehaviourSpec {
+                name: format!("behaviour {i}"),
+                uuid: uuid(&mut rng),
+            })
+            .collect();
+        let belief_uuids: Vec<Uuid> = (0..n_beliefs).map(|_| uuid(&mut rng)).collect();
+        let beliefs: Vec<BeliefSpec> = belief_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| BeliefSpec {
+                name: format!("belief {i}"),
+                uuid,
+                perceptions: behaviours
+                    .iter()
+                    .map(|b| (b.uuid, rng.gen_range(-1.0..=1.0)))
+                    .collect(),
+                relationships: belief_uuids
+                    .iter()
+                    .map(|&b| (b, rng.gen_range(-1.0..=1.0)))
+                    .collect(),
+                delta_distribution: None,
+            })
+            .collect();
+        let prs = belief_uuids
+            .iter()
+            .flat_map(|&belief_uuid| {
+                behaviours
+                    .iter()
+                    .map(move |b| (belief_uuid, b.uuid))
+                    .collect::<Vec<_>>()
+            })
+            .map(
+                |(belief_uuid, behaviour_uuid)| PerformanceRelationshipSpec {
+                    behaviour_uuid,
+                    belief_uuid,
+                    value: rng.gen_range(-1.0..=1.0),
+                },
+            )
+            .collect();
+
+        let agent_uuids: Vec<Uuid> = (0..n_agents).map(|_| uuid(&mut rng)).collect();
+        let agents = agent_uuids
+            .iter()
+            .map(|&uuid| AgentSpec {
+                uuid,
+                actions: HashMap::from([(0, behaviours.choose(&mut rng).unwrap().uuid)]),
+                activations: HashMap::from([(
+                    0,
+                    belief_uuids
+                        .iter()
+                        .map(|&b| (b, rng.gen_range(-1.0..=1.0)))
+                        .collect(),
+                )]),
+                deltas: belief_uuids
+                    .iter()
+                    .map(|&b| (b, rng.gen_range(0.5..1.5)))
+                    .collect(),
+                friends: agent_uuids
+                    .choose_multiple(&mut rng, n_friends)
+                    .map(|&f| (f, rng.gen_range(0.0..=1.0)))
+                    .collect(),
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+
+        Population {
+            behaviours,
+            beliefs,
+            agents,
+            prs,
+        }
+    }
+
+    /// A [ConfigurationBuilder] for the population, starting at time 1 and
+    /// writing output to memory.
+    pub fn builder(&self) -> ConfigurationBuilder {
+        ConfigurationBuilder::new()
+            .with_behaviours(self.behaviours.clone())
+            .with_beliefs(self.beliefs.clone())
+            .with_agents(self.agents.clone())
+            .with_prs(self.prs.clone())
+            .time_range(1, 1)
+            .output(Box::new(Vec::new()))
+    }
+
+    /// The behaviours and beliefs of the population, without their
+    /// relationships to each other.
+    pub fn entities(&self) -> (Vec<BehaviourPtr>, Vec<BeliefPtr>) {
+        let behaviours: Vec<BehaviourPtr> = self
+            .behaviours
+            .iter()
+            .map(|b| b.to_basic_behaviour().into())
+            .collect();
+        let beliefs = self
+            .beliefs
+            .iter()
+            .map(|b| b.to_basic_belief(&behaviours))
+            .collect();
+        (behaviours, beliefs)
+    }
+}
@@ -0,0 +1,103 @@
+//! Compares converting [AgentSpec]s to agents when the UUID lookup maps are
+//! built once for all agents against rebuilding them for every agent, as the
+//! loaders used to, over a seeded population of 10,000 agents with 50 beliefs
+//! and 20 behaviours.
+//!
+//! Also times building a [Configuration](concept::configuration::Configuration)
+//! from those specs, which validates them and links friends, and compares
+//! looking up UUIDs in a std [HashMap] against a [UuidMap].
+
+mod common;
+
+use std::collections::HashMap;
+
+use belief_spread::{BehaviourPtr, BeliefPtr};
+use common::Population;
+use concept::collections::UuidMap;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use uuid::Uuid;
+
+const N_AGENTS: usize = 10_000;
+const N_BELIEFS: usize = 50;
+const N_BEHAVIOURS: usize = 20;
+
+fn uuid_behaviours(behaviours: &[BehaviourPtr]) -> UuidMap<BehaviourPtr> {
+    behaviours
+        .iter()
+        .map(|b| (*b.borrow().uuid(), b.clone()))
+        .collect()
+}
+
+fn uuid_beliefs(beliefs: &[BeliefPtr]) -> UuidMap<BeliefPtr> {
+    beliefs
+        .iter()
+        .map(|b| (*b.borrow().uuid(), b.clone()))
+        .collect()
+}
+
+fn bench_agent_conversion(c: &mut Criterion) {
+    let population = Population::generate(N_AGENTS, N_BELIEFS, N_BEHAVIOURS, 0, 0);
+    let (behaviours, beliefs) = population.entities();
+    let specs = &population.agents;
+    let mut group = c.benchmark_group("agent conversion 10000x50x20");
+    group.sample_size(10);
+
+    group.bench_function("maps per agent", |b| {
+        b.iter(|| {
+            for spec in specs {
+                black_box(
+                    spec.to_basic_agent(&uuid_behaviours(&behaviours), &uuid_beliefs(&beliefs)),
+                );
+            }
+        })
+    });
+
+    group.bench_function("maps shared", |b| {
+        b.iter(|| {
+            let behaviours = uuid_behaviours(&behaviours);
+            let beliefs = uuid_beliefs(&beliefs);
+            for spec in specs {
+                black_box(spec.to_basic_agent(&behaviours, &beliefs));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let population = Population::generate(N_AGENTS, N_BELIEFS, N_BEHAVIOURS, 10, 0);
+
+    let mut group = c.benchmark_group("build 10000x50x20");
+    group.sample_size(10);
+    group.bench_function("with 10 friends each", |b| {
+        b.iter(|| black_box(population.builder().build().unwrap()))
+    });
+    group.finish();
+}
+
+fn bench_uuid_index(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let uuids: Vec<Uuid> = (0..N_AGENTS).map(|_| Uuid::from_u128(rng.gen())).collect();
+    let std_index: HashMap<Uuid, usize> = uuids.iter().enumerate().map(|(i, &u)| (u, i)).collect();
+    let fast_index: UuidMap<usize> = uuids.iter().enumerate().map(|(i, &u)| (u, i)).collect();
+
+    let mut group = c.benchmark_group("uuid index 10000 lookups");
+    group.bench_function("std HashMap", |b| {
+        b.iter(|| uuids.iter().map(|u| std_index[u]).sum::<usize>())
+    });
+    group.bench_function("UuidMap", |b| {
+        b.iter(|| uuids.iter().map(|u| fast_index[u]).sum::<usize>())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_agent_conversion,
+    bench_build,
+    bench_uuid_index
+);
+criterion_main!(benches);
@@ -0,0 +1,152 @@
+//! Compares looking up performance relationships keyed by `(BeliefPtr,
+//! BehaviourPtr)` against keying them by `(Uuid, Uuid)`, over a matrix of 50
+//! beliefs and 20 behaviours, in the same order as the action hot loop.
+//!
+//! Also compares scoring the behaviours of an agent with the
+//! `(Uuid, Uuid)` map against the dense [PrsMatrix] used by the runner.
+
+use std::collections::HashMap;
+
+use belief_spread::{
+    Agent, AgentPtr, BasicAgent, BasicBehaviour, BasicBelief, BehaviourPtr, BeliefPtr,
+};
+use concept::{
+    performance_relationships::{PerformanceRelationships, PrsMatrix},
+    scoring::{compute_behaviour_scores, compute_behaviour_scores_dense},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use uuid::Uuid;
+
+const N_BELIEFS: usize = 50;
+const N_BEHAVIOURS: usize = 20;
+
+fn setup() -> (Vec<BeliefPtr>, Vec<BehaviourPtr>) {
+    let beliefs: Vec<BeliefPtr> = (0..N_BELIEFS)
+        .map(|i| BasicBelief::new(format!("belief {i}")).into())
+        .collect();
+    let behaviours: Vec<BehaviourPtr> = (0..N_BEHAVIOURS)
+        .map(|i| BasicBehaviour::new(format!("behaviour {i}")).into())
+        .collect();
+    (beliefs, behaviours)
+}
+
+fn value(i: usize, j: usize) -> f64 {
+    ((i * N_BEHAVIOURS + j) as f64 / (N_BELIEFS * N_BEHAVIOURS) as f64) * 2.0 - 1.0
+}
+
+#[allow(clippy::mutable_key_type)]
+fn bench_ptr_keys(c: &mut Criterion) {
+    let (beliefs, behaviours) = setup();
+    let prs: HashMap<(BeliefPtr, BehaviourPtr), f64> = beliefs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, belief)| {
+            behaviours
+                .iter()
+                .enumerate()
+                .map(move |(j, behaviour)| ((belief.clone(), behaviour.clone()), value(i, j)))
+        })
+        .collect();
+
+    c.bench_function("prs lookup (BeliefPtr, BehaviourPtr) 50x20", |b| {
+        b.iter(|| {
+            behaviours
+                .iter()
+                .map(|behaviour| {
+                    beliefs
+                        .iter()
+                        .map(|belief| {
+                            prs.get(&(belief.clone(), behaviour.clone()))
+                                .unwrap_or(&0.0)
+                        })
+                        .sum::<f64>()
+                })
+                .fold(0.0, |acc, v| acc + black_box(v))
+        })
+    });
+}
+
+fn bench_uuid_keys(c: &mut Criterion) {
+    let (beliefs, behaviours) = setup();
+    let prs: HashMap<(Uuid, Uuid), f64> = beliefs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, belief)| {
+            behaviours.iter().enumerate().map(move |(j, behaviour)| {
+                (
+                    (*belief.borrow().uuid(), *behaviour.borrow().uuid()),
+                    value(i, j),
+                )
+            })
+        })
+        .collect();
+
+    c.bench_function("prs lookup (Uuid, Uuid) 50x20", |b| {
+        b.iter(|| {
+            behaviours
+                .iter()
+                .map(|behaviour| {
+                    let behaviour_uuid = *behaviour.borrow().uuid();
+                    beliefs
+                        .iter()
+                        .map(|belief| {
+                            prs.get(&(*belief.borrow().uuid(), behaviour_uuid))
+                                .unwrap_or(&0.0)
+                        })
+                        .sum::<f64>()
+                })
+                .fold(0.0, |acc, v| acc + black_box(v))
+        })
+    });
+}
+
+fn bench_scoring(c: &mut Criterion) {
+    let (beliefs, behaviours) = setup();
+    let prs: PerformanceRelationships = beliefs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, belief)| {
+            behaviours.iter().enumerate().map(move |(j, behaviour)| {
+                (
+                    (*belief.borrow().uuid(), *behaviour.borrow().uuid()),
+                    value(i, j),
+                )
+            })
+        })
+        .collect();
+    let matrix = PrsMatrix::new(&prs, &beliefs, &behaviours);
+
+    let mut agent = BasicAgent::new();
+    for (i, belief) in beliefs.iter().enumerate() {
+        agent
+            .set_activation(1, belief.clone(), Some(value(i, 0)))
+            .unwrap();
+    }
+    let agent: AgentPtr = agent.into();
+
+    c.bench_function("behaviour scores (Uuid, Uuid) map 50x20", |b| {
+        b.iter(|| {
+            black_box(compute_behaviour_scores(
+                &agent,
+                1,
+                &beliefs,
+                &behaviours,
+                &prs,
+            ))
+        })
+    });
+    c.bench_function("behaviour scores dense matrix 50x20", |b| {
+        b.iter(|| {
+            black_box(compute_behaviour_scores_dense(
+                &agent,
+                1,
+                &beliefs,
+                &behaviours,
+                &matrix,
+            ))
+        })
+    });
+}
+
+criterion_group!(benches, bench_ptr_keys, bench_uuid_keys, bench_scoring);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+//! Times writing the summary output and a snapshot of a seeded synthetic
+//! population of 10,000 agents with 10 beliefs, 5 behaviours and 10 friends
+//! each, after 10 ticks.
+//!
+//! Both are written to [io::sink], so only serialization is measured.
+
+mod common;
+
+use std::io;
+
+use common::Population;
+use concept::runner::Runner;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const N_AGENTS: usize = 10_000;
+const N_TICKS: u32 = 10;
+
+fn bench_serialize(c: &mut Criterion) {
+    let population = Population::generate(N_AGENTS, 10, 5, 10, 0);
+    let mut runner = Runner::new(population.builder().build().unwrap()).with_seed(0);
+    runner.run_until(N_TICKS).unwrap();
+
+    let mut group = c.benchmark_group("serialize 10000x10x5 after 10 ticks");
+    group.sample_size(10);
+    group.bench_function("output", |b| {
+        b.iter(|| runner.serialize_output_to(io::sink()).unwrap())
+    });
+    group.bench_function("snapshot", |b| {
+        b.iter(|| runner.write_snapshot_to(io::sink()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize);
+criterion_main!(benches);
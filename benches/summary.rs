@@ -0,0 +1,49 @@
+//! Times computing the summary statistics written as output, over 20,000
+//! agents with 5 beliefs, 4 behaviours and 10 ticks.
+
+use belief_spread::{
+    Agent, AgentPtr, BasicAgent, BasicBehaviour, BasicBelief, BehaviourPtr, BeliefPtr,
+};
+use concept::json::OutputSpecs;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const N_AGENTS: usize = 20_000;
+const N_BELIEFS: usize = 5;
+const N_BEHAVIOURS: usize = 4;
+const N_TICKS: u32 = 10;
+
+fn setup() -> (Vec<AgentPtr>, Vec<BeliefPtr>) {
+    let beliefs: Vec<BeliefPtr> = (0..N_BELIEFS)
+        .map(|i| BasicBelief::new(format!("belief {i}")).into())
+        .collect();
+    let behaviours: Vec<BehaviourPtr> = (0..N_BEHAVIOURS)
+        .map(|i| BasicBehaviour::new(format!("behaviour {i}")).into())
+        .collect();
+    let agents = (0..N_AGENTS)
+        .map(|i| {
+            let mut agent = BasicAgent::new();
+            for t in 1..=N_TICKS {
+                for (j, belief) in beliefs.iter().enumerate() {
+                    let v = ((i * 7 + j * 13 + t as usize * 3) % 200) as f64 / 100.0 - 1.0;
+                    agent.set_activation(t, belief.clone(), Some(v)).unwrap();
+                }
+                agent.set_action(t, Some(behaviours[(i + t as usize) % N_BEHAVIOURS].clone()));
+            }
+            agent.into()
+        })
+        .collect();
+    (agents, beliefs)
+}
+
+fn bench_summary(c: &mut Criterion) {
+    let (agents, beliefs) = setup();
+    let mut group = c.benchmark_group("summary");
+    group.sample_size(10);
+    group.bench_function("20000 agents x 5 beliefs x 10 ticks", |b| {
+        b.iter(|| black_box(OutputSpecs::from_agents(&agents, &beliefs, 1, N_TICKS)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_summary);
+criterion_main!(benches);
@@ -0,0 +1,71 @@
+//! Times simulating one tick of seeded synthetic populations of 1,000,
+//! 10,000 and 50,000 agents, with 10 beliefs, 5 behaviours and 10 friends
+//! each, and of the largest on 1, 2, 4 and 8 threads, which should take
+//! roughly the inverse of the number of threads up to the number of CPUs.
+//!
+//! The runner is built outside the timed section, so only the tick is
+//! measured.
+
+mod common;
+
+use common::Population;
+use concept::runner::Runner;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+const N_BELIEFS: usize = 10;
+const N_BEHAVIOURS: usize = 5;
+const N_FRIENDS: usize = 10;
+
+fn bench_tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick 10x5");
+    group.sample_size(10);
+    for n_agents in [1_000, 10_000, 50_000] {
+        let population = Population::generate(n_agents, N_BELIEFS, N_BEHAVIOURS, N_FRIENDS, 0);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n_agents),
+            &population,
+            |b, population| {
+                b.iter_batched(
+                    || Runner::new(population.builder().build().unwrap()).with_seed(0),
+                    |mut runner| {
+                        runner.run_until(runner.time() + 1).unwrap();
+                        runner
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_tick_threads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick 10x5 50000 agents");
+    group.sample_size(10);
+    let population = Population::generate(50_000, N_BELIEFS, N_BEHAVIOURS, N_FRIENDS, 0);
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("threads", threads),
+            &population,
+            |b, population| {
+                b.iter_batched(
+                    || {
+                        Runner::new(population.builder().build().unwrap())
+                            .with_seed(0)
+                            .with_threads(threads)
+                            .unwrap()
+                    },
+                    |mut runner| {
+                        runner.run_until(runner.time() + 1).unwrap();
+                        runner
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tick, bench_tick_threads);
+criterion_main!(benches);
@@ -0,0 +1,327 @@
+//! An append-only log of the actions of every [Agent], written as each tick
+//! completes.
+//!
+//! Each tick is written as one NDJSON record per [Agent], in the order of
+//! the model:
+//!
+//! ```json
+//! {"time":3,"agent":"...","behaviour":"..."}
+//! ```
+//!
+//! where `behaviour` is null if the [Agent] performed no action. When the
+//! log is zstd compressed, each tick is a separate zstd frame, flushed to
+//! the file as the tick completes. Concatenated frames decode as one
+//! stream, so the log is valid up to the last completed tick even if the
+//! process dies while writing a later one. A plain log is flushed the same
+//! way, and a last tick with fewer records than the first is dropped when
+//! it is resumed.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use belief_spread::{AgentPtr, SimTime};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::ConceptError,
+    sink::{Compression, OutputSettings},
+};
+
+/// One action of the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionRecord {
+    pub time: SimTime,
+    pub agent: Uuid,
+    pub behaviour: Option<Uuid>,
+}
+
+/// An actions log open for appending.
+#[derive(Debug)]
+pub struct ActionsLog {
+    settings: OutputSettings,
+    file: File,
+    /// The last tick in the log, or [None] if it is empty.
+    last_tick: Option<SimTime>,
+    /// The records of the tick being written, before compression.
+    buffer: Vec<u8>,
+}
+
+impl ActionsLog {
+    /// Create an empty log, replacing any existing file.
+    pub fn create(settings: OutputSettings) -> Result<Self, ConceptError> {
+        check_compression(settings.compression)?;
+        let file = File::create(&settings.path).map_err(|source| ConceptError::Io {
+            path: settings.path.clone(),
+            source,
+        })?;
+        Ok(ActionsLog {
+            settings,
+            file,
+            last_tick: None,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Open an existing log to continue appending to it, creating it if it
+    /// does not exist.
+    ///
+    /// A tick left partly written by a process that died is removed, so the
+    /// log ends at its last completed tick.
+    pub fn resume(settings: OutputSettings) -> Result<Self, ConceptError> {
+        check_compression(settings.compression)?;
+        let io_error = |source| ConceptError::Io {
+            path: settings.path.clone(),
+            source,
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .truncate(false)
+            .open(&settings.path)
+            .map_err(io_error)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(io_error)?;
+        let (complete, records) = complete_ticks(&bytes, settings.compression).map_err(io_error)?;
+        if complete < bytes.len() {
+            file.set_len(complete as u64).map_err(io_error)?;
+        }
+        let last_tick = match records.lines().last() {
+            Some(line) => {
+                let record: ActionRecord =
+                    serde_json::from_str(line).map_err(|source| ConceptError::Parse {
+                        file: settings.path.clone(),
+                        json_path: String::from("."),
+                        source,
+                    })?;
+                Some(record.time)
+            }
+            None => None,
+        };
+        Ok(ActionsLog {
+            settings,
+            file,
+            last_tick,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// The path of the log.
+    pub fn path(&self) -> &Path {
+        &self.settings.path
+    }
+
+    /// The last tick in the log, or [None] if it is empty.
+    pub fn last_tick(&self) -> Option<SimTime> {
+        self.last_tick
+    }
+
+    /// Append the actions of every [Agent] at `time`, and flush them to the
+    /// file.
+    pub(crate) fn append_tick(&mut self, time: SimTime, agents: &[AgentPtr]) -> io::Result<()> {
+        self.buffer.clear();
+        for agent in agents {
+            let agent = agent.borrow();
+            let record = ActionRecord {
+                time,
+                agent: *agent.uuid(),
+                behaviour: agent.get_action(time).map(|b| *b.borrow().uuid()),
+            };
+            serde_json::to_writer(&mut self.buffer, &record)?;
+            self.buffer.push(b'\n');
+        }
+        match self.settings.compression {
+            Compression::None => self.file.write_all(&self.buffer)?,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level, .. } => self
+                .file
+                .write_all(&zstd::bulk::compress(&self.buffer, level)?)?,
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd { .. } => unreachable!("checked when the log was opened"),
+        }
+        self.file.flush()?;
+        self.last_tick = Some(time);
+        Ok(())
+    }
+}
+
+/// Read every record of a log.
+pub fn read_actions_log(settings: &OutputSettings) -> Result<Vec<ActionRecord>, ConceptError> {
+    check_compression(settings.compression)?;
+    let io_error = |source| ConceptError::Io {
+        path: settings.path.clone(),
+        source,
+    };
+    let bytes = std::fs::read(&settings.path).map_err(io_error)?;
+    let (_, records) = complete_ticks(&bytes, settings.compression).map_err(io_error)?;
+    records
+        .lines()
+        .map(|line| {
+            serde_json::from_str(line).map_err(|source| ConceptError::Parse {
+                file: settings.path.clone(),
+                json_path: String::from("."),
+                source,
+            })
+        })
+        .collect()
+}
+
+fn check_compression(compression: Compression) -> Result<(), ConceptError> {
+    match compression {
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd { .. } => Err(ConceptError::Output {
+            source: crate::sink::zstd_disabled(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Find the ticks of a log that were completely written.
+///
+/// # Returns
+/// The number of bytes of the log holding complete ticks, and their
+/// records.
+fn complete_ticks(bytes: &[u8], compression: Compression) -> io::Result<(usize, String)> {
+    match compression {
+        Compression::None => {
+            let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+            let lines = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            let records = std::str::from_utf8(&bytes[..lines]).map_err(invalid)?;
+            // Every tick has a record for every agent, so the last tick was
+            // partly written if it has fewer records than the first
+            let mut ticks: Vec<(SimTime, usize, usize)> = Vec::new();
+            let mut start = 0;
+            for line in records.split_inclusive('\n') {
+                let time = serde_json::from_str::<ActionRecord>(line)
+                    .map_err(io::Error::from)?
+                    .time;
+                match ticks.last_mut() {
+                    Some((last, _, n)) if *last == time => *n += 1,
+                    _ => ticks.push((time, start, 1)),
+                }
+                start += line.len();
+            }
+            let complete = match (ticks.first(), ticks.last()) {
+                (Some(&(_, _, n_agents)), Some(&(_, start, n))) if n < n_agents => start,
+                _ => lines,
+            };
+            Ok((complete, records[..complete].to_string()))
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd { .. } => {
+            let mut complete = 0;
+            while let Ok(size) = zstd::zstd_safe::find_frame_compressed_size(&bytes[complete..]) {
+                if size == 0 || complete + size > bytes.len() {
+                    break;
+                }
+                complete += size;
+            }
+            let mut records = String::new();
+            zstd::stream::read::Decoder::new(&bytes[..complete])?.read_to_string(&mut records)?;
+            Ok((complete, records))
+        }
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd { .. } => Err(crate::sink::zstd_disabled()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configuration::tests::{small_builder, temp_path},
+        runner::Runner,
+    };
+
+    fn settings(compression: Compression) -> OutputSettings {
+        OutputSettings {
+            path: temp_path(".ndjson"),
+            compression,
+        }
+    }
+
+    fn records_of(runner: &Runner) -> Vec<ActionRecord> {
+        let mut records: Vec<ActionRecord> = runner
+            .actions_iter()
+            .filter(|&(_, time, _)| time >= 1)
+            .map(|(agent, time, behaviour)| ActionRecord {
+                time,
+                agent,
+                behaviour: Some(behaviour),
+            })
+            .collect();
+        records.sort_by_key(|r| (r.time, r.agent));
+        records
+    }
+
+    #[test]
+    fn every_action_is_logged_as_its_tick_completes() {
+        for compression in [Compression::None, Compression::default()] {
+            let settings = settings(compression);
+            let log = ActionsLog::create(settings.clone()).unwrap();
+            let config = small_builder().build().unwrap();
+            let mut runner = Runner::new(config).with_seed(6).with_actions_log(log);
+            runner.run_until(2).unwrap();
+            assert_eq!(read_actions_log(&settings).unwrap().len(), 6);
+            runner.run().unwrap();
+
+            assert_eq!(read_actions_log(&settings).unwrap(), records_of(&runner));
+            std::fs::remove_file(&settings.path).unwrap();
+        }
+    }
+
+    #[test]
+    fn resumed_logs_drop_partly_written_ticks() {
+        for compression in [Compression::None, Compression::default()] {
+            let settings = settings(compression);
+            let config = small_builder().build().unwrap();
+            let log = ActionsLog::create(settings.clone()).unwrap();
+            let mut runner = Runner::new(config).with_seed(6).with_actions_log(log);
+            runner.run_until(2).unwrap();
+            let snapshot = runner.snapshot();
+            let complete = std::fs::read(&settings.path).unwrap();
+            runner.run_until(3).unwrap();
+            // The process dies while the last tick is being written
+            let written = std::fs::read(&settings.path).unwrap();
+            std::fs::write(&settings.path, &written[..written.len() - 4]).unwrap();
+
+            let log = ActionsLog::resume(settings.clone()).unwrap();
+            assert_eq!(log.last_tick(), Some(2));
+            assert_eq!(std::fs::read(&settings.path).unwrap(), complete);
+
+            let config = small_builder().build().unwrap();
+            let mut resumed = Runner::new(config).with_actions_log(log);
+            resumed.restore(snapshot).unwrap();
+            resumed.run().unwrap();
+            assert_eq!(read_actions_log(&settings).unwrap(), records_of(&runner));
+            std::fs::remove_file(&settings.path).unwrap();
+        }
+    }
+
+    #[test]
+    fn logs_must_end_at_the_tick_resumed_from() {
+        let settings = settings(Compression::None);
+        let log = ActionsLog::create(settings.clone()).unwrap();
+        let config = small_builder().build().unwrap();
+        let mut runner = Runner::new(config).with_seed(6).with_actions_log(log);
+        runner.run_until(1).unwrap();
+        let snapshot = runner.snapshot();
+        runner.run_until(2).unwrap();
+
+        let log = ActionsLog::resume(settings.clone()).unwrap();
+        let config = small_builder().build().unwrap();
+        let mut resumed = Runner::new(config).with_actions_log(log);
+        resumed.restore(snapshot).unwrap();
+        match resumed.run_until(3) {
+            Err(ConceptError::ActionsLogMismatch {
+                last_tick, time, ..
+            }) => assert_eq!((last_tick, time), (Some(2), 1)),
+            result => panic!("expected an actions log mismatch, got {result:?}"),
+        }
+        std::fs::remove_file(&settings.path).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+//! Simulating only the [Agent]s of an agents file that match filters on
+//! their fields.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::json::AgentSpec;
+
+/// A filter keeping only the [Agent]s whose field `field` is `value`,
+/// parsed from `field=value`.
+///
+/// The field may be any top-level field of the [Agent] in the agents file,
+/// including fields not used by the model, such as a region. A string field
+/// matches if it equals the value, and any other field matches if its JSON
+/// equals the value, so `region=3` matches both `"region": "3"` and
+/// `"region": 3`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFilter {
+    pub field: String,
+    pub value: String,
+}
+
+impl AgentFilter {
+    /// Whether the fields of an [Agent] match the filter.
+    pub fn matches(&self, fields: &Map<String, Value>) -> bool {
+        match fields.get(&self.field) {
+            Some(Value::String(s)) => *s == self.value,
+            Some(v) => self.value.parse::<Value>().is_ok_and(|value| value == *v),
+            None => false,
+        }
+    }
+}
+
+impl FromStr for AgentFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((field, value)) if !field.is_empty() => Ok(AgentFilter {
+                field: field.to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!("expected field=value, found {s}")),
+        }
+    }
+}
+
+/// Which [Agent]s were kept by [AgentFilter]s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFiltering {
+    /// The filters, every one of which an [Agent] matched to be kept.
+    pub filters: Vec<AgentFilter>,
+    /// The number of [Agent]s read.
+    pub agents_read: usize,
+    /// The number of [Agent]s kept.
+    pub agents_kept: usize,
+    /// The number of friendships of the [Agent]s kept with [Agent]s that
+    /// were not, which were dropped.
+    pub dropped_friendships: usize,
+}
+
+/// Whether the fields of an [Agent] match every one of `filters`.
+pub fn matches_all(filters: &[AgentFilter], fields: &Map<String, Value>) -> bool {
+    filters.iter().all(|filter| filter.matches(fields))
+}
+
+/// The fields of an [AgentSpec], as they are written to JSON.
+pub fn spec_fields(spec: &AgentSpec) -> Map<String, Value> {
+    match serde_json::to_value(spec) {
+        Ok(Value::Object(fields)) => fields,
+        _ => unreachable!("an agent is serialized as an object"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn filters_match_strings_and_json() {
+        let fields = json!({"region": "north", "zone": 3, "urban": true});
+        let fields = fields.as_object().unwrap();
+        let filter = |s: &str| s.parse::<AgentFilter>().unwrap();
+        assert!(filter("region=north").matches(fields));
+        assert!(!filter("region=south").matches(fields));
+        assert!(filter("zone=3").matches(fields));
+        assert!(filter("urban=true").matches(fields));
+        assert!(!filter("missing=3").matches(fields));
+
+        assert!(matches_all(
+            &[filter("region=north"), filter("zone=3")],
+            fields
+        ));
+        assert!(!matches_all(
+            &[filter("region=north"), filter("zone=4")],
+            fields
+        ));
+        assert!(matches_all(&[], fields));
+
+        assert!("region".parse::<AgentFilter>().is_err());
+        assert!("=north".parse::<AgentFilter>().is_err());
+    }
+}
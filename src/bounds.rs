@@ -0,0 +1,227 @@
+//! What happens to activations computed outside of their legal range,
+//! [-1, 1].
+
+use crate::{
+    error::ConceptError,
+    influence::Influence,
+    perception::{actions_of_friends, activation_change},
+};
+use belief_spread::{errors::UpdateActivationError, AgentPtr, BeliefPtr, SimTime};
+use serde::Serialize;
+
+/// How an activation computed outside of [-1, 1] is brought back within
+/// it, applied to every activation the [Runner](crate::runner::Runner)
+/// computes.
+///
+/// Values on a bound are within the range, so every policy keeps them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundsPolicy {
+    /// Saturate at the bound that was crossed.
+    #[default]
+    Clamp,
+    /// Fold the excess back from the bound that was crossed, as many times
+    /// as needed, so 1.25 becomes 0.75 and -3.5 becomes 0.5.
+    Reflect,
+    /// Abort the run.
+    Error,
+}
+
+impl BoundsPolicy {
+    /// Bring `value` within [-1, 1].
+    ///
+    /// # Returns
+    /// The value within the range, or [None] if it is outside of it and the
+    /// policy is [BoundsPolicy::Error].
+    pub fn apply(self, value: f64) -> Option<f64> {
+        match self {
+            // The same operations as belief_spread, so that clamped runs are
+            // identical to it
+            BoundsPolicy::Clamp => Some((-1.0_f64).max(1.0_f64.min(value))),
+            BoundsPolicy::Reflect if (-1.0..=1.0).contains(&value) => Some(value),
+            BoundsPolicy::Reflect => {
+                // Reflecting at both bounds is a triangle wave with period 4
+                let phase = (value + 1.0).rem_euclid(4.0);
+                Some(if phase > 2.0 {
+                    3.0 - phase
+                } else {
+                    phase - 1.0
+                })
+            }
+            BoundsPolicy::Error => (-1.0..=1.0).contains(&value).then_some(value),
+        }
+    }
+}
+
+/// Update the activation of an [Agent] for every [Belief] at `time`, as
+/// [update_activation_for_all_beliefs_for_agent](belief_spread::update_activation_for_all_beliefs_for_agent)
+/// does, but bringing each activation within [-1, 1] by a [BoundsPolicy].
+///
+/// The activations `carried` into the tick by an intervention, by the
+/// position of the [Belief], are read in place of those at the tick before.
+///
+/// If `influences` are given, they are replaced by the [Influence] on the
+/// activation of each [Belief], in the order of `beliefs`.
+///
+/// # Returns
+/// The number of activations that were brought within [-1, 1].
+pub(crate) fn update_activations(
+    agent: &AgentPtr,
+    time: SimTime,
+    beliefs: &[BeliefPtr],
+    policy: BoundsPolicy,
+    mut influences: Option<&mut Vec<Influence>>,
+    carried: impl Fn(usize) -> Option<f64>,
+) -> Result<usize, ConceptError> {
+    let agent_uuid = *agent.borrow().uuid();
+    let simulation_error = |source| ConceptError::Simulation {
+        agent: agent_uuid,
+        time,
+        source,
+    };
+    let actions_of_friends = actions_of_friends(agent, time - 1);
+    if let Some(influences) = influences.as_deref_mut() {
+        influences.clear();
+    }
+    let mut bounded = 0;
+    for (b, belief) in beliefs.iter().enumerate() {
+        let value = {
+            let a = agent.borrow();
+            let belief_uuid = || *belief.borrow().uuid();
+            let delta = a.get_delta(belief).ok_or_else(|| {
+                simulation_error(UpdateActivationError::GetDeltaNone {
+                    belief: belief_uuid(),
+                })
+            })?;
+            let activation = match carried(b) {
+                Some(activation) => activation,
+                None => a.get_activation(time - 1, belief).ok_or_else(|| {
+                    simulation_error(UpdateActivationError::GetActivationNone {
+                        time: time - 1,
+                        belief: belief_uuid(),
+                    })
+                })?,
+            };
+            let influence = Influence {
+                own: delta * activation,
+                friends: activation_change(agent, activation, belief, beliefs, &actions_of_friends),
+            };
+            if let Some(influences) = influences.as_deref_mut() {
+                influences.push(influence);
+            }
+            influence.own + influence.friends
+        };
+        bounded += usize::from(set_bounded_activation(agent, time, belief, policy, value)?);
+    }
+    Ok(bounded)
+}
+
+/// Set the activation of an [Agent] for a [Belief] at `time` to `value`,
+/// brought within [-1, 1] by a [BoundsPolicy].
+///
+/// # Returns
+/// Whether the value was brought within [-1, 1], or an error if it is
+/// outside of it and the policy is [BoundsPolicy::Error].
+pub(crate) fn set_bounded_activation(
+    agent: &AgentPtr,
+    time: SimTime,
+    belief: &BeliefPtr,
+    policy: BoundsPolicy,
+    value: f64,
+) -> Result<bool, ConceptError> {
+    let activation = policy
+        .apply(value)
+        .ok_or_else(|| ConceptError::ActivationOutOfBounds {
+            agent: *agent.borrow().uuid(),
+            belief: *belief.borrow().uuid(),
+            time,
+            value,
+        })?;
+    agent
+        .borrow_mut()
+        .set_activation(time, belief.clone(), Some(activation))
+        .expect("the activation is within [-1, 1]");
+    Ok(activation != value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configuration::tests::small_builder, runner::Runner};
+
+    /// Run [small_builder] with the deltas of the last [Agent], whose
+    /// initial activations are 0.5, raised so that its activations leave
+    /// [-1, 1].
+    fn run(policy: BoundsPolicy) -> (Runner, Result<(), ConceptError>) {
+        let config = small_builder().build().unwrap();
+        for belief in &config.beliefs {
+            config.agents[2]
+                .borrow_mut()
+                .set_delta(belief.clone(), Some(4.0))
+                .unwrap();
+        }
+        let mut runner = Runner::new(config).with_seed(3).with_bounds_policy(policy);
+        let result = runner.run_until(3);
+        (runner, result)
+    }
+
+    #[test]
+    fn clamp_saturates_at_both_bounds() {
+        let clamp = |v| BoundsPolicy::Clamp.apply(v);
+        assert_eq!(clamp(1.25), Some(1.0));
+        assert_eq!(clamp(-1.25), Some(-1.0));
+        assert_eq!(clamp(1.0), Some(1.0));
+        assert_eq!(clamp(-1.0), Some(-1.0));
+        assert_eq!(clamp(0.5), Some(0.5));
+    }
+
+    #[test]
+    fn reflect_folds_the_excess_back_at_both_bounds() {
+        let reflect = |v| BoundsPolicy::Reflect.apply(v);
+        assert_eq!(reflect(1.25), Some(0.75));
+        assert_eq!(reflect(-1.25), Some(-0.75));
+        assert_eq!(reflect(1.0), Some(1.0));
+        assert_eq!(reflect(-1.0), Some(-1.0));
+        assert_eq!(reflect(0.5), Some(0.5));
+        // Past the opposite bound, the excess is folded back again
+        assert_eq!(reflect(3.5), Some(-0.5));
+        assert_eq!(reflect(-3.5), Some(0.5));
+        assert_eq!(reflect(5.0), Some(1.0));
+    }
+
+    #[test]
+    fn error_rejects_only_values_outside_both_bounds() {
+        let error = |v| BoundsPolicy::Error.apply(v);
+        assert_eq!(error(1.25), None);
+        assert_eq!(error(-1.25), None);
+        assert_eq!(error(1.0), Some(1.0));
+        assert_eq!(error(-1.0), Some(-1.0));
+        assert_eq!(error(0.5), Some(0.5));
+    }
+
+    #[test]
+    fn runs_apply_the_policy_to_perception() {
+        let (clamped, result) = run(BoundsPolicy::Clamp);
+        result.unwrap();
+        assert!(clamped.activations_iter().any(|(_, _, _, v)| v == 1.0));
+
+        let (reflected, result) = run(BoundsPolicy::Reflect);
+        result.unwrap();
+        assert!(reflected
+            .activations_iter()
+            .all(|(_, _, _, v)| (-1.0..=1.0).contains(&v)));
+        assert!(reflected.activations_iter().all(|(_, _, _, v)| v != 1.0));
+
+        let (_, result) = run(BoundsPolicy::Error);
+        match result {
+            Err(ConceptError::ActivationOutOfBounds {
+                agent, time, value, ..
+            }) => {
+                assert_eq!(agent, uuid::Uuid::from_u128(0x302));
+                assert_eq!(time, 1);
+                assert!(value > 1.0);
+            }
+            _ => panic!("expected an activation out of bounds"),
+        }
+    }
+}
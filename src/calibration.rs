@@ -0,0 +1,481 @@
+//! Calibrating a scalar parameter of the model, such as the scale of the
+//! performance relationships, so that the simulated adoption of a
+//! [Behaviour] matches an observed time series.
+//!
+//! The observed adoption is read from CSV, one row per tick, giving the
+//! fraction of the population performing the [Behaviour] at that tick:
+//!
+//! ```csv
+//! time,fraction
+//! 1,0.05
+//! 2,0.08
+//! ```
+//!
+//! The parameter is found by a golden-section search over its range, which
+//! assumes the loss has a single minimum in it. Each evaluation is a full
+//! run from the same initial state with the same seed, so the search is
+//! deterministic.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use belief_spread::SimTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{ConceptError, ValidationIssue, ValidationReport},
+    runner::Runner,
+};
+
+/// The fraction of the population performing a [Behaviour] at each tick,
+/// as observed, in order of time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservedAdoption(Vec<(SimTime, f64)>);
+
+impl ObservedAdoption {
+    /// Observed adoption from `(time, fraction)` pairs.
+    pub fn new(mut points: Vec<(SimTime, f64)>) -> Self {
+        points.sort_unstable_by_key(|&(time, _)| time);
+        ObservedAdoption(points)
+    }
+
+    /// Read observed adoption from a CSV file with a `time,fraction` header.
+    pub fn read(path: &Path) -> Result<Self, ConceptError> {
+        let file = File::open(path).map_err(|source| ConceptError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_reader(BufReader::new(file), path)
+    }
+
+    /// Read observed adoption as CSV from a reader, naming it `path` in
+    /// errors.
+    pub fn from_reader(reader: impl BufRead, path: &Path) -> Result<Self, ConceptError> {
+        let invalid = |line, reason: String| ConceptError::Observations {
+            path: path.to_path_buf(),
+            line,
+            reason,
+        };
+        let mut points = Vec::new();
+        let mut times = BTreeMap::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|source| ConceptError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            let line_number = i + 1;
+            if i == 0 {
+                if line.trim() != "time,fraction" {
+                    return Err(invalid(
+                        line_number,
+                        format!("expected the header time,fraction, found {line}"),
+                    ));
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((time, fraction)) = line.split_once(',') else {
+                return Err(invalid(
+                    line_number,
+                    format!("expected two fields, found {line}"),
+                ));
+            };
+            let time: SimTime = time
+                .trim()
+                .parse()
+                .map_err(|err| invalid(line_number, format!("invalid time {time}: {err}")))?;
+            let fraction: f64 = fraction.trim().parse().map_err(|err| {
+                invalid(line_number, format!("invalid fraction {fraction}: {err}"))
+            })?;
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(invalid(
+                    line_number,
+                    format!("fraction {fraction} outside of [0, 1]"),
+                ));
+            }
+            if let Some(first) = times.insert(time, line_number) {
+                return Err(invalid(
+                    line_number,
+                    format!("tick {time} already observed at line {first}"),
+                ));
+            }
+            points.push((time, fraction));
+        }
+        if points.is_empty() {
+            return Err(invalid(1, String::from("no observations")));
+        }
+        Ok(Self::new(points))
+    }
+
+    /// The observations, in order of time.
+    pub fn points(&self) -> &[(SimTime, f64)] {
+        &self.0
+    }
+
+    /// Check that every observation is of a tick from `start` to `end`.
+    pub fn validate(&self, start: SimTime, end: SimTime) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        report.extend(
+            self.0
+                .iter()
+                .filter(|&&(time, _)| !(start..=end).contains(&time))
+                .map(|&(time, _)| ValidationIssue::InvalidObservationTime { time, start, end }),
+        );
+        report
+    }
+}
+
+/// How the simulated adoption is compared with the observed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Loss {
+    /// The root mean square error.
+    #[default]
+    Rmse,
+    /// The mean absolute error.
+    Mae,
+}
+
+impl Loss {
+    /// The loss of `simulated` against `observed`, both given at the same
+    /// ticks in the same order.
+    pub fn of(self, simulated: &[f64], observed: &[f64]) -> f64 {
+        debug_assert_eq!(simulated.len(), observed.len());
+        let n = observed.len() as f64;
+        let errors = simulated.iter().zip(observed).map(|(s, o)| s - o);
+        match self {
+            Loss::Rmse => (errors.map(|e| e * e).sum::<f64>() / n).sqrt(),
+            Loss::Mae => errors.map(f64::abs).sum::<f64>() / n,
+        }
+    }
+}
+
+/// The parameters that can be calibrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalibrationParam {
+    /// The factor every performance relationship is multiplied by, as
+    /// [ConfigurationBuilder::prs_scale](crate::configuration::ConfigurationBuilder::prs_scale).
+    PrsScale,
+}
+
+/// How a parameter is calibrated.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Calibration {
+    pub param: CalibrationParam,
+    /// The behaviour whose adoption is matched.
+    pub behaviour: Uuid,
+    /// The smallest and largest values searched.
+    pub range: (f64, f64),
+    /// The search stops once the minimum is bracketed within an interval
+    /// of the parameter no wider than this.
+    pub tolerance: f64,
+    pub loss: Loss,
+}
+
+/// The loss of the run with a value of the parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    pub value: f64,
+    pub loss: f64,
+}
+
+/// The result of a [Calibration].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationOutcome {
+    pub calibration: Calibration,
+    /// The seed of every run.
+    pub seed: u64,
+    /// The value with the lowest loss of those evaluated.
+    pub best_value: f64,
+    /// The loss of the run with [CalibrationOutcome::best_value].
+    pub best_loss: f64,
+    /// Every evaluation, in the order they were made.
+    pub trace: Vec<Evaluation>,
+    /// The outputs written by a run with the best value, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// The reciprocal of the golden ratio.
+const INV_PHI: f64 = 0.618_033_988_749_894_8;
+
+impl Calibration {
+    /// Find the value of the parameter whose run best matches `observed`.
+    ///
+    /// `runner` sets up a [Runner] with the parameter set to a value, from
+    /// its own copy of the same inputs and with `seed`. Each is run to the
+    /// last tick observed. The range must be finite, with its smallest value
+    /// first, and the tolerance positive.
+    pub fn run(
+        &self,
+        observed: &ObservedAdoption,
+        seed: u64,
+        mut runner: impl FnMut(f64) -> Result<Runner, ConceptError>,
+    ) -> Result<CalibrationOutcome, ConceptError> {
+        debug_assert!(self.range.0 < self.range.1 && self.tolerance > 0.0);
+        let trace = golden_section(self.range, self.tolerance, |value| {
+            let loss = self.evaluate(&mut runner(value)?, observed)?;
+            log::info!("Calibration: {:?} {value} has loss {loss}", self.param);
+            Ok(loss)
+        })?;
+        let best = trace[best_evaluation(&trace)];
+        log::info!(
+            "Calibration: best {:?} {} with loss {} after {} runs",
+            self.param,
+            best.value,
+            best.loss,
+            trace.len()
+        );
+        Ok(CalibrationOutcome {
+            calibration: *self,
+            seed,
+            best_value: best.value,
+            best_loss: best.loss,
+            trace,
+            artifacts: Vec::new(),
+        })
+    }
+
+    /// Run `runner` to the last tick of `observed`, and compare its adoption
+    /// of the [Behaviour] with it.
+    fn evaluate(
+        &self,
+        runner: &mut Runner,
+        observed: &ObservedAdoption,
+    ) -> Result<f64, ConceptError> {
+        let mut report = observed.validate(runner.start_time(), runner.end_time());
+        if !runner.names().behaviours.contains_key(&self.behaviour) {
+            report.extend([ValidationIssue::UnknownTarget {
+                input: "calibration",
+                target_kind: "behaviour",
+                target: self.behaviour,
+            }]);
+        }
+        report.into_result()?;
+        let last = observed.points().last().map_or(0, |&(time, _)| time);
+        runner.run_until(last)?;
+        let fractions = performer_fractions(runner, self.behaviour);
+        let (simulated, observed): (Vec<f64>, Vec<f64>) = observed
+            .points()
+            .iter()
+            .map(|&(time, fraction)| (fractions.get(&time).copied().unwrap_or(0.0), fraction))
+            .unzip();
+        Ok(self.loss.of(&simulated, &observed))
+    }
+}
+
+/// The fraction of the [Agent]s of a [Runner] performing `behaviour` at
+/// each tick they performed it.
+pub fn performer_fractions(runner: &Runner, behaviour: Uuid) -> BTreeMap<SimTime, f64> {
+    let mut counts: BTreeMap<SimTime, usize> = BTreeMap::new();
+    for (_, time, performed) in runner.actions_iter() {
+        if performed == behaviour {
+            *counts.entry(time).or_default() += 1;
+        }
+    }
+    let n_agents = runner.n_agents().max(1) as f64;
+    counts
+        .into_iter()
+        .map(|(time, count)| (time, count as f64 / n_agents))
+        .collect()
+}
+
+/// The position of the evaluation with the lowest loss, the first if
+/// several tie.
+fn best_evaluation(trace: &[Evaluation]) -> usize {
+    let mut best = 0;
+    for (i, evaluation) in trace.iter().enumerate() {
+        if evaluation.loss < trace[best].loss {
+            best = i;
+        }
+    }
+    best
+}
+
+/// Minimize `f` over `range` by golden-section search, until the minimum
+/// is bracketed within `tolerance`.
+///
+/// # Returns
+/// Every evaluation of `f`, in order.
+fn golden_section<E>(
+    (mut low, mut high): (f64, f64),
+    tolerance: f64,
+    mut f: impl FnMut(f64) -> Result<f64, E>,
+) -> Result<Vec<Evaluation>, E> {
+    let mut trace = Vec::new();
+    let mut evaluate = |value: f64| -> Result<f64, E> {
+        let loss = f(value)?;
+        trace.push(Evaluation { value, loss });
+        Ok(loss)
+    };
+    let mut left = high - INV_PHI * (high - low);
+    let mut right = low + INV_PHI * (high - low);
+    let mut left_loss = evaluate(left)?;
+    let mut right_loss = evaluate(right)?;
+    while high - low > tolerance {
+        if left_loss <= right_loss {
+            high = right;
+            (right, right_loss) = (left, left_loss);
+            left = high - INV_PHI * (high - low);
+            left_loss = evaluate(left)?;
+        } else {
+            low = left;
+            (left, left_loss) = (right, right_loss);
+            right = low + INV_PHI * (high - low);
+            right_loss = evaluate(right)?;
+        }
+    }
+    Ok(trace)
+}
+
+/// The observed adoption, as written by [ObservedAdoption::read], of the
+/// [Agent]s of a [Runner] that has been run, at every tick it simulated.
+pub fn write_observed(runner: &Runner, behaviour: Uuid, w: &mut dyn io::Write) -> io::Result<()> {
+    let fractions = performer_fractions(runner, behaviour);
+    writeln!(w, "time,fraction")?;
+    for time in runner.start_time()..=runner.time() {
+        writeln!(w, "{time},{}", fractions.get(&time).copied().unwrap_or(0.0))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        configuration::tests::small_builder, json::AgentSpec, selection::SoftmaxSelection,
+    };
+
+    use super::*;
+
+    /// A [Runner] of 200 agents on a ring, who choose by softmax so that
+    /// the scale of the performance relationships changes their choices,
+    /// simulated from time 1 to 8.
+    fn runner(prs_scale: f64) -> Runner {
+        let beliefs = [0x200, 0x201].map(Uuid::from_u128);
+        let n = 200;
+        let agents = (0..n)
+            .map(|i| AgentSpec {
+                uuid: Uuid::from_u128(0x1000 + i),
+                actions: HashMap::from([(0, Uuid::from_u128(0x100 + i % 2))]),
+                activations: HashMap::from([(
+                    0,
+                    HashMap::from([(beliefs[0], (i % 10) as f64 / 10.0), (beliefs[1], 0.2)]),
+                )]),
+                deltas: beliefs.iter().map(|&b| (b, 0.9)).collect(),
+                friends: HashMap::from([
+                    (Uuid::from_u128(0x1000 + (i + 1) % n), 0.5),
+                    (Uuid::from_u128(0x1000 + (i + n - 1) % n), 0.5),
+                ]),
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+        let config = small_builder()
+            .with_agents(agents)
+            .time_range(1, 8)
+            .prs_scale(prs_scale)
+            .build()
+            .unwrap();
+        Runner::new(config)
+            .with_seed(11)
+            .with_action_selection(Box::new(SoftmaxSelection { temperature: 0.2 }))
+    }
+
+    fn calibration() -> Calibration {
+        Calibration {
+            param: CalibrationParam::PrsScale,
+            behaviour: Uuid::from_u128(0x100),
+            range: (0.1, 5.0),
+            tolerance: 0.02,
+            loss: Loss::Rmse,
+        }
+    }
+
+    #[test]
+    fn calibration_recovers_the_scale_of_a_synthetic_target() {
+        // The target is the adoption of a run of the model itself
+        let mut target = runner(2.0);
+        target.run_until(8).unwrap();
+        let mut csv = Vec::new();
+        write_observed(&target, Uuid::from_u128(0x100), &mut csv).unwrap();
+        let observed = ObservedAdoption::from_reader(&csv[..], Path::new("target.csv")).unwrap();
+        assert_eq!(observed.points().len(), 8);
+
+        let outcome = calibration()
+            .run(&observed, 11, |scale| Ok(runner(scale)))
+            .unwrap();
+        // Golden-section search narrows the range by the golden ratio with
+        // each evaluation after the first two
+        let narrowings = (4.9_f64 / 0.02).ln() / (1.0 / INV_PHI).ln();
+        assert_eq!(outcome.trace.len(), 2 + narrowings.ceil() as usize);
+        assert!(
+            (outcome.best_value - 2.0).abs() < 0.5,
+            "best {}",
+            outcome.best_value
+        );
+        assert!(outcome.best_loss < 0.05, "loss {}", outcome.best_loss);
+        assert_eq!(
+            outcome.best_loss,
+            outcome
+                .trace
+                .iter()
+                .map(|e| e.loss)
+                .fold(f64::INFINITY, f64::min)
+        );
+
+        // Every run has the same seed, so calibrating again gives the same
+        let again = calibration()
+            .run(&observed, 11, |scale| Ok(runner(scale)))
+            .unwrap();
+        assert_eq!(outcome, again);
+    }
+
+    #[test]
+    fn observations_are_checked() {
+        let read = |csv: &str| ObservedAdoption::from_reader(csv.as_bytes(), Path::new("o.csv"));
+        assert_eq!(
+            read("time,fraction\n2,0.5\n1,0.25\n\n").unwrap().points(),
+            [(1, 0.25), (2, 0.5)]
+        );
+        for (csv, line) in [
+            ("t,f\n1,0.5\n", 1),
+            ("time,fraction\n1,1.5\n", 2),
+            ("time,fraction\n1,0.5\n1,0.5\n", 3),
+            ("time,fraction\nx,0.5\n", 2),
+            ("time,fraction\n", 1),
+        ] {
+            match read(csv) {
+                Err(ConceptError::Observations { line: l, .. }) => assert_eq!(l, line, "{csv}"),
+                other => panic!("expected invalid observations, got {other:?}"),
+            }
+        }
+
+        let observed = read("time,fraction\n1,0.5\n9,0.5\n").unwrap();
+        match calibration().run(&observed, 11, |scale| Ok(runner(scale))) {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                report.issues[..],
+                [ValidationIssue::InvalidObservationTime { time: 9, .. }]
+            )),
+            other => panic!("expected an observation out of range, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn losses_compare_the_curves() {
+        assert_eq!(Loss::Rmse.of(&[0.0, 0.0], &[0.3, 0.4]), 0.125_f64.sqrt());
+        assert!((Loss::Mae.of(&[0.0, 0.5], &[0.3, 0.4]) - 0.2).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,89 @@
+//! Floors and ceilings on the activations of individual [Agent]s, such as a
+//! committed minority whose activation for a [Belief] never falls below a
+//! floor whatever their friends do.
+
+use std::collections::BTreeMap;
+
+use belief_spread::{AgentPtr, BeliefPtr, SimTime};
+use uuid::Uuid;
+
+use crate::{collections::ModelIndex, json::AgentSpec};
+
+/// The floors and ceilings of the activations of an [Agent], as the
+/// position of each [Belief] with either, in order, with its floor and
+/// ceiling.
+///
+/// Most [Agent]s have none, which allocates nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivationCaps(Vec<(usize, Option<f64>, Option<f64>)>);
+
+impl ActivationCaps {
+    /// Resolve the caps of a valid [AgentSpec].
+    pub(crate) fn new(spec: &AgentSpec, index: &ModelIndex) -> Self {
+        let mut caps: BTreeMap<usize, (Option<f64>, Option<f64>)> = BTreeMap::new();
+        let belief = |uuid| index.belief(uuid).expect("the agent is valid");
+        for (b, &floor) in &spec.activation_floors {
+            caps.entry(belief(b)).or_default().0 = Some(floor);
+        }
+        for (b, &ceiling) in &spec.activation_ceilings {
+            caps.entry(belief(b)).or_default().1 = Some(ceiling);
+        }
+        ActivationCaps(
+            caps.into_iter()
+                .map(|(b, (floor, ceiling))| (b, floor, ceiling))
+                .collect(),
+        )
+    }
+
+    /// Hold the activations of `agent` at `time` to its floors and
+    /// ceilings.
+    ///
+    /// # Returns
+    /// The number of activations that were held.
+    pub(crate) fn apply(&self, agent: &AgentPtr, time: SimTime, beliefs: &[BeliefPtr]) -> usize {
+        let mut held_activations = 0;
+        for &(b, floor, ceiling) in &self.0 {
+            let belief = &beliefs[b];
+            let Some(activation) = agent.borrow().get_activation(time, belief) else {
+                continue;
+            };
+            let held = activation
+                .max(floor.unwrap_or(-1.0))
+                .min(ceiling.unwrap_or(1.0));
+            if held != activation {
+                agent
+                    .borrow_mut()
+                    .set_activation(time, belief.clone(), Some(held))
+                    .expect("the caps are within [-1, 1]");
+                held_activations += 1;
+            }
+        }
+        held_activations
+    }
+
+    /// Write the floors and ceilings to `spec`, by the UUIDs of the
+    /// [Belief]s.
+    pub(crate) fn write_to(&self, spec: &mut AgentSpec, index: &ModelIndex) {
+        let uuids = index.belief_uuids();
+        spec.activation_floors = self
+            .0
+            .iter()
+            .filter_map(|&(b, floor, _)| Some((uuids[b], floor?)))
+            .collect();
+        spec.activation_ceilings = self
+            .0
+            .iter()
+            .filter_map(|&(b, _, ceiling)| Some((uuids[b], ceiling?)))
+            .collect();
+    }
+}
+
+/// The number of [Agent]s with a floor or ceiling for each [Belief] with
+/// any, by [Belief].
+pub(crate) fn capped_agents(caps: &[ActivationCaps], index: &ModelIndex) -> BTreeMap<Uuid, usize> {
+    let mut counts: BTreeMap<Uuid, usize> = BTreeMap::new();
+    for &(b, _, _) in caps.iter().flat_map(|caps| &caps.0) {
+        *counts.entry(index.belief_uuids()[b]).or_default() += 1;
+    }
+    counts
+}
@@ -0,0 +1,156 @@
+//! Hash maps and sets for lookups keyed by UUID, and the [ModelIndex] built
+//! from them.
+//!
+//! These use a faster, non-cryptographic hasher than the std default, and
+//! are only used for maps built internally while loading, simulating and
+//! summarising. The specs keep std maps, so their serialization is
+//! unaffected.
+
+use std::collections::{HashMap, HashSet};
+
+use belief_spread::{BehaviourPtr, BeliefPtr};
+use rustc_hash::FxBuildHasher;
+use uuid::Uuid;
+
+/// A [HashMap] keyed by [Uuid].
+pub type UuidMap<V> = HashMap<Uuid, V, FxBuildHasher>;
+
+/// A [HashSet] of [Uuid]s.
+pub type UuidSet = HashSet<Uuid, FxBuildHasher>;
+
+/// The positions of the [Belief]s and [Behaviour]s of a model, by [Uuid].
+///
+/// A [ModelIndex] is built once from the UUIDs of the [Belief]s and
+/// [Behaviour]s, and shared by everything that resolves UUIDs while a model
+/// is loaded and run: validation, agent conversion and the
+/// [PrsMatrix](crate::performance_relationships::PrsMatrix). It holds
+/// positions rather than pointers, so it can be shared between threads, and
+/// a [Belief] or [Behaviour] is found from its position in the model.
+///
+/// It also holds the canonical orderings of the [Belief]s and [Behaviour]s,
+/// sorted by [Uuid], which every artifact of a run that lists them uses, so
+/// they are listed in the same order everywhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelIndex {
+    belief_uuids: Vec<Uuid>,
+    behaviour_uuids: Vec<Uuid>,
+    beliefs: UuidMap<usize>,
+    behaviours: UuidMap<usize>,
+    /// The positions of the [Belief]s, sorted by [Uuid].
+    belief_order: Vec<usize>,
+    /// The positions of the [Behaviour]s, sorted by [Uuid].
+    behaviour_order: Vec<usize>,
+}
+
+impl ModelIndex {
+    /// Create a [ModelIndex] from the [Uuid]s of the [Belief]s and
+    /// [Behaviour]s, in the order of the model.
+    pub fn new(beliefs: Vec<Uuid>, behaviours: Vec<Uuid>) -> Self {
+        let positions = |uuids: &[Uuid]| uuids.iter().enumerate().map(|(i, &u)| (u, i)).collect();
+        let order = |uuids: &[Uuid]| {
+            let mut order: Vec<usize> = (0..uuids.len()).collect();
+            order.sort_unstable_by_key(|&i| uuids[i]);
+            order
+        };
+        ModelIndex {
+            beliefs: positions(&beliefs),
+            behaviours: positions(&behaviours),
+            belief_order: order(&beliefs),
+            behaviour_order: order(&behaviours),
+            belief_uuids: beliefs,
+            behaviour_uuids: behaviours,
+        }
+    }
+
+    /// Create a [ModelIndex] from [Belief]s and [Behaviour]s.
+    pub fn from_model(beliefs: &[BeliefPtr], behaviours: &[BehaviourPtr]) -> Self {
+        Self::new(
+            beliefs.iter().map(|b| *b.borrow().uuid()).collect(),
+            behaviours.iter().map(|b| *b.borrow().uuid()).collect(),
+        )
+    }
+
+    /// The [Uuid] of every [Belief], in the order of the model.
+    pub fn belief_uuids(&self) -> &[Uuid] {
+        &self.belief_uuids
+    }
+
+    /// The [Uuid] of every [Behaviour], in the order of the model.
+    pub fn behaviour_uuids(&self) -> &[Uuid] {
+        &self.behaviour_uuids
+    }
+
+    /// The position of every [Belief], in the canonical order, which is by
+    /// [Uuid].
+    pub fn canonical_beliefs(&self) -> &[usize] {
+        &self.belief_order
+    }
+
+    /// The position of every [Behaviour], in the canonical order, which is
+    /// by [Uuid].
+    pub fn canonical_behaviours(&self) -> &[usize] {
+        &self.behaviour_order
+    }
+
+    /// The position of the [Belief] with a [Uuid].
+    pub fn belief(&self, uuid: &Uuid) -> Option<usize> {
+        self.beliefs.get(uuid).copied()
+    }
+
+    /// The position of the [Behaviour] with a [Uuid].
+    pub fn behaviour(&self, uuid: &Uuid) -> Option<usize> {
+        self.behaviours.get(uuid).copied()
+    }
+
+    /// Whether the model has a [Belief] with a [Uuid].
+    pub fn has_belief(&self, uuid: &Uuid) -> bool {
+        self.beliefs.contains_key(uuid)
+    }
+
+    /// Whether the model has a [Behaviour] with a [Uuid].
+    pub fn has_behaviour(&self, uuid: &Uuid) -> bool {
+        self.behaviours.contains_key(uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use belief_spread::{BasicBehaviour, BasicBelief};
+
+    use super::*;
+
+    #[test]
+    fn model_index_positions_follow_model_order() {
+        let beliefs: Vec<BeliefPtr> = (0..3)
+            .map(|i| BasicBelief::new(format!("b{i}")).into())
+            .collect();
+        let behaviours: Vec<BehaviourPtr> = (0..2)
+            .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+            .collect();
+        let index = ModelIndex::from_model(&beliefs, &behaviours);
+
+        for (i, belief) in beliefs.iter().enumerate() {
+            let uuid = *belief.borrow().uuid();
+            assert_eq!(index.belief(&uuid), Some(i));
+            assert_eq!(index.belief_uuids()[i], uuid);
+            assert!(index.has_belief(&uuid));
+            assert!(!index.has_behaviour(&uuid));
+        }
+        for (j, behaviour) in behaviours.iter().enumerate() {
+            let uuid = *behaviour.borrow().uuid();
+            assert_eq!(index.behaviour(&uuid), Some(j));
+            assert_eq!(index.behaviour_uuids()[j], uuid);
+        }
+        assert_eq!(index.belief(&Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn model_index_canonical_orders_are_by_uuid() {
+        let index = ModelIndex::new(
+            [3, 1, 2].map(Uuid::from_u128).to_vec(),
+            [5, 4].map(Uuid::from_u128).to_vec(),
+        );
+        assert_eq!(index.canonical_beliefs(), [1, 2, 0]);
+        assert_eq!(index.canonical_behaviours(), [1, 0]);
+    }
+}
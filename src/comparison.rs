@@ -0,0 +1,208 @@
+//! The differences between two variants of a scenario, such as a control
+//! and an intervention, simulated from the same initial state with the same
+//! seed.
+
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use belief_spread::SimTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    error::ConceptError,
+    json::{OutputSpec, OutputSpecs},
+    runner::Runner,
+};
+
+/// The differences between the summaries of two variants at a tick, as the
+/// alternative minus the base.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TickDifference {
+    /// The difference in the mean activation of each [Belief] with an
+    /// activation in both variants, by [Belief].
+    pub mean_activation: BTreeMap<Uuid, f64>,
+    /// The difference in the number of [Agent]s performing each
+    /// [Behaviour], by [Behaviour].
+    pub n_performers: BTreeMap<Uuid, i64>,
+}
+
+impl TickDifference {
+    /// The differences between the [OutputSpec]s of the base and the
+    /// alternative at a tick.
+    pub fn new(base: &OutputSpec, alt: &OutputSpec) -> Self {
+        let mean_activation = alt
+            .mean_activation
+            .iter()
+            .filter_map(|(uuid, &a)| Some((*uuid, a - base.mean_activation.get(uuid)?)))
+            .collect();
+        let count = |spec: &OutputSpec, uuid| spec.n_performers.get(uuid).map_or(0, |&n| n as i64);
+        let n_performers = base
+            .n_performers
+            .keys()
+            .chain(alt.n_performers.keys())
+            .map(|uuid| (*uuid, count(alt, uuid) - count(base, uuid)))
+            .collect();
+        TickDifference {
+            mean_activation,
+            n_performers,
+        }
+    }
+}
+
+/// The differences between the summaries of two variants at every tick
+/// summarised in both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    /// The time of the initial activations of the base, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_origin: Option<SimTime>,
+    /// The [TickDifference] at each tick.
+    pub data: BTreeMap<SimTime, TickDifference>,
+}
+
+impl Comparison {
+    /// Compare the [OutputSpecs] of the base and the alternative.
+    pub fn new(base: &OutputSpecs, alt: &OutputSpecs) -> Self {
+        Comparison {
+            time_origin: base.time_origin,
+            data: base
+                .data
+                .iter()
+                .filter_map(|(&time, b)| Some((time, TickDifference::new(b, alt.data.get(&time)?))))
+                .collect(),
+        }
+    }
+
+    /// Simulate the remaining ticks of the base and the alternative, and
+    /// compare their summaries.
+    ///
+    /// Each [Runner] should start from a copy of the same initial state with
+    /// the same seed, so that they only differ by the variant. Their outputs
+    /// are not written.
+    pub fn run(base: &mut Runner, alt: &mut Runner) -> Result<Self, ConceptError> {
+        base.run_until(base.end_time())?;
+        alt.run_until(alt.end_time())?;
+        Ok(Self::new(&base.output_specs(), &alt.output_specs()))
+    }
+
+    /// Write the [Comparison] as JSON.
+    pub fn to_writer<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+/// What was compared, printed when the comparison finishes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonOutcome {
+    /// The identifier of the comparison, suffixed with `-base` and `-alt`
+    /// for the run of each variant.
+    pub run_id: String,
+    /// The seed both variants were run with.
+    pub seed: u64,
+    /// The last tick simulated.
+    pub last_tick: SimTime,
+    /// The number of [Agent]s in each variant.
+    pub n_agents: usize,
+    /// The files written.
+    pub artifacts: Vec<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{configuration::tests::small_builder, json::PerformanceRelationshipSpec};
+
+    fn spec(means: &[(u128, f64)], performers: &[(u128, usize)]) -> OutputSpec {
+        OutputSpec {
+            mean_activation: means
+                .iter()
+                .map(|&(u, v)| (Uuid::from_u128(u), v))
+                .collect(),
+            sd_activation: HashMap::new(),
+            median_activation: HashMap::new(),
+            nonzero_activation_count: HashMap::new(),
+            n_performers: performers
+                .iter()
+                .map(|&(u, n)| (Uuid::from_u128(u), n))
+                .collect(),
+            weighted_mean_activation: None,
+            weighted_sd_activation: None,
+            action_assortativity: None,
+        }
+    }
+
+    #[test]
+    fn differences_are_the_alternative_minus_the_base() {
+        let base = OutputSpecs {
+            run_id: None,
+            fingerprint: None,
+            time_origin: Some(0),
+            output_every: None,
+            names: None,
+            data: HashMap::from([
+                (1, spec(&[(1, 0.25), (2, 0.5)], &[(3, 4), (4, 1)])),
+                (2, spec(&[(1, 0.5)], &[(3, 2)])),
+            ]),
+        };
+        let alt = OutputSpecs {
+            run_id: None,
+            fingerprint: None,
+            time_origin: Some(0),
+            output_every: None,
+            names: None,
+            data: HashMap::from([(1, spec(&[(1, 0.75)], &[(3, 1), (5, 2)]))]),
+        };
+        let comparison = Comparison::new(&base, &alt);
+        assert_eq!(comparison.time_origin, Some(0));
+        assert_eq!(comparison.data.keys().collect::<Vec<_>>(), vec![&1]);
+        let difference = &comparison.data[&1];
+        assert_eq!(
+            difference.mean_activation,
+            BTreeMap::from([(Uuid::from_u128(1), 0.5)])
+        );
+        assert_eq!(
+            difference.n_performers,
+            BTreeMap::from([
+                (Uuid::from_u128(3), -3),
+                (Uuid::from_u128(4), -1),
+                (Uuid::from_u128(5), 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn running_variants_compares_their_summaries() {
+        let runner = || Runner::new(small_builder().build().unwrap()).with_seed(11);
+        let comparison = Comparison::run(&mut runner(), &mut runner()).unwrap();
+        assert_eq!(
+            comparison.data.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        for difference in comparison.data.values() {
+            assert!(difference.mean_activation.values().all(|&d| d == 0.0));
+            assert!(difference.n_performers.values().all(|&d| d == 0));
+        }
+
+        // Without performance relationships every agent performs the
+        // behaviour with the lowest UUID
+        let alt = small_builder().with_prs(Vec::<PerformanceRelationshipSpec>::new());
+        let mut alt = Runner::new(alt.build().unwrap()).with_seed(11);
+        let mut base = runner();
+        let comparison = Comparison::run(&mut base, &mut alt).unwrap();
+        let base = base.output_specs();
+        let first = Uuid::from_u128(0x100);
+        for (time, difference) in &comparison.data {
+            let n_base = base.data[time]
+                .n_performers
+                .get(&first)
+                .map_or(0, |&n| n as i64);
+            assert_eq!(difference.n_performers[&first], 3 - n_base);
+            assert_eq!(difference.n_performers.values().sum::<i64>(), 0);
+        }
+    }
+}
@@ -0,0 +1,2819 @@
+//! Construction and validation of the model [Configuration].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use belief_spread::{Agent, AgentPtr, BasicAgent, BehaviourPtr, BeliefPtr, SimTime};
+use rayon::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    agent_filter::{matches_all, spec_fields, AgentFilter, AgentFiltering},
+    caps::ActivationCaps,
+    collections::{ModelIndex, UuidMap, UuidSet},
+    deltas::{DeltaDistribution, DeltaGeneration, GeneratedDeltas},
+    error::{ConceptError, ValidationIssue, ValidationReport, ValidationWarning},
+    initialization::{ActivationInitialization, SummaryActivations},
+    input_summary::InputSummary,
+    interventions::Interventions,
+    json::{
+        AgentSpec, BehaviourSpec, BeliefSpec, InterventionSpec, OutputSpecs,
+        PerformanceRelationshipSpec,
+    },
+    loader::{
+        for_each_agent_in_window_from_path, for_each_matching_agent_from_path,
+        load_behaviours_from_path, load_beliefs_from_path, load_interventions_from_path,
+        load_prs_from_path, load_summary_from_path, HistoryWindow, SkippedHistory,
+    },
+    network::NetworkSnapshots,
+    performance_relationships::{PrsMatrix, PrsModifications, PrsOverride},
+    restriction::{DroppedReferences, ModelRestriction, Restriction},
+    sink::{Compression, OutputSettings, OutputSink},
+    verify::InputFile,
+};
+
+/// The configuration of the model.
+///
+/// A [Configuration] is constructed using a [ConfigurationBuilder], which
+/// ensures that every reference between the inputs resolves before the
+/// simulation starts.
+pub struct Configuration {
+    /// The identifier of the run, which tags its logs and outputs.
+    pub(crate) run_id: String,
+
+    /// The seed of the run, which generated deltas are drawn with.
+    pub(crate) seed: u64,
+
+    /// The [Behaviour]s in the model.
+    pub(crate) behaviours: Vec<BehaviourPtr>,
+
+    /// The [Belief]s in the model.
+    pub(crate) beliefs: Vec<BeliefPtr>,
+
+    /// The [Agent]s in the model.
+    pub(crate) agents: Vec<AgentPtr>,
+
+    /// The floors and ceilings of the activations of each [Agent], in the
+    /// order of the [Agent]s.
+    pub(crate) activation_caps: Vec<ActivationCaps>,
+
+    /// The performance relationships in the model, indexed by the positions
+    /// of the [Belief]s and [Behaviour]s.
+    pub(crate) prs: PrsMatrix,
+
+    /// Start time.
+    pub(crate) start_time: SimTime,
+
+    /// End time.
+    pub(crate) end_time: SimTime,
+
+    /// The time of the initial activations and actions of the [Agent]s.
+    pub(crate) time_origin: SimTime,
+
+    /// Output sink, taken when the output is written, or [None] if there is
+    /// no output.
+    pub(crate) output: Option<Box<dyn OutputSink>>,
+
+    /// The path of the output file, if the output is written to a file.
+    pub(crate) output_path: Option<PathBuf>,
+
+    /// How the history of the [Agent]s was cut as they were loaded, if it
+    /// was.
+    pub(crate) history_trimming: Option<HistoryTrimming>,
+
+    /// How the friend weights of each [Agent] were normalized.
+    pub(crate) friend_normalization: FriendNormalization,
+
+    /// How the friends of the [Agent]s were pruned, if they were.
+    pub(crate) friend_pruning: Option<FriendPruning>,
+
+    /// How the performance relationships loaded were modified, if they were.
+    pub(crate) prs_modifications: Option<PrsModifications>,
+
+    /// How the relationships between the [Belief]s were scaled, if they
+    /// were.
+    pub(crate) relationship_scaling: Option<RelationshipScaling>,
+
+    /// The warnings from the optional checks of the inputs.
+    pub(crate) validation_warnings: Vec<ValidationWarning>,
+
+    /// The delta of the [Agent]s for the [Belief]s they have none for, if
+    /// set.
+    pub(crate) default_delta: Option<f64>,
+
+    /// How the deltas missing from the [Agent]s were generated, if any
+    /// [Belief] has a [DeltaDistribution].
+    pub(crate) delta_generation: Option<DeltaGeneration>,
+
+    /// How the initial activations of the [Agent]s were drawn from a
+    /// summary, if they were.
+    pub(crate) activation_initialization: Option<ActivationInitialization>,
+
+    /// Which [Agent]s were kept by [AgentFilter]s, if there were any.
+    pub(crate) agent_filtering: Option<AgentFiltering>,
+
+    /// How the model was restricted to a subset of its [Belief]s and
+    /// [Behaviour]s, if it was.
+    pub(crate) restriction: Option<Restriction>,
+
+    /// The ticks at which the friendship network is written that have not
+    /// been yet, if any were set.
+    pub(crate) network_snapshots: Option<NetworkSnapshots>,
+
+    /// The changes to the [Agent]s applied at the start of each tick, if
+    /// any were given.
+    pub(crate) interventions: Option<Interventions>,
+
+    /// Summary statistics of the static inputs, after any pruning.
+    pub(crate) input_summary: InputSummary,
+
+    /// The input files read, with their checksums.
+    pub(crate) input_files: Vec<InputFile>,
+}
+
+/// How the friend weights of each [Agent] are normalized as they are loaded,
+/// set by [ConfigurationBuilder::normalize_friend_weights].
+///
+/// The weights are normalized before they are validated, so they may be
+/// given as raw counts, and before the friends are pruned by
+/// [ConfigurationBuilder::max_friends_per_agent].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FriendNormalization {
+    /// Keep the weights as they were given.
+    #[default]
+    None,
+    /// Divide each weight by the largest weight of the [Agent].
+    Max,
+    /// Divide each weight by the sum of the weights of the [Agent], so that
+    /// they sum to 1.
+    Sum,
+}
+
+impl FriendNormalization {
+    /// Normalize the weights of the friends of an [Agent].
+    ///
+    /// Weights are left as they are if the divisor is not positive, as for
+    /// an [Agent] without friends or whose weights are all zero.
+    pub fn normalize(self, friends: &mut HashMap<Uuid, f64>) {
+        let divisor = match self {
+            FriendNormalization::None => return,
+            FriendNormalization::Max => friends.values().copied().fold(0.0, f64::max),
+            FriendNormalization::Sum => friends.values().sum(),
+        };
+        if divisor > 0.0 {
+            friends.values_mut().for_each(|w| *w /= divisor);
+        }
+    }
+}
+
+/// How the friends of the [Agent]s were pruned to the highest weights by
+/// [ConfigurationBuilder::max_friends_per_agent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FriendPruning {
+    /// The most friends kept by each [Agent].
+    pub max_friends_per_agent: usize,
+    /// The number of friendships dropped.
+    pub dropped_edges: usize,
+    /// The number of [Agent]s with friends dropped.
+    pub affected_agents: usize,
+}
+
+/// How the history of the [Agent]s was cut as they were loaded by
+/// [ConfigurationBuilder::load_activations_from] and
+/// [ConfigurationBuilder::load_actions_from].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryTrimming {
+    /// The earliest tick of activations kept, if they were cut.
+    pub activations_from: Option<SimTime>,
+    /// The earliest tick of actions kept, if they were cut.
+    pub actions_from: Option<SimTime>,
+    /// The number of ticks of activations skipped, over every [Agent].
+    pub skipped_activations: usize,
+    /// The number of actions skipped, over every [Agent].
+    pub skipped_actions: usize,
+}
+
+/// How the relationships between the [Belief]s were scaled by
+/// [ConfigurationBuilder::relationship_scale].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationshipScaling {
+    /// The factor every relationship was multiplied by.
+    pub factor: f64,
+    /// The number of relationships clamped to [-1, 1] once scaled.
+    pub clamped: usize,
+}
+
+/// A builder for a [Configuration].
+///
+/// Each input is either read from a file or supplied as specs constructed
+/// in memory. Every input must be supplied before calling
+/// [ConfigurationBuilder::build], which loads the inputs, checks that they
+/// are consistent with each other, and only then opens the output, if one
+/// was given. Without an output, the summary is only written by an explicit
+/// call to [Runner::serialize_output_to](crate::runner::Runner::serialize_output_to).
+///
+/// # Examples
+/// ```no_run
+/// use concept::configuration::ConfigurationBuilder;
+///
+/// let config = ConfigurationBuilder::new()
+///     .behaviours_from_path("behaviours.json")
+///     .beliefs_from_path("beliefs.json")
+///     .agents_from_path("agents.json.zst")
+///     .prs_from_path("prs.json")
+///     .time_range(1, 10)
+///     .output_path("output.json.zst")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct ConfigurationBuilder {
+    run_id: Option<String>,
+    seed: Option<u64>,
+    behaviours: Option<Input<BehaviourSpec>>,
+    beliefs: Option<Input<BeliefSpec>>,
+    agents: Option<Input<AgentSpec>>,
+    prs: Option<Input<PerformanceRelationshipSpec>>,
+    time_range: Option<(SimTime, SimTime)>,
+    time_origin: Option<SimTime>,
+    output: Option<Output>,
+    max_friends_per_agent: Option<usize>,
+    history_window: HistoryWindow,
+    friend_normalization: FriendNormalization,
+    prs_modifications: PrsModifications,
+    relationship_scale: Option<f64>,
+    sign_consistency_threshold: Option<f64>,
+    default_delta: Option<f64>,
+    agent_filters: Vec<AgentFilter>,
+    restriction: ModelRestriction,
+    network_snapshots: Option<NetworkSnapshots>,
+    initial_summary: Option<(Summary, SimTime)>,
+    interventions: Option<Input<InterventionSpec>>,
+}
+
+/// Where the specs of an input of a [Configuration] come from.
+enum Input<T> {
+    /// A file, loaded when the [Configuration] is built.
+    Path(PathBuf),
+    /// Specs constructed in memory.
+    Specs(Vec<T>),
+}
+
+impl<T> Input<T> {
+    /// The path of the file the specs are read from, if they are.
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Input::Path(path) => Some(path),
+            Input::Specs(_) => None,
+        }
+    }
+
+    /// Load the specs, using `load` if they are in a file.
+    fn load(self, load: fn(&Path) -> Result<Vec<T>, ConceptError>) -> Result<Vec<T>, ConceptError> {
+        match self {
+            Input::Path(path) => load(&path),
+            Input::Specs(specs) => Ok(specs),
+        }
+    }
+}
+
+/// Where the summary the initial activations are drawn from comes from.
+enum Summary {
+    /// A file, loaded when the [Configuration] is built.
+    Path(PathBuf),
+    /// [OutputSpecs] constructed in memory.
+    Specs(Box<OutputSpecs>),
+}
+
+/// Where the output of a [Configuration] is written.
+enum Output {
+    /// A file, compressed as its name suggests.
+    Path(PathBuf),
+    /// A file, opened once the inputs have been validated.
+    Settings(OutputSettings),
+    /// An already opened [OutputSink].
+    Sink(Box<dyn OutputSink>),
+}
+
+impl Configuration {
+    /// The positions of the [Belief]s and [Behaviour]s in the model, by
+    /// UUID.
+    pub fn index(&self) -> &ModelIndex {
+        self.prs.index()
+    }
+}
+
+impl ConfigurationBuilder {
+    /// Create a new [ConfigurationBuilder] with no inputs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the [Behaviour]s from a behaviours.json file.
+    pub fn behaviours_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.behaviours = Some(Input::Path(path.into()));
+        self
+    }
+
+    /// Read the [Belief]s from a beliefs.json file.
+    pub fn beliefs_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.beliefs = Some(Input::Path(path.into()));
+        self
+    }
+
+    /// Read the [Agent]s from an agents.json file, which may be zstd
+    /// compressed.
+    pub fn agents_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.agents = Some(Input::Path(path.into()));
+        self
+    }
+
+    /// Read the performance relationships from a prs.json file.
+    pub fn prs_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.prs = Some(Input::Path(path.into()));
+        self
+    }
+
+    /// Use [BehaviourSpec]s constructed in memory.
+    pub fn with_behaviours(mut self, specs: Vec<BehaviourSpec>) -> Self {
+        self.behaviours = Some(Input::Specs(specs));
+        self
+    }
+
+    /// Use [BeliefSpec]s constructed in memory.
+    pub fn with_beliefs(mut self, specs: Vec<BeliefSpec>) -> Self {
+        self.beliefs = Some(Input::Specs(specs));
+        self
+    }
+
+    /// Use [AgentSpec]s constructed in memory.
+    pub fn with_agents(mut self, specs: Vec<AgentSpec>) -> Self {
+        self.agents = Some(Input::Specs(specs));
+        self
+    }
+
+    /// Use [PerformanceRelationshipSpec]s constructed in memory.
+    pub fn with_prs(mut self, specs: Vec<PerformanceRelationshipSpec>) -> Self {
+        self.prs = Some(Input::Specs(specs));
+        self
+    }
+
+    /// Set the (inclusive) range of times to simulate.
+    pub fn time_range(mut self, start: SimTime, end: SimTime) -> Self {
+        self.time_range = Some((start, end));
+        self
+    }
+
+    /// Set the time of the initial activations and actions of the [Agent]s,
+    /// which must be before the start time. If it is earlier than the tick
+    /// before the start time, the initial state is taken to hold until then,
+    /// and is copied to the tick before the start time, which perception at
+    /// the start time reads.
+    ///
+    /// By default, it is the tick before the start time.
+    pub fn time_origin(mut self, origin: SimTime) -> Self {
+        self.time_origin = Some(origin);
+        self
+    }
+
+    /// Identify the run by `run_id` in its logs and outputs, instead of a
+    /// generated [new_run_id].
+    pub fn run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// Write the output to a file, compressed as its name suggests by
+    /// [Compression::for_path].
+    pub fn output_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(Output::Path(path.into()));
+        self
+    }
+
+    /// Write the output to a file described by [OutputSettings].
+    pub fn output_settings(mut self, settings: OutputSettings) -> Self {
+        self.output = Some(Output::Settings(settings));
+        self
+    }
+
+    /// Write the output to an [OutputSink].
+    pub fn output(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.output = Some(Output::Sink(sink));
+        self
+    }
+
+    /// Keep only the `k` highest-weight friends of each [Agent], breaking
+    /// ties by keeping the friends with the lowest UUIDs.
+    ///
+    /// This approximates the model to speed up perception for [Agent]s with
+    /// very many friends, so it is off unless set.
+    pub fn max_friends_per_agent(mut self, k: usize) -> Self {
+        self.max_friends_per_agent = Some(k);
+        self
+    }
+
+    /// Skip the activations of each [Agent] before tick `time` as it is
+    /// loaded, so the memory for them is never allocated.
+    ///
+    /// The activations at the time origin, which perception at the start
+    /// time reads, are always kept, so `time` is moved back to it if it is
+    /// later.
+    pub fn load_activations_from(mut self, time: SimTime) -> Self {
+        self.history_window.activations_from = Some(time);
+        self
+    }
+
+    /// Skip the actions of each [Agent] before tick `time` as it is loaded,
+    /// as [ConfigurationBuilder::load_activations_from].
+    pub fn load_actions_from(mut self, time: SimTime) -> Self {
+        self.history_window.actions_from = Some(time);
+        self
+    }
+
+    /// Normalize the friend weights of each [Agent] as it is loaded, so
+    /// that weights given as raw counts are within [0, 1]. See
+    /// [FriendNormalization].
+    pub fn normalize_friend_weights(mut self, normalization: FriendNormalization) -> Self {
+        self.friend_normalization = normalization;
+        self
+    }
+
+    /// Multiply every performance relationship loaded by `factor`, before
+    /// any [ConfigurationBuilder::prs_override] is applied.
+    pub fn prs_scale(mut self, factor: f64) -> Self {
+        self.prs_modifications.scale = Some(factor);
+        self
+    }
+
+    /// Warn of every [Belief] whose perception of a [Behaviour] and
+    /// performance relationship with it have opposite signs, where both
+    /// have a magnitude greater than `threshold`.
+    ///
+    /// The warnings are logged and kept with the [Configuration], and do not
+    /// stop it from being built.
+    pub fn check_sign_consistency(mut self, threshold: f64) -> Self {
+        self.sign_consistency_threshold = Some(threshold);
+        self
+    }
+
+    /// Multiply every relationship between the [Belief]s loaded by
+    /// `factor`, clamping the results to [-1, 1].
+    ///
+    /// The relationships are validated before they are scaled, so only the
+    /// relationships loaded must be within [-1, 1].
+    pub fn relationship_scale(mut self, factor: f64) -> Self {
+        self.relationship_scale = Some(factor);
+        self
+    }
+
+    /// Replace the performance relationship of a [Belief] and a [Behaviour]
+    /// loaded, after any [ConfigurationBuilder::prs_scale]. The [Belief] and
+    /// [Behaviour] must be in the model.
+    pub fn prs_override(mut self, prs_override: PrsOverride) -> Self {
+        self.prs_modifications.overrides.push(prs_override);
+        self
+    }
+
+    /// Seed the random draws made as the model is built, such as the deltas
+    /// generated from a [DeltaDistribution]. A [Runner](crate::runner::Runner)
+    /// of the [Configuration] is seeded the same unless it is given another
+    /// seed. The seed is random if it is not set.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Give every [Agent] without a delta for a [Belief] the delta `delta`,
+    /// instead of failing validation. The deltas of the [Agent]s that have
+    /// them are kept, and those generated from the [DeltaDistribution] of a
+    /// [Belief] take precedence.
+    pub fn default_delta(mut self, delta: f64) -> Self {
+        self.default_delta = Some(delta);
+        self
+    }
+
+    /// Keep only the [Agent]s that match `filter`, and every other filter
+    /// added, dropping their friendships with the [Agent]s that do not.
+    ///
+    /// The [Agent]s of an agents file are matched against every field of
+    /// their JSON, and those supplied as specs against their own fields.
+    pub fn agent_filter(mut self, filter: AgentFilter) -> Self {
+        self.agent_filters.push(filter);
+        self
+    }
+
+    /// Simulate only the [Belief]s in `beliefs`, dropping the others and the
+    /// perceptions, relationships, performance relationships, activations
+    /// and deltas that refer to them.
+    pub fn only_beliefs(mut self, beliefs: impl IntoIterator<Item = Uuid>) -> Self {
+        self.restriction.beliefs = Some(beliefs.into_iter().collect());
+        self
+    }
+
+    /// Simulate only the [Behaviour]s in `behaviours`, dropping the others
+    /// and the perceptions, performance relationships and actions that refer
+    /// to them.
+    pub fn only_behaviours(mut self, behaviours: impl IntoIterator<Item = Uuid>) -> Self {
+        self.restriction.behaviours = Some(behaviours.into_iter().collect());
+        self
+    }
+
+    /// Write the friendship network at the end of each tick of a
+    /// [NetworkSnapshots], or as it was loaded for ticks before the start
+    /// time.
+    ///
+    /// Every tick must be from the time origin to the end time.
+    pub fn network_snapshots(mut self, snapshots: NetworkSnapshots) -> Self {
+        self.network_snapshots = Some(snapshots);
+        self
+    }
+
+    /// Draw the initial activation of each [Agent] for each [Belief] from a
+    /// normal distribution with the mean and standard deviation of the
+    /// activations at `tick` of the summary in a file, written by a prior
+    /// run, replacing those it was given.
+    ///
+    /// [Belief]s the summary has no statistics for keep the activations the
+    /// [Agent]s were given. The summary must have `tick`, and only
+    /// [Belief]s that were loaded.
+    pub fn init_from_summary_path(mut self, path: impl Into<PathBuf>, tick: SimTime) -> Self {
+        self.initial_summary = Some((Summary::Path(path.into()), tick));
+        self
+    }
+
+    /// Draw the initial activations of the [Agent]s from [OutputSpecs]
+    /// constructed in memory, as [ConfigurationBuilder::init_from_summary_path].
+    pub fn init_from_summary(mut self, summary: OutputSpecs, tick: SimTime) -> Self {
+        self.initial_summary = Some((Summary::Specs(Box::new(summary)), tick));
+        self
+    }
+
+    /// Read [InterventionSpec]s from an interventions.json file, applied at
+    /// the start of their ticks. See [interventions](crate::interventions).
+    pub fn interventions_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.interventions = Some(Input::Path(path.into()));
+        self
+    }
+
+    /// Use [InterventionSpec]s constructed in memory.
+    pub fn with_interventions(mut self, specs: Vec<InterventionSpec>) -> Self {
+        self.interventions = Some(Input::Specs(specs));
+        self
+    }
+
+    /// The paths of the input files read when the [Configuration] is built,
+    /// in the order they are read, whose contents become its input files.
+    pub fn input_paths(&self) -> Vec<&Path> {
+        let summary_path = match &self.initial_summary {
+            Some((Summary::Path(path), _)) => Some(path.as_path()),
+            _ => None,
+        };
+        [
+            self.behaviours.as_ref().and_then(Input::path),
+            self.beliefs.as_ref().and_then(Input::path),
+            self.agents.as_ref().and_then(Input::path),
+            self.prs.as_ref().and_then(Input::path),
+            summary_path,
+            self.interventions.as_ref().and_then(Input::path),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Load and validate the inputs, then open the output, if one was given.
+    ///
+    /// # Returns
+    /// The [Configuration], or a [ConceptError]. If the inputs are
+    /// inconsistent, the [ConceptError::Validation] lists every issue found.
+    pub fn build(self) -> Result<Configuration, ConceptError> {
+        let missing = |input| ValidationIssue::MissingInput { input };
+        let mut report = ValidationReport::default();
+        report.extend(self.behaviours.is_none().then(|| missing("behaviours")));
+        report.extend(self.beliefs.is_none().then(|| missing("beliefs")));
+        report.extend(self.agents.is_none().then(|| missing("agents")));
+        report.extend(
+            self.prs
+                .is_none()
+                .then(|| missing("performance relationships")),
+        );
+        report.extend(self.time_range.is_none().then(|| missing("time range")));
+        if let Some((start, end)) = self.time_range {
+            report.extend(validate_time_range(start, end));
+            if let Some(origin) = self.time_origin {
+                report.extend(validate_time_origin(origin, start));
+            }
+            if let Some(snapshots) = &self.network_snapshots {
+                let origin = self.time_origin.unwrap_or(start.saturating_sub(1));
+                report.extend(validate_snapshot_times(snapshots, origin, end));
+            }
+        }
+        if let Some(delta) = self.default_delta {
+            report.extend(validate_default_delta(delta));
+        }
+        report.into_result()?;
+        let input_paths: Vec<PathBuf> = self
+            .input_paths()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+
+        let (
+            Some(behaviours),
+            Some(beliefs),
+            Some(agents),
+            Some(prs),
+            Some((start_time, end_time)),
+        ) = (
+            self.behaviours,
+            self.beliefs,
+            self.agents,
+            self.prs,
+            self.time_range,
+        )
+        else {
+            unreachable!("missing inputs are reported above")
+        };
+        let time_origin = self.time_origin.unwrap_or(start_time - 1);
+        let seed = self.seed.unwrap_or_else(rand::random);
+        // An output whose format cannot be told fails before anything is
+        // loaded
+        let output = match self.output {
+            Some(Output::Path(path)) => Some(Output::Settings(OutputSettings {
+                compression: Compression::for_path(&path)?,
+                path,
+            })),
+            output => output,
+        };
+
+        let interventions_path = self.interventions.as_ref().and_then(Input::path);
+        let files: BTreeMap<&'static str, PathBuf> = [
+            ("behaviours", behaviours.path()),
+            ("beliefs", beliefs.path()),
+            ("agents", agents.path()),
+            ("performance relationships", prs.path()),
+            ("interventions", interventions_path),
+        ]
+        .into_iter()
+        .filter_map(|(input, path)| Some((input, path?.to_path_buf())))
+        .collect();
+        let input_files = input_paths
+            .iter()
+            .map(|path| {
+                InputFile::read(path).map_err(|source| ConceptError::Io {
+                    path: path.clone(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for file in &input_files {
+            log::debug!("Checksum of {}: {}", file.path.display(), file.checksum);
+        }
+
+        let mut behaviour_specs = behaviours.load(load_behaviours_from_path)?;
+        let mut belief_specs = beliefs.load(load_beliefs_from_path)?;
+        let mut prs_specs = prs.load(load_prs_from_path)?;
+        let intervention_specs = self
+            .interventions
+            .map(|interventions| interventions.load(load_interventions_from_path))
+            .transpose()?;
+        let loaded_beliefs: UuidSet = belief_specs.iter().map(|b| b.uuid).collect();
+        let restriction = &self.restriction;
+        let restriction_report = restriction.validate(&belief_specs, &behaviour_specs);
+        let mut dropped = DroppedReferences::default();
+        restriction.restrict_specs(
+            &mut behaviour_specs,
+            &mut belief_specs,
+            &mut prs_specs,
+            &mut dropped,
+        );
+        let index = ModelIndex::new(
+            belief_specs.iter().map(|b| b.uuid).collect(),
+            behaviour_specs.iter().map(|b| b.uuid).collect(),
+        );
+        let mut report = validate_specs(&behaviour_specs, &belief_specs, &prs_specs, &index);
+        report.files = files;
+        report.extend(self.prs_modifications.validate(&index).issues);
+        report.extend(restriction_report.issues);
+        if let Some(threshold) = self.sign_consistency_threshold {
+            report.warnings.extend(check_sign_consistency(
+                &belief_specs,
+                &behaviour_specs,
+                &prs_specs,
+                threshold,
+            ));
+        }
+        let initialization = match self.initial_summary {
+            Some((summary, tick)) => {
+                let summary = match summary {
+                    Summary::Path(path) => load_summary_from_path(&path)?,
+                    Summary::Specs(specs) => *specs,
+                };
+                SummaryActivations::new(&summary, tick, &index, &loaded_beliefs, seed)
+                    .map_err(|issues| report.extend(issues))
+                    .ok()
+            }
+            None => None,
+        };
+        let validation_warnings = report.warnings.clone();
+        let relationship_scaling = self
+            .relationship_scale
+            .map(|factor| scale_relationships(&mut belief_specs, factor));
+
+        let behaviours = behaviours_from_specs(&behaviour_specs);
+        let beliefs = beliefs_from_specs(&belief_specs, &behaviours, &index);
+
+        log::info!("Reading agents");
+        match self.friend_normalization {
+            FriendNormalization::None => {}
+            FriendNormalization::Max => {
+                log::info!("Dividing the friend weights of each agent by their maximum")
+            }
+            FriendNormalization::Sum => {
+                log::info!("Dividing the friend weights of each agent by their sum")
+            }
+        }
+        let missing_deltas = MissingDeltas::new(&belief_specs, &index, self.default_delta, seed);
+        let mut loader = AgentLoader::new(
+            &beliefs,
+            &behaviours,
+            &index,
+            time_origin,
+            start_time,
+            &missing_deltas,
+            self.friend_normalization,
+        );
+        let filters = &self.agent_filters;
+        let mut initialized = 0;
+        let mut filter = |mut spec: AgentSpec, matched: bool| {
+            if matched {
+                restriction.restrict_agent(&mut spec, &mut dropped);
+                if let Some(initialization) = &initialization {
+                    initialization.initialize(&mut spec, time_origin);
+                    initialized += 1;
+                }
+                loader.push(spec)
+            } else {
+                loader.exclude(spec.uuid)
+            }
+        };
+        let window = self.history_window.keeping(time_origin);
+        let skipped = match agents {
+            Input::Path(path) if filters.is_empty() => {
+                for_each_agent_in_window_from_path(&path, window, |spec| filter(spec, true))?
+            }
+            Input::Path(path) => for_each_matching_agent_from_path(&path, filters, window, filter)?,
+            Input::Specs(specs) => {
+                let mut skipped = SkippedHistory::default();
+                for mut spec in specs {
+                    skipped += window.retain(&mut spec);
+                    let matched = matches_all(filters, &spec_fields(&spec));
+                    filter(spec, matched)
+                }
+                skipped
+            }
+        };
+        let history_trimming = (!window.is_empty()).then(|| {
+            log::info!(
+                "Skipped {} ticks of activations before {} and {} actions before {} as the agents were read",
+                skipped.activations,
+                window.activations_from.map_or("the start".to_string(), |t| t.to_string()),
+                skipped.actions,
+                window.actions_from.map_or("the start".to_string(), |t| t.to_string()),
+            );
+            HistoryTrimming {
+                activations_from: window.activations_from,
+                actions_from: window.actions_from,
+                skipped_activations: skipped.activations,
+                skipped_actions: skipped.actions,
+            }
+        });
+        let agent_filtering = (!filters.is_empty()).then(|| loader.drop_excluded_friends(filters));
+        let restriction = (!restriction.is_empty()).then(|| restriction.finish(dropped));
+        let (agents, friend_pruning, generated, activation_caps) =
+            loader.finish(report, self.max_friends_per_agent)?;
+        let delta_generation = missing_deltas.generation(&generated);
+        let activation_initialization =
+            initialization.map(|initialization| initialization.initialization(initialized));
+        let interventions = intervention_specs
+            .map(|specs| {
+                Interventions::new(&specs, &agents, &index, start_time, end_time).map_err(
+                    |issues| {
+                        let mut report = ValidationReport::default();
+                        report.extend(issues);
+                        ConceptError::Validation(report)
+                    },
+                )
+            })
+            .transpose()?;
+        let mut prs = PrsMatrix::from_specs(&prs_specs, index);
+        prs.modify(&self.prs_modifications);
+        let input_summary = InputSummary::new(&agents, &activation_caps, &beliefs, &prs);
+        input_summary.log();
+
+        let (output, output_path) = match output {
+            Some(Output::Settings(settings)) => (
+                Some(settings.open().map_err(|source| ConceptError::Io {
+                    path: settings.path.clone(),
+                    source,
+                })?),
+                Some(settings.path),
+            ),
+            Some(Output::Sink(sink)) => (Some(sink), None),
+            Some(Output::Path(_)) => unreachable!("output paths are resolved above"),
+            None => (None, None),
+        };
+
+        Ok(Configuration {
+            run_id: self.run_id.unwrap_or_else(new_run_id),
+            seed,
+            behaviours,
+            beliefs,
+            agents,
+            activation_caps,
+            prs,
+            start_time,
+            end_time,
+            time_origin,
+            output,
+            output_path,
+            history_trimming,
+            friend_normalization: self.friend_normalization,
+            friend_pruning,
+            prs_modifications: (!self.prs_modifications.is_empty())
+                .then_some(self.prs_modifications),
+            relationship_scaling,
+            validation_warnings,
+            default_delta: self.default_delta,
+            delta_generation,
+            activation_initialization,
+            agent_filtering,
+            restriction,
+            network_snapshots: self.network_snapshots,
+            interventions,
+            input_summary,
+            input_files,
+        })
+    }
+}
+
+/// A new identifier for a run, as a [ULID](https://github.com/ulid/spec),
+/// which sorts by the time it was generated.
+pub fn new_run_id() -> String {
+    ulid::Ulid::new().to_string()
+}
+
+/// Check that the time range can be simulated.
+///
+/// Perception at `start` reads activations at `start - 1`, so `start` must
+/// be at least 1.
+fn validate_time_range(start: SimTime, end: SimTime) -> Option<ValidationIssue> {
+    (start == 0 || start > end).then_some(ValidationIssue::InvalidTimeRange { start, end })
+}
+
+/// Check that a default delta is positive, as every delta must be.
+fn validate_default_delta(delta: f64) -> Option<ValidationIssue> {
+    (delta.is_nan() || delta <= 0.0).then_some(ValidationIssue::InvalidDefault {
+        field: "delta",
+        value: delta,
+        range: "(0, inf)",
+    })
+}
+
+/// Check that the time origin is before the start time.
+fn validate_time_origin(origin: SimTime, start: SimTime) -> Option<ValidationIssue> {
+    (origin >= start).then_some(ValidationIssue::InvalidTimeOrigin { origin, start })
+}
+
+/// Check that every network snapshot is from the time origin to the end
+/// time.
+fn validate_snapshot_times(
+    snapshots: &NetworkSnapshots,
+    origin: SimTime,
+    end: SimTime,
+) -> impl Iterator<Item = ValidationIssue> + '_ {
+    snapshots
+        .ticks
+        .iter()
+        .filter(move |&&time| !(origin..=end).contains(&time))
+        .map(move |&time| ValidationIssue::InvalidSnapshotTime { time, origin, end })
+}
+
+/// Check that a value lies within `[min, max]`.
+fn check_range(
+    kind: &'static str,
+    uuid: Uuid,
+    field: &'static str,
+    value: f64,
+    (min, max, range): (f64, f64, &'static str),
+) -> Option<ValidationIssue> {
+    (!(min..=max).contains(&value)).then_some(ValidationIssue::OutOfRange {
+        kind,
+        uuid,
+        field,
+        value,
+        range,
+    })
+}
+
+/// The legal range of activations, perceptions and relationships.
+const UNIT_RANGE: (f64, f64, &str) = (-1.0, 1.0, "[-1, 1]");
+
+/// The legal range of friend weights.
+const WEIGHT_RANGE: (f64, f64, &str) = (0.0, 1.0, "[0, 1]");
+
+/// Find every pair of a [Belief] and a [Behaviour] whose perception and
+/// performance relationship have opposite signs and magnitudes both greater
+/// than `threshold`, logging a warning for each.
+///
+/// # Returns
+/// The warnings, in the order of the performance relationships.
+fn check_sign_consistency(
+    beliefs: &[BeliefSpec],
+    behaviours: &[BehaviourSpec],
+    prs: &[PerformanceRelationshipSpec],
+    threshold: f64,
+) -> Vec<ValidationWarning> {
+    let beliefs: UuidMap<&BeliefSpec> = beliefs.iter().map(|b| (b.uuid, b)).collect();
+    let behaviour_names: UuidMap<&str> = behaviours
+        .iter()
+        .map(|b| (b.uuid, b.name.as_str()))
+        .collect();
+    let warnings: Vec<ValidationWarning> = prs
+        .iter()
+        .filter_map(|spec| {
+            let belief = beliefs.get(&spec.belief_uuid)?;
+            let &perception = belief.perceptions.get(&spec.behaviour_uuid)?;
+            let disagree = perception.signum() != spec.value.signum()
+                && perception.abs() > threshold
+                && spec.value.abs() > threshold;
+            disagree.then(|| ValidationWarning::SignDisagreement {
+                belief: belief.uuid,
+                belief_name: belief.name.clone(),
+                behaviour: spec.behaviour_uuid,
+                behaviour_name: behaviour_names
+                    .get(&spec.behaviour_uuid)
+                    .map_or_else(String::new, |name| name.to_string()),
+                perception,
+                prs: spec.value,
+            })
+        })
+        .collect();
+    for warning in &warnings {
+        log::warn!("{warning}");
+    }
+    warnings
+}
+
+/// Check that a reference resolves, given whether its target is known.
+fn check_reference(
+    known: bool,
+    (kind, uuid, field): (&'static str, Uuid, &'static str),
+    target_kind: &'static str,
+    target: Uuid,
+) -> Option<ValidationIssue> {
+    (!known).then_some(ValidationIssue::UnknownReference {
+        kind,
+        uuid,
+        field,
+        target_kind,
+        target,
+    })
+}
+
+/// Check that no two specs of the same kind have the same UUID, reporting
+/// each UUID given more than once in the order it is first repeated.
+fn check_duplicates(
+    kind: &'static str,
+    uuids: impl IntoIterator<Item = Uuid>,
+) -> Vec<ValidationIssue> {
+    let mut seen = UuidSet::default();
+    let mut reported = UuidSet::default();
+    uuids
+        .into_iter()
+        .filter(|&uuid| !seen.insert(uuid) && reported.insert(uuid))
+        .map(|uuid| ValidationIssue::DuplicateUuid { kind, uuid })
+        .collect()
+}
+
+/// Check that the [BehaviourSpec]s, [BeliefSpec]s and
+/// [PerformanceRelationshipSpec]s are consistent with each other.
+///
+/// Every UUID must be unique, every reference must resolve and every value
+/// must be in range. The [AgentSpec]s are checked as they are loaded, by
+/// [AgentLoader].
+fn validate_specs(
+    behaviours: &[BehaviourSpec],
+    beliefs: &[BeliefSpec],
+    prs: &[PerformanceRelationshipSpec],
+    index: &ModelIndex,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    report.extend(check_duplicates(
+        "behaviour",
+        behaviours.iter().map(|b| b.uuid),
+    ));
+    report.extend(check_duplicates("belief", beliefs.iter().map(|b| b.uuid)));
+
+    for belief in beliefs {
+        for (&behaviour, &v) in &belief.perceptions {
+            let src = ("belief", belief.uuid, "perceptions");
+            report.extend(check_reference(
+                index.has_behaviour(&behaviour),
+                src,
+                "behaviour",
+                behaviour,
+            ));
+            report.extend(check_range(src.0, src.1, src.2, v, UNIT_RANGE));
+        }
+        for (&other, &v) in &belief.relationships {
+            let src = ("belief", belief.uuid, "relationships");
+            report.extend(check_reference(
+                index.has_belief(&other),
+                src,
+                "belief",
+                other,
+            ));
+            report.extend(check_range(src.0, src.1, src.2, v, UNIT_RANGE));
+        }
+        if let Some(distribution) = &belief.delta_distribution {
+            report.extend(distribution.validate(belief.uuid));
+        }
+    }
+
+    for spec in prs {
+        let src = ("performance relationship", spec.belief_uuid, "beliefUuid");
+        report.extend(check_reference(
+            index.has_belief(&spec.belief_uuid),
+            src,
+            "belief",
+            spec.belief_uuid,
+        ));
+        let src = (
+            "performance relationship",
+            spec.behaviour_uuid,
+            "behaviourUuid",
+        );
+        report.extend(check_reference(
+            index.has_behaviour(&spec.behaviour_uuid),
+            src,
+            "behaviour",
+            spec.behaviour_uuid,
+        ));
+    }
+
+    report
+}
+
+/// Check that the [AgentSpec]s are consistent with the [Belief]s and
+/// [Behaviour]s of a model, and with each other, so that they can be
+/// simulated from their initial activations at `origin`.
+pub(crate) fn validate_agents(
+    agents: &[AgentSpec],
+    index: &ModelIndex,
+    origin: SimTime,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    for agent in agents {
+        report.extend(validate_agent(agent, index, origin, &MissingDeltas::default()).issues);
+    }
+    report.extend(validate_friends(agents).issues);
+    report
+}
+
+/// Check that an [AgentSpec] is consistent with the [Belief]s and
+/// [Behaviour]s of a model, except for whether its friends exist, which is
+/// checked by [validate_friends].
+///
+/// Missing initial activations at `origin` and missing deltas are reported
+/// in the order of the [Belief]s in the [ModelIndex]. Deltas are not
+/// missing if they are filled by `missing_deltas`.
+fn validate_agent(
+    agent: &AgentSpec,
+    index: &ModelIndex,
+    origin: SimTime,
+    missing_deltas: &MissingDeltas,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    for &behaviour in agent.actions.values() {
+        let src = ("agent", agent.uuid, "actions");
+        report.extend(check_reference(
+            index.has_behaviour(&behaviour),
+            src,
+            "behaviour",
+            behaviour,
+        ));
+    }
+    for acts in agent.activations.values() {
+        for (&belief, &v) in acts {
+            let src = ("agent", agent.uuid, "activations");
+            report.extend(check_reference(
+                index.has_belief(&belief),
+                src,
+                "belief",
+                belief,
+            ));
+            report.extend(check_range(src.0, src.1, src.2, v, UNIT_RANGE));
+        }
+    }
+    for (&belief, &v) in &agent.deltas {
+        let src = ("agent", agent.uuid, "deltas");
+        report.extend(check_reference(
+            index.has_belief(&belief),
+            src,
+            "belief",
+            belief,
+        ));
+        if v <= 0.0 {
+            report.extend([ValidationIssue::OutOfRange {
+                kind: src.0,
+                uuid: src.1,
+                field: src.2,
+                value: v,
+                range: "(0, inf)",
+            }]);
+        }
+    }
+    for &w in agent.friends.values() {
+        report.extend(check_range("agent", agent.uuid, "friends", w, WEIGHT_RANGE));
+    }
+    for (field, caps) in [
+        ("activationFloors", &agent.activation_floors),
+        ("activationCeilings", &agent.activation_ceilings),
+    ] {
+        for (&belief, &v) in caps {
+            report.extend(check_reference(
+                index.has_belief(&belief),
+                ("agent", agent.uuid, field),
+                "belief",
+                belief,
+            ));
+            report.extend(check_range("agent", agent.uuid, field, v, UNIT_RANGE));
+        }
+    }
+    for (&belief, &floor) in &agent.activation_floors {
+        match agent.activation_ceilings.get(&belief) {
+            Some(&ceiling) if floor > ceiling => {
+                report.extend([ValidationIssue::CrossedCaps {
+                    agent: agent.uuid,
+                    belief,
+                    floor,
+                    ceiling,
+                }]);
+            }
+            _ => {}
+        }
+    }
+
+    let initial = agent.activations.get(&origin);
+    for (b, &belief) in index.belief_uuids().iter().enumerate() {
+        if !initial.is_some_and(|acts| acts.contains_key(&belief)) {
+            report.extend([ValidationIssue::MissingActivation {
+                agent: agent.uuid,
+                belief,
+                time: origin,
+            }]);
+        }
+        if !missing_deltas.fills(b) && !agent.deltas.contains_key(&belief) {
+            report.extend([ValidationIssue::MissingDelta {
+                agent: agent.uuid,
+                belief,
+            }]);
+        }
+    }
+
+    report
+}
+
+/// Check that no two [AgentSpec]s have the same UUID, and that the friends
+/// of every [AgentSpec] are among them.
+fn validate_friends(agents: &[AgentSpec]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    report.extend(check_duplicates("agent", agents.iter().map(|a| a.uuid)));
+    let agent_uuids: UuidSet = agents.iter().map(|a| a.uuid).collect();
+    for agent in agents {
+        for &friend in agent.friends.keys() {
+            let src = ("agent", agent.uuid, "friends");
+            report.extend(check_reference(
+                agent_uuids.contains(&friend),
+                src,
+                "agent",
+                friend,
+            ));
+        }
+    }
+    report
+}
+
+/// The number of [AgentSpec]s that [AgentLoader] validates and resolves in
+/// parallel at once.
+const AGENT_BATCH_SIZE: usize = 4096;
+
+/// An [AgentSpec] with its [Belief]s and [Behaviour]s replaced by their
+/// positions in the model.
+///
+/// Unlike an [Agent], this holds no [Rc](std::rc::Rc)s, so it can be
+/// prepared on any thread.
+struct ResolvedAgent {
+    uuid: Uuid,
+    actions: Vec<(SimTime, usize)>,
+    activations: Vec<(SimTime, usize, f64)>,
+    deltas: Vec<(usize, f64)>,
+}
+
+impl ResolvedAgent {
+    /// Resolve a valid [AgentSpec].
+    fn new(spec: &AgentSpec, index: &ModelIndex) -> Self {
+        let belief = |uuid| index.belief(uuid).expect("the agent is valid");
+        let behaviour = |uuid| index.behaviour(uuid).expect("the agent is valid");
+        ResolvedAgent {
+            uuid: spec.uuid,
+            actions: spec
+                .actions
+                .iter()
+                .map(|(&time, b)| (time, behaviour(b)))
+                .collect(),
+            activations: spec
+                .activations
+                .iter()
+                .flat_map(|(&time, acts)| acts.iter().map(move |(b, &v)| (time, belief(b), v)))
+                .collect(),
+            deltas: spec.deltas.iter().map(|(b, &v)| (belief(b), v)).collect(),
+        }
+    }
+
+    /// Copy the activations and actions at `origin` to `to`, replacing any
+    /// there.
+    fn carry_forward(&mut self, origin: SimTime, to: SimTime) {
+        if origin == to {
+            return;
+        }
+        self.actions.retain(|&(time, _)| time != to);
+        self.activations.retain(|&(time, _, _)| time != to);
+        let actions: Vec<(SimTime, usize)> = self
+            .actions
+            .iter()
+            .filter(|&&(time, _)| time == origin)
+            .map(|&(_, b)| (to, b))
+            .collect();
+        let activations: Vec<(SimTime, usize, f64)> = self
+            .activations
+            .iter()
+            .filter(|&&(time, _, _)| time == origin)
+            .map(|&(_, b, v)| (to, b, v))
+            .collect();
+        self.actions.extend(actions);
+        self.activations.extend(activations);
+    }
+
+    /// Give the [Agent] a delta from [MissingDeltas] for each of the
+    /// `n_beliefs` [Belief]s it has no delta for.
+    ///
+    /// # Returns
+    /// The number of deltas given the default delta, and the positions of
+    /// the [Belief]s whose deltas were generated.
+    fn fill_deltas(&mut self, missing: &MissingDeltas) -> (usize, Vec<usize>) {
+        let mut has_delta = vec![false; missing.belief_uuids.len()];
+        for &(b, _) in &self.deltas {
+            has_delta[b] = true;
+        }
+        let (mut defaulted, mut generated) = (0, Vec::new());
+        for (b, _) in has_delta.iter().enumerate().filter(|&(_, &has)| !has) {
+            if let Some(distribution) = &missing.distributions[b] {
+                let delta = distribution.sample(missing.seed, self.uuid, missing.belief_uuids[b]);
+                self.deltas.push((b, delta));
+                generated.push(b);
+            } else if let Some(delta) = missing.default {
+                self.deltas.push((b, delta));
+                defaulted += 1;
+            }
+        }
+        (defaulted, generated)
+    }
+
+    /// Create the [Agent].
+    fn to_agent(&self, beliefs: &[BeliefPtr], behaviours: &[BehaviourPtr]) -> AgentPtr {
+        let mut agent = BasicAgent::new_with_uuid(self.uuid);
+        for &(time, b) in &self.actions {
+            agent.set_action(time, Some(behaviours[b].clone()));
+        }
+        for &(time, b, v) in &self.activations {
+            agent
+                .set_activation(time, beliefs[b].clone(), Some(v))
+                .unwrap();
+        }
+        for &(b, v) in &self.deltas {
+            agent.set_delta(beliefs[b].clone(), Some(v)).unwrap();
+        }
+        agent.into()
+    }
+}
+
+/// How the deltas missing from [AgentSpec]s are filled: drawn from the
+/// [DeltaDistribution] of the [Belief] if it has one, or else given the
+/// default delta if there is one.
+#[derive(Default)]
+struct MissingDeltas {
+    /// The UUIDs of the [Belief]s, by position.
+    belief_uuids: Vec<Uuid>,
+    /// The [DeltaDistribution] of each [Belief], by position.
+    distributions: Vec<Option<DeltaDistribution>>,
+    default: Option<f64>,
+    seed: u64,
+}
+
+impl MissingDeltas {
+    fn new(beliefs: &[BeliefSpec], index: &ModelIndex, default: Option<f64>, seed: u64) -> Self {
+        let mut distributions = vec![None; index.belief_uuids().len()];
+        for belief in beliefs {
+            if let Some(b) = index.belief(&belief.uuid) {
+                distributions[b] = belief.delta_distribution;
+            }
+        }
+        MissingDeltas {
+            belief_uuids: index.belief_uuids().to_vec(),
+            distributions,
+            default,
+            seed,
+        }
+    }
+
+    /// Whether a missing delta for the [Belief] at position `b` is filled.
+    fn fills(&self, b: usize) -> bool {
+        self.default.is_some() || self.distributions.get(b).is_some_and(Option::is_some)
+    }
+
+    /// How the deltas were generated, given the number generated for each
+    /// [Belief] by position, logging how many were for each [Belief].
+    ///
+    /// # Returns
+    /// [None] if no [Belief] has a [DeltaDistribution].
+    fn generation(&self, generated: &[usize]) -> Option<DeltaGeneration> {
+        let mut beliefs: Vec<GeneratedDeltas> = self
+            .distributions
+            .iter()
+            .enumerate()
+            .filter_map(|(b, distribution)| {
+                distribution.map(|distribution| GeneratedDeltas {
+                    belief: self.belief_uuids[b],
+                    distribution,
+                    generated: generated[b],
+                })
+            })
+            .collect();
+        if beliefs.is_empty() {
+            return None;
+        }
+        beliefs.sort_unstable_by_key(|generated| generated.belief);
+        for generated in &beliefs {
+            log::info!(
+                "Generated {} missing deltas for belief {} from {:?}",
+                generated.generated,
+                generated.belief,
+                generated.distribution
+            );
+        }
+        Some(DeltaGeneration {
+            seed: self.seed,
+            beliefs,
+        })
+    }
+}
+
+/// Validates and converts [AgentSpec]s to [Agent]s as they are loaded.
+///
+/// [AgentSpec]s are collected into batches, which are validated and
+/// resolved in parallel before the [Agent]s are created on this thread, in
+/// the order they were loaded. Only the UUID and friends of each
+/// [AgentSpec] are kept, so that friends can be checked and linked once
+/// every [Agent] has been converted.
+struct AgentLoader<'a> {
+    beliefs: &'a [BeliefPtr],
+    behaviours: &'a [BehaviourPtr],
+    index: &'a ModelIndex,
+    origin: SimTime,
+    start_time: SimTime,
+    missing_deltas: &'a MissingDeltas,
+    friend_normalization: FriendNormalization,
+    /// The number of deltas given the default delta.
+    defaulted_deltas: usize,
+    /// The number of deltas generated for each [Belief], by position.
+    generated_deltas: Vec<usize>,
+    pending: Vec<AgentSpec>,
+    report: ValidationReport,
+    agents: Vec<AgentPtr>,
+    /// The floors and ceilings of each [Agent], in the same order.
+    caps: Vec<ActivationCaps>,
+    friends: Vec<AgentSpec>,
+    /// The UUIDs of the [AgentSpec]s excluded by [AgentFilter]s.
+    excluded: UuidSet,
+}
+
+impl<'a> AgentLoader<'a> {
+    fn new(
+        beliefs: &'a [BeliefPtr],
+        behaviours: &'a [BehaviourPtr],
+        index: &'a ModelIndex,
+        origin: SimTime,
+        start_time: SimTime,
+        missing_deltas: &'a MissingDeltas,
+        friend_normalization: FriendNormalization,
+    ) -> Self {
+        AgentLoader {
+            beliefs,
+            behaviours,
+            index,
+            origin,
+            start_time,
+            missing_deltas,
+            friend_normalization,
+            defaulted_deltas: 0,
+            generated_deltas: vec![0; beliefs.len()],
+            pending: Vec::with_capacity(AGENT_BATCH_SIZE),
+            report: ValidationReport::default(),
+            agents: Vec::new(),
+            caps: Vec::new(),
+            friends: Vec::new(),
+            excluded: UuidSet::default(),
+        }
+    }
+
+    /// Add an [AgentSpec], converting the pending batch if it is full.
+    fn push(&mut self, spec: AgentSpec) {
+        self.pending.push(spec);
+        if self.pending.len() == AGENT_BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    /// Record an [AgentSpec] excluded by [AgentFilter]s, which is not
+    /// converted.
+    fn exclude(&mut self, uuid: Uuid) {
+        self.excluded.insert(uuid);
+    }
+
+    /// Drop the friendships of the [Agent]s with those excluded by
+    /// `filters`, logging how many were dropped.
+    fn drop_excluded_friends(&mut self, filters: &[AgentFilter]) -> AgentFiltering {
+        self.flush();
+        let excluded = &self.excluded;
+        let dropped_friendships = self
+            .friends
+            .iter_mut()
+            .map(|spec| {
+                let n_friends = spec.friends.len();
+                spec.friends.retain(|uuid, _| !excluded.contains(uuid));
+                n_friends - spec.friends.len()
+            })
+            .sum();
+        let filtering = AgentFiltering {
+            filters: filters.to_vec(),
+            agents_read: self.friends.len() + excluded.len(),
+            agents_kept: self.friends.len(),
+            dropped_friendships,
+        };
+        log::info!(
+            "Kept {} of {} agents matching the filters, dropping {} friendships with the others",
+            filtering.agents_kept,
+            filtering.agents_read,
+            filtering.dropped_friendships
+        );
+        filtering
+    }
+
+    /// Validate and resolve the pending [AgentSpec]s in parallel, then, if
+    /// no issues have been found so far, create their [Agent]s.
+    fn flush(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        let (index, origin, start_time) = (self.index, self.origin, self.start_time);
+        let (missing_deltas, normalization) = (self.missing_deltas, self.friend_normalization);
+        let batch: Vec<_> = pending
+            .into_par_iter()
+            .map(|mut spec| {
+                normalization.normalize(&mut spec.friends);
+                let report = validate_agent(&spec, index, origin, missing_deltas);
+                let resolved = report.is_empty().then(|| {
+                    let mut resolved = ResolvedAgent::new(&spec, index);
+                    resolved.carry_forward(origin, start_time - 1);
+                    let filled = resolved.fill_deltas(missing_deltas);
+                    let caps = ActivationCaps::new(&spec, index);
+                    (resolved, filled, caps)
+                });
+                let friends = AgentSpec {
+                    uuid: spec.uuid,
+                    actions: HashMap::new(),
+                    activations: HashMap::new(),
+                    deltas: HashMap::new(),
+                    friends: std::mem::take(&mut spec.friends),
+                    activation_floors: HashMap::new(),
+                    activation_ceilings: HashMap::new(),
+                };
+                (report, resolved, friends)
+            })
+            .collect();
+
+        for (report, resolved, friends) in batch {
+            self.report.extend(report.issues);
+            if let (true, Some((resolved, (defaulted, generated), caps))) =
+                (self.report.is_empty(), resolved)
+            {
+                self.agents
+                    .push(resolved.to_agent(self.beliefs, self.behaviours));
+                self.caps.push(caps);
+                self.defaulted_deltas += defaulted;
+                for b in generated {
+                    self.generated_deltas[b] += 1;
+                }
+            }
+            self.friends.push(friends);
+        }
+    }
+
+    /// Check and link the friends of every [Agent].
+    ///
+    /// The friends of each [Agent] are resolved to positions and pruned in
+    /// parallel, then linked on this thread.
+    ///
+    /// # Arguments
+    /// - `report`: The issues found in the rest of the model, which are
+    ///   reported along with those found in the [AgentSpec]s.
+    /// - `max_friends`: The most friends to keep for each [Agent], if they
+    ///   are pruned.
+    ///
+    /// # Returns
+    /// The [Agent]s, how their friends were pruned, the number of deltas
+    /// generated for each [Belief] by position, and the floors and ceilings
+    /// of each [Agent], or a [ConceptError::Validation] if any issues were
+    /// found.
+    #[allow(clippy::type_complexity)]
+    fn finish(
+        mut self,
+        mut report: ValidationReport,
+        max_friends: Option<usize>,
+    ) -> Result<
+        (
+            Vec<AgentPtr>,
+            Option<FriendPruning>,
+            Vec<usize>,
+            Vec<ActivationCaps>,
+        ),
+        ConceptError,
+    > {
+        self.flush();
+        report.extend(self.report.issues);
+        report.extend(validate_friends(&self.friends).issues);
+        report.into_result()?;
+        if let (Some(delta), n @ 1..) = (self.missing_deltas.default, self.defaulted_deltas) {
+            log::info!("Gave {n} missing deltas the default delta {delta}");
+        }
+
+        let agent_index: UuidMap<usize> = self
+            .friends
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| (spec.uuid, i))
+            .collect();
+        let specs = &self.friends;
+        let friends: Vec<(Vec<(usize, f64)>, usize)> = specs
+            .par_iter()
+            .map(|spec| {
+                let mut friends: Vec<(usize, f64)> = spec
+                    .friends
+                    .iter()
+                    .map(|(uuid, &w)| (agent_index[uuid], w))
+                    .collect();
+                let dropped = match max_friends {
+                    Some(k) if friends.len() > k => {
+                        friends.sort_unstable_by(|(a, wa), (b, wb)| {
+                            wb.total_cmp(wa).then(specs[*a].uuid.cmp(&specs[*b].uuid))
+                        });
+                        let dropped = friends.len() - k;
+                        friends.truncate(k);
+                        dropped
+                    }
+                    _ => 0,
+                };
+                (friends, dropped)
+            })
+            .collect();
+
+        let friend_pruning = max_friends.map(|k| FriendPruning {
+            max_friends_per_agent: k,
+            dropped_edges: friends.iter().map(|(_, dropped)| dropped).sum(),
+            affected_agents: friends.iter().filter(|(_, dropped)| *dropped > 0).count(),
+        });
+        if let Some(pruning) = &friend_pruning {
+            log::info!(
+                "Pruned friends to {} per agent: dropped {} friendships of {} agents",
+                pruning.max_friends_per_agent,
+                pruning.dropped_edges,
+                pruning.affected_agents
+            );
+        }
+
+        for (agent, (friends, _)) in self.agents.iter().zip(friends) {
+            let mut agent = agent.borrow_mut();
+            for (friend, w) in friends {
+                agent
+                    .set_friend_weight(self.agents[friend].clone(), Some(w))
+                    .unwrap();
+            }
+        }
+        Ok((
+            self.agents,
+            friend_pruning,
+            self.generated_deltas,
+            self.caps,
+        ))
+    }
+}
+
+fn behaviours_from_specs(specs: &[BehaviourSpec]) -> Vec<BehaviourPtr> {
+    specs
+        .iter()
+        .map(|spec| spec.to_basic_behaviour().into())
+        .collect()
+}
+
+/// Multiply every relationship of the [Belief]s by `factor`, clamping the
+/// results to [-1, 1], and log the scaling.
+fn scale_relationships(specs: &mut [BeliefSpec], factor: f64) -> RelationshipScaling {
+    log::info!("Scaling every belief relationship by {factor}");
+    let clamped = specs
+        .iter_mut()
+        .map(|spec| spec.scale_relationships(factor))
+        .sum();
+    if clamped > 0 {
+        log::warn!("Clamped {clamped} scaled belief relationships to [-1, 1]");
+    }
+    RelationshipScaling { factor, clamped }
+}
+
+/// Create the [Belief]s, linking their relationships with each other.
+fn beliefs_from_specs(
+    specs: &[BeliefSpec],
+    behaviours: &[BehaviourPtr],
+    index: &ModelIndex,
+) -> Vec<BeliefPtr> {
+    let beliefs: Vec<BeliefPtr> = specs
+        .iter()
+        .map(|spec| spec.to_basic_belief(behaviours))
+        .collect();
+
+    for (spec, belief) in specs.iter().zip(&beliefs) {
+        let mut belief = belief.borrow_mut();
+        for (other, &v) in &spec.relationships {
+            if let Some(i) = index.belief(other) {
+                belief
+                    .set_relationship(beliefs[i].clone(), Some(v))
+                    .unwrap();
+            }
+        }
+    }
+    beliefs
+}
+
+/// Create [Agent]s from valid [AgentSpec]s and link their friends.
+///
+/// # Returns
+/// The [Agent]s, and the floors and ceilings of their activations.
+pub(crate) fn agents_from_specs(
+    specs: &[AgentSpec],
+    beliefs: &[BeliefPtr],
+    behaviours: &[BehaviourPtr],
+    index: &ModelIndex,
+) -> (Vec<AgentPtr>, Vec<ActivationCaps>) {
+    let agents: Vec<AgentPtr> = specs
+        .iter()
+        .map(|spec| ResolvedAgent::new(spec, index).to_agent(beliefs, behaviours))
+        .collect();
+    let uuid_agents: UuidMap<AgentPtr> = agents
+        .iter()
+        .map(|a| (*a.borrow().uuid(), a.clone()))
+        .collect();
+
+    specs
+        .iter()
+        .for_each(|spec| spec.link_friends(&uuid_agents));
+
+    let caps = specs
+        .iter()
+        .map(|spec| ActivationCaps::new(spec, index))
+        .collect();
+    (agents, caps)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "config", name]
+            .iter()
+            .collect()
+    }
+
+    fn fixture_builder() -> ConfigurationBuilder {
+        ConfigurationBuilder::new()
+            .behaviours_from_path(fixture("behaviours.json"))
+            .beliefs_from_path(fixture("beliefs.json"))
+            .agents_from_path(fixture("agents.json"))
+            .prs_from_path(fixture("prs.json"))
+    }
+
+    fn specs() -> (Vec<BehaviourSpec>, Vec<BeliefSpec>, Vec<AgentSpec>) {
+        let behaviour = BehaviourSpec {
+            name: "b1".to_string(),
+            uuid: Uuid::new_v4(),
+        };
+        let belief = BeliefSpec {
+            name: "b1".to_string(),
+            uuid: Uuid::new_v4(),
+            perceptions: HashMap::from([(behaviour.uuid, 0.5)]),
+            relationships: HashMap::new(),
+            delta_distribution: None,
+        };
+        let agent = AgentSpec {
+            uuid: Uuid::new_v4(),
+            actions: HashMap::from([(0, behaviour.uuid)]),
+            activations: HashMap::from([(0, HashMap::from([(belief.uuid, 0.2)]))]),
+            deltas: HashMap::from([(belief.uuid, 1.0)]),
+            friends: HashMap::new(),
+            activation_floors: HashMap::new(),
+            activation_ceilings: HashMap::new(),
+        };
+        (vec![behaviour], vec![belief], vec![agent])
+    }
+
+    pub(crate) fn temp_path(suffix: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("concept-{}{suffix}", Uuid::new_v4()))
+    }
+
+    /// Validate a whole model, as [ConfigurationBuilder::build] does.
+    fn validate_model(
+        behaviours: &[BehaviourSpec],
+        beliefs: &[BeliefSpec],
+        agents: &[AgentSpec],
+        prs: &[PerformanceRelationshipSpec],
+        start_time: SimTime,
+    ) -> ValidationReport {
+        let index = ModelIndex::new(
+            beliefs.iter().map(|b| b.uuid).collect(),
+            behaviours.iter().map(|b| b.uuid).collect(),
+        );
+        let mut report = validate_specs(behaviours, beliefs, prs, &index);
+        report.extend(validate_agents(agents, &index, start_time - 1).issues);
+        report
+    }
+
+    /// The single issue in a [ValidationReport].
+    fn single_issue(report: ValidationReport) -> ValidationIssue {
+        assert_eq!(report.issues.len(), 1, "{:?}", report.issues);
+        report.issues.into_iter().next().unwrap()
+    }
+
+    /// A [ConfigurationBuilder] for a small in-memory model of three
+    /// [Agent]s who are all friends, simulated from time 1 to 3 with its
+    /// output discarded.
+    ///
+    /// The UUIDs are fixed, so separate builders describe the same model.
+    pub(crate) fn small_builder() -> ConfigurationBuilder {
+        let behaviours: Vec<BehaviourSpec> = (0..2)
+            .map(|i| BehaviourSpec {
+                name: format!("behaviour {i}"),
+                uuid: Uuid::from_u128(0x100 + i),
+            })
+            .collect();
+        let belief_uuids: Vec<Uuid> = (0..2).map(|i| Uuid::from_u128(0x200 + i)).collect();
+        let beliefs: Vec<BeliefSpec> = belief_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| BeliefSpec {
+                name: format!("belief {i}"),
+                uuid,
+                perceptions: HashMap::from([(behaviours[i].uuid, 0.5)]),
+                relationships: HashMap::from([(belief_uuids[1 - i], -0.2)]),
+                delta_distribution: None,
+            })
+            .collect();
+        let agent_uuids: Vec<Uuid> = (0..3).map(|i| Uuid::from_u128(0x300 + i)).collect();
+        let agents: Vec<AgentSpec> = agent_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| AgentSpec {
+                uuid,
+                actions: HashMap::from([(0, behaviours[i % 2].uuid)]),
+                activations: HashMap::from([(
+                    0,
+                    belief_uuids.iter().map(|&b| (b, 0.25 * i as f64)).collect(),
+                )]),
+                deltas: belief_uuids.iter().map(|&b| (b, 1.0)).collect(),
+                friends: agent_uuids
+                    .iter()
+                    .filter(|&&f| f != uuid)
+                    .map(|&f| (f, 0.5))
+                    .collect(),
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+        let prs: Vec<PerformanceRelationshipSpec> = belief_uuids
+            .iter()
+            .zip(&behaviours)
+            .map(|(&belief_uuid, behaviour)| PerformanceRelationshipSpec {
+                behaviour_uuid: behaviour.uuid,
+                belief_uuid,
+                value: 1.0,
+            })
+            .collect();
+
+        ConfigurationBuilder::new()
+            .with_behaviours(behaviours)
+            .with_beliefs(beliefs)
+            .with_agents(agents)
+            .with_prs(prs)
+            .time_range(1, 3)
+            .output(Box::new(Vec::new()))
+    }
+
+    /// [small_builder] with five [Agent]s instead, where the first is friends
+    /// with every other with weights 0.1, 0.4, 0.4 and 0.2, and the others
+    /// are only friends with the first.
+    pub(crate) fn hub_builder() -> ConfigurationBuilder {
+        let agent_uuids: Vec<Uuid> = (0..5).map(|i| Uuid::from_u128(0x300 + i)).collect();
+        let belief_uuids: Vec<Uuid> = (0..2).map(|i| Uuid::from_u128(0x200 + i)).collect();
+        let hub_weights = [0.1, 0.4, 0.4, 0.2];
+        let agents: Vec<AgentSpec> = agent_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| AgentSpec {
+                uuid,
+                actions: HashMap::from([(0, Uuid::from_u128(0x100 + i as u128 % 2))]),
+                activations: HashMap::from([(
+                    0,
+                    belief_uuids.iter().map(|&b| (b, 0.2 * i as f64)).collect(),
+                )]),
+                deltas: belief_uuids.iter().map(|&b| (b, 1.0)).collect(),
+                friends: if i == 0 {
+                    agent_uuids[1..].iter().copied().zip(hub_weights).collect()
+                } else {
+                    HashMap::from([(agent_uuids[0], 0.5)])
+                },
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+        small_builder().with_agents(agents)
+    }
+
+    /// The friends of the `i`th [Agent] and their weights, ordered by UUID.
+    fn friends_of(config: &Configuration, i: usize) -> Vec<(Uuid, f64)> {
+        let mut friends: Vec<(Uuid, f64)> = config.agents[i]
+            .borrow()
+            .get_friends()
+            .iter()
+            .map(|(a, &w)| (*a.borrow().uuid(), w))
+            .collect();
+        friends.sort_by_key(|(uuid, _)| *uuid);
+        friends
+    }
+
+    #[test]
+    fn max_friends_per_agent_keeps_highest_weights_then_lowest_uuids() {
+        let config = hub_builder().max_friends_per_agent(1).build().unwrap();
+        assert_eq!(
+            config.friend_pruning,
+            Some(FriendPruning {
+                max_friends_per_agent: 1,
+                dropped_edges: 3,
+                affected_agents: 1,
+            })
+        );
+        // 0x302 and 0x303 both have the highest weight
+        assert_eq!(friends_of(&config, 0), vec![(Uuid::from_u128(0x302), 0.4)]);
+        for i in 1..5 {
+            assert_eq!(friends_of(&config, i), vec![(Uuid::from_u128(0x300), 0.5)]);
+        }
+
+        let config = hub_builder().max_friends_per_agent(2).build().unwrap();
+        assert_eq!(
+            friends_of(&config, 0),
+            vec![(Uuid::from_u128(0x302), 0.4), (Uuid::from_u128(0x303), 0.4)]
+        );
+
+        let config = hub_builder().build().unwrap();
+        assert_eq!(config.friend_pruning, None);
+        assert_eq!(friends_of(&config, 0).len(), 4);
+    }
+
+    /// The [hub_builder] with friend weights given as contact counts: the
+    /// hub has three friends, two agents have the hub as a friend with the
+    /// second count and the last has no friends.
+    fn counts_builder(hub_counts: [f64; 3]) -> ConfigurationBuilder {
+        let agent_uuids: Vec<Uuid> = (0..5).map(|i| Uuid::from_u128(0x300 + i)).collect();
+        let belief_uuids: Vec<Uuid> = (0..2).map(|i| Uuid::from_u128(0x200 + i)).collect();
+        let agents: Vec<AgentSpec> = agent_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| AgentSpec {
+                uuid,
+                actions: HashMap::from([(0, Uuid::from_u128(0x100))]),
+                activations: HashMap::from([(0, belief_uuids.iter().map(|&b| (b, 0.5)).collect())]),
+                deltas: belief_uuids.iter().map(|&b| (b, 1.0)).collect(),
+                friends: match i {
+                    0 => agent_uuids[1..4].iter().copied().zip(hub_counts).collect(),
+                    1 | 2 => HashMap::from([(agent_uuids[0], hub_counts[1])]),
+                    _ => HashMap::new(),
+                },
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+        small_builder().with_agents(agents)
+    }
+
+    #[test]
+    fn history_before_the_window_is_skipped_as_agents_are_read() {
+        let builder = small_builder();
+        let Some(Input::Specs(specs)) = &builder.agents else {
+            unreachable!("the small model is in memory")
+        };
+        let agents: Vec<AgentSpec> = specs
+            .iter()
+            .map(|spec| {
+                let mut spec = spec.clone();
+                for time in 1..3 {
+                    spec.activations.insert(time, spec.activations[&0].clone());
+                    spec.actions.insert(time, spec.actions[&0]);
+                }
+                spec
+            })
+            .collect();
+        let path = temp_path(".json");
+        std::fs::write(&path, serde_json::to_vec(&agents).unwrap()).unwrap();
+
+        // The activations at the time origin are kept, however late the
+        // window starts
+        let config = small_builder()
+            .agents_from_path(&path)
+            .time_range(3, 5)
+            .load_activations_from(10)
+            .load_actions_from(1)
+            .build()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            config.history_trimming,
+            Some(HistoryTrimming {
+                activations_from: Some(2),
+                actions_from: Some(1),
+                skipped_activations: 6,
+                skipped_actions: 3,
+            })
+        );
+        for agent in &config.agents {
+            let agent = agent.borrow();
+            let mut activations: Vec<SimTime> = agent.get_activations().keys().copied().collect();
+            let mut actions: Vec<SimTime> = agent.get_actions().keys().copied().collect();
+            activations.sort_unstable();
+            actions.sort_unstable();
+            assert_eq!((activations, actions), (vec![2], vec![1, 2]));
+        }
+
+        // Specs in memory are cut too
+        let config = small_builder().load_activations_from(1).build().unwrap();
+        assert_eq!(
+            config.history_trimming.unwrap(),
+            HistoryTrimming {
+                activations_from: Some(0),
+                actions_from: None,
+                skipped_activations: 0,
+                skipped_actions: 0,
+            }
+        );
+        assert_eq!(small_builder().build().unwrap().history_trimming, None);
+    }
+
+    #[test]
+    fn friend_weights_are_normalized_by_each_mode() {
+        let (a, b, c) = (
+            Uuid::from_u128(0x301),
+            Uuid::from_u128(0x302),
+            Uuid::from_u128(0x303),
+        );
+        let config = counts_builder([2.0, 6.0, 4.0])
+            .normalize_friend_weights(FriendNormalization::Max)
+            .build()
+            .unwrap();
+        assert_eq!(config.friend_normalization, FriendNormalization::Max);
+        assert_eq!(
+            friends_of(&config, 0),
+            vec![(a, 2.0 / 6.0), (b, 1.0), (c, 4.0 / 6.0)]
+        );
+        assert_eq!(friends_of(&config, 1), vec![(Uuid::from_u128(0x300), 1.0)]);
+        // Agents without friends are left without them
+        assert_eq!(friends_of(&config, 4), vec![]);
+
+        let config = counts_builder([2.0, 6.0, 4.0])
+            .normalize_friend_weights(FriendNormalization::Sum)
+            .build()
+            .unwrap();
+        assert_eq!(
+            friends_of(&config, 0),
+            vec![(a, 2.0 / 12.0), (b, 6.0 / 12.0), (c, 4.0 / 12.0)]
+        );
+        assert_eq!(friends_of(&config, 2), vec![(Uuid::from_u128(0x300), 1.0)]);
+        assert_eq!(friends_of(&config, 3), vec![]);
+
+        // Counts are out of range unless they are normalized
+        match counts_builder([2.0, 6.0, 4.0]).build() {
+            Err(ConceptError::Validation(report)) => assert_eq!(report.issues.len(), 5),
+            result => panic!("expected counts to be out of range, got {:?}", result.err()),
+        }
+        let config = counts_builder([0.2, 0.6, 0.4]).build().unwrap();
+        assert_eq!(config.friend_normalization, FriendNormalization::None);
+        assert_eq!(friends_of(&config, 0), vec![(a, 0.2), (b, 0.6), (c, 0.4)]);
+
+        // Weights out of range once normalized are still reported
+        let result = counts_builder([-2.0, 6.0, 0.0])
+            .normalize_friend_weights(FriendNormalization::Max)
+            .build();
+        match result {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                report.issues[..],
+                [ValidationIssue::OutOfRange { field: "friends", value, .. }] if value == -2.0 / 6.0
+            )),
+            result => panic!("expected a negative weight, got {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn build_works_with_specs() {
+        let config = small_builder().build().unwrap();
+        assert_eq!(config.behaviours.len(), 2);
+        assert_eq!(config.beliefs.len(), 2);
+        assert_eq!(config.agents.len(), 3);
+        assert_eq!(config.prs.behaviour_row(0), &[1.0, 0.0]);
+        assert_eq!(config.prs.behaviour_row(1), &[0.0, 1.0]);
+        for agent in &config.agents {
+            assert_eq!(agent.borrow().get_friends().len(), 2);
+        }
+    }
+
+    #[test]
+    fn build_validates_specs() {
+        let (behaviours, beliefs, mut agents) = specs();
+        agents[0].deltas.insert(beliefs[0].uuid, -1.0);
+        let result = ConfigurationBuilder::new()
+            .with_behaviours(behaviours)
+            .with_beliefs(beliefs)
+            .with_agents(agents)
+            .with_prs(Vec::new())
+            .time_range(1, 2)
+            .output(Box::new(Vec::new()))
+            .build();
+        match result {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                single_issue(report),
+                ValidationIssue::OutOfRange {
+                    field: "deltas",
+                    ..
+                }
+            )),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn build_checks_friends_after_every_agent_is_loaded() {
+        let (behaviours, beliefs, mut agents) = specs();
+        let unknown = Uuid::new_v4();
+        let first = agents[0].uuid;
+        agents[0].friends.insert(unknown, 0.5);
+        agents.last_mut().unwrap().friends.insert(first, 0.5);
+        let result = ConfigurationBuilder::new()
+            .with_behaviours(behaviours)
+            .with_beliefs(beliefs)
+            .with_agents(agents)
+            .with_prs(Vec::new())
+            .time_range(1, 2)
+            .output(Box::new(Vec::new()))
+            .build();
+        match result {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                single_issue(report),
+                ValidationIssue::UnknownReference { target, .. } if target == unknown
+            )),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn build_converts_agents_in_order_and_deterministically() {
+        let specs = crate::loader::load_agents_from_path(&fixture("agents.json")).unwrap();
+        let build = || {
+            fixture_builder()
+                .with_agents(specs.clone())
+                .time_range(1, 2)
+                .output(Box::new(Vec::new()))
+                .build()
+                .unwrap()
+        };
+        let (first, second) = (build(), build());
+        assert_eq!(first.agents.len(), specs.len());
+        for ((a, b), spec) in first.agents.iter().zip(&second.agents).zip(&specs) {
+            assert_eq!(&AgentSpec::from_agent(a), spec);
+            assert_eq!(AgentSpec::from_agent(a), AgentSpec::from_agent(b));
+        }
+    }
+
+    #[test]
+    fn build_indexes_beliefs_and_behaviours_in_model_order() {
+        let config = small_builder().build().unwrap();
+        assert_eq!(
+            config.index(),
+            &ModelIndex::from_model(&config.beliefs, &config.behaviours)
+        );
+        // The index used to build the performance relationships is kept
+        assert!(std::ptr::eq(config.index(), config.prs.index()));
+    }
+
+    #[test]
+    fn build_works_with_fixtures() {
+        let output = temp_path(".json");
+        let config = fixture_builder()
+            .time_range(1, 2)
+            .output_path(&output)
+            .build()
+            .unwrap();
+        assert_eq!(config.behaviours.len(), 4);
+        assert_eq!(config.beliefs.len(), 5);
+        assert_eq!(config.agents.len(), 500);
+        assert!((0..4).all(|j| config.prs.behaviour_row(j).len() == 5));
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn build_without_inputs_reports_each_missing_input() {
+        let result = ConfigurationBuilder::new().time_range(1, 2).build();
+        match result {
+            Err(ConceptError::Validation(report)) => {
+                assert_eq!(report.issues.len(), 4);
+                assert!(report
+                    .issues
+                    .contains(&ValidationIssue::MissingInput { input: "agents" }));
+            }
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn build_with_invalid_time_range_fails_before_creating_output() {
+        let output = temp_path(".json.zst");
+        let result = small_builder()
+            .time_range(3, 2)
+            .output_path(&output)
+            .build();
+        match result {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::InvalidTimeRange { start: 3, end: 2 }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn network_snapshots_outside_the_simulated_range_fail() {
+        let snapshots = NetworkSnapshots::new([0, 3, 4], "/nonexistent");
+        match small_builder().network_snapshots(snapshots).build() {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::InvalidSnapshotTime {
+                    time: 4,
+                    origin: 0,
+                    end: 3
+                }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn time_origin_defaults_to_the_tick_before_the_start() {
+        let config = small_builder().build().unwrap();
+        assert_eq!(config.time_origin, 0);
+
+        // The initial activations of small_builder are at 0
+        match small_builder().time_range(3, 4).build() {
+            Err(ConceptError::Validation(report)) => assert!(report
+                .issues
+                .iter()
+                .all(|issue| matches!(issue, ValidationIssue::MissingActivation { time: 2, .. }))),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn earlier_time_origin_is_carried_to_the_tick_before_the_start() {
+        let config = small_builder()
+            .time_range(3, 4)
+            .time_origin(0)
+            .build()
+            .unwrap();
+        assert_eq!(config.time_origin, 0);
+        for agent in &config.agents {
+            let agent = agent.borrow();
+            for belief in &config.beliefs {
+                assert_eq!(
+                    agent.get_activation(2, belief),
+                    agent.get_activation(0, belief)
+                );
+                assert!(agent.get_activation(1, belief).is_none());
+            }
+            assert_eq!(agent.get_action(2), agent.get_action(0));
+        }
+    }
+
+    #[test]
+    fn sign_disagreements_are_warnings_with_names() {
+        let (belief, behaviour) = (Uuid::from_u128(0x200), Uuid::from_u128(0x100));
+        let prs = vec![
+            PerformanceRelationshipSpec {
+                behaviour_uuid: behaviour,
+                belief_uuid: belief,
+                value: -0.8,
+            },
+            PerformanceRelationshipSpec {
+                behaviour_uuid: Uuid::from_u128(0x101),
+                belief_uuid: Uuid::from_u128(0x201),
+                value: 0.8,
+            },
+        ];
+        let config = small_builder()
+            .with_prs(prs.clone())
+            .check_sign_consistency(0.4)
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.validation_warnings,
+            [ValidationWarning::SignDisagreement {
+                belief,
+                belief_name: String::from("belief 0"),
+                behaviour,
+                behaviour_name: String::from("behaviour 0"),
+                perception: 0.5,
+                prs: -0.8,
+            }]
+        );
+
+        // Both magnitudes must exceed the threshold, and the check is opt-in
+        let config = small_builder()
+            .with_prs(prs.clone())
+            .check_sign_consistency(0.6);
+        assert!(config.build().unwrap().validation_warnings.is_empty());
+        let config = small_builder().with_prs(prs).build().unwrap();
+        assert!(config.validation_warnings.is_empty());
+    }
+
+    #[test]
+    fn scaled_relationships_are_what_perception_sees() {
+        let config = small_builder().relationship_scale(2.0).build().unwrap();
+        assert_eq!(
+            config.relationship_scaling,
+            Some(RelationshipScaling {
+                factor: 2.0,
+                clamped: 0,
+            })
+        );
+        let beliefs = config.beliefs.clone();
+        assert_eq!(
+            beliefs[0].borrow().get_relationship(&beliefs[1]),
+            Some(-0.4)
+        );
+        let agent = config.agents[2].clone();
+        let mut runner = crate::runner::Runner::new(config);
+        runner.run_until(1).unwrap();
+
+        // The last agent has activations of 0.5, and its friends performed
+        // each behaviour with weight 0.5, so the pressure on belief 0 is
+        // 0.5 * 0.5 / 2 = 0.125 and its context is 0.5 * -0.4 / 2 = -0.1
+        let pressure = 0.125;
+        let context = 0.5 * -0.4 / 2.0;
+        assert_eq!(
+            agent.borrow().get_activation(1, &beliefs[0]),
+            Some(0.5 + (1.0 + context) / 2.0 * pressure)
+        );
+
+        let config = small_builder().relationship_scale(6.0).build().unwrap();
+        assert_eq!(config.relationship_scaling.unwrap().clamped, 2);
+        let beliefs = &config.beliefs;
+        assert_eq!(
+            beliefs[1].borrow().get_relationship(&beliefs[0]),
+            Some(-1.0)
+        );
+        assert_eq!(small_builder().build().unwrap().relationship_scaling, None);
+    }
+
+    #[test]
+    fn prs_modifications_are_applied_and_recorded() {
+        let (belief, behaviour) = (Uuid::from_u128(0x201), Uuid::from_u128(0x100));
+        let prs_override = PrsOverride {
+            belief_uuid: belief,
+            behaviour_uuid: behaviour,
+            value: -0.3,
+        };
+        let config = small_builder()
+            .prs_scale(0.5)
+            .prs_override(prs_override)
+            .build()
+            .unwrap();
+        let prs = &config.prs;
+        assert_eq!(prs.behaviour_row(0), [0.5, -0.3]);
+        assert_eq!(prs.behaviour_row(1), [0.0, 0.5]);
+        assert_eq!(
+            config.prs_modifications,
+            Some(PrsModifications {
+                scale: Some(0.5),
+                overrides: vec![prs_override],
+            })
+        );
+        assert_eq!(small_builder().build().unwrap().prs_modifications, None);
+
+        let unknown = Uuid::from_u128(0x999);
+        let result = small_builder()
+            .prs_override(PrsOverride {
+                belief_uuid: unknown,
+                ..prs_override
+            })
+            .build();
+        match result {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::UnknownTarget {
+                    input: "performance relationship overrides",
+                    target_kind: "belief",
+                    target: unknown,
+                }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn agent_filters_keep_matching_agents_and_drop_their_friendships() {
+        let builder = small_builder();
+        let Some(Input::Specs(specs)) = &builder.agents else {
+            unreachable!("the small model is in memory")
+        };
+        let agents: Vec<_> = specs
+            .iter()
+            .zip(["north", "north", "south"])
+            .map(|(spec, region)| {
+                let mut fields = spec_fields(spec);
+                fields.insert("region".to_string(), region.into());
+                fields.insert("cohort".to_string(), 1.into());
+                fields
+            })
+            .collect();
+        let path = temp_path(".json");
+        std::fs::write(&path, serde_json::to_vec(&agents).unwrap()).unwrap();
+
+        let config = small_builder()
+            .agents_from_path(&path)
+            .agent_filter("region=north".parse().unwrap())
+            .agent_filter("cohort=1".parse().unwrap())
+            .build()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            config.agent_filtering.unwrap(),
+            AgentFiltering {
+                filters: vec!["region=north".parse().unwrap(), "cohort=1".parse().unwrap()],
+                agents_read: 3,
+                agents_kept: 2,
+                dropped_friendships: 2,
+            }
+        );
+        let uuids: Vec<Uuid> = config.agents.iter().map(|a| *a.borrow().uuid()).collect();
+        assert_eq!(uuids, vec![Uuid::from_u128(0x300), Uuid::from_u128(0x301)]);
+        for agent in &config.agents {
+            assert_eq!(agent.borrow().get_friends().len(), 1);
+        }
+        assert_eq!(config.input_summary.friend_weights.unwrap().count, 2);
+
+        // Specs in memory only have their own fields
+        let config = small_builder()
+            .agent_filter(format!("uuid={}", Uuid::from_u128(0x302)).parse().unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(config.agents.len(), 1);
+        assert!(config.agents[0].borrow().get_friends().is_empty());
+        assert_eq!(config.agent_filtering.unwrap().dropped_friendships, 2);
+        assert_eq!(small_builder().build().unwrap().agent_filtering, None);
+    }
+
+    #[test]
+    fn restriction_drops_every_reference_to_excluded_beliefs_and_behaviours() {
+        let (belief, behaviour) = (Uuid::from_u128(0x200), Uuid::from_u128(0x100));
+        let config = small_builder()
+            .only_beliefs([belief])
+            .only_behaviours([behaviour])
+            .build()
+            .unwrap();
+        assert_eq!(config.beliefs.len(), 1);
+        assert_eq!(config.behaviours.len(), 1);
+        for agent in &config.agents {
+            let spec = AgentSpec::from_agent(agent);
+            assert_eq!(spec.deltas.len(), 1);
+            assert_eq!(spec.activations[&0].len(), 1);
+        }
+        assert_eq!(
+            config.restriction.unwrap(),
+            Restriction {
+                beliefs: Some(vec![belief]),
+                behaviours: Some(vec![behaviour]),
+                dropped: DroppedReferences {
+                    beliefs: 1,
+                    behaviours: 1,
+                    perceptions: 0,
+                    relationships: 1,
+                    performance_relationships: 1,
+                    activations: 3,
+                    deltas: 3,
+                    actions: 1,
+                    activation_caps: 0,
+                },
+            }
+        );
+        assert_eq!(small_builder().build().unwrap().restriction, None);
+
+        let unknown = Uuid::from_u128(0x999);
+        match small_builder().only_behaviours([unknown]).build() {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::UnknownTarget {
+                    input: "only behaviours",
+                    target_kind: "behaviour",
+                    target: unknown,
+                }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn default_delta_fills_only_missing_deltas() {
+        let beliefs: Vec<Uuid> = (0..2).map(|i| Uuid::from_u128(0x200 + i)).collect();
+        let agents: Vec<AgentSpec> = (0..2)
+            .map(|i| AgentSpec {
+                uuid: Uuid::from_u128(0x300 + i),
+                actions: HashMap::new(),
+                activations: HashMap::from([(0, beliefs.iter().map(|&b| (b, 0.0)).collect())]),
+                // The first agent only has a delta for the first belief
+                deltas: if i == 0 {
+                    HashMap::from([(beliefs[0], 1.5)])
+                } else {
+                    HashMap::new()
+                },
+                friends: HashMap::new(),
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect();
+        let builder = || small_builder().with_agents(agents.clone());
+
+        match builder().build() {
+            Err(ConceptError::Validation(report)) => {
+                assert_eq!(report.issues.len(), 3);
+                assert!(report
+                    .issues
+                    .iter()
+                    .all(|issue| matches!(issue, ValidationIssue::MissingDelta { .. })));
+            }
+            _ => panic!("expected a validation error"),
+        }
+
+        let config = builder().default_delta(0.5).build().unwrap();
+        assert_eq!(config.default_delta, Some(0.5));
+        let deltas = |i: usize| -> Vec<Option<f64>> {
+            config
+                .beliefs
+                .iter()
+                .map(|b| config.agents[i].borrow().get_delta(b))
+                .collect()
+        };
+        assert_eq!(deltas(0), [Some(1.5), Some(0.5)]);
+        assert_eq!(deltas(1), [Some(0.5), Some(0.5)]);
+
+        match builder().default_delta(0.0).build() {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::InvalidDefault {
+                    field: "delta",
+                    value: 0.0,
+                    range: "(0, inf)"
+                }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn missing_deltas_are_drawn_from_the_distribution_of_their_belief() {
+        let distribution = DeltaDistribution::Uniform { min: 2.0, max: 3.0 };
+        let belief = Uuid::from_u128(0x200);
+        let builder = |distribution| {
+            let mut builder = small_builder().seed(5);
+            let Some(Input::Specs(beliefs)) = &mut builder.beliefs else {
+                unreachable!("the small model is in memory")
+            };
+            beliefs[0].delta_distribution = Some(distribution);
+            let Some(Input::Specs(agents)) = &mut builder.agents else {
+                unreachable!("the small model is in memory")
+            };
+            // Only the first agent has a delta for the first belief
+            for agent in &mut agents[1..] {
+                agent.deltas.remove(&belief);
+            }
+            builder
+        };
+        let delta = |config: &Configuration, i: usize| {
+            config.agents[i]
+                .borrow()
+                .get_delta(&config.beliefs[0])
+                .unwrap()
+        };
+
+        let config = builder(distribution).build().unwrap();
+        assert_eq!(
+            config.delta_generation,
+            Some(DeltaGeneration {
+                seed: 5,
+                beliefs: vec![GeneratedDeltas {
+                    belief,
+                    distribution,
+                    generated: 2
+                }]
+            })
+        );
+        assert_eq!(delta(&config, 0), 1.0);
+        for i in 1..3 {
+            let generated = distribution.sample(5, Uuid::from_u128(0x300 + i as u128), belief);
+            assert!((2.0..=3.0).contains(&generated));
+            assert_eq!(delta(&config, i), generated);
+        }
+        assert_ne!(delta(&config, 1), delta(&config, 2));
+        // The deltas of the other belief are untouched
+        assert_eq!(
+            config.agents[1].borrow().get_delta(&config.beliefs[1]),
+            Some(1.0)
+        );
+
+        // Generated deltas take precedence over the default delta
+        let defaulted = builder(distribution).default_delta(0.5).build().unwrap();
+        assert_eq!(delta(&defaulted, 1), delta(&config, 1));
+        // The runner is seeded the same as the deltas
+        assert_eq!(crate::runner::Runner::new(config).seed(), 5);
+        assert_eq!(small_builder().build().unwrap().delta_generation, None);
+
+        let invalid = DeltaDistribution::Uniform { min: 0.0, max: 1.0 };
+        match builder(invalid).build() {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                single_issue(report),
+                ValidationIssue::OutOfRange {
+                    field: "deltaDistribution.min",
+                    ..
+                }
+            )),
+            result => panic!("expected a validation error, got {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn time_origin_must_be_before_the_start() {
+        match small_builder().time_range(1, 2).time_origin(1).build() {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                single_issue(report),
+                ValidationIssue::InvalidTimeOrigin {
+                    origin: 1,
+                    start: 1
+                }
+            ),
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn build_with_missing_file_is_io_error() {
+        let missing = fixture("missing.json");
+        let result = fixture_builder()
+            .behaviours_from_path(&missing)
+            .time_range(1, 2)
+            .output(Box::new(Vec::new()))
+            .build();
+        assert!(matches!(result, Err(ConceptError::Io { path, .. }) if path == missing));
+    }
+
+    #[test]
+    fn build_with_broken_files_names_the_spec_and_file() {
+        let (behaviours, beliefs, agents) = specs();
+        // The message of the error building the files, with their paths
+        // replaced by their names
+        let error = |beliefs: &[&BeliefSpec], agents: &[&AgentSpec]| -> String {
+            let (beliefs_path, agents_path) = (temp_path(".json"), temp_path(".json"));
+            std::fs::write(&beliefs_path, serde_json::to_vec(beliefs).unwrap()).unwrap();
+            std::fs::write(&agents_path, serde_json::to_vec(agents).unwrap()).unwrap();
+            let result = ConfigurationBuilder::new()
+                .with_behaviours(behaviours.clone())
+                .beliefs_from_path(&beliefs_path)
+                .agents_from_path(&agents_path)
+                .with_prs(Vec::new())
+                .time_range(1, 2)
+                .output(Box::new(Vec::new()))
+                .build();
+            std::fs::remove_file(&beliefs_path).unwrap();
+            std::fs::remove_file(&agents_path).unwrap();
+            let path = |p: PathBuf| p.display().to_string();
+            let Err(err) = result else {
+                panic!("expected the files to be rejected");
+            };
+            err.to_string()
+                .replace(&path(beliefs_path), "beliefs.json")
+                .replace(&path(agents_path), "agents.json")
+        };
+        let (belief, agent) = (&beliefs[0], &agents[0]);
+
+        let unknown = Uuid::from_u128(0x1234);
+        let mut dangling = agent.clone();
+        dangling.deltas.insert(unknown, 1.0);
+        assert_eq!(
+            error(&[belief], &[&dangling]),
+            format!(
+                "invalid configuration: agent {} references unknown belief {unknown} in deltas \
+                 (agents.json)",
+                agent.uuid
+            )
+        );
+        assert_eq!(
+            error(&[belief, belief], &[agent]),
+            format!(
+                "invalid configuration: belief {} is given more than once (beliefs.json)",
+                belief.uuid
+            )
+        );
+        assert_eq!(
+            error(&[belief], &[agent, agent, agent]),
+            format!(
+                "invalid configuration: agent {} is given more than once (agents.json)",
+                agent.uuid
+            )
+        );
+    }
+
+    #[test]
+    fn build_with_invalid_beliefs_file_is_parse_error() {
+        let beliefs = temp_path(".json");
+        std::fs::write(&beliefs, r#"[{"name": "b1"}, {"name": 2}]"#).unwrap();
+        let result = fixture_builder()
+            .beliefs_from_path(&beliefs)
+            .time_range(1, 2)
+            .output(Box::new(Vec::new()))
+            .build();
+        std::fs::remove_file(&beliefs).unwrap();
+        match result {
+            Err(ConceptError::Parse {
+                file, json_path, ..
+            }) => {
+                assert_eq!(file, beliefs);
+                assert_eq!(json_path, "[1].name");
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn validate_specs_works() {
+        let (behaviours, beliefs, agents) = specs();
+        assert!(validate_model(&behaviours, &beliefs, &agents, &[], 1).is_empty());
+    }
+
+    #[test]
+    fn validate_specs_unknown_belief_in_deltas_fails() {
+        let (behaviours, beliefs, mut agents) = specs();
+        let unknown = Uuid::new_v4();
+        agents[0].deltas.insert(unknown, 1.0);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::UnknownReference { field: "deltas", target, .. } if target == unknown
+        ));
+    }
+
+    #[test]
+    fn validate_specs_unknown_friend_fails() {
+        let (behaviours, beliefs, mut agents) = specs();
+        agents[0].friends.insert(Uuid::new_v4(), 0.5);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::UnknownReference {
+                target_kind: "agent",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_specs_unknown_prs_behaviour_fails() {
+        let (behaviours, beliefs, agents) = specs();
+        let prs = vec![PerformanceRelationshipSpec {
+            behaviour_uuid: Uuid::new_v4(),
+            belief_uuid: beliefs[0].uuid,
+            value: 0.1,
+        }];
+        let report = validate_model(&behaviours, &beliefs, &agents, &prs, 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::UnknownReference {
+                target_kind: "behaviour",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_specs_out_of_range_activation_fails() {
+        let (behaviours, beliefs, mut agents) = specs();
+        agents[0]
+            .activations
+            .get_mut(&0)
+            .unwrap()
+            .insert(beliefs[0].uuid, 1.5);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::OutOfRange {
+                field: "activations",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_specs_out_of_range_or_crossed_caps_fail() {
+        let (behaviours, beliefs, mut agents) = specs();
+        agents[0].activation_floors.insert(beliefs[0].uuid, -1.5);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::OutOfRange {
+                field: "activationFloors",
+                ..
+            }
+        ));
+
+        agents[0].activation_floors.insert(beliefs[0].uuid, 0.5);
+        agents[0].activation_ceilings.insert(beliefs[0].uuid, 0.2);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::CrossedCaps { floor, ceiling, .. } if floor == 0.5 && ceiling == 0.2
+        ));
+    }
+
+    #[test]
+    fn validate_specs_missing_initial_activation_fails() {
+        let (behaviours, beliefs, agents) = specs();
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 2);
+        assert!(matches!(
+            single_issue(report),
+            ValidationIssue::MissingActivation { time: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn validate_specs_collects_every_issue() {
+        let (behaviours, beliefs, mut agents) = specs();
+        agents[0].friends.insert(Uuid::new_v4(), 0.5);
+        agents[0].deltas.insert(beliefs[0].uuid, -1.0);
+        let report = validate_model(&behaviours, &beliefs, &agents, &[], 1);
+        assert_eq!(report.issues.len(), 2);
+    }
+
+    #[test]
+    fn validate_time_range_works() {
+        assert!(validate_time_range(1, 1).is_none());
+        assert!(validate_time_range(0, 1).is_some());
+        assert!(validate_time_range(2, 1).is_some());
+    }
+}
@@ -0,0 +1,203 @@
+//! Generating the deltas of [Agent]s from a distribution given for each
+//! [Belief], for the [Agent]s with no delta of their own for it.
+//!
+//! Each delta is drawn from a random number generator seeded from the seed
+//! of the run and the UUIDs of the [Agent] and [Belief], so it does not
+//! depend on the order the [Agent]s are loaded in or on which other deltas
+//! are generated.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::ValidationIssue;
+
+/// The distribution the deltas of a [Belief] are drawn from, given as the
+/// `deltaDistribution` of a [BeliefSpec](crate::json::BeliefSpec).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum DeltaDistribution {
+    /// A log-normal distribution, whose logarithm has mean `mu` and standard
+    /// deviation `sigma`.
+    Lognormal { mu: f64, sigma: f64 },
+    /// A uniform distribution over [`min`, `max`].
+    Uniform { min: f64, max: f64 },
+}
+
+impl DeltaDistribution {
+    /// Check that the parameters of the distribution of the [Belief] `uuid`
+    /// only give deltas within (0, inf).
+    pub fn validate(&self, uuid: Uuid) -> Vec<ValidationIssue> {
+        let issue = |field, value, range| ValidationIssue::OutOfRange {
+            kind: "belief",
+            uuid,
+            field,
+            value,
+            range,
+        };
+        let mut issues = Vec::new();
+        match *self {
+            DeltaDistribution::Lognormal { mu, sigma } => {
+                if !mu.is_finite() {
+                    issues.push(issue("deltaDistribution.mu", mu, "(-inf, inf)"));
+                }
+                if !(sigma.is_finite() && sigma >= 0.0) {
+                    issues.push(issue("deltaDistribution.sigma", sigma, "[0, inf)"));
+                }
+            }
+            DeltaDistribution::Uniform { min, max } => {
+                if !(min.is_finite() && min > 0.0) {
+                    issues.push(issue("deltaDistribution.min", min, "(0, inf)"));
+                }
+                if !(max.is_finite() && max >= min) {
+                    issues.push(issue("deltaDistribution.max", max, "[min, inf)"));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Draw the delta of the [Agent] `agent` for the [Belief] `belief`.
+    ///
+    /// The same seed, [Agent] and [Belief] always give the same delta. The
+    /// delta is clamped to be positive and finite, in case the parameters
+    /// are so extreme that it underflows or overflows.
+    pub fn sample(&self, seed: u64, agent: Uuid, belief: Uuid) -> f64 {
+        let mut rng = delta_rng(seed, agent, belief);
+        let delta = match *self {
+            DeltaDistribution::Lognormal { mu, sigma } => {
+                (mu + sigma * standard_normal(&mut rng)).exp()
+            }
+            DeltaDistribution::Uniform { min, max } => min + (max - min) * rng.gen::<f64>(),
+        };
+        delta.clamp(f64::MIN_POSITIVE, f64::MAX)
+    }
+}
+
+/// How the deltas missing from the [Agent]s were generated.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaGeneration {
+    /// The seed the deltas were drawn with.
+    pub seed: u64,
+    /// The deltas generated for each [Belief] with a [DeltaDistribution], in
+    /// order of UUID.
+    pub beliefs: Vec<GeneratedDeltas>,
+}
+
+/// The deltas generated for a [Belief].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedDeltas {
+    /// The UUID of the [Belief].
+    pub belief: Uuid,
+    /// The distribution the deltas were drawn from.
+    pub distribution: DeltaDistribution,
+    /// The number of [Agent]s given a generated delta.
+    pub generated: usize,
+}
+
+/// The random number generator for the delta of an [Agent] for a [Belief].
+///
+/// Other values drawn for an [Agent] and [Belief] use another stream of the
+/// same generator, so they are independent of its delta.
+pub(crate) fn delta_rng(seed: u64, agent: Uuid, belief: Uuid) -> ChaCha8Rng {
+    let mut key = [0; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..24].copy_from_slice(agent.as_bytes());
+    let (high, low) = belief.as_u64_pair();
+    key[24..].copy_from_slice(&(high ^ low).to_le_bytes());
+    ChaCha8Rng::from_seed(key)
+}
+
+/// Draw from the standard normal distribution, by the Box-Muller transform.
+pub(crate) fn standard_normal(rng: &mut impl Rng) -> f64 {
+    // 1 - [0, 1) is (0, 1], so the logarithm is finite
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lognormal_deltas_have_a_log_mean_near_mu() {
+        let distribution = DeltaDistribution::Lognormal {
+            mu: -0.1,
+            sigma: 0.2,
+        };
+        let belief = Uuid::from_u128(0x200);
+        let logs: Vec<f64> = (0..20_000)
+            .map(|i| distribution.sample(7, Uuid::from_u128(i), belief).ln())
+            .collect();
+        let n = logs.len() as f64;
+        let mean = logs.iter().sum::<f64>() / n;
+        let sd = (logs.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        // Five standard errors either side
+        assert!((mean + 0.1).abs() < 5.0 * 0.2 / n.sqrt(), "mean {mean}");
+        assert!((sd - 0.2).abs() < 0.01, "sd {sd}");
+    }
+
+    #[test]
+    fn uniform_deltas_are_within_their_range() {
+        let distribution = DeltaDistribution::Uniform { min: 0.9, max: 1.1 };
+        let belief = Uuid::from_u128(0x200);
+        let deltas: Vec<f64> = (0..10_000)
+            .map(|i| distribution.sample(7, Uuid::from_u128(i), belief))
+            .collect();
+        assert!(deltas.iter().all(|d| (0.9..=1.1).contains(d)));
+        let mean = deltas.iter().sum::<f64>() / deltas.len() as f64;
+        assert!((mean - 1.0).abs() < 0.005, "mean {mean}");
+    }
+
+    #[test]
+    fn deltas_depend_only_on_the_seed_agent_and_belief() {
+        let distribution = DeltaDistribution::Lognormal {
+            mu: 0.0,
+            sigma: 1.0,
+        };
+        let (agent, belief) = (Uuid::from_u128(0x300), Uuid::from_u128(0x200));
+        let delta = distribution.sample(1, agent, belief);
+        assert_eq!(delta, distribution.sample(1, agent, belief));
+        assert_ne!(delta, distribution.sample(2, agent, belief));
+        assert_ne!(
+            delta,
+            distribution.sample(1, Uuid::from_u128(0x301), belief)
+        );
+        assert_ne!(delta, distribution.sample(1, agent, Uuid::from_u128(0x201)));
+    }
+
+    #[test]
+    fn parameters_giving_invalid_deltas_are_rejected() {
+        let uuid = Uuid::from_u128(0x200);
+        let lognormal = |mu, sigma| DeltaDistribution::Lognormal { mu, sigma }.validate(uuid);
+        let uniform = |min, max| DeltaDistribution::Uniform { min, max }.validate(uuid);
+        assert!(lognormal(0.0, 0.0).is_empty());
+        assert_eq!(lognormal(f64::NAN, -1.0).len(), 2);
+        assert!(uniform(0.5, 0.5).is_empty());
+        assert!(matches!(
+            uniform(0.0, 1.0)[..],
+            [ValidationIssue::OutOfRange {
+                field: "deltaDistribution.min",
+                ..
+            }]
+        ));
+        assert_eq!(uniform(1.0, 0.5).len(), 1);
+    }
+
+    #[test]
+    fn distributions_are_tagged_by_type() {
+        let distribution: DeltaDistribution =
+            serde_json::from_str(r#"{"type": "lognormal", "mu": 0.1, "sigma": 0.2}"#).unwrap();
+        assert_eq!(
+            distribution,
+            DeltaDistribution::Lognormal {
+                mu: 0.1,
+                sigma: 0.2
+            }
+        );
+    }
+}
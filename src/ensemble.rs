@@ -0,0 +1,229 @@
+//! Running replicates of a model, each from the same initial state with its
+//! own seed, so that the spread of outcomes from the stochastic choice of
+//! actions can be measured from inputs loaded only once.
+//!
+//! The seed of each replicate is derived from a base seed and its index, so
+//! any replicate can be run again on its own with [run_seed].
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ConceptError,
+    json::OutputSpecs,
+    runner::{RunOutcome, Runner},
+    sink::OutputSettings,
+    snapshot::insert_before_extensions,
+};
+
+/// The seed of replicate `run` of an ensemble with `base_seed`.
+pub fn run_seed(base_seed: u64, run: usize) -> u64 {
+    base_seed.wrapping_add(run as u64)
+}
+
+/// The path of the output of replicate `run` of an ensemble writing to
+/// `path`, which inserts the index before the extensions, so
+/// `output.json.zst` gives `output.run003.json.zst` for replicate 3.
+pub fn run_output_path(path: &Path, run: usize) -> PathBuf {
+    path.with_file_name(insert_before_extensions(path, &format!(".run{run:03}")))
+}
+
+/// A number of replicates of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ensemble {
+    /// The seed the seed of each replicate is derived from.
+    pub base_seed: u64,
+    pub n_runs: usize,
+}
+
+/// A replicate of an [Ensemble] and how it ended.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleRun {
+    /// The index of the replicate, from 0.
+    pub run: usize,
+    pub outcome: RunOutcome,
+}
+
+/// What happened during an [Ensemble].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleOutcome {
+    pub ensemble: Ensemble,
+    /// Every replicate, in order.
+    pub runs: Vec<EnsembleRun>,
+    /// The files written, those of each replicate in order and then the
+    /// [EnsembleSummary], if it was written.
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// The summary statistics of every replicate of an [Ensemble] in one file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleSummary {
+    pub base_seed: u64,
+    /// The output of each replicate, by its index.
+    pub runs: BTreeMap<usize, EnsembleRunSummary>,
+}
+
+/// The summary statistics of a replicate.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleRunSummary {
+    pub seed: u64,
+    pub output: OutputSpecs,
+}
+
+impl Ensemble {
+    /// Run every replicate in turn.
+    ///
+    /// `runner` sets up the [Runner] of a replicate given its index and
+    /// seed, which must build its [Agent]s afresh, so that nothing simulated
+    /// by one replicate is seen by the next. If `summary` is given, the
+    /// output of every replicate is also written to it as an
+    /// [EnsembleSummary].
+    pub fn run(
+        &self,
+        mut runner: impl FnMut(usize, u64) -> Result<Runner, ConceptError>,
+        summary: Option<&OutputSettings>,
+    ) -> Result<EnsembleOutcome, ConceptError> {
+        let mut runs = Vec::with_capacity(self.n_runs);
+        let mut artifacts = Vec::new();
+        let mut outputs = BTreeMap::new();
+        for run in 0..self.n_runs {
+            let seed = run_seed(self.base_seed, run);
+            log::info!(
+                "Ensemble: run {} of {} with seed {seed}",
+                run + 1,
+                self.n_runs
+            );
+            let mut replicate = runner(run, seed)?;
+            let outcome = replicate.run()?;
+            if summary.is_some() {
+                let output = replicate.output_specs();
+                outputs.insert(run, EnsembleRunSummary { seed, output });
+            }
+            artifacts.extend_from_slice(&outcome.artifacts);
+            runs.push(EnsembleRun { run, outcome });
+        }
+        if let Some(settings) = summary {
+            let summary = EnsembleSummary {
+                base_seed: self.base_seed,
+                runs: outputs,
+            };
+            let mut sink = settings.open().map_err(|source| ConceptError::Io {
+                path: settings.path.clone(),
+                source,
+            })?;
+            serde_json::to_writer(&mut sink, &summary)
+                .map_err(|err| ConceptError::Output { source: err.into() })?;
+            sink.finish()
+                .map_err(|source| ConceptError::Output { source })?;
+            artifacts.push(settings.path.clone());
+        }
+        Ok(EnsembleOutcome {
+            ensemble: *self,
+            runs,
+            artifacts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{configuration::ConfigurationBuilder, example::ExampleScenario, sink::Compression};
+
+    /// Run an ensemble of three replicates of the example into `dir`.
+    fn run_ensemble(dir: &Path) -> EnsembleOutcome {
+        let example = ExampleScenario::generate();
+        let ensemble = Ensemble {
+            base_seed: 11,
+            n_runs: 3,
+        };
+        let summary = OutputSettings {
+            path: dir.join("ensemble.json"),
+            compression: Compression::None,
+        };
+        ensemble
+            .run(
+                |run, seed| {
+                    let config = ConfigurationBuilder::new()
+                        .with_behaviours(example.behaviours.clone())
+                        .with_beliefs(example.beliefs.clone())
+                        .with_agents(example.agents.clone())
+                        .with_prs(example.prs.clone())
+                        .time_range(1, 10)
+                        .run_id(format!("ensemble-{run}"))
+                        .seed(seed)
+                        .output_settings(OutputSettings {
+                            path: run_output_path(&dir.join("output.json"), run),
+                            compression: Compression::None,
+                        })
+                        .build()?;
+                    Ok(Runner::new(config))
+                },
+                Some(&summary),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn replicates_differ_from_each_other_but_not_between_ensembles() {
+        let dir = std::env::temp_dir().join(format!("concept-ensemble-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let first = run_ensemble(&dir);
+        // The maps of the outputs may be written in hash order
+        let read = |path: &Path| {
+            serde_json::from_str::<serde_json::Value>(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let outputs: Vec<_> = first.artifacts[..3].iter().map(|p| read(p)).collect();
+        let summary = read(&first.artifacts[3]);
+        assert_eq!(
+            first.artifacts[..3],
+            [0, 1, 2].map(|run| dir.join(format!("output.run{run:03}.json")))
+        );
+        assert_eq!(
+            first
+                .runs
+                .iter()
+                .map(|r| r.outcome.seed)
+                .collect::<Vec<_>>(),
+            [11, 12, 13]
+        );
+        assert_ne!(outputs[0]["data"], outputs[1]["data"]);
+        assert_ne!(outputs[1]["data"], outputs[2]["data"]);
+        assert_ne!(outputs[0]["data"], outputs[2]["data"]);
+
+        run_ensemble(&dir);
+        for (path, output) in first.artifacts[..3].iter().zip(&outputs) {
+            assert_eq!(&read(path), output);
+        }
+        assert_eq!(read(&first.artifacts[3]), summary);
+        let summary: EnsembleSummary = serde_json::from_value(summary).unwrap();
+        assert_eq!(summary.runs.keys().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(summary.runs[&2].seed, 13);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_outputs_are_numbered_before_the_extensions() {
+        assert_eq!(
+            run_output_path(Path::new("out/output.json.zst"), 3),
+            Path::new("out/output.run003.json.zst")
+        );
+        assert_eq!(
+            run_output_path(Path::new("output"), 12),
+            Path::new("output.run012")
+        );
+    }
+}
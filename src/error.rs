@@ -0,0 +1,425 @@
+//! Errors produced by the library.
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use belief_spread::{errors::UpdateActivationError, SimTime};
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::warnings::{WarningCounter, WarningKind};
+
+/// An error produced by the library.
+#[derive(Error, Debug)]
+pub enum ConceptError {
+    /// A file could not be opened or created.
+    #[error("failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A file could not be parsed.
+    #[error("{} invalid at {json_path}", file.display())]
+    Parse {
+        file: PathBuf,
+        json_path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The inputs are inconsistent with each other.
+    #[error("invalid configuration: {0}")]
+    Validation(ValidationReport),
+
+    /// The simulation could not update an agent.
+    #[error("failed to update agent {agent} at time {time}")]
+    Simulation {
+        agent: Uuid,
+        time: SimTime,
+        #[source]
+        source: UpdateActivationError,
+    },
+
+    /// An activation was computed outside of [-1, 1] with
+    /// [BoundsPolicy::Error](crate::bounds::BoundsPolicy::Error).
+    #[error(
+        "activation {value} of agent {agent} for belief {belief} at time {time} outside of [-1, 1]"
+    )]
+    ActivationOutOfBounds {
+        agent: Uuid,
+        belief: Uuid,
+        time: SimTime,
+        value: f64,
+    },
+
+    /// An actions log does not end at the tick a run continues from.
+    #[error(
+        "actions log {} ends at {}, but the run continues from {time}",
+        path.display(),
+        last_tick.map_or(String::from("no tick"), |t| format!("tick {t}"))
+    )]
+    ActionsLogMismatch {
+        path: PathBuf,
+        last_tick: Option<SimTime>,
+        time: SimTime,
+    },
+
+    /// A path template has a brace that is neither part of a placeholder
+    /// nor doubled to escape it.
+    #[error("unmatched {brace:?} at byte {position} of path template {template}")]
+    MalformedTemplate {
+        template: String,
+        brace: char,
+        position: usize,
+    },
+
+    /// A path template has placeholders with no value.
+    #[error(
+        "unknown placeholders {} in path template {template}, expected any of {}",
+        unknown.iter().map(|name| format!("{{{name}}}")).collect::<Vec<_>>().join(", "),
+        known.iter().map(|name| format!("{{{name}}}")).collect::<Vec<_>>().join(", ")
+    )]
+    UnknownPlaceholders {
+        template: String,
+        unknown: Vec<String>,
+        known: Vec<String>,
+    },
+
+    /// The format of an output cannot be told from the name of its file.
+    #[error(
+        "cannot tell the format of {} from its name, which must end in .json or .zst",
+        path.display()
+    )]
+    UnknownFormat { path: PathBuf },
+
+    /// Two outputs would be written to the same path.
+    #[error("{first} and {second} would both be written to {}", path.display())]
+    PathCollision {
+        path: PathBuf,
+        first: String,
+        second: String,
+    },
+
+    /// A file of observations could not be read.
+    #[error("invalid observations at line {line} of {}: {reason}", path.display())]
+    Observations {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+
+    /// The output could not be written.
+    #[error("failed to write output")]
+    Output {
+        #[source]
+        source: io::Error,
+    },
+
+    /// The threads to simulate the ticks on could not be started.
+    #[error("failed to start {threads} threads")]
+    Threads {
+        threads: usize,
+        #[source]
+        source: rayon::ThreadPoolBuildError,
+    },
+
+    /// A results database could not be read or written.
+    #[cfg(feature = "sqlite")]
+    #[error("failed to update results database {}", path.display())]
+    Database {
+        path: PathBuf,
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    /// A results database was written by a newer version, with a schema
+    /// this version cannot migrate.
+    #[cfg(feature = "sqlite")]
+    #[error(
+        "results database {} has schema version {version}, newer than the supported {supported}",
+        path.display()
+    )]
+    DatabaseVersion {
+        path: PathBuf,
+        version: i64,
+        supported: i64,
+    },
+}
+
+/// A problem found when validating the inputs of a simulation.
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ValidationIssue {
+    /// A required input was not supplied.
+    #[error("no {input} supplied")]
+    MissingInput { input: &'static str },
+
+    /// The time range cannot be simulated.
+    #[error("invalid time range [{start}, {end}], start must be at least 1 and not after end")]
+    InvalidTimeRange { start: SimTime, end: SimTime },
+
+    /// The initial activations are not before the start of the simulation.
+    #[error("invalid time origin {origin}, it must be before the start time {start}")]
+    InvalidTimeOrigin { origin: SimTime, start: SimTime },
+
+    /// A network snapshot is requested at a tick that is not simulated.
+    #[error("network snapshot at {time} outside of the simulated range [{origin}, {end}]")]
+    InvalidSnapshotTime {
+        time: SimTime,
+        origin: SimTime,
+        end: SimTime,
+    },
+
+    /// A default for missing values is outside of the legal range of the
+    /// values.
+    #[error("default {field} {value} outside of {range}")]
+    InvalidDefault {
+        field: &'static str,
+        value: f64,
+        range: &'static str,
+    },
+
+    /// A spec references a UUID which is not in the model.
+    #[error("{kind} {uuid} references unknown {target_kind} {target} in {field}")]
+    UnknownReference {
+        kind: &'static str,
+        uuid: Uuid,
+        field: &'static str,
+        target_kind: &'static str,
+        target: Uuid,
+    },
+
+    /// Two specs of the same input have the same UUID, so one would shadow
+    /// the other.
+    #[error("{kind} {uuid} is given more than once")]
+    DuplicateUuid { kind: &'static str, uuid: Uuid },
+
+    /// An option names a UUID which is not in the model.
+    #[error("{input} name unknown {target_kind} {target}")]
+    UnknownTarget {
+        input: &'static str,
+        target_kind: &'static str,
+        target: Uuid,
+    },
+
+    /// A spec contains a value outside of its legal range.
+    #[error("{kind} {uuid} has {field} value {value} outside of {range}")]
+    OutOfRange {
+        kind: &'static str,
+        uuid: Uuid,
+        field: &'static str,
+        value: f64,
+        range: &'static str,
+    },
+
+    /// An agent has no activation for a belief at the time before the start.
+    #[error("agent {agent} has no activation for belief {belief} at time {time}")]
+    MissingActivation {
+        agent: Uuid,
+        belief: Uuid,
+        time: SimTime,
+    },
+
+    /// An agent has no delta for a belief.
+    #[error("agent {agent} has no delta for belief {belief}")]
+    MissingDelta { agent: Uuid, belief: Uuid },
+
+    /// The summary to initialize the activations from has no statistics
+    /// for the tick asked for.
+    #[error("the initial summary has no tick {tick}, only ticks {ticks:?}")]
+    MissingSummaryTick { tick: SimTime, ticks: Vec<SimTime> },
+
+    /// An observation is of a tick that is not simulated.
+    #[error("observation at time {time} outside of the simulated range [{start}, {end}]")]
+    InvalidObservationTime {
+        time: SimTime,
+        start: SimTime,
+        end: SimTime,
+    },
+
+    /// An intervention is scheduled at a tick that is not simulated.
+    #[error("intervention at time {time} outside of the simulated range [{start}, {end}]")]
+    InvalidInterventionTime {
+        time: SimTime,
+        start: SimTime,
+        end: SimTime,
+    },
+
+    /// A snapshot to resume from was taken at a tick the run cannot
+    /// continue from.
+    #[error("cannot resume from a snapshot of time {time}, it must be in [{before_start}, {end}]")]
+    InvalidResumeTime {
+        time: SimTime,
+        before_start: SimTime,
+        end: SimTime,
+    },
+
+    /// An agent's floor for its activation of a belief is above its
+    /// ceiling.
+    #[error("agent {agent} has activation floor {floor} above its ceiling {ceiling} for belief {belief}")]
+    CrossedCaps {
+        agent: Uuid,
+        belief: Uuid,
+        floor: f64,
+        ceiling: f64,
+    },
+}
+
+impl ValidationIssue {
+    /// The input the spec the issue is about came from, if it is about a
+    /// spec, as named by [ValidationIssue::MissingInput].
+    pub fn input(&self) -> Option<&'static str> {
+        let kind = match *self {
+            ValidationIssue::UnknownReference { kind, .. }
+            | ValidationIssue::OutOfRange { kind, .. }
+            | ValidationIssue::DuplicateUuid { kind, .. } => kind,
+            ValidationIssue::MissingActivation { .. }
+            | ValidationIssue::MissingDelta { .. }
+            | ValidationIssue::CrossedCaps { .. } => "agent",
+            _ => return None,
+        };
+        match kind {
+            "agent" => Some("agents"),
+            "belief" => Some("beliefs"),
+            "behaviour" => Some("behaviours"),
+            "performance relationship" => Some("performance relationships"),
+            _ => None,
+        }
+    }
+}
+
+/// Something suspicious found when validating the inputs of a simulation,
+/// which does not stop it from being simulated.
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ValidationWarning {
+    /// A belief's perception of a behaviour and its performance
+    /// relationship with it are both large, but of opposite signs, which
+    /// usually means one of them had its sign flipped.
+    #[error(
+        "belief {belief_name:?} ({belief}) perceives behaviour {behaviour_name:?} ({behaviour}) \
+         as {perception} but has performance relationship {prs} with it"
+    )]
+    #[serde(rename_all = "camelCase")]
+    SignDisagreement {
+        belief: Uuid,
+        belief_name: String,
+        behaviour: Uuid,
+        behaviour_name: String,
+        perception: f64,
+        prs: f64,
+    },
+}
+
+/// Every [ValidationIssue] found when validating the inputs of a
+/// simulation, and any [ValidationWarning]s from the optional checks.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// The issues, in the order they were found.
+    pub issues: Vec<ValidationIssue>,
+    /// The warnings, in the order they were found, which do not make the
+    /// report an error.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ValidationWarning>,
+    /// The file each input was read from, by the name of the input, so that
+    /// issues can name the file of the spec they are about.
+    #[serde(skip)]
+    pub files: BTreeMap<&'static str, PathBuf>,
+}
+
+impl ValidationReport {
+    /// Whether no issues were found.
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Convert the report into an [Err] if any issues were found, whatever
+    /// the warnings.
+    pub fn into_result(self) -> Result<(), ConceptError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(ConceptError::Validation(self))
+        }
+    }
+
+    /// The file the spec an issue is about was read from, if it was read
+    /// from one.
+    pub fn file(&self, issue: &ValidationIssue) -> Option<&Path> {
+        issue
+            .input()
+            .and_then(|input| self.files.get(input))
+            .map(PathBuf::as_path)
+    }
+
+    /// Count the issues of the report that are about individual [Agent]s,
+    /// which there may be one of per [Agent].
+    pub fn agent_warnings(&self) -> WarningCounter {
+        let counter = WarningCounter::new();
+        for issue in &self.issues {
+            match *issue {
+                ValidationIssue::MissingActivation { agent, .. } => {
+                    counter.record(WarningKind::MissingActivation, agent, 1)
+                }
+                ValidationIssue::MissingDelta { agent, .. } => {
+                    counter.record(WarningKind::MissingDelta, agent, 1)
+                }
+                _ => {}
+            }
+        }
+        counter
+    }
+}
+
+impl Extend<ValidationIssue> for ValidationReport {
+    fn extend<T: IntoIterator<Item = ValidationIssue>>(&mut self, iter: T) {
+        self.issues.extend(iter)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(first) = self.issues.first() else {
+            return write!(f, "no issues");
+        };
+        write!(f, "{first}")?;
+        if let Some(file) = self.file(first) {
+            write!(f, " ({})", file.display())?;
+        }
+        match self.issues.len() {
+            1 => Ok(()),
+            n => write!(f, " (and {} more issues)", n - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_report_is_ok() {
+        assert!(ValidationReport::default().into_result().is_ok());
+    }
+
+    #[test]
+    fn report_with_issues_is_validation_error() {
+        let mut report = ValidationReport::default();
+        report.extend([
+            ValidationIssue::MissingInput { input: "agents" },
+            ValidationIssue::InvalidTimeRange { start: 2, end: 1 },
+        ]);
+        let err = report.into_result().unwrap_err();
+        assert!(matches!(&err, ConceptError::Validation(r) if r.issues.len() == 2));
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration: no agents supplied (and 1 more issues)"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! A ledger of what the [Runner](crate::runner::Runner) did to the [Agent]s
+//! at each tick, beyond perceiving and acting, for auditing a run.
+//!
+//! The ledger is written as CSV, one row per tick:
+//!
+//! ```csv
+//! time,bounded_activations,no_action_agents
+//! 1,3,0
+//! ```
+
+use std::io::{self, Write};
+
+use belief_spread::SimTime;
+use serde::Serialize;
+
+use crate::warnings::{WarningCounter, WarningKind};
+
+/// The events of one tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TickEvents {
+    /// The tick.
+    pub time: SimTime,
+    /// The number of activations computed outside of [-1, 1] and brought
+    /// within it by the [BoundsPolicy](crate::bounds::BoundsPolicy).
+    pub bounded_activations: usize,
+    /// The number of [Agent]s that performed no action.
+    pub no_action_agents: usize,
+}
+
+impl TickEvents {
+    /// The events of the tick `time`, from the warnings recorded during
+    /// it.
+    pub fn from_warnings(time: SimTime, warnings: &WarningCounter) -> Self {
+        TickEvents {
+            time,
+            bounded_activations: warnings.count(WarningKind::BoundedActivation),
+            no_action_agents: warnings.count(WarningKind::NoAction),
+        }
+    }
+}
+
+/// The events of every tick of a run, added up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTotals {
+    /// The number of ticks recorded.
+    pub ticks: usize,
+    /// The number of activations brought within [-1, 1].
+    pub bounded_activations: usize,
+    /// The number of times an [Agent] performed no action.
+    pub no_action_agents: usize,
+}
+
+impl EventTotals {
+    /// Add up the events of some ticks.
+    pub fn of(ticks: &[TickEvents]) -> Self {
+        ticks
+            .iter()
+            .fold(EventTotals::default(), |totals, events| EventTotals {
+                ticks: totals.ticks + 1,
+                bounded_activations: totals.bounded_activations + events.bounded_activations,
+                no_action_agents: totals.no_action_agents + events.no_action_agents,
+            })
+    }
+}
+
+/// The [TickEvents] of every tick simulated, in order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EventLedger {
+    ticks: Vec<TickEvents>,
+}
+
+impl EventLedger {
+    /// Record the events of a tick.
+    pub fn record(&mut self, events: TickEvents) {
+        self.ticks.push(events);
+    }
+
+    /// The events of each tick recorded.
+    pub fn ticks(&self) -> &[TickEvents] {
+        &self.ticks
+    }
+
+    /// The events of every tick recorded, added up.
+    pub fn totals(&self) -> EventTotals {
+        EventTotals::of(&self.ticks)
+    }
+
+    /// Write the ledger as CSV, with a header row.
+    pub fn write_csv(&self, w: &mut dyn Write) -> io::Result<()> {
+        writeln!(w, "time,bounded_activations,no_action_agents")?;
+        for events in &self.ticks {
+            writeln!(
+                w,
+                "{},{},{}",
+                events.time, events.bounded_activations, events.no_action_agents
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bounds::BoundsPolicy, configuration::tests::small_builder, runner::Runner};
+
+    #[test]
+    fn the_ledger_counts_the_events_of_each_tick() {
+        // The last agent's activations leave [-1, 1] from its initial 0.5
+        let config = small_builder().build().unwrap();
+        for belief in &config.beliefs {
+            config.agents[2]
+                .borrow_mut()
+                .set_delta(belief.clone(), Some(4.0))
+                .unwrap();
+        }
+        let mut runner = Runner::new(config)
+            .with_seed(3)
+            .with_bounds_policy(BoundsPolicy::Clamp)
+            .with_event_ledger();
+        runner.run_until(3).unwrap();
+        let ledger = runner.event_ledger().unwrap();
+        assert_eq!(
+            ledger.ticks().iter().map(|e| e.time).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        // Both of its beliefs are clamped from the first tick
+        assert!(ledger.ticks().iter().all(|e| e.bounded_activations >= 2));
+        let totals = ledger.totals();
+        assert_eq!(totals.ticks, 3);
+        assert_eq!(
+            totals.bounded_activations,
+            ledger
+                .ticks()
+                .iter()
+                .map(|e| e.bounded_activations)
+                .sum::<usize>()
+        );
+
+        let mut csv = Vec::new();
+        ledger.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("time,bounded_activations,no_action_agents")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                format!(
+                    "1,{},{}",
+                    ledger.ticks()[0].bounded_activations,
+                    ledger.ticks()[0].no_action_agents
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(lines.count(), 2);
+    }
+
+    #[test]
+    fn no_ledger_is_kept_unless_asked_for() {
+        let mut runner = Runner::new(small_builder().build().unwrap()).with_seed(3);
+        runner.run_until(3).unwrap();
+        assert!(runner.event_ledger().is_none());
+    }
+}
@@ -0,0 +1,339 @@
+//! A small example scenario, generated from the same spec structs the
+//! loaders read, so it always matches the schema of the input files.
+//!
+//! The scenario has 3 [Behaviour]s, 4 [Belief]s, 20 [Agent]s on a ring of
+//! friendships, and a performance relationship between every [Belief] and
+//! [Behaviour]. It is the same every time it is generated.
+//!
+//! The binary is configured only by its command-line options, and has no
+//! config file format, so the options that run the example are written as
+//! a `run.sh` script alongside the input files.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use belief_spread::SimTime;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    error::ConceptError,
+    json::{AgentSpec, BehaviourSpec, BeliefSpec, PerformanceRelationshipSpec},
+};
+
+/// The seed the example is generated with.
+const EXAMPLE_SEED: u64 = 2022;
+
+/// The number of [Agent]s in the example.
+pub const EXAMPLE_AGENTS: usize = 20;
+
+/// The first tick the example is run for.
+pub const EXAMPLE_START_TIME: SimTime = 1;
+
+/// The last tick the example is run for.
+pub const EXAMPLE_END_TIME: SimTime = 10;
+
+/// The files of the example, named as the command-line reads them.
+const BEHAVIOURS_FILE: &str = "behaviours.json";
+const BELIEFS_FILE: &str = "beliefs.json";
+const AGENTS_FILE: &str = "agents.json";
+const PRS_FILE: &str = "prs.json";
+const OUTPUT_FILE: &str = "output.json.zst";
+const SCRIPT_FILE: &str = "run.sh";
+
+/// The example scenario.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExampleScenario {
+    pub behaviours: Vec<BehaviourSpec>,
+    pub beliefs: Vec<BeliefSpec>,
+    pub agents: Vec<AgentSpec>,
+    pub prs: Vec<PerformanceRelationshipSpec>,
+}
+
+/// What the example subcommand wrote, printed when it finishes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExampleOutcome {
+    /// The directory the example was written to.
+    pub directory: PathBuf,
+    /// The files written.
+    pub artifacts: Vec<PathBuf>,
+    /// The script that runs the example from its directory, in place of a
+    /// config file.
+    pub run_script: PathBuf,
+    /// The command that runs the example from the current directory.
+    pub run_command: String,
+}
+
+impl ExampleScenario {
+    /// Generate the example scenario.
+    pub fn generate() -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(EXAMPLE_SEED);
+        let mut uuid = || uuid::Builder::from_random_bytes(rng.gen()).into_uuid();
+        let behaviours: Vec<BehaviourSpec> = ["Walk", "Cycle", "Drive"]
+            .into_iter()
+            .map(|name| BehaviourSpec {
+                name: name.to_string(),
+                uuid: uuid(),
+            })
+            .collect();
+        let belief_names = [
+            "I care about the environment",
+            "I want to keep fit",
+            "I want to get to work quickly",
+            "I want to save money",
+        ];
+        let belief_uuids: Vec<Uuid> = belief_names.iter().map(|_| uuid()).collect();
+        let agent_uuids: Vec<Uuid> = (0..EXAMPLE_AGENTS).map(|_| uuid()).collect();
+
+        // Each belief is perceived to favour walking and cycling, or driving,
+        // and is related to itself and more weakly to the next belief
+        let favours_driving = [false, false, true, false];
+        let beliefs = belief_names
+            .iter()
+            .zip(&belief_uuids)
+            .enumerate()
+            .map(|(i, (name, &uuid))| BeliefSpec {
+                name: name.to_string(),
+                uuid,
+                perceptions: behaviours
+                    .iter()
+                    .map(|b| {
+                        let drives = b.name == "Drive";
+                        (
+                            b.uuid,
+                            if drives == favours_driving[i] {
+                                0.5
+                            } else {
+                                -0.25
+                            },
+                        )
+                    })
+                    .collect(),
+                relationships: [
+                    (uuid, 1.0),
+                    (belief_uuids[(i + 1) % belief_uuids.len()], 0.2),
+                ]
+                .into_iter()
+                .collect(),
+                delta_distribution: None,
+            })
+            .collect();
+
+        let prs = belief_uuids
+            .iter()
+            .zip(favours_driving)
+            .flat_map(|(&belief_uuid, favours_driving)| {
+                behaviours.iter().map(move |b| PerformanceRelationshipSpec {
+                    behaviour_uuid: b.uuid,
+                    belief_uuid,
+                    value: if (b.name == "Drive") == favours_driving {
+                        0.75
+                    } else {
+                        -0.5
+                    },
+                })
+            })
+            .collect();
+
+        // Each agent is friends with the two agents either side of it on a
+        // ring
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+        let agents = agent_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| {
+                let n = agent_uuids.len();
+                AgentSpec {
+                    uuid,
+                    actions: [(0, behaviours.choose(&mut rng).unwrap().uuid)]
+                        .into_iter()
+                        .collect(),
+                    activations: [(
+                        0,
+                        belief_uuids
+                            .iter()
+                            .map(|&b| (b, round(rng.gen_range(-1.0..=1.0))))
+                            .collect(),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    deltas: belief_uuids
+                        .iter()
+                        .map(|&b| (b, round(rng.gen_range(0.9..=1.1))))
+                        .collect(),
+                    friends: [n - 2, n - 1, 1, 2]
+                        .into_iter()
+                        .map(|offset| {
+                            (
+                                agent_uuids[(i + offset) % n],
+                                round(rng.gen_range(0.1..=1.0)),
+                            )
+                        })
+                        .collect(),
+                    activation_floors: HashMap::new(),
+                    activation_ceilings: HashMap::new(),
+                }
+            })
+            .collect();
+
+        ExampleScenario {
+            behaviours,
+            beliefs,
+            agents,
+            prs,
+        }
+    }
+
+    /// Write the input files of the example to `dir`, creating it if it does
+    /// not exist, with a `run.sh` script that runs it from `dir`, as there
+    /// is no config file format.
+    ///
+    /// # Returns
+    /// The [ExampleOutcome].
+    pub fn write_to(&self, dir: &Path) -> Result<ExampleOutcome, ConceptError> {
+        std::fs::create_dir_all(dir).map_err(|source| ConceptError::Io {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        let run_script = write_file(&dir.join(SCRIPT_FILE), |w| {
+            writeln!(w, "#!/bin/sh")?;
+            writeln!(w, "# Run the example scenario from this directory")?;
+            writeln!(w, "cd \"$(dirname \"$0\")\" || exit 1")?;
+            writeln!(w, "exec {} \"$@\"", command(Path::new("")))
+        })?;
+        let artifacts = vec![
+            write_json(&dir.join(BEHAVIOURS_FILE), &self.behaviours)?,
+            write_json(&dir.join(BELIEFS_FILE), &self.beliefs)?,
+            write_json(&dir.join(AGENTS_FILE), &self.agents)?,
+            write_json(&dir.join(PRS_FILE), &self.prs)?,
+            run_script.clone(),
+        ];
+        Ok(ExampleOutcome {
+            directory: dir.to_path_buf(),
+            artifacts,
+            run_script,
+            run_command: command(dir),
+        })
+    }
+}
+
+/// The command that runs the example written to `dir`, from the current
+/// directory.
+pub fn command(dir: &Path) -> String {
+    let path = |file: &str| shell_quote(&dir.join(file).to_string_lossy());
+    format!(
+        "concept --behaviours {} --beliefs {} --agents {} --performance-relationships {} \
+         --start {EXAMPLE_START_TIME} --end {EXAMPLE_END_TIME} --output {}",
+        path(BEHAVIOURS_FILE),
+        path(BELIEFS_FILE),
+        path(AGENTS_FILE),
+        path(PRS_FILE),
+        path(OUTPUT_FILE),
+    )
+}
+
+/// Quote a word for a POSIX shell if it has any characters the shell would
+/// interpret.
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-+:,@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<PathBuf, ConceptError> {
+    write_file(path, |w| {
+        serde_json::to_writer_pretty(&mut *w, value)?;
+        writeln!(w)
+    })
+}
+
+fn write_file(
+    path: &Path,
+    f: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> Result<PathBuf, ConceptError> {
+    let io_error = |source| ConceptError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    f(&mut writer).map_err(io_error)?;
+    writer.flush().map_err(io_error)?;
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configuration::ConfigurationBuilder,
+        json::OutputSpecs,
+        runner::Runner,
+        sink::{Compression, OutputSettings},
+    };
+
+    #[test]
+    fn the_example_is_the_same_every_time() {
+        let example = ExampleScenario::generate();
+        assert_eq!(example, ExampleScenario::generate());
+        assert_eq!(
+            (
+                example.behaviours.len(),
+                example.beliefs.len(),
+                example.agents.len(),
+                example.prs.len()
+            ),
+            (3, 4, EXAMPLE_AGENTS, 12)
+        );
+        assert!(example.agents.iter().all(|agent| agent.friends.len() == 4));
+    }
+
+    #[test]
+    fn the_written_example_runs_end_to_end() {
+        let dir = std::env::temp_dir().join(format!("concept example {}", Uuid::new_v4()));
+        let outcome = ExampleScenario::generate().write_to(&dir).unwrap();
+        assert_eq!(outcome.artifacts.len(), 5);
+        assert_eq!(outcome.run_script, dir.join(SCRIPT_FILE));
+        assert!(outcome
+            .run_command
+            .contains(&format!("--agents '{}'", dir.join(AGENTS_FILE).display())));
+
+        let output = dir.join("output.json");
+        let config = ConfigurationBuilder::new()
+            .behaviours_from_path(dir.join(BEHAVIOURS_FILE))
+            .beliefs_from_path(dir.join(BELIEFS_FILE))
+            .agents_from_path(dir.join(AGENTS_FILE))
+            .prs_from_path(dir.join(PRS_FILE))
+            .time_range(EXAMPLE_START_TIME, EXAMPLE_END_TIME)
+            .output_settings(OutputSettings {
+                path: output.clone(),
+                compression: Compression::None,
+            })
+            .build()
+            .unwrap();
+        let outcome = Runner::new(config).with_seed(1).run().unwrap();
+        assert_eq!(outcome.last_tick, EXAMPLE_END_TIME);
+        let output: OutputSpecs = serde_json::from_reader(File::open(&output).unwrap()).unwrap();
+        assert_eq!(output.data.len(), 10);
+        for spec in output.data.values() {
+            assert_eq!(spec.n_performers.values().sum::<usize>(), EXAMPLE_AGENTS);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn words_are_quoted_only_when_needed() {
+        assert_eq!(shell_quote("out/example.json"), "out/example.json");
+        assert_eq!(shell_quote("my example"), "'my example'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,158 @@
+//! A fingerprint of the complete configuration of a run, so that a run
+//! whose inputs, options, seed and code are the same as one already seen can
+//! be skipped.
+//!
+//! The fingerprint is the 64-bit FNV-1a hash, in hexadecimal, of the
+//! canonical JSON of:
+//!
+//! - the versions of this crate and of belief_spread,
+//! - the size and checksum of each input file, but not its path, so the
+//!   same inputs moved elsewhere give the same fingerprint,
+//! - the options of the run, and
+//! - the seed.
+//!
+//! The canonical JSON has the keys of every object in sorted order and no
+//! whitespace, and numbers are written as serde_json writes them on every
+//! platform, so it does not depend on the order the options were given in.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::verify::{checksum, InputFile};
+
+/// The version of belief_spread this crate is built against, which must be
+/// kept in step with Cargo.toml.
+pub const BELIEF_SPREAD_VERSION: &str = "0.11.0-pre6";
+
+/// Everything the fingerprint of a run is computed from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Fingerprinted<'a, O> {
+    concept_version: &'a str,
+    belief_spread_version: &'a str,
+    inputs: Vec<FingerprintedInput<'a>>,
+    options: &'a O,
+    seed: u64,
+}
+
+/// The contents of an input file, as far as the fingerprint is concerned.
+#[derive(Debug, Clone, Serialize)]
+struct FingerprintedInput<'a> {
+    bytes: u64,
+    checksum: &'a str,
+}
+
+/// The fingerprint of a run with `inputs`, in the order they are read,
+/// `options` and `seed`.
+///
+/// The options should leave out anything that does not change what the run
+/// does, such as the paths of the input files, which are covered by their
+/// checksums, where the outputs are written, and how much is logged.
+pub fn fingerprint(
+    inputs: &[InputFile],
+    options: &impl Serialize,
+    seed: u64,
+) -> serde_json::Result<String> {
+    let value = serde_json::to_value(Fingerprinted {
+        concept_version: env!("CARGO_PKG_VERSION"),
+        belief_spread_version: BELIEF_SPREAD_VERSION,
+        inputs: inputs
+            .iter()
+            .map(|input| FingerprintedInput {
+                bytes: input.bytes,
+                checksum: &input.checksum,
+            })
+            .collect(),
+        options,
+        seed,
+    })?;
+    let mut json = Vec::new();
+    write_canonical(&value, &mut json)?;
+    let (_, hash) = checksum(json.as_slice()).expect("reading from memory cannot fail");
+    Ok(format!("{hash:016x}"))
+}
+
+/// Write `value` as JSON with the keys of every object in sorted order, and
+/// no whitespace.
+fn write_canonical(value: &Value, json: &mut Vec<u8>) -> serde_json::Result<()> {
+    match value {
+        Value::Array(values) => {
+            json.push(b'[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    json.push(b',');
+                }
+                write_canonical(value, json)?;
+            }
+            json.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by_key(|&(key, _)| key);
+            json.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(b',');
+                }
+                serde_json::to_writer(&mut *json, key)?;
+                json.push(b':');
+                write_canonical(value, json)?;
+            }
+            json.push(b'}');
+        }
+        value => serde_json::to_writer(&mut *json, value)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn input(checksum: &str) -> InputFile {
+        InputFile {
+            path: PathBuf::from("agents.json"),
+            bytes: 10,
+            modified: Some(1),
+            checksum: checksum.to_string(),
+        }
+    }
+
+    #[test]
+    fn fingerprints_are_canonical() {
+        let options = json!({"end": 10, "filters": {"a": 1, "b": [1.5, null]}});
+        let reordered = json!({"filters": {"b": [1.5, null], "a": 1}, "end": 10});
+        let fp = fingerprint(&[input("0123")], &options, 4).unwrap();
+        assert_eq!(fp.len(), 16);
+        assert_eq!(fp, fingerprint(&[input("0123")], &reordered, 4).unwrap());
+
+        let mut canonical = Vec::new();
+        write_canonical(&reordered, &mut canonical).unwrap();
+        assert_eq!(canonical, br#"{"end":10,"filters":{"a":1,"b":[1.5,null]}}"#);
+
+        // Neither the path nor the modification time of an input matter
+        let moved = InputFile {
+            path: PathBuf::from("elsewhere/agents.json"),
+            modified: Some(2),
+            ..input("0123")
+        };
+        assert_eq!(fp, fingerprint(&[moved], &options, 4).unwrap());
+    }
+
+    #[test]
+    fn changing_anything_changes_the_fingerprint() {
+        let options = HashMap::from([("end", 10)]);
+        let fp = fingerprint(&[input("0123")], &options, 4).unwrap();
+        assert_ne!(fp, fingerprint(&[input("0124")], &options, 4).unwrap());
+        assert_ne!(fp, fingerprint(&[input("0123")], &options, 5).unwrap());
+        assert_ne!(
+            fp,
+            fingerprint(&[input("0123")], &HashMap::from([("end", 11)]), 4).unwrap()
+        );
+        assert_ne!(fp, fingerprint(&[], &options, 4).unwrap());
+    }
+}
@@ -0,0 +1,210 @@
+//! A diagnostic log of how much of the change in each activation came from
+//! an [Agent]'s friends, and how much from its own delta, written as each
+//! tick is perceived.
+//!
+//! The log is written as CSV, one row per [Agent] and [Belief] at each
+//! tick, ordered by time, then by [Agent] in the order of the model, then by
+//! [Belief] in the canonical order:
+//!
+//! ```csv
+//! time,agent_uuid,belief_uuid,own,friends,activation
+//! 1,...,...,0.25,-0.125,0.125
+//! ```
+//!
+//! `own` is the delta of the [Agent] times its activation at the previous
+//! tick, and `friends` is the change from the actions its friends performed
+//! at the previous tick, as computed during perception, so `own + friends`
+//! is the activation before it is brought within [-1, 1] by the
+//! [BoundsPolicy](crate::bounds::BoundsPolicy) and held to any floor or
+//! ceiling. `friends` is not purely the friends' doing: it is their
+//! weighted pressure scaled by how compatible the [Belief] is with the
+//! others the [Agent] holds, as
+//! [activation_change](belief_spread::Agent::activation_change) computes
+//! it.
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use belief_spread::{AgentPtr, BeliefPtr, SimTime};
+
+use crate::{
+    collections::ModelIndex,
+    error::ConceptError,
+    sink::{OutputSettings, OutputSink},
+};
+
+/// The contributions to the activation of an [Agent] for a [Belief] at a
+/// tick, before it is brought within [-1, 1].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Influence {
+    /// The delta of the [Agent] times its previous activation.
+    pub own: f64,
+    /// The change from the actions of the [Agent]'s friends.
+    pub friends: f64,
+}
+
+/// An influence log open for writing.
+pub struct InfluenceLog {
+    path: PathBuf,
+    sink: Box<dyn OutputSink>,
+    /// The positions of the [Agent]s logged, in ascending order, or [None]
+    /// if every [Agent] is.
+    agents: Option<Vec<usize>>,
+}
+
+impl InfluenceLog {
+    /// Create a log, replacing any existing file, and write its header.
+    ///
+    /// Only the [Agent]s at `agents` are logged if they are given, such as
+    /// the positions of a [panel](crate::panel::PanelSpec::sample), as
+    /// logging every [Agent] writes a row per [Agent] and [Belief] each tick.
+    pub fn create(
+        settings: &OutputSettings,
+        agents: Option<Vec<usize>>,
+    ) -> Result<Self, ConceptError> {
+        let io_error = |source| ConceptError::Io {
+            path: settings.path.clone(),
+            source,
+        };
+        let mut sink = settings.open().map_err(io_error)?;
+        writeln!(sink, "time,agent_uuid,belief_uuid,own,friends,activation").map_err(io_error)?;
+        Ok(InfluenceLog {
+            path: settings.path.clone(),
+            sink,
+            agents: agents.map(|mut agents| {
+                agents.sort_unstable();
+                agents
+            }),
+        })
+    }
+
+    /// The path of the log.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Whether the [Agent] at position `i` in the model is logged.
+    pub(crate) fn logs(&self, i: usize) -> bool {
+        self.agents
+            .as_ref()
+            .is_none_or(|agents| agents.binary_search(&i).is_ok())
+    }
+
+    /// Write the [Influence]s on the activations of `agent` at `time`, given
+    /// in the order of `beliefs`, beside the activations it was left with.
+    pub(crate) fn write_agent(
+        &mut self,
+        time: SimTime,
+        agent: &AgentPtr,
+        beliefs: &[BeliefPtr],
+        index: &ModelIndex,
+        influences: &[Influence],
+    ) -> io::Result<()> {
+        let agent = agent.borrow();
+        for &j in index.canonical_beliefs() {
+            let Influence { own, friends } = influences[j];
+            let activation = agent
+                .get_activation(time, &beliefs[j])
+                .expect("the activation was just perceived");
+            writeln!(
+                self.sink,
+                "{time},{},{},{own},{friends},{activation}",
+                agent.uuid(),
+                index.belief_uuids()[j]
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Finish writing the log.
+    ///
+    /// # Returns
+    /// The path of the log.
+    pub fn finish(self) -> Result<PathBuf, ConceptError> {
+        self.sink
+            .finish()
+            .map_err(|source| ConceptError::Output { source })?;
+        Ok(self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use uuid::Uuid;
+
+    use crate::{
+        configuration::tests::small_builder,
+        json::{AgentSpec, BeliefSpec},
+        runner::Runner,
+        sink::Compression,
+    };
+
+    use super::*;
+
+    #[test]
+    fn influence_splits_each_change_between_the_agent_and_its_friends() {
+        // One belief, perceived at 0.5 from behaviour 0 and related to itself
+        // at 1, so the context of an agent holding it at a is a
+        let belief = Uuid::from_u128(0x200);
+        let beliefs = vec![BeliefSpec {
+            name: String::from("belief 0"),
+            uuid: belief,
+            perceptions: HashMap::from([(Uuid::from_u128(0x100), 0.5)]),
+            relationships: HashMap::from([(belief, 1.0)]),
+            delta_distribution: None,
+        }];
+        let [a, b] = [0x300, 0x301].map(Uuid::from_u128);
+        let agent = |uuid, activation, friends: HashMap<Uuid, f64>| AgentSpec {
+            uuid,
+            actions: HashMap::from([(0, Uuid::from_u128(0x100))]),
+            activations: HashMap::from([(0, HashMap::from([(belief, activation)]))]),
+            deltas: HashMap::from([(belief, 0.5)]),
+            friends,
+            activation_floors: HashMap::new(),
+            activation_ceilings: HashMap::new(),
+        };
+        let path = std::env::temp_dir().join(format!("concept-influence-{}.csv", Uuid::new_v4()));
+        let settings = OutputSettings {
+            path: path.clone(),
+            compression: Compression::None,
+        };
+        let config = small_builder()
+            .with_beliefs(beliefs)
+            .with_agents(vec![
+                agent(a, 0.5, HashMap::from([(b, 0.8)])),
+                agent(b, -0.5, HashMap::new()),
+            ])
+            .with_prs(Vec::new())
+            .time_range(1, 2)
+            .build()
+            .unwrap();
+        let mut runner = Runner::new(config)
+            .with_seed(1)
+            .with_influence_log(InfluenceLog::create(&settings, Some(vec![0])).unwrap());
+        runner.run_until(1).unwrap();
+        assert_eq!(runner.finish_influence_log().unwrap(), Some(path.clone()));
+
+        // a: own = 0.5 * 0.5, and its friend performed behaviour 0 at
+        // weight 0.8, so pressure = 0.5 * 0.8 = 0.4, and the context is
+        // 0.5, so friends = (1 + 0.5) / 2 * 0.4
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("time,agent_uuid,belief_uuid,own,friends,activation")
+        );
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(row[..3], ["1", &a.to_string(), &belief.to_string()]);
+        let values: Vec<f64> = row[3..].iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values[0], 0.25);
+        assert!((values[1] - 0.3).abs() < 1e-12);
+        assert!((values[2] - 0.55).abs() < 1e-12);
+        // b has no friends, and is not logged
+        assert_eq!(lines.next(), None);
+    }
+}
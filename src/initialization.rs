@@ -0,0 +1,318 @@
+//! Initializing the activations of [Agent]s from the summary of a prior run
+//! that wrote only [OutputSpecs], so a run can continue from where another
+//! left off without a snapshot of its [Agent]s.
+//!
+//! The initial activation of each [Agent] for each [Belief] is drawn from a
+//! normal distribution with the mean and standard deviation of the
+//! activations of that [Belief] at a tick of the summary, clamped to
+//! [-1, 1]. Each is drawn from a random number generator seeded from the
+//! seed of the run and the UUIDs of the [Agent] and [Belief], as deltas are
+//! by [DeltaDistribution::sample](crate::deltas::DeltaDistribution::sample),
+//! so it does not depend on the order the [Agent]s are loaded in.
+
+use belief_spread::SimTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    collections::{ModelIndex, UuidSet},
+    deltas::{delta_rng, standard_normal},
+    error::ValidationIssue,
+    json::{AgentSpec, OutputSpecs},
+};
+
+/// The stream of the generator of [delta_rng] that initial activations are
+/// drawn from, so they are independent of generated deltas.
+const ACTIVATION_STREAM: u64 = 1;
+
+/// Draw the initial activation of the [Agent] `agent` for the [Belief]
+/// `belief` from a normal distribution with `mean` and standard deviation
+/// `sd`, clamped to [-1, 1].
+///
+/// The same seed, [Agent] and [Belief] always give the same activation.
+pub fn sample_activation(mean: f64, sd: f64, seed: u64, agent: Uuid, belief: Uuid) -> f64 {
+    let mut rng = delta_rng(seed, agent, belief);
+    rng.set_stream(ACTIVATION_STREAM);
+    (mean + sd * standard_normal(&mut rng)).clamp(-1.0, 1.0)
+}
+
+/// The initial activations drawn for the [Agent]s from a summary, set by
+/// [ConfigurationBuilder::init_from_summary](crate::configuration::ConfigurationBuilder::init_from_summary).
+pub(crate) struct SummaryActivations {
+    tick: SimTime,
+    seed: u64,
+    /// The UUID, mean and standard deviation of each [Belief] of the model
+    /// the summary has statistics for.
+    beliefs: Vec<(Uuid, f64, f64)>,
+}
+
+impl SummaryActivations {
+    /// Read the statistics of `tick` in `summary` for the [Belief]s in
+    /// `index`.
+    ///
+    /// `known` is every [Belief] loaded, including those the model may have
+    /// been restricted away from, which the summary may still have.
+    ///
+    /// # Returns
+    /// The activations to draw, or every issue found if the summary does not
+    /// have `tick`, has a [Belief] not in `known`, or has statistics outside
+    /// of their legal range.
+    pub(crate) fn new(
+        summary: &OutputSpecs,
+        tick: SimTime,
+        index: &ModelIndex,
+        known: &UuidSet,
+        seed: u64,
+    ) -> Result<Self, Vec<ValidationIssue>> {
+        let Some(spec) = summary.data.get(&tick) else {
+            let mut ticks: Vec<SimTime> = summary.data.keys().copied().collect();
+            ticks.sort_unstable();
+            return Err(vec![ValidationIssue::MissingSummaryTick { tick, ticks }]);
+        };
+        let mut unknown: Vec<Uuid> = spec
+            .mean_activation
+            .keys()
+            .chain(spec.sd_activation.keys())
+            .filter(|belief| !known.contains(belief))
+            .copied()
+            .collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        let mut issues: Vec<ValidationIssue> = unknown
+            .into_iter()
+            .map(|target| ValidationIssue::UnknownTarget {
+                input: "initial summary",
+                target_kind: "belief",
+                target,
+            })
+            .collect();
+
+        let mut beliefs = Vec::new();
+        for &belief in index.belief_uuids() {
+            let (Some(&mean), Some(&sd)) = (
+                spec.mean_activation.get(&belief),
+                spec.sd_activation.get(&belief),
+            ) else {
+                continue;
+            };
+            let issue = |field, value, range| ValidationIssue::OutOfRange {
+                kind: "summary of belief",
+                uuid: belief,
+                field,
+                value,
+                range,
+            };
+            if !(-1.0..=1.0).contains(&mean) {
+                issues.push(issue("meanActivation", mean, "[-1, 1]"));
+            }
+            if !(sd.is_finite() && sd >= 0.0) {
+                issues.push(issue("sdActivation", sd, "[0, inf)"));
+            }
+            beliefs.push((belief, mean, sd));
+        }
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+        Ok(SummaryActivations {
+            tick,
+            seed,
+            beliefs,
+        })
+    }
+
+    /// Replace the activations of an [AgentSpec] at `origin` with ones
+    /// drawn for each [Belief] the summary has statistics for.
+    pub(crate) fn initialize(&self, spec: &mut AgentSpec, origin: SimTime) {
+        let activations = spec.activations.entry(origin).or_default();
+        for &(belief, mean, sd) in &self.beliefs {
+            activations.insert(
+                belief,
+                sample_activation(mean, sd, self.seed, spec.uuid, belief),
+            );
+        }
+    }
+
+    /// How the activations were initialized, given the number of [Agent]s
+    /// initialized, logging the statistics each [Belief] was drawn from.
+    pub(crate) fn initialization(&self, agents: usize) -> ActivationInitialization {
+        let mut beliefs: Vec<InitializedBelief> = self
+            .beliefs
+            .iter()
+            .map(|&(belief, mean, sd)| InitializedBelief { belief, mean, sd })
+            .collect();
+        beliefs.sort_unstable_by_key(|initialized| initialized.belief);
+        for initialized in &beliefs {
+            log::info!(
+                "Drew the initial activations of {} agents for belief {} with mean {} and SD {} from tick {} of the summary",
+                agents,
+                initialized.belief,
+                initialized.mean,
+                initialized.sd,
+                self.tick
+            );
+        }
+        ActivationInitialization {
+            tick: self.tick,
+            seed: self.seed,
+            agents,
+            beliefs,
+        }
+    }
+}
+
+/// How the initial activations of the [Agent]s were drawn from a summary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivationInitialization {
+    /// The tick of the summary the statistics were taken from.
+    pub tick: SimTime,
+    /// The seed the activations were drawn with.
+    pub seed: u64,
+    /// The number of [Agent]s initialized.
+    pub agents: usize,
+    /// The statistics each [Belief] was drawn from, in order of UUID.
+    pub beliefs: Vec<InitializedBelief>,
+}
+
+/// The statistics the initial activations of a [Belief] were drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializedBelief {
+    /// The UUID of the [Belief].
+    pub belief: Uuid,
+    /// The mean activation at the tick of the summary.
+    pub mean: f64,
+    /// The standard deviation of the activations at the tick of the summary.
+    pub sd: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{configuration::tests::small_builder, error::ConceptError, json::OutputSpec};
+
+    use super::*;
+
+    /// A summary with only the mean and SD of each [Belief] of `beliefs`
+    /// at tick 7.
+    fn summary(beliefs: &[(Uuid, f64, f64)]) -> OutputSpecs {
+        OutputSpecs {
+            run_id: None,
+            fingerprint: None,
+            time_origin: Some(0),
+            output_every: None,
+            names: None,
+            data: HashMap::from([(
+                7,
+                OutputSpec {
+                    mean_activation: beliefs.iter().map(|&(b, mean, _)| (b, mean)).collect(),
+                    sd_activation: beliefs.iter().map(|&(b, _, sd)| (b, sd)).collect(),
+                    median_activation: HashMap::new(),
+                    nonzero_activation_count: HashMap::new(),
+                    n_performers: HashMap::new(),
+                    weighted_mean_activation: None,
+                    weighted_sd_activation: None,
+                    action_assortativity: None,
+                },
+            )]),
+        }
+    }
+
+    fn agents(n: u128) -> Vec<AgentSpec> {
+        let beliefs = [0x200, 0x201].map(Uuid::from_u128);
+        (0..n)
+            .map(|i| AgentSpec {
+                uuid: Uuid::from_u128(0x1000 + i),
+                actions: HashMap::from([(0, Uuid::from_u128(0x100))]),
+                activations: HashMap::from([(0, beliefs.iter().map(|&b| (b, 0.5)).collect())]),
+                deltas: beliefs.iter().map(|&b| (b, 1.0)).collect(),
+                friends: HashMap::new(),
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn initial_activations_match_the_summary() {
+        let [b0, b1] = [0x200, 0x201].map(Uuid::from_u128);
+        let config = small_builder()
+            .with_agents(agents(5_000))
+            .seed(3)
+            .init_from_summary(summary(&[(b0, -0.3, 0.2)]), 7)
+            .build()
+            .unwrap();
+        let activations = |b: usize| -> Vec<f64> {
+            config
+                .agents
+                .iter()
+                .map(|agent| {
+                    agent
+                        .borrow()
+                        .get_activation(0, &config.beliefs[b])
+                        .unwrap()
+                })
+                .collect()
+        };
+
+        let drawn = activations(config.index().belief(&b0).unwrap());
+        let n = drawn.len() as f64;
+        let mean = drawn.iter().sum::<f64>() / n;
+        let sd = (drawn.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        // Five standard errors either side
+        assert!((mean + 0.3).abs() < 5.0 * 0.2 / n.sqrt(), "mean {mean}");
+        assert!((sd - 0.2).abs() < 0.01, "sd {sd}");
+
+        // The summary has nothing for the other belief, which keeps the
+        // activations the agents were given
+        let given = activations(config.index().belief(&b1).unwrap());
+        assert!(given.iter().all(|&v| v == 0.5));
+
+        let initialization = config.activation_initialization.unwrap();
+        assert_eq!((initialization.tick, initialization.agents), (7, 5_000));
+        assert_eq!(
+            initialization.beliefs,
+            [InitializedBelief {
+                belief: b0,
+                mean: -0.3,
+                sd: 0.2
+            }]
+        );
+    }
+
+    #[test]
+    fn activations_are_clamped_and_depend_only_on_the_seed_agent_and_belief() {
+        let (agent, belief) = (Uuid::from_u128(0x300), Uuid::from_u128(0x200));
+        let activation = sample_activation(0.0, 0.5, 1, agent, belief);
+        assert_eq!(activation, sample_activation(0.0, 0.5, 1, agent, belief));
+        assert_ne!(activation, sample_activation(0.0, 0.5, 2, agent, belief));
+        assert_eq!(sample_activation(0.9, 100.0, 1, agent, belief).abs(), 1.0);
+    }
+
+    #[test]
+    fn summaries_without_the_tick_or_with_unknown_beliefs_fail() {
+        let b0 = Uuid::from_u128(0x200);
+        let unknown = Uuid::from_u128(0x299);
+        match small_builder()
+            .init_from_summary(summary(&[(b0, 0.0, 0.1)]), 8)
+            .build()
+        {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                &report.issues[..],
+                [ValidationIssue::MissingSummaryTick { tick: 8, ticks }] if ticks == &[7]
+            )),
+            result => panic!("expected a missing tick, got {:?}", result.err()),
+        }
+        match small_builder()
+            .init_from_summary(summary(&[(b0, 0.0, 0.1), (unknown, 0.0, 0.1)]), 7)
+            .build()
+        {
+            Err(ConceptError::Validation(report)) => assert!(matches!(
+                report.issues[..],
+                [ValidationIssue::UnknownTarget { target, .. }] if target == unknown
+            )),
+            result => panic!("expected an unknown belief, got {:?}", result.err()),
+        }
+    }
+}
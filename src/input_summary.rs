@@ -0,0 +1,248 @@
+//! Summary statistics of the static inputs of a model, for comparing the
+//! deltas, friendships and performance relationships of scenarios.
+
+use std::collections::BTreeMap;
+
+use belief_spread::{AgentPtr, BeliefPtr};
+use log::info;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    caps::{capped_agents, ActivationCaps},
+    performance_relationships::PrsMatrix,
+};
+
+/// The distribution of a set of values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Distribution {
+    /// The number of values.
+    pub count: usize,
+    /// The mean.
+    pub mean: f64,
+    /// The sample standard deviation, or [None] if there are fewer than two
+    /// values.
+    pub sd: Option<f64>,
+    /// The smallest value.
+    pub min: f64,
+    /// The first quartile.
+    pub q1: f64,
+    /// The median.
+    pub median: f64,
+    /// The third quartile.
+    pub q3: f64,
+    /// The largest value.
+    pub max: f64,
+}
+
+impl Distribution {
+    /// The distribution of `values`, with the quartiles interpolated linearly
+    /// between the closest values.
+    ///
+    /// # Returns
+    /// The [Distribution], or [None] if there are no values.
+    pub fn from_values(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable_by(f64::total_cmp);
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let sd = (count > 1).then(|| {
+            let sq_dev: f64 = values.iter().map(|v| (v - mean) * (v - mean)).sum();
+            f64::sqrt(sq_dev / (count - 1) as f64)
+        });
+        let quantile = |q: f64| {
+            let at = q * (count - 1) as f64;
+            let (lower, upper) = (at.floor() as usize, at.ceil() as usize);
+            values[lower] + (values[upper] - values[lower]) * (at - lower as f64)
+        };
+        Some(Distribution {
+            count,
+            mean,
+            sd,
+            min: values[0],
+            q1: quantile(0.25),
+            median: quantile(0.5),
+            q3: quantile(0.75),
+            max: values[count - 1],
+        })
+    }
+}
+
+/// The distribution of the number of friends of each [Agent].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DegreeSummary {
+    /// The distribution of the number of friends, or [None] if there are no
+    /// [Agent]s.
+    pub distribution: Option<Distribution>,
+    /// The number of [Agent]s without friends.
+    pub isolates: usize,
+}
+
+/// Summary statistics of the static inputs of a model, computed after it
+/// is loaded.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputSummary {
+    /// The distribution of the deltas of the [Agent]s for each [Belief], by
+    /// [Belief].
+    pub deltas: BTreeMap<Uuid, Distribution>,
+    /// The distribution of the weights of every friendship, or [None] if
+    /// there are none.
+    pub friend_weights: Option<Distribution>,
+    /// The distribution of the number of friends of each [Agent].
+    pub degree: DegreeSummary,
+    /// The distribution of the performance relationships of each
+    /// [Behaviour] over every [Belief], with missing relationships as zero,
+    /// by [Behaviour].
+    pub prs: BTreeMap<Uuid, Distribution>,
+    /// The number of [Agent]s with a floor or ceiling on their activation
+    /// for each [Belief] with any, by [Belief].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub capped_agents: BTreeMap<Uuid, usize>,
+}
+
+impl InputSummary {
+    /// Summarise the deltas, friends and activation caps of the [Agent]s,
+    /// and the performance relationships.
+    pub fn new(
+        agents: &[AgentPtr],
+        caps: &[ActivationCaps],
+        beliefs: &[BeliefPtr],
+        prs: &PrsMatrix,
+    ) -> Self {
+        let mut deltas = vec![Vec::with_capacity(agents.len()); beliefs.len()];
+        let mut friend_weights = Vec::new();
+        let mut degrees = Vec::with_capacity(agents.len());
+        for agent in agents {
+            let agent = agent.borrow();
+            for (values, belief) in deltas.iter_mut().zip(beliefs) {
+                values.extend(agent.get_delta(belief));
+            }
+            friend_weights.extend(agent.get_friends().values());
+            degrees.push(agent.get_friends().len() as f64);
+        }
+
+        let index = prs.index();
+        InputSummary {
+            deltas: index
+                .belief_uuids()
+                .iter()
+                .zip(deltas)
+                .filter_map(|(&uuid, values)| Some((uuid, Distribution::from_values(values)?)))
+                .collect(),
+            friend_weights: Distribution::from_values(friend_weights),
+            degree: DegreeSummary {
+                isolates: degrees.iter().filter(|&&d| d == 0.0).count(),
+                distribution: Distribution::from_values(degrees),
+            },
+            prs: index
+                .behaviour_uuids()
+                .iter()
+                .enumerate()
+                .filter_map(|(j, &uuid)| {
+                    Some((
+                        uuid,
+                        Distribution::from_values(prs.behaviour_row(j).to_vec())?,
+                    ))
+                })
+                .collect(),
+            capped_agents: capped_agents(caps, index),
+        }
+    }
+
+    /// Log the summary, a line for each distribution.
+    pub fn log(&self) {
+        for (uuid, deltas) in &self.deltas {
+            info!("Deltas of belief {uuid}: {}", describe(deltas));
+        }
+        if let Some(weights) = &self.friend_weights {
+            info!("Friend weights: {}", describe(weights));
+        }
+        if let Some(degree) = &self.degree.distribution {
+            info!(
+                "Friends per agent: {}, {} isolated",
+                describe(degree),
+                self.degree.isolates
+            );
+        }
+        for (uuid, prs) in &self.prs {
+            info!(
+                "Performance relationships of behaviour {uuid}: {}",
+                describe(prs)
+            );
+        }
+        for (uuid, n) in &self.capped_agents {
+            info!("Agents with a floor or ceiling for belief {uuid}: {n}");
+        }
+    }
+}
+
+/// A [Distribution] in one line.
+fn describe(d: &Distribution) -> String {
+    format!(
+        "n {}, mean {:.4}, sd {}, min {:.4}, quartiles [{:.4}, {:.4}, {:.4}], max {:.4}",
+        d.count,
+        d.mean,
+        d.sd.map_or("-".to_string(), |sd| format!("{sd:.4}")),
+        d.min,
+        d.q1,
+        d.median,
+        d.q3,
+        d.max
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::tests::hub_builder;
+
+    #[test]
+    fn distribution_interpolates_quartiles() {
+        let d = Distribution::from_values(vec![4.0, 1.0, 3.0, 2.0, 5.0]).unwrap();
+        assert_eq!((d.count, d.mean, d.min, d.max), (5, 3.0, 1.0, 5.0));
+        assert_eq!((d.q1, d.median, d.q3), (2.0, 3.0, 4.0));
+        assert_eq!(d.sd, Some(f64::sqrt(2.5)));
+
+        let d = Distribution::from_values(vec![1.0, 2.0]).unwrap();
+        assert_eq!((d.q1, d.median, d.q3), (1.25, 1.5, 1.75));
+
+        assert_eq!(Distribution::from_values(vec![7.0]).unwrap().sd, None);
+        assert_eq!(Distribution::from_values(Vec::new()), None);
+    }
+
+    #[test]
+    fn summary_covers_every_belief_and_behaviour() {
+        let config = hub_builder().build().unwrap();
+        let summary = InputSummary::new(
+            &config.agents,
+            &config.activation_caps,
+            &config.beliefs,
+            &config.prs,
+        );
+
+        let index = config.index();
+        assert!(summary.deltas.keys().eq(index
+            .canonical_beliefs()
+            .iter()
+            .map(|&i| &index.belief_uuids()[i])));
+        assert!(summary.prs.keys().eq(index
+            .canonical_behaviours()
+            .iter()
+            .map(|&i| &index.behaviour_uuids()[i])));
+        for deltas in summary.deltas.values() {
+            assert_eq!(deltas.count, config.agents.len());
+        }
+
+        // The hub has four friends and every other agent only the hub
+        let degree = summary.degree.distribution.unwrap();
+        assert_eq!((degree.max, degree.median, degree.mean), (4.0, 1.0, 1.6));
+        assert_eq!(summary.degree.isolates, 0);
+        let weights = summary.friend_weights.unwrap();
+        assert_eq!((weights.count, weights.min, weights.max), (8, 0.1, 0.5));
+    }
+}
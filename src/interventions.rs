@@ -0,0 +1,314 @@
+//! Exogenous changes to the deltas and activations of the [Agent]s at
+//! chosen ticks, such as a campaign that makes a [Belief] stick for a group
+//! of [Agent]s from a tick on.
+//!
+//! Interventions are read from a JSON list of [InterventionSpec]s and
+//! applied by the [Runner](crate::runner::Runner) at the start of their
+//! tick, before perception, in the order they were given:
+//!
+//! ```json
+//! [
+//!   {
+//!     "time": 50,
+//!     "target": "all",
+//!     "beliefUuid": "6a3e5bd6-34a5-4f5b-8a2c-5d4a8d1b2c3e",
+//!     "kind": "delta",
+//!     "value": 1.2
+//!   }
+//! ]
+//! ```
+//!
+//! A delta intervention sets the delta of the [Belief] for the rest of the
+//! run. An activation intervention replaces the activation the [Agent]s
+//! carry into the tick, which perception at the tick reads in place of the
+//! activation of the tick before. The activation of the tick before is left
+//! as it was simulated, so every output reports it, and the intervention
+//! shows in the activations from the tick on. Neither is held to the floors
+//! or ceilings of the [Agent]s.
+
+use std::collections::BTreeMap;
+
+use belief_spread::{AgentPtr, BeliefPtr, SimTime};
+use uuid::Uuid;
+
+use crate::{
+    collections::{ModelIndex, UuidMap},
+    error::ValidationIssue,
+    json::{InterventionKind, InterventionSpec, InterventionTarget},
+};
+
+/// The [InterventionSpec]s of a run, resolved to the positions of the
+/// [Agent]s and [Belief]s they change, by tick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Interventions(BTreeMap<SimTime, Vec<Intervention>>);
+
+/// The activations activation interventions replace those the [Agent]s
+/// carry into a tick with, by the positions of the [Agent] and [Belief].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CarriedActivations(BTreeMap<(usize, usize), f64>);
+
+impl CarriedActivations {
+    /// The activation the [Agent] at position `agent` carries into the tick
+    /// for the [Belief] at position `belief`, if an intervention replaced
+    /// it.
+    pub(crate) fn get(&self, agent: usize, belief: usize) -> Option<f64> {
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0.get(&(agent, belief)).copied()
+    }
+}
+
+/// An [InterventionSpec] resolved to positions.
+#[derive(Debug, Clone, PartialEq)]
+struct Intervention {
+    /// The positions of the [Agent]s changed, or [None] for every [Agent].
+    agents: Option<Vec<usize>>,
+    /// The position of the [Belief] changed.
+    belief: usize,
+    kind: InterventionKind,
+    value: f64,
+}
+
+impl Interventions {
+    /// Resolve `specs` against the [Agent]s and [Belief]s that are
+    /// simulated from `start` to `end`.
+    ///
+    /// # Returns
+    /// The interventions, or an issue for each that is outside of the
+    /// simulated range, has a value a delta or activation cannot take, or
+    /// names an [Agent] or [Belief] that is not simulated.
+    pub(crate) fn new(
+        specs: &[InterventionSpec],
+        agents: &[AgentPtr],
+        index: &ModelIndex,
+        start: SimTime,
+        end: SimTime,
+    ) -> Result<Self, Vec<ValidationIssue>> {
+        let positions: UuidMap<usize> = agents
+            .iter()
+            .enumerate()
+            .map(|(i, agent)| (*agent.borrow().uuid(), i))
+            .collect();
+        let mut issues = Vec::new();
+        let mut unknown: Vec<(&'static str, Uuid)> = Vec::new();
+        let mut interventions = Interventions::default();
+        for spec in specs {
+            if !(start..=end).contains(&spec.time) {
+                issues.push(ValidationIssue::InvalidInterventionTime {
+                    time: spec.time,
+                    start,
+                    end,
+                });
+            }
+            let (field, valid, range) = match spec.kind {
+                InterventionKind::Delta => ("delta", spec.value > 0.0, "(0, inf)"),
+                InterventionKind::Activation => {
+                    ("activation", (-1.0..=1.0).contains(&spec.value), "[-1, 1]")
+                }
+            };
+            if !valid || !spec.value.is_finite() {
+                issues.push(ValidationIssue::OutOfRange {
+                    kind: "intervention on belief",
+                    uuid: spec.belief_uuid,
+                    field,
+                    value: spec.value,
+                    range,
+                });
+            }
+            let belief = index.belief(&spec.belief_uuid);
+            if belief.is_none() {
+                unknown.push(("belief", spec.belief_uuid));
+            }
+            let targets = match &spec.target {
+                InterventionTarget::All => None,
+                InterventionTarget::Agents(uuids) => Some(
+                    uuids
+                        .iter()
+                        .filter_map(|uuid| {
+                            let position = positions.get(uuid).copied();
+                            if position.is_none() {
+                                unknown.push(("agent", *uuid));
+                            }
+                            position
+                        })
+                        .collect(),
+                ),
+            };
+            if let Some(belief) = belief {
+                interventions
+                    .0
+                    .entry(spec.time)
+                    .or_default()
+                    .push(Intervention {
+                        agents: targets,
+                        belief,
+                        kind: spec.kind,
+                        value: spec.value,
+                    });
+            }
+        }
+        unknown.sort_unstable();
+        unknown.dedup();
+        issues.extend(unknown.into_iter().map(|(target_kind, target)| {
+            ValidationIssue::UnknownTarget {
+                input: "interventions",
+                target_kind,
+                target,
+            }
+        }));
+        if issues.is_empty() {
+            Ok(interventions)
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Apply the interventions of `time` to `agents`, in the order they were
+    /// given, replacing `carried` by the activations they carry into the
+    /// tick.
+    ///
+    /// # Returns
+    /// The number of interventions applied.
+    pub(crate) fn apply(
+        &self,
+        time: SimTime,
+        agents: &[AgentPtr],
+        beliefs: &[BeliefPtr],
+        carried: &mut CarriedActivations,
+    ) -> usize {
+        carried.0.clear();
+        let Some(interventions) = self.0.get(&time) else {
+            return 0;
+        };
+        for intervention in interventions {
+            let belief = &beliefs[intervention.belief];
+            let mut apply = |i: usize| match intervention.kind {
+                InterventionKind::Delta => agents[i]
+                    .borrow_mut()
+                    .set_delta(belief.clone(), Some(intervention.value))
+                    .expect("the value of the intervention is validated"),
+                InterventionKind::Activation => {
+                    carried
+                        .0
+                        .insert((i, intervention.belief), intervention.value);
+                }
+            };
+            match &intervention.agents {
+                Some(positions) => positions.iter().for_each(|&i| apply(i)),
+                None => (0..agents.len()).for_each(apply),
+            }
+        }
+        interventions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configuration::tests::small_builder,
+        error::{ConceptError, ValidationReport},
+    };
+
+    fn spec(target: InterventionTarget, belief: Uuid, kind: InterventionKind) -> InterventionSpec {
+        InterventionSpec {
+            time: 2,
+            target,
+            belief_uuid: belief,
+            kind,
+            value: 0.5,
+        }
+    }
+
+    /// The issues of building the small model with `interventions`.
+    fn issues(interventions: Vec<InterventionSpec>) -> Vec<ValidationIssue> {
+        match small_builder().with_interventions(interventions).build() {
+            Err(ConceptError::Validation(ValidationReport { issues, .. })) => issues,
+            Err(err) => panic!("unexpected error {err}"),
+            Ok(_) => panic!("the interventions are valid"),
+        }
+    }
+
+    #[test]
+    fn unknown_agents_and_beliefs_fail_once_each() {
+        let belief = Uuid::from_u128(0x200);
+        let unknown = Uuid::new_v4();
+        let issues = issues(vec![
+            spec(
+                InterventionTarget::Agents(vec![unknown, unknown]),
+                belief,
+                InterventionKind::Delta,
+            ),
+            spec(
+                InterventionTarget::All,
+                unknown,
+                InterventionKind::Activation,
+            ),
+        ]);
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::UnknownTarget {
+                    input: "interventions",
+                    target_kind: "agent",
+                    target: unknown,
+                },
+                ValidationIssue::UnknownTarget {
+                    input: "interventions",
+                    target_kind: "belief",
+                    target: unknown,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn values_and_times_outside_of_their_ranges_fail() {
+        let belief = Uuid::from_u128(0x200);
+        let mut late = spec(
+            InterventionTarget::All,
+            belief,
+            InterventionKind::Activation,
+        );
+        late.time = 4;
+        let mut negative = spec(InterventionTarget::All, belief, InterventionKind::Delta);
+        negative.value = -1.0;
+        assert_eq!(
+            issues(vec![late, negative]),
+            [
+                ValidationIssue::InvalidInterventionTime {
+                    time: 4,
+                    start: 1,
+                    end: 3,
+                },
+                ValidationIssue::OutOfRange {
+                    kind: "intervention on belief",
+                    uuid: belief,
+                    field: "delta",
+                    value: -1.0,
+                    range: "(0, inf)",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn targets_are_read_as_all_or_a_list_of_agents() {
+        let json = r#"[
+            {"time": 2, "target": "all", "beliefUuid": "00000000-0000-0000-0000-000000000200",
+             "kind": "delta", "value": 1.2},
+            {"time": 3, "target": ["00000000-0000-0000-0000-000000000300"],
+             "beliefUuid": "00000000-0000-0000-0000-000000000200", "kind": "activation",
+             "value": -0.5}
+        ]"#;
+        let specs: Vec<InterventionSpec> = serde_json::from_str(json).unwrap();
+        assert_eq!(specs[0].target, InterventionTarget::All);
+        assert_eq!(
+            specs[1].target,
+            InterventionTarget::Agents(vec![Uuid::from_u128(0x300)])
+        );
+        assert_eq!(specs[1].kind, InterventionKind::Activation);
+        assert!(serde_json::from_str::<InterventionTarget>(r#""some""#).is_err());
+    }
+}
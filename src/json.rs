@@ -1,14 +1,28 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::{self, Write},
+};
 
 use belief_spread::{
     Agent, AgentPtr, BasicAgent, BasicBehaviour, BasicBelief, BehaviourPtr, Belief, BeliefPtr,
     SimTime,
 };
-use serde::{Deserialize, Serialize};
+use rayon::prelude::*;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use uuid::Uuid;
 
+use crate::{
+    collections::{ModelIndex, UuidMap},
+    deltas::DeltaDistribution,
+    precision::{Activation, Precision},
+    sampling::OutputSampling,
+    stability::{StabilityOptions, StabilityReport, StabilityWindow},
+    thresholds::{ThresholdCrossings, ThresholdMetrics},
+};
+
 /// The specification for a JSON file representing behaviours.
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct BehaviourSpec {
     /// The name of the behaviour.
     pub name: String,
@@ -18,13 +32,22 @@ pub struct BehaviourSpec {
 }
 
 impl BehaviourSpec {
+    /// Create a [BehaviourSpec] describing a [Behaviour].
+    pub fn from_behaviour(behaviour: &BehaviourPtr) -> Self {
+        let b = behaviour.borrow();
+        BehaviourSpec {
+            name: b.name().to_string(),
+            uuid: *b.uuid(),
+        }
+    }
+
     /// Convert this [BehaviourSpec] into a [BasicBehaviour].
     pub fn to_basic_behaviour(&self) -> BasicBehaviour {
         BasicBehaviour::new_with_uuid(self.name.clone(), self.uuid)
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct BeliefSpec {
     pub name: String,
     #[serde(default = "Uuid::new_v4")]
@@ -33,9 +56,54 @@ pub struct BeliefSpec {
     pub perceptions: HashMap<Uuid, f64>,
     #[serde(default = "HashMap::new")]
     pub relationships: HashMap<Uuid, f64>,
+    /// The distribution the deltas of the [Agent]s with no delta for the
+    /// [Belief] are drawn from, if they are generated.
+    #[serde(
+        default,
+        rename = "deltaDistribution",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub delta_distribution: Option<DeltaDistribution>,
 }
 
 impl BeliefSpec {
+    /// Create a [BeliefSpec] describing a [Belief].
+    ///
+    /// [Belief]s can only be queried for a given [Behaviour] or [Belief], so
+    /// only perceptions of `behaviours` and relationships with `beliefs` are
+    /// included.
+    ///
+    /// # Arguments
+    /// - `belief`: The [Belief].
+    /// - `behaviours`: The [Behaviour]s in the model.
+    /// - `beliefs`: The [Belief]s in the model.
+    ///
+    /// # Returns
+    /// The [BeliefSpec].
+    pub fn from_belief(
+        belief: &BeliefPtr,
+        behaviours: &[BehaviourPtr],
+        beliefs: &[BeliefPtr],
+    ) -> Self {
+        let b = belief.borrow();
+        BeliefSpec {
+            name: b.name().to_string(),
+            uuid: *b.uuid(),
+            perceptions: behaviours
+                .iter()
+                .filter_map(|beh| b.get_perception(beh).map(|v| (*beh.borrow().uuid(), v)))
+                .collect(),
+            relationships: beliefs
+                .iter()
+                .filter_map(|other| {
+                    b.get_relationship(other)
+                        .map(|v| (*other.borrow().uuid(), v))
+                })
+                .collect(),
+            delta_distribution: None,
+        }
+    }
+
     pub fn to_basic_belief(&self, behaviours: &[BehaviourPtr]) -> BeliefPtr {
         let mut b = BasicBelief::new_with_uuid(self.name.clone(), self.uuid);
         behaviours.iter().for_each(|beh| {
@@ -46,23 +114,32 @@ impl BeliefSpec {
         b.into()
     }
 
-    pub fn link_belief_relationships(&self, beliefs: &[BeliefPtr]) {
-        let uuid_beliefs: HashMap<Uuid, &BeliefPtr> =
-            beliefs.iter().map(|b| (*b.borrow().uuid(), b)).collect();
+    /// Multiply every relationship by `factor`, clamping the results to
+    /// [-1, 1].
+    ///
+    /// # Returns
+    /// The number of relationships clamped.
+    pub fn scale_relationships(&mut self, factor: f64) -> usize {
+        let mut clamped = 0;
+        for v in self.relationships.values_mut() {
+            let scaled = *v * factor;
+            *v = scaled.clamp(-1.0, 1.0);
+            clamped += usize::from(*v != scaled);
+        }
+        clamped
+    }
+
+    pub fn link_belief_relationships(&self, beliefs: &UuidMap<BeliefPtr>) {
+        let mut this_belief = beliefs.get(&self.uuid).unwrap().borrow_mut();
         self.relationships.iter().for_each(|(r, &v)| {
-            if let Some(b) = uuid_beliefs.get(r) {
-                uuid_beliefs
-                    .get(&self.uuid)
-                    .unwrap()
-                    .borrow_mut()
-                    .set_relationship((*b).clone(), Some(v))
-                    .unwrap()
+            if let Some(b) = beliefs.get(r) {
+                this_belief.set_relationship(b.clone(), Some(v)).unwrap()
             }
         })
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct AgentSpec {
     #[serde(default = "Uuid::new_v4")]
     pub uuid: Uuid,
@@ -74,9 +151,26 @@ pub struct AgentSpec {
     pub deltas: HashMap<Uuid, f64>,
     #[serde(default = "HashMap::new")]
     pub friends: HashMap<Uuid, f64>,
+    /// The lowest activation of the [Agent] for each [Belief] with a floor,
+    /// which the [Runner](crate::runner::Runner) holds it to after each
+    /// tick of perception.
+    #[serde(
+        default,
+        rename = "activationFloors",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub activation_floors: HashMap<Uuid, f64>,
+    /// The highest activation of the [Agent] for each [Belief] with a
+    /// ceiling.
+    #[serde(
+        default,
+        rename = "activationCeilings",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub activation_ceilings: HashMap<Uuid, f64>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PerformanceRelationshipSpec {
     pub behaviour_uuid: Uuid,
@@ -84,35 +178,139 @@ pub struct PerformanceRelationshipSpec {
     pub value: f64,
 }
 
+/// The specification of an exogenous change to the [Agent]s at a tick of
+/// the simulation, read from a JSON file of interventions.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InterventionSpec {
+    /// The tick the intervention is applied at, before perception.
+    pub time: SimTime,
+    /// The [Agent]s changed.
+    pub target: InterventionTarget,
+    /// The [Belief] whose delta or activation is changed.
+    pub belief_uuid: Uuid,
+    pub kind: InterventionKind,
+    pub value: f64,
+}
+
+/// The [Agent]s an [InterventionSpec] changes, given as `"all"` or a list
+/// of UUIDs.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(from = "TargetRepr", into = "TargetRepr")]
+pub enum InterventionTarget {
+    /// Every [Agent] simulated.
+    All,
+    /// The [Agent]s with these UUIDs.
+    Agents(Vec<Uuid>),
+}
+
+/// How an [InterventionTarget] is written in JSON.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum TargetRepr {
+    All(AllAgents),
+    Agents(Vec<Uuid>),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AllAgents {
+    All,
+}
+
+impl From<TargetRepr> for InterventionTarget {
+    fn from(repr: TargetRepr) -> Self {
+        match repr {
+            TargetRepr::All(AllAgents::All) => InterventionTarget::All,
+            TargetRepr::Agents(agents) => InterventionTarget::Agents(agents),
+        }
+    }
+}
+
+impl From<InterventionTarget> for TargetRepr {
+    fn from(target: InterventionTarget) -> Self {
+        match target {
+            InterventionTarget::All => TargetRepr::All(AllAgents::All),
+            InterventionTarget::Agents(agents) => TargetRepr::Agents(agents),
+        }
+    }
+}
+
+/// What an [InterventionSpec] sets.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InterventionKind {
+    /// The delta of the [Belief], from the tick on.
+    Delta,
+    /// The activation of the [Belief] carried into the tick, which
+    /// perception at the tick reads in place of the one recorded at the tick
+    /// before.
+    Activation,
+}
+
 impl AgentSpec {
-    pub fn to_basic_agent(&self, behaviours: &[BehaviourPtr], beliefs: &[BeliefPtr]) -> AgentPtr {
-        let mut a = BasicAgent::new_with_uuid(self.uuid);
-        let uuid_behaviours: HashMap<Uuid, &BehaviourPtr> =
-            behaviours.iter().map(|b| (*b.borrow().uuid(), b)).collect();
+    /// Create an [AgentSpec] capturing the current state of an [Agent].
+    pub fn from_agent(agent: &AgentPtr) -> Self {
+        let a = agent.borrow();
+        AgentSpec {
+            uuid: *a.uuid(),
+            actions: a
+                .get_actions()
+                .iter()
+                .map(|(&time, b)| (time, *b.borrow().uuid()))
+                .collect(),
+            activations: a
+                .get_activations()
+                .iter()
+                .map(|(&time, acts)| {
+                    (
+                        time,
+                        acts.iter().map(|(b, &v)| (*b.borrow().uuid(), v)).collect(),
+                    )
+                })
+                .collect(),
+            deltas: a
+                .get_deltas()
+                .iter()
+                .map(|(b, &v)| (*b.borrow().uuid(), v))
+                .collect(),
+            friends: a
+                .get_friends()
+                .iter()
+                .map(|(f, &w)| (*f.borrow().uuid(), w))
+                .collect(),
+            activation_floors: HashMap::new(),
+            activation_ceilings: HashMap::new(),
+        }
+    }
 
-        self.actions.iter().for_each(|(&time, b)| {
-            a.set_action(time, Some((*uuid_behaviours.get(b).unwrap()).clone()))
-        });
+    pub fn to_basic_agent(
+        &self,
+        behaviours: &UuidMap<BehaviourPtr>,
+        beliefs: &UuidMap<BeliefPtr>,
+    ) -> AgentPtr {
+        let mut a = BasicAgent::new_with_uuid(self.uuid);
 
-        let uuid_beliefs: HashMap<Uuid, &BeliefPtr> =
-            beliefs.iter().map(|b| (*b.borrow().uuid(), b)).collect();
+        self.actions
+            .iter()
+            .for_each(|(&time, b)| a.set_action(time, Some(behaviours.get(b).unwrap().clone())));
 
         self.activations.iter().for_each(|(&time, acts)| {
             acts.iter().for_each(|(b, &v)| {
-                a.set_activation(time, (*uuid_beliefs.get(b).unwrap()).clone(), Some(v))
+                a.set_activation(time, beliefs.get(b).unwrap().clone(), Some(v))
                     .unwrap()
             })
         });
 
         self.deltas.iter().for_each(|(b, &v)| {
-            a.set_delta((*uuid_beliefs.get(b).unwrap()).clone(), Some(v))
+            a.set_delta(beliefs.get(b).unwrap().clone(), Some(v))
                 .unwrap()
         });
 
         a.into()
     }
 
-    pub fn link_friends(&self, agents: &HashMap<Uuid, AgentPtr>) {
+    pub fn link_friends(&self, agents: &UuidMap<AgentPtr>) {
         let mut this_agent = agents.get(&self.uuid).unwrap().borrow_mut();
 
         self.friends.iter().for_each(|(a, &v)| {
@@ -123,7 +321,7 @@ impl AgentSpec {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OutputSpec {
     pub mean_activation: HashMap<Uuid, f64>,
@@ -131,127 +329,992 @@ pub struct OutputSpec {
     pub median_activation: HashMap<Uuid, f64>,
     pub nonzero_activation_count: HashMap<Uuid, usize>,
     pub n_performers: HashMap<Uuid, usize>,
+    /// The mean activation with each [Agent] weighted by
+    /// [SummaryOptions::weighting], if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weighted_mean_activation: Option<HashMap<Uuid, f64>>,
+    /// The standard deviation of the activations with each [Agent] weighted
+    /// by [SummaryOptions::weighting], if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weighted_sd_activation: Option<HashMap<Uuid, f64>>,
+    /// Whether friends performed the same [Behaviour], if
+    /// [SummaryOptions::action_assortativity] is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_assortativity: Option<ActionAssortativity>,
+}
+
+/// Whether the two [Agent]s of each friendship performed the same
+/// [Behaviour] at a time.
+///
+/// Each friendship is counted once from the [Agent] whose friend the other
+/// is, so a pair of [Agent]s who are friends of each other is counted
+/// twice.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionAssortativity {
+    /// The fraction of the friendships where both [Agent]s performed a
+    /// [Behaviour] in which they performed the same one, or [None] if there
+    /// are no such friendships.
+    pub same_fraction: Option<f64>,
+    /// The number of friendships where both [Agent]s performed the same
+    /// [Behaviour].
+    pub n_same: usize,
+    /// The number of friendships where the [Agent]s performed different
+    /// [Behaviour]s.
+    pub n_different: usize,
+    /// The number of friendships where either [Agent] performed no
+    /// [Behaviour].
+    pub n_without_action: usize,
+    /// The number of friendships where both [Agent]s performed the same
+    /// [Behaviour], by that [Behaviour].
+    pub same_by_behaviour: HashMap<Uuid, usize>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct OutputSpecs {
+    /// The identifier of the run, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// The [fingerprint](crate::fingerprint) of the configuration of the
+    /// run, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// The time of the initial activations of the run, which are not
+    /// summarised, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_origin: Option<SimTime>,
+    /// The interval between the ticks summarised, if only some were. See
+    /// [OutputSampling].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_every: Option<usize>,
+    /// The names of the [Belief]s and [Behaviour]s, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub names: Option<ModelNames>,
     pub data: HashMap<SimTime, OutputSpec>,
 }
 
-impl OutputSpecs {
-    pub fn from_agents(
-        agents: &[AgentPtr],
-        beliefs: &[BeliefPtr],
-        start_time: SimTime,
-        end_time: SimTime,
-    ) -> Self {
-        let data: HashMap<SimTime, OutputSpec> = (start_time..=end_time)
-            .map(|t| {
-                // Calculate avg_activation
-                let mut mean_activation: HashMap<Uuid, f64> = HashMap::new();
-
-                for agent in agents {
-                    if let Some(m) = agent.borrow().get_activations().get(&t) {
-                        for (belief, activation) in m {
-                            let entry = mean_activation
-                                .entry(*belief.borrow().uuid())
-                                .or_insert(0.0);
-                            *entry += activation;
-                        }
-                    }
-                }
+/// The names of the [Belief]s and [Behaviour]s of a model, by [Uuid], so
+/// that outputs keyed by [Uuid] can be read without the input files.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelNames {
+    pub beliefs: HashMap<Uuid, String>,
+    pub behaviours: HashMap<Uuid, String>,
+}
 
-                let n_agents = agents.len();
-                for (_, activation) in mean_activation.iter_mut() {
-                    *activation /= n_agents as f64;
-                }
+impl ModelNames {
+    /// The names of the [Belief]s and [Behaviour]s of a model.
+    pub fn from_model(beliefs: &[BeliefPtr], behaviours: &[BehaviourPtr]) -> Self {
+        ModelNames {
+            beliefs: beliefs
+                .iter()
+                .map(|b| {
+                    let b = b.borrow();
+                    (*b.uuid(), b.name().to_string())
+                })
+                .collect(),
+            behaviours: behaviours
+                .iter()
+                .map(|b| {
+                    let b = b.borrow();
+                    (*b.uuid(), b.name().to_string())
+                })
+                .collect(),
+        }
+    }
+}
 
-                // Calculate sd_activation
-                let mut sd_activation: HashMap<Uuid, f64> = HashMap::new();
+/// A value serialized with its [Uuid] keyed maps in the canonical order of a
+/// [ModelIndex], and its times in ascending order.
+struct Ordered<'a, T> {
+    value: &'a T,
+    index: &'a ModelIndex,
+}
 
-                for agent in agents {
-                    if let Some(m) = agent.borrow().get_activations().get(&t) {
-                        for (belief, activation) in m {
-                            let entry = sd_activation.entry(*belief.borrow().uuid()).or_insert(0.0);
+/// A [Uuid] keyed map serialized with the entries for the [Uuid]s at
+/// `positions` in `uuids`, in that order.
+struct OrderedMap<'a, V> {
+    map: &'a HashMap<Uuid, V>,
+    positions: &'a [usize],
+    uuids: &'a [Uuid],
+}
 
-                            *entry += f64::powf(
-                                activation - mean_activation.get(belief.borrow().uuid()).unwrap(),
-                                2.0,
-                            )
-                        }
-                    }
-                }
+impl<V: Serialize> Serialize for OrderedMap<'_, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.positions
+                .iter()
+                .filter_map(|&i| self.map.get_key_value(&self.uuids[i])),
+        )
+    }
+}
 
-                for (_, sd) in sd_activation.iter_mut() {
-                    *sd = f64::sqrt(*sd / ((n_agents - 1) as f64));
-                }
+impl Serialize for Ordered<'_, OutputSpecs> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("OutputSpecs", 7)?;
+        if let Some(run_id) = &self.value.run_id {
+            state.serialize_field("runId", run_id)?;
+        }
+        if let Some(fingerprint) = &self.value.fingerprint {
+            state.serialize_field("fingerprint", fingerprint)?;
+        }
+        if let Some(time_origin) = self.value.time_origin {
+            state.serialize_field("timeOrigin", &time_origin)?;
+        }
+        if let Some(every) = self.value.output_every {
+            state.serialize_field("outputEvery", &every)?;
+        }
+        if let Some(value) = &self.value.names {
+            state.serialize_field(
+                "names",
+                &Ordered {
+                    value,
+                    index: self.index,
+                },
+            )?;
+        }
+        state.serialize_field(
+            "data",
+            &Ordered {
+                value: &self.value.data,
+                index: self.index,
+            },
+        )?;
+        state.end()
+    }
+}
 
-                // Calculate median activation
-                let mut activations_by_uuid: HashMap<Uuid, Vec<f64>> = HashMap::new();
+impl Serialize for Ordered<'_, ModelNames> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ModelNames", 2)?;
+        state.serialize_field(
+            "beliefs",
+            &OrderedMap {
+                map: &self.value.beliefs,
+                positions: self.index.canonical_beliefs(),
+                uuids: self.index.belief_uuids(),
+            },
+        )?;
+        state.serialize_field(
+            "behaviours",
+            &OrderedMap {
+                map: &self.value.behaviours,
+                positions: self.index.canonical_behaviours(),
+                uuids: self.index.behaviour_uuids(),
+            },
+        )?;
+        state.end()
+    }
+}
 
-                for agent in agents {
-                    let agent_ptr = agent.borrow();
-                    for belief in beliefs {
-                        let entry = activations_by_uuid
-                            .entry(*belief.borrow().uuid())
-                            .or_insert_with(Vec::new);
-                        entry.push(agent_ptr.get_activation(t, belief).unwrap_or(0.0));
+impl Serialize for Ordered<'_, HashMap<SimTime, OutputSpec>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut times: Vec<&SimTime> = self.value.keys().collect();
+        times.sort_unstable();
+        serializer.collect_map(times.into_iter().map(|time| {
+            let value = &self.value[time];
+            (
+                time,
+                Ordered {
+                    value,
+                    index: self.index,
+                },
+            )
+        }))
+    }
+}
+
+impl Serialize for Ordered<'_, OutputSpec> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (spec, index) = (self.value, self.index);
+        fn beliefs<'a, V>(map: &'a HashMap<Uuid, V>, index: &'a ModelIndex) -> OrderedMap<'a, V> {
+            OrderedMap {
+                map,
+                positions: index.canonical_beliefs(),
+                uuids: index.belief_uuids(),
+            }
+        }
+
+        let mut state = serializer.serialize_struct("OutputSpec", 8)?;
+        state.serialize_field("meanActivation", &beliefs(&spec.mean_activation, index))?;
+        state.serialize_field("sdActivation", &beliefs(&spec.sd_activation, index))?;
+        state.serialize_field("medianActivation", &beliefs(&spec.median_activation, index))?;
+        state.serialize_field(
+            "nonzeroActivationCount",
+            &beliefs(&spec.nonzero_activation_count, index),
+        )?;
+        state.serialize_field(
+            "nPerformers",
+            &OrderedMap {
+                map: &spec.n_performers,
+                positions: index.canonical_behaviours(),
+                uuids: index.behaviour_uuids(),
+            },
+        )?;
+        if let Some(map) = &spec.weighted_mean_activation {
+            state.serialize_field("weightedMeanActivation", &beliefs(map, index))?;
+        }
+        if let Some(map) = &spec.weighted_sd_activation {
+            state.serialize_field("weightedSdActivation", &beliefs(map, index))?;
+        }
+        if let Some(value) = &spec.action_assortativity {
+            state.serialize_field("actionAssortativity", &Ordered { value, index })?;
+        }
+        state.end()
+    }
+}
+
+impl Serialize for Ordered<'_, ActionAssortativity> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.value;
+        let mut state = serializer.serialize_struct("ActionAssortativity", 5)?;
+        state.serialize_field("sameFraction", &value.same_fraction)?;
+        state.serialize_field("nSame", &value.n_same)?;
+        state.serialize_field("nDifferent", &value.n_different)?;
+        state.serialize_field("nWithoutAction", &value.n_without_action)?;
+        state.serialize_field(
+            "sameByBehaviour",
+            &OrderedMap {
+                map: &value.same_by_behaviour,
+                positions: self.index.canonical_behaviours(),
+                uuids: self.index.behaviour_uuids(),
+            },
+        )?;
+        state.end()
+    }
+}
+
+/// The activations and actions of the [Agent]s at a time, copied out of the
+/// [Agent]s so that their summary statistics can be computed on any thread.
+///
+/// The activations are copied as `T`, which may be [f32] to halve their
+/// memory, but the statistics are accumulated in [f64].
+struct TickData<T> {
+    time: SimTime,
+    /// The activation of each [Agent] for each [Belief], or zero if missing.
+    activations: Vec<Vec<T>>,
+    /// The number of [Agent]s with an activation for each [Belief].
+    counts: Vec<usize>,
+    /// The UUID of the [Behaviour] performed by each [Agent], if any.
+    actions: Vec<Option<Uuid>>,
+}
+
+impl<T: Activation> TickData<T> {
+    fn new(agents: &[AgentPtr], beliefs: &[BeliefPtr], time: SimTime) -> Self {
+        let mut activations = vec![Vec::with_capacity(agents.len()); beliefs.len()];
+        let mut counts = vec![0; beliefs.len()];
+        let mut actions = Vec::new();
+
+        for agent in agents {
+            let agent = agent.borrow();
+            let acts = agent.get_activations().get(&time);
+            for (i, belief) in beliefs.iter().enumerate() {
+                match acts.and_then(|acts| acts.get(belief)) {
+                    Some(&a) => {
+                        counts[i] += 1;
+                        activations[i].push(T::from_f64(a));
                     }
+                    None => activations[i].push(T::default()),
                 }
+            }
+            actions.push(agent.get_action(time).map(|action| *action.borrow().uuid()));
+        }
 
-                let middle_index = n_agents / 2;
+        TickData {
+            time,
+            activations,
+            counts,
+            actions,
+        }
+    }
 
-                let mut median_activation: HashMap<Uuid, f64> = HashMap::new();
+    /// Compute the [OutputSpec], in a single pass over the activations of
+    /// each [Belief].
+    ///
+    /// The standard deviation is computed from the count, sum and sum of
+    /// squares as `sqrt((sum_sq - 2 * mean * sum + count * mean^2) / (n - 1))`,
+    /// which is algebraically the two-pass formula over the [Agent]s with an
+    /// activation, and agrees with it within floating-point tolerance.
+    ///
+    /// The statistics are rounded with [Activation::round_output], and only
+    /// activations with an absolute value greater than the threshold are
+    /// counted as nonzero.
+    ///
+    /// Every [Belief] has an entry in each statistic, so a [Belief] no
+    /// [Agent] has an activation for has a mean, standard deviation and
+    /// nonzero count of zero rather than no entry. With no [Agent]s at all,
+    /// every statistic is zero.
+    ///
+    /// If the [SummaryWindow] has weights, one for each [Agent], the weighted
+    /// mean and standard deviation are also computed, normalised by the sum
+    /// of the weights, as `sum(w * a) / sum(w)` and
+    /// `sqrt(sum(w * a^2) / sum(w) - mean^2)`.
+    ///
+    /// If it has the friendships, the [ActionAssortativity] is computed in a
+    /// single pass over them.
+    fn summarise(mut self, window: &SummaryWindow) -> OutputSpec {
+        let (belief_uuids, threshold) = (&window.belief_uuids, window.threshold);
+        let weights = window.weights.as_deref();
+        let mut weighted_mean_activation = weights.map(|_| HashMap::new());
+        let mut weighted_sd_activation = weights.map(|_| HashMap::new());
+        let mut mean_activation = HashMap::new();
+        let mut sd_activation = HashMap::new();
+        let mut median_activation = HashMap::new();
+        let mut nonzero_activation_count = HashMap::new();
 
-                for (uuid, mut acts) in activations_by_uuid {
-                    acts.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-                    median_activation.insert(uuid, *acts.get(middle_index).unwrap());
+        for ((&uuid, acts), &count) in belief_uuids
+            .iter()
+            .zip(&mut self.activations)
+            .zip(&self.counts)
+        {
+            let n_agents = acts.len();
+            if n_agents == 0 {
+                mean_activation.insert(uuid, 0.0);
+                sd_activation.insert(uuid, 0.0);
+                median_activation.insert(uuid, 0.0);
+                nonzero_activation_count.insert(uuid, 0);
+                continue;
+            }
+            let (mut sum, mut sum_sq, mut nonzero) = (0.0, 0.0, 0);
+            for &a in acts.iter() {
+                let a = a.to_f64();
+                sum += a;
+                sum_sq += a * a;
+                if a.abs() > threshold {
+                    nonzero += 1;
                 }
+            }
 
-                // Calculate non_zero activation count
-                let mut nonzero_activation_count: HashMap<Uuid, usize> = HashMap::new();
+            let mean = sum / n_agents as f64;
+            let sq_dev = (sum_sq - 2.0 * mean * sum + count as f64 * mean * mean).max(0.0);
+            mean_activation.insert(uuid, T::round_output(mean));
+            sd_activation.insert(
+                uuid,
+                T::round_output(f64::sqrt(sq_dev / (n_agents.saturating_sub(1) as f64))),
+            );
+            nonzero_activation_count.insert(uuid, nonzero);
+            if let (Some(weights), Some(means), Some(sds)) = (
+                weights,
+                &mut weighted_mean_activation,
+                &mut weighted_sd_activation,
+            ) {
+                let (mut total, mut sum, mut sum_sq) = (0.0, 0.0, 0.0);
+                for (&a, &w) in acts.iter().zip(weights) {
+                    let a = a.to_f64();
+                    total += w;
+                    sum += w * a;
+                    sum_sq += w * a * a;
+                }
+                if total > 0.0 {
+                    let mean = sum / total;
+                    means.insert(uuid, T::round_output(mean));
+                    sds.insert(
+                        uuid,
+                        T::round_output(f64::sqrt((sum_sq / total - mean * mean).max(0.0))),
+                    );
+                }
+            }
+            let (_, median, _) =
+                acts.select_nth_unstable_by(n_agents / 2, |a, b| a.to_f64().total_cmp(&b.to_f64()));
+            median_activation.insert(uuid, T::round_output(median.to_f64()));
+        }
 
-                for agent in agents {
-                    if let Some(m) = agent.borrow().get_activations().get(&t) {
-                        for (belief, activation) in m {
-                            if *activation != 0.0 {
-                                let entry = nonzero_activation_count
-                                    .entry(*belief.borrow().uuid())
-                                    .or_insert(0);
-                                *entry += 1;
-                            }
-                        }
+        let action_assortativity = window
+            .friendships
+            .as_deref()
+            .map(|friendships| self.action_assortativity(friendships));
+
+        let mut n_performers: UuidMap<usize> = UuidMap::default();
+        for uuid in self.actions.into_iter().flatten() {
+            *n_performers.entry(uuid).or_insert(0) += 1;
+        }
+
+        OutputSpec {
+            mean_activation,
+            sd_activation,
+            median_activation,
+            nonzero_activation_count,
+            n_performers: n_performers.into_iter().collect(),
+            weighted_mean_activation,
+            weighted_sd_activation,
+            action_assortativity,
+        }
+    }
+
+    /// Compare the actions of the [Agent]s of each friendship, given as
+    /// pairs of positions.
+    fn action_assortativity(&self, friendships: &[(usize, usize)]) -> ActionAssortativity {
+        let (mut n_different, mut n_without_action) = (0, 0);
+        let mut same_by_behaviour: UuidMap<usize> = UuidMap::default();
+        for &(a, b) in friendships {
+            match (self.actions[a], self.actions[b]) {
+                (Some(a), Some(b)) if a == b => *same_by_behaviour.entry(a).or_insert(0) += 1,
+                (Some(_), Some(_)) => n_different += 1,
+                _ => n_without_action += 1,
+            }
+        }
+        let n_same: usize = same_by_behaviour.values().sum();
+        ActionAssortativity {
+            same_fraction: (n_same + n_different > 0)
+                .then(|| n_same as f64 / (n_same + n_different) as f64),
+            n_same,
+            n_different,
+            n_without_action,
+            same_by_behaviour: same_by_behaviour.into_iter().collect(),
+        }
+    }
+}
+
+/// What the summaries of every tick in a window share.
+struct SummaryWindow {
+    /// The UUID of each [Belief], in the order of the model.
+    belief_uuids: Vec<Uuid>,
+    /// See [SummaryOptions::activation_threshold].
+    threshold: f64,
+    /// The weight of each [Agent], if the weighted statistics are computed.
+    weights: Option<Vec<f64>>,
+    /// The positions of the [Agent] and its friend in each friendship, if
+    /// the [ActionAssortativity] is computed.
+    friendships: Option<Vec<(usize, usize)>>,
+}
+
+impl SummaryWindow {
+    fn new(agents: &[AgentPtr], beliefs: &[BeliefPtr], options: SummaryOptions) -> Self {
+        let friendships = options.action_assortativity.then(|| {
+            let positions: UuidMap<usize> = agents
+                .iter()
+                .enumerate()
+                .map(|(i, a)| (*a.borrow().uuid(), i))
+                .collect();
+            agents
+                .iter()
+                .enumerate()
+                .flat_map(|(i, agent)| {
+                    let friends: Vec<(usize, usize)> = agent
+                        .borrow()
+                        .get_friends()
+                        .keys()
+                        .map(|friend| (i, positions[friend.borrow().uuid()]))
+                        .collect();
+                    friends
+                })
+                .collect()
+        });
+        SummaryWindow {
+            belief_uuids: beliefs.iter().map(|b| *b.borrow().uuid()).collect(),
+            threshold: options.activation_threshold,
+            weights: options.weighting.map(|w| w.weights(agents)),
+            friendships,
+        }
+    }
+}
+
+/// How the summary output is computed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SummaryOptions {
+    /// The precision to copy activations out of the [Agent]s at.
+    pub precision: Precision,
+    /// The absolute value an activation must exceed to be counted in
+    /// [OutputSpec::nonzero_activation_count]. With the default of zero,
+    /// every activation that is not exactly zero is counted.
+    pub activation_threshold: f64,
+    /// How to weight each [Agent] in the weighted statistics, which are only
+    /// computed if set.
+    pub weighting: Option<StatWeighting>,
+    /// Whether to compute the [ActionAssortativity] of the friendships.
+    pub action_assortativity: bool,
+}
+
+/// How each [Agent] is weighted in the weighted summary statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StatWeighting {
+    /// By its number of friends.
+    Degree,
+    /// By the sum of the weights of its friends.
+    FriendWeight,
+}
+
+impl StatWeighting {
+    /// The weight of each [Agent], in order.
+    fn weights(self, agents: &[AgentPtr]) -> Vec<f64> {
+        agents
+            .iter()
+            .map(|agent| {
+                let agent = agent.borrow();
+                match self {
+                    StatWeighting::Degree => agent.get_friends().len() as f64,
+                    StatWeighting::FriendWeight => {
+                        // Summed in order of UUID, so runs are identical
+                        let mut friends: Vec<(Uuid, f64)> = agent
+                            .get_friends()
+                            .iter()
+                            .map(|(friend, &w)| (*friend.borrow().uuid(), w))
+                            .collect();
+                        friends.sort_unstable_by_key(|&(uuid, _)| uuid);
+                        friends.iter().map(|&(_, w)| w).sum()
                     }
                 }
+            })
+            .collect()
+    }
+}
 
-                // Calculate n_performers
-                let n_performers: HashMap<Uuid, usize> = agents
-                    .iter()
-                    .flat_map(|a| {
-                        a.borrow()
-                            .get_action(t)
-                            .map(|action| *action.borrow().uuid())
-                    })
-                    .fold(HashMap::new(), |mut counts, elem| {
-                        let count = counts.entry(elem).or_insert(0);
-                        *count += 1;
-                        counts
-                    });
-                (
-                    t,
-                    OutputSpec {
-                        mean_activation,
-                        sd_activation,
-                        median_activation,
-                        nonzero_activation_count,
-                        n_performers,
+impl OutputSpecs {
+    /// Write the [OutputSpecs] as JSON, with the times in ascending order and
+    /// the [Belief]s and [Behaviour]s in the canonical order of a
+    /// [ModelIndex], so that the output of a run is the same every time it is
+    /// written.
+    pub fn to_writer_ordered<W: Write>(
+        &self,
+        writer: W,
+        index: &ModelIndex,
+    ) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, &Ordered { value: self, index })
+    }
+
+    /// Write the [OutputSpecs] as a long format CSV table of
+    /// `time,uuid,kind,statistic,value` rows, where the kind is `belief` or
+    /// `behaviour`, ordered by time and then by the canonical order of a
+    /// [ModelIndex].
+    ///
+    /// The weighted statistics are written for every tick if any tick has
+    /// them, so that every tick has the same rows. Only the statistics of
+    /// the [Belief]s and [Behaviour]s are written: the run id, fingerprint,
+    /// names and the other fields are only written in JSON.
+    pub fn to_csv_writer<W: Write>(&self, mut writer: W, index: &ModelIndex) -> io::Result<()> {
+        write_csv_header(&mut writer)?;
+        let weighted = self
+            .data
+            .values()
+            .any(|spec| spec.weighted_mean_activation.is_some());
+        let mut times: Vec<SimTime> = self.data.keys().copied().collect();
+        times.sort_unstable();
+        for time in times {
+            self.data[&time].write_csv_rows(&mut writer, time, index, weighted)?;
+        }
+        Ok(())
+    }
+
+    /// Compute the summary statistics of the [Agent]s at each time from
+    /// `start_time` to `end_time`.
+    ///
+    /// The [Agent]s cannot be shared between threads, so the activations and
+    /// actions of as many times as there are rayon threads are copied out of
+    /// the [Agent]s on this thread, then summarised in parallel. The results
+    /// do not depend on the number of threads.
+    pub fn from_agents(
+        agents: &[AgentPtr],
+        beliefs: &[BeliefPtr],
+        start_time: SimTime,
+        end_time: SimTime,
+    ) -> Self {
+        Self::from_agents_with_options(
+            agents,
+            beliefs,
+            start_time,
+            end_time,
+            SummaryOptions::default(),
+        )
+    }
+
+    /// Compute the summary statistics as [OutputSpecs::from_agents] does,
+    /// with [SummaryOptions].
+    pub fn from_agents_with_options(
+        agents: &[AgentPtr],
+        beliefs: &[BeliefPtr],
+        start_time: SimTime,
+        end_time: SimTime,
+        options: SummaryOptions,
+    ) -> Self {
+        let times: Vec<SimTime> = (start_time..=end_time).collect();
+        let data = summarise_times(agents, beliefs, &times, options)
+            .into_iter()
+            .collect();
+        Self {
+            run_id: None,
+            fingerprint: None,
+            time_origin: None,
+            output_every: None,
+            names: None,
+            data,
+        }
+    }
+}
+
+/// The format the summary output of a run is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SummaryFormat {
+    /// The [OutputSpecs] as JSON.
+    #[default]
+    Json,
+    /// A long format CSV table, with a row for each statistic of each
+    /// [Belief] and [Behaviour] at each tick, as written by
+    /// [OutputSpecs::to_csv_writer].
+    Csv,
+}
+
+/// Write the header of the summary output in CSV.
+fn write_csv_header<W: Write>(mut writer: W) -> io::Result<()> {
+    writeln!(writer, "time,uuid,kind,statistic,value")
+}
+
+impl OutputSpec {
+    /// Estimate the uncompressed size of the summary of a tick of a model
+    /// with the [Belief]s and [Behaviour]s of `index`, written in `format`,
+    /// without simulating it.
+    ///
+    /// A summary of every [Belief] and [Behaviour] is written with each
+    /// statistic as long as an activation or count is likely to be, so the
+    /// estimate is near the largest a tick can be. The action
+    /// assortativity is left out, as it is small beside the rest.
+    pub fn estimated_bytes(
+        index: &ModelIndex,
+        options: SummaryOptions,
+        format: SummaryFormat,
+    ) -> u64 {
+        // As long as the shortest representation of an activation can be
+        let activations = || -> HashMap<Uuid, f64> {
+            index
+                .belief_uuids()
+                .iter()
+                .map(|&uuid| (uuid, -0.12345678901234568))
+                .collect()
+        };
+        let weighted = options.weighting.is_some();
+        let spec = OutputSpec {
+            mean_activation: activations(),
+            sd_activation: activations(),
+            median_activation: activations(),
+            nonzero_activation_count: index
+                .belief_uuids()
+                .iter()
+                .map(|&uuid| (uuid, 99999))
+                .collect(),
+            n_performers: index
+                .behaviour_uuids()
+                .iter()
+                .map(|&uuid| (uuid, 99999))
+                .collect(),
+            weighted_mean_activation: weighted.then(activations),
+            weighted_sd_activation: weighted.then(activations),
+            action_assortativity: None,
+        };
+        let time = SimTime::MAX;
+        let mut bytes = Vec::new();
+        match format {
+            SummaryFormat::Json => {
+                write!(bytes, r#""{time}":,"#).expect("writing to memory cannot fail");
+                serde_json::to_writer(
+                    &mut bytes,
+                    &Ordered {
+                        value: &spec,
+                        index,
                     },
                 )
-            })
+                .expect("an OutputSpec can always be serialized");
+            }
+            SummaryFormat::Csv => spec
+                .write_csv_rows(&mut bytes, time, index, weighted)
+                .expect("writing to memory cannot fail"),
+        }
+        bytes.len() as u64
+    }
+
+    /// Write the statistics of the tick `time` as rows of the summary output
+    /// in CSV, with the [Belief]s and then the [Behaviour]s of `index` in
+    /// canonical order.
+    ///
+    /// Every [Belief] and [Behaviour] has a row for each statistic, with
+    /// zero for those missing from the [OutputSpec], so every tick has the
+    /// same rows. The weighted statistics are written if `weighted`, as zero
+    /// if the [OutputSpec] has none. The action assortativity is only
+    /// written in JSON.
+    fn write_csv_rows<W: Write>(
+        &self,
+        mut writer: W,
+        time: SimTime,
+        index: &ModelIndex,
+        weighted: bool,
+    ) -> io::Result<()> {
+        let mut row = |uuid: Uuid, kind: &str, statistic: &str, value: &dyn Display| {
+            writeln!(writer, "{time},{uuid},{kind},{statistic},{value}")
+        };
+        let activations = [
+            ("mean_activation", Some(&self.mean_activation), true),
+            ("sd_activation", Some(&self.sd_activation), true),
+            ("median_activation", Some(&self.median_activation), true),
+            (
+                "weighted_mean_activation",
+                self.weighted_mean_activation.as_ref(),
+                weighted,
+            ),
+            (
+                "weighted_sd_activation",
+                self.weighted_sd_activation.as_ref(),
+                weighted,
+            ),
+        ];
+        for &i in index.canonical_beliefs() {
+            let uuid = index.belief_uuids()[i];
+            for (statistic, values, written) in activations {
+                if written {
+                    let value = values
+                        .and_then(|values| values.get(&uuid))
+                        .copied()
+                        .unwrap_or(0.0);
+                    row(uuid, "belief", statistic, &value)?;
+                }
+            }
+            let count = self
+                .nonzero_activation_count
+                .get(&uuid)
+                .copied()
+                .unwrap_or(0);
+            row(uuid, "belief", "nonzero_activation_count", &count)?;
+        }
+        for &i in index.canonical_behaviours() {
+            let uuid = index.behaviour_uuids()[i];
+            let count = self.n_performers.get(&uuid).copied().unwrap_or(0);
+            row(uuid, "behaviour", "n_performers", &count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the [OutputSpec] of the [Agent]s at each of `times`, in order.
+///
+/// The [Agent]s cannot be shared between threads, so the activations and
+/// actions of as many times as there are rayon threads are copied out of the
+/// [Agent]s on this thread, then summarised in parallel.
+fn summarise_times(
+    agents: &[AgentPtr],
+    beliefs: &[BeliefPtr],
+    times: &[SimTime],
+    options: SummaryOptions,
+) -> Vec<(SimTime, OutputSpec)> {
+    match options.precision {
+        Precision::F64 => summarise_times_at::<f64>(agents, beliefs, times, options),
+        Precision::F32 => summarise_times_at::<f32>(agents, beliefs, times, options),
+    }
+}
+
+fn summarise_times_at<T: Activation>(
+    agents: &[AgentPtr],
+    beliefs: &[BeliefPtr],
+    times: &[SimTime],
+    options: SummaryOptions,
+) -> Vec<(SimTime, OutputSpec)> {
+    let window = SummaryWindow::new(agents, beliefs, options);
+    let mut specs = Vec::with_capacity(times.len());
+    for chunk in times.chunks(rayon::current_num_threads()) {
+        let ticks: Vec<TickData<T>> = chunk
+            .iter()
+            .map(|&t| TickData::new(agents, beliefs, t))
             .collect();
+        specs.par_extend(
+            ticks
+                .into_par_iter()
+                .map(|tick| (tick.time, tick.summarise(&window))),
+        );
+    }
+    specs
+}
 
-        Self { data }
+/// Writes the summary output of a run as JSON or CSV a window of ticks at a
+/// time, so that only the [OutputSpec]s of one window are held in memory
+/// however long the run.
+///
+/// The JSON is the same as that written by [OutputSpecs::to_writer_ordered]
+/// for [OutputSpecs::from_agents_with_options], followed by the
+/// [ThresholdCrossings] of the `thresholds` if there are any. The CSV is
+/// the same as that written by [OutputSpecs::to_csv_writer].
+///
+/// The [SummaryResults] are found from the [OutputSpec] of each tick as it
+/// is written.
+pub struct SummaryWriter<'a> {
+    /// The [Agent]s to summarise.
+    pub agents: &'a [AgentPtr],
+    /// The [Belief]s of the model.
+    pub beliefs: &'a [BeliefPtr],
+    /// The canonical order to write the [Belief]s and [Behaviour]s in.
+    pub index: &'a ModelIndex,
+    /// How the summary is computed.
+    pub options: SummaryOptions,
+    /// The number of ticks summarised before they are written.
+    pub window: usize,
+    /// The identifier of the run, written first if set.
+    pub run_id: Option<&'a str>,
+    /// The fingerprint of the configuration of the run, written after its
+    /// identifier if set.
+    pub fingerprint: Option<&'a str>,
+    /// The time of the initial activations, written before the summaries if
+    /// set.
+    pub time_origin: Option<SimTime>,
+    /// The interval between the ticks summarised, as [OutputSampling::every].
+    /// Every tick is summarised if it is 1.
+    pub output_every: usize,
+    /// The names of the [Belief]s and [Behaviour]s, written before the
+    /// summaries if set.
+    pub names: Option<&'a ModelNames>,
+    /// The levels whose crossing times are found from the summary of each
+    /// tick.
+    pub thresholds: &'a ThresholdMetrics,
+    /// How the stability of the final ticks is measured, if it is.
+    pub stability: Option<StabilityOptions>,
+    /// The [OutputSpec]s of ticks summarised before their activations and
+    /// actions were pruned from the [Agent]s, which are written instead of
+    /// summarising the [Agent]s at those ticks.
+    pub summarised: &'a BTreeMap<SimTime, OutputSpec>,
+    /// The format the summary is written in.
+    pub format: SummaryFormat,
+}
+
+/// What is found from the summary of every tick as it is written by a
+/// [SummaryWriter].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryResults {
+    /// The first ticks at which [SummaryWriter::thresholds] were reached.
+    pub threshold_crossings: ThresholdCrossings,
+    /// The stability of the final ticks, if [SummaryWriter::stability] is
+    /// set.
+    pub stability: Option<StabilityReport>,
+}
+
+impl SummaryWriter<'_> {
+    /// Summarise the [Agent]s at each time from `start_time` to `end_time`
+    /// sampled by [SummaryWriter::output_every], writing each window of ticks
+    /// before summarising the next.
+    ///
+    /// The threshold crossings and stability are found from only the ticks
+    /// summarised.
+    ///
+    /// # Returns
+    /// The [SummaryResults].
+    pub fn write<W: Write>(
+        &self,
+        mut writer: W,
+        start_time: SimTime,
+        end_time: SimTime,
+    ) -> serde_json::Result<SummaryResults> {
+        let mut crossings = ThresholdCrossings::new(self.thresholds);
+        let mut stability = self
+            .stability
+            .map(|options| StabilityWindow::new(options, self.index));
+        let times = OutputSampling {
+            every: self.output_every,
+            start_time,
+            end_time,
+        }
+        .times();
+        match self.format {
+            SummaryFormat::Json => self.write_json_prefix(&mut writer)?,
+            SummaryFormat::Csv => write_csv_header(&mut writer).map_err(serde_json::Error::io)?,
+        }
+        for (i, window) in times.chunks(self.window.max(1)).enumerate() {
+            let missing: Vec<SimTime> = window
+                .iter()
+                .copied()
+                .filter(|time| !self.summarised.contains_key(time))
+                .collect();
+            let computed = summarise_times(self.agents, self.beliefs, &missing, self.options);
+            let mut computed = computed.iter();
+            let specs = window.iter().map(|time| match self.summarised.get(time) {
+                Some(value) => (time, value),
+                None => {
+                    let (time, value) = computed.next().expect("every tick is summarised");
+                    (time, value)
+                }
+            });
+            for (j, (time, value)) in specs.enumerate() {
+                crossings.observe(*time, value, self.agents.len());
+                if let Some(stability) = &mut stability {
+                    stability.observe(*time, value);
+                }
+                if self.format == SummaryFormat::Csv {
+                    value
+                        .write_csv_rows(
+                            &mut writer,
+                            *time,
+                            self.index,
+                            self.options.weighting.is_some(),
+                        )
+                        .map_err(serde_json::Error::io)?;
+                    continue;
+                }
+                if i > 0 || j > 0 {
+                    writer.write_all(b",").map_err(serde_json::Error::io)?;
+                }
+                write!(writer, r#""{time}":"#).map_err(serde_json::Error::io)?;
+                serde_json::to_writer(
+                    &mut writer,
+                    &Ordered {
+                        value,
+                        index: self.index,
+                    },
+                )?;
+            }
+        }
+        if self.format == SummaryFormat::Json {
+            writer.write_all(b"}").map_err(serde_json::Error::io)?;
+            if !self.thresholds.is_empty() {
+                writer
+                    .write_all(br#","thresholdCrossings":"#)
+                    .map_err(serde_json::Error::io)?;
+                serde_json::to_writer(&mut writer, &crossings)?;
+            }
+            writer.write_all(b"}").map_err(serde_json::Error::io)?;
+        }
+        Ok(SummaryResults {
+            threshold_crossings: crossings,
+            stability: stability.map(|window| window.finish(self.agents.len())),
+        })
+    }
+
+    /// Write the JSON before the summaries of the ticks: the run id,
+    /// fingerprint, time origin, output interval and names, if set, and
+    /// the start of the data.
+    fn write_json_prefix<W: Write>(&self, mut writer: W) -> serde_json::Result<()> {
+        writer.write_all(b"{").map_err(serde_json::Error::io)?;
+        if let Some(run_id) = self.run_id {
+            writer
+                .write_all(br#""runId":"#)
+                .map_err(serde_json::Error::io)?;
+            serde_json::to_writer(&mut writer, run_id)?;
+            writer.write_all(b",").map_err(serde_json::Error::io)?;
+        }
+        if let Some(fingerprint) = self.fingerprint {
+            writer
+                .write_all(br#""fingerprint":"#)
+                .map_err(serde_json::Error::io)?;
+            serde_json::to_writer(&mut writer, fingerprint)?;
+            writer.write_all(b",").map_err(serde_json::Error::io)?;
+        }
+        if let Some(time_origin) = self.time_origin {
+            write!(writer, r#""timeOrigin":{time_origin},"#).map_err(serde_json::Error::io)?;
+        }
+        if self.output_every > 1 {
+            write!(writer, r#""outputEvery":{},"#, self.output_every)
+                .map_err(serde_json::Error::io)?;
+        }
+        if let Some(value) = self.names {
+            writer
+                .write_all(br#""names":"#)
+                .map_err(serde_json::Error::io)?;
+            serde_json::to_writer(
+                &mut writer,
+                &Ordered {
+                    value,
+                    index: self.index,
+                },
+            )?;
+            writer.write_all(b",").map_err(serde_json::Error::io)?;
+        }
+        writer
+            .write_all(br#""data":{"#)
+            .map_err(serde_json::Error::io)?;
+        Ok(())
     }
 }
 
@@ -346,10 +1409,10 @@ mod test {
 
             let b: Vec<BehaviourSpec> = serde_json::from_str(json_str).unwrap();
             assert_eq!(b.len(), 2);
-            assert_eq!(b.get(0).unwrap().name, "Behaviour 1");
-            assert_eq!(b.get(0).unwrap().uuid, uuid);
+            assert_eq!(b.first().unwrap().name, "Behaviour 1");
+            assert_eq!(b.first().unwrap().uuid, uuid);
             assert_eq!(b.get(1).unwrap().name, "Behaviour 2");
-            assert_ne!(b.get(0).unwrap().uuid, b.get(1).unwrap().uuid);
+            assert_ne!(b.first().unwrap().uuid, b.get(1).unwrap().uuid);
             let zero_uuid = uuid::uuid!("00000000-0000-0000-0000-000000000000");
             assert_ne!(b.get(1).unwrap().uuid, zero_uuid)
         }
@@ -365,5 +1428,982 @@ mod test {
             assert_eq!(bo.name(), "b1");
             assert_eq!(bo.uuid(), &u);
         }
+
+        #[test]
+        fn from_behaviour_round_trips() {
+            for name in ["b1", "", "Behaviour with spaces"] {
+                let spec = BehaviourSpec {
+                    name: name.to_string(),
+                    uuid: Uuid::new_v4(),
+                };
+                let behaviour: BehaviourPtr = spec.to_basic_behaviour().into();
+                assert_eq!(BehaviourSpec::from_behaviour(&behaviour), spec);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod belief_spec {
+        use super::super::*;
+
+        fn behaviours(n: usize) -> Vec<BehaviourPtr> {
+            (0..n)
+                .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+                .collect()
+        }
+
+        #[test]
+        fn from_belief_round_trips() {
+            let behaviours = behaviours(3);
+            let uuids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+            let specs: Vec<BeliefSpec> = uuids
+                .iter()
+                .enumerate()
+                .map(|(i, &uuid)| BeliefSpec {
+                    name: format!("b{i}"),
+                    uuid,
+                    perceptions: behaviours
+                        .iter()
+                        .skip(i)
+                        .map(|b| (*b.borrow().uuid(), 0.5 - i as f64 * 0.25))
+                        .collect(),
+                    relationships: uuids
+                        .iter()
+                        .take(i + 1)
+                        .map(|&u| (u, -0.1 * i as f64))
+                        .collect(),
+                    delta_distribution: None,
+                })
+                .collect();
+
+            let beliefs: Vec<BeliefPtr> = specs
+                .iter()
+                .map(|spec| spec.to_basic_belief(&behaviours))
+                .collect();
+            let uuid_beliefs: UuidMap<BeliefPtr> = beliefs
+                .iter()
+                .map(|b| (*b.borrow().uuid(), b.clone()))
+                .collect();
+            specs
+                .iter()
+                .for_each(|spec| spec.link_belief_relationships(&uuid_beliefs));
+
+            for (spec, belief) in specs.iter().zip(&beliefs) {
+                assert_eq!(
+                    &BeliefSpec::from_belief(belief, &behaviours, &beliefs),
+                    spec
+                );
+            }
+        }
+
+        #[test]
+        fn from_belief_without_perceptions_or_relationships() {
+            let spec = BeliefSpec {
+                name: "b1".to_string(),
+                uuid: Uuid::new_v4(),
+                perceptions: HashMap::new(),
+                relationships: HashMap::new(),
+                delta_distribution: None,
+            };
+            let behaviours = behaviours(2);
+            let belief = spec.to_basic_belief(&behaviours);
+            assert_eq!(
+                BeliefSpec::from_belief(&belief, &behaviours, std::slice::from_ref(&belief)),
+                spec
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod agent_spec {
+        use super::super::*;
+
+        #[test]
+        fn from_agent_round_trips() {
+            let behaviours: Vec<BehaviourPtr> = (0..2)
+                .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+                .collect();
+            let beliefs: Vec<BeliefPtr> = (0..2)
+                .map(|i| BasicBelief::new(format!("b{i}")).into())
+                .collect();
+            let belief_uuids: Vec<Uuid> = beliefs.iter().map(|b| *b.borrow().uuid()).collect();
+            let agent_uuids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+            let specs: Vec<AgentSpec> = agent_uuids
+                .iter()
+                .enumerate()
+                .map(|(i, &uuid)| AgentSpec {
+                    uuid,
+                    actions: (0..i as SimTime)
+                        .map(|t| (t, *behaviours[t as usize % 2].borrow().uuid()))
+                        .collect(),
+                    activations: (0..=i as SimTime)
+                        .map(|t| {
+                            (
+                                t,
+                                belief_uuids
+                                    .iter()
+                                    .map(|&b| (b, 0.1 * t as f64 - 0.2))
+                                    .collect(),
+                            )
+                        })
+                        .collect(),
+                    deltas: belief_uuids.iter().map(|&b| (b, 1.0 + i as f64)).collect(),
+                    friends: agent_uuids
+                        .iter()
+                        .filter(|&&u| u != uuid)
+                        .map(|&u| (u, 0.5))
+                        .collect(),
+                    activation_floors: HashMap::new(),
+                    activation_ceilings: HashMap::new(),
+                })
+                .collect();
+
+            let uuid_behaviours: UuidMap<BehaviourPtr> = behaviours
+                .iter()
+                .map(|b| (*b.borrow().uuid(), b.clone()))
+                .collect();
+            let uuid_beliefs: UuidMap<BeliefPtr> = beliefs
+                .iter()
+                .map(|b| (*b.borrow().uuid(), b.clone()))
+                .collect();
+            let agents: UuidMap<AgentPtr> = specs
+                .iter()
+                .map(|spec| {
+                    (
+                        spec.uuid,
+                        spec.to_basic_agent(&uuid_behaviours, &uuid_beliefs),
+                    )
+                })
+                .collect();
+            specs.iter().for_each(|spec| spec.link_friends(&agents));
+
+            for spec in &specs {
+                assert_eq!(&AgentSpec::from_agent(&agents[&spec.uuid]), spec);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod round_trip {
+        use super::super::*;
+
+        use proptest::{collection::vec, option, prelude::*};
+
+        /// A valid random model: behaviours, beliefs referring to them, and
+        /// agents referring to both and to each other, with every value in
+        /// its legal range.
+        ///
+        /// Times without activations are left out, as an [Agent] cannot hold
+        /// an empty set of activations for a time.
+        fn model() -> impl Strategy<Value = (Vec<BehaviourSpec>, Vec<BeliefSpec>, Vec<AgentSpec>)> {
+            (1..4usize, 1..4usize, 1..6usize).prop_flat_map(
+                |(n_behaviours, n_beliefs, n_agents)| {
+                    let unit = || option::of(-1.0..=1.0f64);
+                    let beliefs = vec(
+                        ("\\PC*", vec(unit(), n_behaviours), vec(unit(), n_beliefs)),
+                        n_beliefs,
+                    );
+                    let agent = (
+                        vec(option::of(0..n_behaviours), 4),
+                        vec(vec(unit(), n_beliefs), 4),
+                        vec(option::of(f64::EPSILON..=10.0), n_beliefs),
+                        vec(option::of(0.0..=1.0f64), n_agents),
+                    );
+                    (vec("\\PC*", n_behaviours), beliefs, vec(agent, n_agents)).prop_map(
+                        |(behaviours, beliefs, agents)| {
+                            let behaviour_uuid = |i: usize| Uuid::from_u128(0x100 + i as u128);
+                            let belief_uuid = |i: usize| Uuid::from_u128(0x200 + i as u128);
+                            let agent_uuid = |i: usize| Uuid::from_u128(0x300 + i as u128);
+                            let present =
+                                |values: Vec<Option<f64>>, uuid: &dyn Fn(usize) -> Uuid| {
+                                    values
+                                        .into_iter()
+                                        .enumerate()
+                                        .filter_map(|(i, v)| Some((uuid(i), v?)))
+                                        .collect::<HashMap<Uuid, f64>>()
+                                };
+                            let behaviours = behaviours
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, name)| BehaviourSpec {
+                                    name,
+                                    uuid: behaviour_uuid(i),
+                                })
+                                .collect();
+                            let beliefs = beliefs
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, (name, perceptions, relationships))| BeliefSpec {
+                                    name,
+                                    uuid: belief_uuid(i),
+                                    perceptions: present(perceptions, &behaviour_uuid),
+                                    relationships: present(relationships, &belief_uuid),
+                                    delta_distribution: None,
+                                })
+                                .collect();
+                            let agents = agents
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, (actions, activations, deltas, mut friends))| {
+                                    friends[i] = None;
+                                    AgentSpec {
+                                        uuid: agent_uuid(i),
+                                        actions: (0..)
+                                            .zip(actions)
+                                            .filter_map(|(t, b)| Some((t, behaviour_uuid(b?))))
+                                            .collect(),
+                                        activations: (0..)
+                                            .zip(activations)
+                                            .map(|(t, acts)| (t, present(acts, &belief_uuid)))
+                                            .filter(|(_, acts)| !acts.is_empty())
+                                            .collect(),
+                                        deltas: present(deltas, &belief_uuid),
+                                        friends: present(friends, &agent_uuid),
+                                        activation_floors: HashMap::new(),
+                                        activation_ceilings: HashMap::new(),
+                                    }
+                                })
+                                .collect();
+                            (behaviours, beliefs, agents)
+                        },
+                    )
+                },
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn specs_round_trip_through_the_model(
+                (behaviour_specs, belief_specs, agent_specs) in model()
+            ) {
+                let behaviours: Vec<BehaviourPtr> = behaviour_specs
+                    .iter()
+                    .map(|spec| spec.to_basic_behaviour().into())
+                    .collect();
+                let beliefs: Vec<BeliefPtr> = belief_specs
+                    .iter()
+                    .map(|spec| spec.to_basic_belief(&behaviours))
+                    .collect();
+                let uuid_beliefs: UuidMap<BeliefPtr> = beliefs
+                    .iter()
+                    .map(|b| (*b.borrow().uuid(), b.clone()))
+                    .collect();
+                belief_specs
+                    .iter()
+                    .for_each(|spec| spec.link_belief_relationships(&uuid_beliefs));
+                let uuid_behaviours: UuidMap<BehaviourPtr> = behaviours
+                    .iter()
+                    .map(|b| (*b.borrow().uuid(), b.clone()))
+                    .collect();
+                let agents: UuidMap<AgentPtr> = agent_specs
+                    .iter()
+                    .map(|spec| (spec.uuid, spec.to_basic_agent(&uuid_behaviours, &uuid_beliefs)))
+                    .collect();
+                agent_specs.iter().for_each(|spec| spec.link_friends(&agents));
+
+                for (spec, behaviour) in behaviour_specs.iter().zip(&behaviours) {
+                    prop_assert_eq!(&BehaviourSpec::from_behaviour(behaviour), spec);
+                }
+                for (spec, belief) in belief_specs.iter().zip(&beliefs) {
+                    prop_assert_eq!(&BeliefSpec::from_belief(belief, &behaviours, &beliefs), spec);
+                }
+                for spec in &agent_specs {
+                    prop_assert_eq!(&AgentSpec::from_agent(&agents[&spec.uuid]), spec);
+                }
+            }
+
+            #[test]
+            fn specs_round_trip_through_json(
+                (behaviours, beliefs, agents) in model()
+            ) {
+                let json = serde_json::to_string(&(&behaviours, &beliefs, &agents)).unwrap();
+                let parsed: (Vec<BehaviourSpec>, Vec<BeliefSpec>, Vec<AgentSpec>) =
+                    serde_json::from_str(&json).unwrap();
+                prop_assert_eq!(parsed, (behaviours, beliefs, agents));
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod output_specs {
+        use super::super::*;
+
+        use belief_spread::{BasicAgent, BasicBehaviour, BasicBelief};
+        use float_cmp::approx_eq;
+
+        /// The multi-pass implementation [OutputSpecs::from_agents] replaced,
+        /// kept to check that it gives the same results.
+        fn from_agents_multi_pass(
+            agents: &[AgentPtr],
+            beliefs: &[BeliefPtr],
+            start_time: SimTime,
+            end_time: SimTime,
+        ) -> OutputSpecs {
+            let data: HashMap<SimTime, OutputSpec> = (start_time..=end_time)
+                .map(|t| {
+                    let zeros = || beliefs.iter().map(|b| (*b.borrow().uuid(), 0.0)).collect();
+
+                    // Calculate avg_activation
+                    let mut mean_activation: HashMap<Uuid, f64> = zeros();
+
+                    for agent in agents {
+                        if let Some(m) = agent.borrow().get_activations().get(&t) {
+                            for (belief, activation) in m {
+                                let entry = mean_activation
+                                    .entry(*belief.borrow().uuid())
+                                    .or_insert(0.0);
+                                *entry += activation;
+                            }
+                        }
+                    }
+
+                    let n_agents = agents.len();
+                    for (_, activation) in mean_activation.iter_mut() {
+                        *activation /= n_agents as f64;
+                    }
+
+                    // Calculate sd_activation
+                    let mut sd_activation: HashMap<Uuid, f64> = zeros();
+
+                    for agent in agents {
+                        if let Some(m) = agent.borrow().get_activations().get(&t) {
+                            for (belief, activation) in m {
+                                let entry =
+                                    sd_activation.entry(*belief.borrow().uuid()).or_insert(0.0);
+
+                                *entry += f64::powf(
+                                    activation
+                                        - mean_activation.get(belief.borrow().uuid()).unwrap(),
+                                    2.0,
+                                )
+                            }
+                        }
+                    }
+
+                    for (_, sd) in sd_activation.iter_mut() {
+                        *sd = f64::sqrt(*sd / ((n_agents - 1) as f64));
+                    }
+
+                    // Calculate median activation
+                    let mut activations_by_uuid: HashMap<Uuid, Vec<f64>> = HashMap::new();
+
+                    for agent in agents {
+                        let agent_ptr = agent.borrow();
+                        for belief in beliefs {
+                            let entry = activations_by_uuid
+                                .entry(*belief.borrow().uuid())
+                                .or_default();
+                            entry.push(agent_ptr.get_activation(t, belief).unwrap_or(0.0));
+                        }
+                    }
+
+                    let middle_index = n_agents / 2;
+
+                    let mut median_activation: HashMap<Uuid, f64> = HashMap::new();
+
+                    for (uuid, mut acts) in activations_by_uuid {
+                        acts.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+                        median_activation.insert(uuid, *acts.get(middle_index).unwrap());
+                    }
+
+                    // Calculate non_zero activation count
+                    let mut nonzero_activation_count: HashMap<Uuid, usize> =
+                        beliefs.iter().map(|b| (*b.borrow().uuid(), 0)).collect();
+
+                    for agent in agents {
+                        if let Some(m) = agent.borrow().get_activations().get(&t) {
+                            for (belief, activation) in m {
+                                if *activation != 0.0 {
+                                    let entry = nonzero_activation_count
+                                        .entry(*belief.borrow().uuid())
+                                        .or_insert(0);
+                                    *entry += 1;
+                                }
+                            }
+                        }
+                    }
+
+                    // Calculate n_performers
+                    let n_performers: HashMap<Uuid, usize> = agents
+                        .iter()
+                        .flat_map(|a| {
+                            a.borrow()
+                                .get_action(t)
+                                .map(|action| *action.borrow().uuid())
+                        })
+                        .fold(HashMap::new(), |mut counts, elem| {
+                            let count = counts.entry(elem).or_insert(0);
+                            *count += 1;
+                            counts
+                        });
+                    (
+                        t,
+                        OutputSpec {
+                            mean_activation,
+                            sd_activation,
+                            median_activation,
+                            nonzero_activation_count,
+                            n_performers,
+                            weighted_mean_activation: None,
+                            weighted_sd_activation: None,
+                            action_assortativity: None,
+                        },
+                    )
+                })
+                .collect();
+
+            OutputSpecs {
+                run_id: None,
+                fingerprint: None,
+                time_origin: None,
+                output_every: None,
+                names: None,
+                data,
+            }
+        }
+        /// [Agent]s with varied activations, some of them missing or zero.
+        fn model(n_agents: usize) -> (Vec<AgentPtr>, Vec<BeliefPtr>) {
+            let beliefs: Vec<BeliefPtr> = (0..3)
+                .map(|i| BasicBelief::new(format!("b{i}")).into())
+                .collect();
+            let behaviours: Vec<BehaviourPtr> = (0..2)
+                .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+                .collect();
+            let agents = (0..n_agents)
+                .map(|i| {
+                    let mut agent = BasicAgent::new();
+                    for t in 1..=3 {
+                        for (j, belief) in beliefs.iter().enumerate() {
+                            let k = (i * 7 + j * 5 + t as usize * 3) % 13;
+                            if k != 0 {
+                                let v = if k == 1 { 0.0 } else { k as f64 / 6.5 - 1.0 };
+                                agent.set_activation(t, belief.clone(), Some(v)).unwrap();
+                            }
+                        }
+                        if !(i + t as usize).is_multiple_of(3) {
+                            agent.set_action(t, Some(behaviours[i % 2].clone()));
+                        }
+                    }
+                    agent.into()
+                })
+                .collect();
+            (agents, beliefs)
+        }
+
+        #[test]
+        fn estimated_bytes_are_near_the_largest_tick() {
+            let (agents, beliefs) = model(20);
+            let behaviours = agents
+                .iter()
+                .flat_map(|a| {
+                    a.borrow()
+                        .get_actions()
+                        .values()
+                        .map(|b| *b.borrow().uuid())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<std::collections::BTreeSet<_>>();
+            let index = ModelIndex::new(
+                beliefs.iter().map(|b| *b.borrow().uuid()).collect(),
+                behaviours.into_iter().collect(),
+            );
+            for weighting in [None, Some(StatWeighting::Degree)] {
+                let options = SummaryOptions {
+                    weighting,
+                    ..Default::default()
+                };
+                let specs = OutputSpecs::from_agents_with_options(&agents, &beliefs, 1, 3, options);
+                for format in [SummaryFormat::Json, SummaryFormat::Csv] {
+                    let largest = (1..=3)
+                        .map(|time| {
+                            let mut bytes = Vec::new();
+                            match format {
+                                SummaryFormat::Json => serde_json::to_writer(
+                                    &mut bytes,
+                                    &Ordered {
+                                        value: &specs.data[&time],
+                                        index: &index,
+                                    },
+                                )
+                                .unwrap(),
+                                SummaryFormat::Csv => specs.data[&time]
+                                    .write_csv_rows(&mut bytes, time, &index, weighting.is_some())
+                                    .unwrap(),
+                            }
+                            bytes.len() as u64
+                        })
+                        .max()
+                        .unwrap();
+                    let estimate = OutputSpec::estimated_bytes(&index, options, format);
+                    assert!(
+                        (largest..2 * largest).contains(&estimate),
+                        "{format:?}, {weighting:?}: {estimate} for {largest}"
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn summary_writer_matches_to_writer_ordered() {
+            let (agents, beliefs) = model(20);
+            let mut behaviours: Vec<Uuid> = agents
+                .iter()
+                .flat_map(|a| {
+                    let a = a.borrow();
+                    let uuids: Vec<Uuid> = a
+                        .get_actions()
+                        .values()
+                        .map(|b| *b.borrow().uuid())
+                        .collect();
+                    uuids
+                })
+                .collect();
+            behaviours.sort();
+            behaviours.dedup();
+            let index = ModelIndex::new(
+                beliefs.iter().map(|b| *b.borrow().uuid()).collect(),
+                behaviours,
+            );
+
+            for precision in [Precision::F64, Precision::F32] {
+                let mut expected = Vec::new();
+                let options = SummaryOptions {
+                    precision,
+                    activation_threshold: 0.5,
+                    weighting: Some(StatWeighting::Degree),
+                    action_assortativity: true,
+                };
+                let mut specs =
+                    OutputSpecs::from_agents_with_options(&agents, &beliefs, 1, 4, options);
+                // Whether the run id, fingerprint, time origin and names are
+                // written first
+                specs.run_id = (precision == Precision::F32).then(|| "run \"1\"".to_string());
+                specs.fingerprint = (precision == Precision::F32).then(|| "0123".to_string());
+                specs.time_origin = (precision == Precision::F32).then_some(0);
+                specs.names = (precision == Precision::F32).then(|| ModelNames {
+                    beliefs: index
+                        .belief_uuids()
+                        .iter()
+                        .map(|&uuid| (uuid, format!("belief, \"{uuid}\"")))
+                        .collect(),
+                    behaviours: HashMap::new(),
+                });
+                specs.to_writer_ordered(&mut expected, &index).unwrap();
+                let mut expected_csv = Vec::new();
+                specs.to_csv_writer(&mut expected_csv, &index).unwrap();
+                for (window, format) in [1, 2, 5, 100].into_iter().flat_map(|window| {
+                    [(window, SummaryFormat::Json), (window, SummaryFormat::Csv)]
+                }) {
+                    let expected = match format {
+                        SummaryFormat::Json => &expected,
+                        SummaryFormat::Csv => &expected_csv,
+                    };
+                    let mut actual = Vec::new();
+                    SummaryWriter {
+                        agents: &agents,
+                        beliefs: &beliefs,
+                        index: &index,
+                        options,
+                        window,
+                        run_id: specs.run_id.as_deref(),
+                        fingerprint: specs.fingerprint.as_deref(),
+                        time_origin: specs.time_origin,
+                        output_every: 1,
+                        names: specs.names.as_ref(),
+                        thresholds: &ThresholdMetrics::default(),
+                        stability: None,
+                        summarised: &BTreeMap::new(),
+                        format,
+                    }
+                    .write(&mut actual, 1, 4)
+                    .unwrap();
+                    assert_eq!(
+                        String::from_utf8(actual).unwrap(),
+                        String::from_utf8(expected.clone()).unwrap(),
+                        "window {window}, {format:?}"
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn csv_has_a_row_for_every_statistic_of_every_uuid() {
+            let beliefs = [Uuid::from_u128(0x200), Uuid::from_u128(0x201)];
+            let behaviour = Uuid::from_u128(0x100);
+            let index = ModelIndex::new(vec![beliefs[1], beliefs[0]], vec![behaviour]);
+            let spec = |mean: f64, n_performers: usize| OutputSpec {
+                mean_activation: HashMap::from([(beliefs[0], mean), (beliefs[1], -0.25)]),
+                sd_activation: HashMap::from([(beliefs[0], 0.5), (beliefs[1], 0.0)]),
+                median_activation: HashMap::from([(beliefs[0], mean), (beliefs[1], -0.25)]),
+                nonzero_activation_count: HashMap::from([(beliefs[0], 2), (beliefs[1], 1)]),
+                n_performers: HashMap::from([(behaviour, n_performers)]),
+                weighted_mean_activation: None,
+                weighted_sd_activation: None,
+                action_assortativity: None,
+            };
+            // Nothing about the second belief or the behaviour at tick 2
+            let mut sparse = spec(1.5, 0);
+            sparse.n_performers.clear();
+            for stats in [
+                &mut sparse.mean_activation,
+                &mut sparse.sd_activation,
+                &mut sparse.median_activation,
+            ] {
+                stats.remove(&beliefs[1]);
+            }
+            sparse.nonzero_activation_count.remove(&beliefs[1]);
+            let mut specs = OutputSpecs {
+                run_id: Some("run".to_string()),
+                fingerprint: None,
+                time_origin: Some(0),
+                output_every: None,
+                names: None,
+                data: HashMap::from([(2, sparse), (1, spec(0.125, 3))]),
+            };
+
+            let csv = |specs: &OutputSpecs| {
+                let mut csv = Vec::new();
+                specs.to_csv_writer(&mut csv, &index).unwrap();
+                String::from_utf8(csv).unwrap()
+            };
+            let b0 = "00000000-0000-0000-0000-000000000200";
+            let b1 = "00000000-0000-0000-0000-000000000201";
+            let beh = "00000000-0000-0000-0000-000000000100";
+            let expected = [
+                "time,uuid,kind,statistic,value".to_string(),
+                format!("1,{b0},belief,mean_activation,0.125"),
+                format!("1,{b0},belief,sd_activation,0.5"),
+                format!("1,{b0},belief,median_activation,0.125"),
+                format!("1,{b0},belief,nonzero_activation_count,2"),
+                format!("1,{b1},belief,mean_activation,-0.25"),
+                format!("1,{b1},belief,sd_activation,0"),
+                format!("1,{b1},belief,median_activation,-0.25"),
+                format!("1,{b1},belief,nonzero_activation_count,1"),
+                format!("1,{beh},behaviour,n_performers,3"),
+                format!("2,{b0},belief,mean_activation,1.5"),
+                format!("2,{b0},belief,sd_activation,0.5"),
+                format!("2,{b0},belief,median_activation,1.5"),
+                format!("2,{b0},belief,nonzero_activation_count,2"),
+                format!("2,{b1},belief,mean_activation,0"),
+                format!("2,{b1},belief,sd_activation,0"),
+                format!("2,{b1},belief,median_activation,0"),
+                format!("2,{b1},belief,nonzero_activation_count,0"),
+                format!("2,{beh},behaviour,n_performers,0"),
+            ];
+            assert_eq!(csv(&specs), expected.join("\n") + "\n");
+
+            // The names are not written, and the weighted statistics follow
+            // the unweighted at every tick once any tick has them
+            specs.names = Some(ModelNames {
+                beliefs: HashMap::from([(beliefs[0], "Masks, indoors".to_string())]),
+                behaviours: HashMap::new(),
+            });
+            let tick = specs.data.get_mut(&1).unwrap();
+            tick.weighted_mean_activation = Some(HashMap::from([(beliefs[0], 0.75)]));
+            tick.weighted_sd_activation = Some(HashMap::new());
+            let weighted = csv(&specs);
+            let rows: Vec<&str> = weighted.lines().collect();
+            assert_eq!(rows[0], "time,uuid,kind,statistic,value");
+            assert_eq!(
+                rows[1..7],
+                [
+                    format!("1,{b0},belief,mean_activation,0.125"),
+                    format!("1,{b0},belief,sd_activation,0.5"),
+                    format!("1,{b0},belief,median_activation,0.125"),
+                    format!("1,{b0},belief,weighted_mean_activation,0.75"),
+                    format!("1,{b0},belief,weighted_sd_activation,0"),
+                    format!("1,{b0},belief,nonzero_activation_count,2"),
+                ]
+            );
+            assert_eq!(rows.len(), 1 + 13 + 13);
+            assert!(
+                rows[14..].contains(&format!("2,{b0},belief,weighted_mean_activation,0").as_str())
+            );
+            assert!(
+                rows[14..].contains(&format!("2,{b1},belief,weighted_sd_activation,0").as_str())
+            );
+        }
+
+        #[test]
+        fn weighted_stats_are_dominated_by_the_hub() {
+            let belief: BeliefPtr = BasicBelief::new("b".to_string()).into();
+            let hub: AgentPtr = BasicAgent::new().into();
+            let leaf: AgentPtr = BasicAgent::new().into();
+            for (agent, v) in [(&hub, 1.0), (&leaf, -1.0)] {
+                agent
+                    .borrow_mut()
+                    .set_activation(1, belief.clone(), Some(v))
+                    .unwrap();
+            }
+            // The hub has one friend, of weight 0.75, and the leaf none
+            hub.borrow_mut()
+                .set_friend_weight(leaf.clone(), Some(0.75))
+                .unwrap();
+            let agents = [hub, leaf];
+            let beliefs = [belief.clone()];
+            let uuid = *belief.borrow().uuid();
+            let spec = |weighting| {
+                let options = SummaryOptions {
+                    weighting,
+                    ..Default::default()
+                };
+                OutputSpecs::from_agents_with_options(&agents, &beliefs, 1, 1, options)
+                    .data
+                    .remove(&1)
+                    .unwrap()
+            };
+
+            let unweighted = spec(None);
+            assert_eq!(unweighted.mean_activation[&uuid], 0.0);
+            assert_eq!(unweighted.weighted_mean_activation, None);
+            assert_eq!(unweighted.weighted_sd_activation, None);
+
+            let weighted = spec(Some(StatWeighting::Degree));
+            assert_eq!(weighted.mean_activation[&uuid], 0.0);
+            assert_eq!(weighted.weighted_mean_activation.unwrap()[&uuid], 1.0);
+            assert_eq!(weighted.weighted_sd_activation.unwrap()[&uuid], 0.0);
+
+            // The leaf has no friends, so the hub's weight does not matter
+            let weighted = spec(Some(StatWeighting::FriendWeight));
+            assert_eq!(weighted.weighted_mean_activation.unwrap()[&uuid], 1.0);
+        }
+
+        #[test]
+        fn action_assortativity_counts_each_friendship() {
+            let behaviours: Vec<BehaviourPtr> = (0..2)
+                .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+                .collect();
+            let agents: Vec<AgentPtr> = [Some(0), Some(0), Some(1), None]
+                .into_iter()
+                .map(|action| {
+                    let mut agent = BasicAgent::new();
+                    agent.set_action(1, action.map(|i: usize| behaviours[i].clone()));
+                    agent.into()
+                })
+                .collect();
+            // 0 -> 1 and 1 -> 0 are the same, 0 -> 2 differs and 2 -> 3 and
+            // 3 -> 0 have an agent without an action
+            for (a, b) in [(0, 1), (1, 0), (0, 2), (2, 3), (3, 0)] {
+                agents[a]
+                    .borrow_mut()
+                    .set_friend_weight(agents[b].clone(), Some(0.5))
+                    .unwrap();
+            }
+            let options = SummaryOptions {
+                action_assortativity: true,
+                ..Default::default()
+            };
+
+            let assortativity = OutputSpecs::from_agents_with_options(&agents, &[], 1, 1, options)
+                .data
+                .remove(&1)
+                .unwrap()
+                .action_assortativity
+                .unwrap();
+            assert_eq!(assortativity.n_same, 2);
+            assert_eq!(assortativity.n_different, 1);
+            assert_eq!(assortativity.n_without_action, 2);
+            assert_eq!(assortativity.same_fraction, Some(2.0 / 3.0));
+            assert_eq!(
+                assortativity.same_by_behaviour,
+                HashMap::from([(*behaviours[0].borrow().uuid(), 2)])
+            );
+
+            let spec = OutputSpecs::from_agents(&agents, &[], 1, 1).data.remove(&1);
+            assert_eq!(spec.unwrap().action_assortativity, None);
+        }
+
+        #[test]
+        fn nonzero_activation_count_uses_threshold() {
+            let belief: BeliefPtr = BasicBelief::new("b".to_string()).into();
+            let agents: Vec<AgentPtr> = [0.0, 1e-17, 0.25, -0.25, 0.5]
+                .into_iter()
+                .map(|v| {
+                    let mut agent = BasicAgent::new();
+                    agent.set_activation(1, belief.clone(), Some(v)).unwrap();
+                    agent.into()
+                })
+                .collect();
+            let beliefs = [belief.clone()];
+            let count = |activation_threshold| {
+                let options = SummaryOptions {
+                    activation_threshold,
+                    ..Default::default()
+                };
+                OutputSpecs::from_agents_with_options(&agents, &beliefs, 1, 1, options).data[&1]
+                    .nonzero_activation_count
+                    .get(belief.borrow().uuid())
+                    .copied()
+            };
+
+            assert_eq!(count(0.0), Some(4));
+            // Values exactly at the threshold are not counted
+            assert_eq!(count(1e-17), Some(3));
+            assert_eq!(count(0.25), Some(1));
+            assert_eq!(count(0.5), Some(0));
+        }
+
+        fn assert_maps_match(a: &HashMap<Uuid, f64>, b: &HashMap<Uuid, f64>) {
+            assert_eq!(a.len(), b.len());
+            for (uuid, &v) in a {
+                assert!(
+                    approx_eq!(f64, v, b[uuid], epsilon = 1e-12),
+                    "{v} != {}",
+                    b[uuid]
+                );
+            }
+        }
+
+        #[test]
+        fn from_agents_matches_multi_pass() {
+            for n_agents in [2, 3, 10, 101] {
+                let (agents, beliefs) = model(n_agents);
+                let expected = from_agents_multi_pass(&agents, &beliefs, 0, 4);
+                let actual = OutputSpecs::from_agents(&agents, &beliefs, 0, 4);
+                assert_eq!(expected.data.len(), actual.data.len());
+                for (t, expected) in &expected.data {
+                    let actual = &actual.data[t];
+                    assert_maps_match(&expected.mean_activation, &actual.mean_activation);
+                    assert_maps_match(&expected.sd_activation, &actual.sd_activation);
+                    assert_eq!(expected.median_activation, actual.median_activation);
+                    assert_eq!(
+                        expected.nonzero_activation_count,
+                        actual.nonzero_activation_count
+                    );
+                    assert_eq!(expected.n_performers, actual.n_performers);
+                }
+            }
+        }
+
+        #[test]
+        fn from_agents_matches_serial_path() {
+            let (agents, beliefs) = model(50);
+            let window = SummaryWindow::new(&agents, &beliefs, SummaryOptions::default());
+            let serial = OutputSpecs {
+                run_id: None,
+                fingerprint: None,
+                time_origin: None,
+                output_every: None,
+                names: None,
+                data: (0..=4)
+                    .map(|t| {
+                        (
+                            t,
+                            TickData::<f64>::new(&agents, &beliefs, t).summarise(&window),
+                        )
+                    })
+                    .collect(),
+            };
+            let parallel = OutputSpecs::from_agents(&agents, &beliefs, 0, 4);
+            assert_eq!(serial.data.len(), 5);
+            for (t, serial) in &serial.data {
+                let parallel = &parallel.data[t];
+                assert_eq!(serial.mean_activation, parallel.mean_activation);
+                assert_eq!(serial.sd_activation, parallel.sd_activation);
+                assert_eq!(serial.median_activation, parallel.median_activation);
+                assert_eq!(
+                    serial.nonzero_activation_count,
+                    parallel.nonzero_activation_count
+                );
+                assert_eq!(serial.n_performers, parallel.n_performers);
+            }
+        }
+
+        #[test]
+        fn from_agents_single_agent_matches_multi_pass() {
+            let (agents, beliefs) = model(1);
+            let expected = from_agents_multi_pass(&agents, &beliefs, 1, 1);
+            let actual = OutputSpecs::from_agents(&agents, &beliefs, 1, 1);
+            let (expected, actual) = (&expected.data[&1], &actual.data[&1]);
+            assert_eq!(expected.median_activation, actual.median_activation);
+            assert_eq!(
+                expected
+                    .sd_activation
+                    .keys()
+                    .collect::<std::collections::HashSet<_>>(),
+                actual.sd_activation.keys().collect()
+            );
+            assert!(actual.sd_activation.values().all(|v| v.is_nan()));
+        }
+
+        #[test]
+        fn hand_computed_statistics_cover_every_belief() {
+            let held: BeliefPtr = BasicBelief::new("held".to_string()).into();
+            let unheld: BeliefPtr = BasicBelief::new("unheld".to_string()).into();
+            let behaviour: BehaviourPtr = BasicBehaviour::new("beh".to_string()).into();
+            let agents: Vec<AgentPtr> = [0.5, -0.5, 0.75]
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let mut agent = BasicAgent::new();
+                    agent.set_activation(1, held.clone(), Some(v)).unwrap();
+                    agent.set_action(1, (i > 0).then(|| behaviour.clone()));
+                    agent.into()
+                })
+                .collect();
+            let spec = OutputSpecs::from_agents(&agents, &[held.clone(), unheld.clone()], 1, 1)
+                .data
+                .remove(&1)
+                .unwrap();
+            let (held, unheld) = (*held.borrow().uuid(), *unheld.borrow().uuid());
+
+            assert_eq!(spec.mean_activation[&held], 0.25);
+            // The squared deviations are 0.0625, 0.5625 and 0.25
+            assert!(approx_eq!(
+                f64,
+                spec.sd_activation[&held],
+                f64::sqrt(0.875 / 2.0),
+                epsilon = 1e-12
+            ));
+            assert_eq!(spec.median_activation[&held], 0.5);
+            assert_eq!(spec.nonzero_activation_count[&held], 3);
+
+            // No agent holds the belief, but it is still summarised
+            assert_eq!(spec.mean_activation[&unheld], 0.0);
+            assert_eq!(spec.sd_activation[&unheld], 0.0);
+            assert_eq!(spec.median_activation[&unheld], 0.0);
+            assert_eq!(spec.nonzero_activation_count[&unheld], 0);
+
+            assert_eq!(
+                spec.n_performers,
+                HashMap::from([(*behaviour.borrow().uuid(), 2)])
+            );
+        }
+
+        #[test]
+        fn nan_activations_sort_above_the_median() {
+            let beliefs: [BeliefPtr; 1] = [BasicBelief::new("b".to_string()).into()];
+            let tick = TickData {
+                time: 1,
+                activations: vec![vec![f64::NAN, 0.5, -0.5]],
+                counts: vec![3],
+                actions: vec![None; 3],
+            };
+            let window = SummaryWindow::new(&[], &beliefs, SummaryOptions::default());
+            let spec = tick.summarise(&window);
+            assert_eq!(spec.median_activation[beliefs[0].borrow().uuid()], 0.5);
+        }
+
+        #[test]
+        fn statistics_without_agents_are_zero() {
+            let beliefs: [BeliefPtr; 1] = [BasicBelief::new("b".to_string()).into()];
+            let uuid = *beliefs[0].borrow().uuid();
+            for weighting in [None, Some(StatWeighting::Degree)] {
+                let options = SummaryOptions {
+                    weighting,
+                    ..Default::default()
+                };
+                let spec = OutputSpecs::from_agents_with_options(&[], &beliefs, 1, 2, options)
+                    .data
+                    .remove(&2)
+                    .unwrap();
+                assert_eq!(spec.mean_activation[&uuid], 0.0);
+                assert_eq!(spec.sd_activation[&uuid], 0.0);
+                assert_eq!(spec.median_activation[&uuid], 0.0);
+                assert_eq!(spec.nonzero_activation_count[&uuid], 0);
+                assert!(spec.n_performers.is_empty());
+            }
+        }
     }
 }
@@ -0,0 +1,92 @@
+//! A model of how beliefs spread through a population of agents and drive
+//! the behaviours those agents perform.
+//!
+//! A simulation is set up with a
+//! [ConfigurationBuilder](configuration::ConfigurationBuilder) and run with
+//! a [Runner](runner::Runner).
+//!
+//! # Examples
+//! The inputs can be given as specs built in memory rather than files, and
+//! the model stepped a tick at a time, reading the activations between
+//! ticks. Nothing is written unless an output is given or asked for:
+//! ```
+//! use concept::{configuration::ConfigurationBuilder, example::ExampleScenario, runner::Runner};
+//!
+//! let example = ExampleScenario::generate();
+//! let config = ConfigurationBuilder::new()
+//!     .with_behaviours(example.behaviours)
+//!     .with_beliefs(example.beliefs)
+//!     .with_agents(example.agents)
+//!     .with_prs(example.prs)
+//!     .time_range(1, 3)
+//!     .seed(42)
+//!     .build()?;
+//! let mut runner = Runner::new(config);
+//! while runner.time() < runner.end_time() {
+//!     let time = runner.step()?;
+//!     let activations: Vec<f64> = runner
+//!         .activations_iter()
+//!         .filter(|&(_, t, _, _)| t == time)
+//!         .map(|(_, _, _, activation)| activation)
+//!         .collect();
+//!     let mean = activations.iter().sum::<f64>() / activations.len() as f64;
+//!     println!("Day {time}: mean activation {mean}");
+//! }
+//! assert_eq!(runner.time(), 3);
+//!
+//! // The summary of the three ticks, written explicitly
+//! let mut summary = Vec::new();
+//! runner.serialize_output_to(&mut summary)?;
+//! # Ok::<(), concept::error::ConceptError>(())
+//! ```
+//!
+//! # Features
+//! - `cli` (default): The `concept` binary. Library users can disable it
+//!   with `default-features = false`.
+//! - `zstd` (default): Reading and writing zstd compressed files.
+//! - `sqlite`: Appending the results of runs to a SQLite database with
+//!   `results_db::ResultsDatabase`, and the `--append-to` option of the
+//!   binary. It builds SQLite from source, so it is not a default feature.
+pub mod actions_log;
+pub mod agent_filter;
+pub mod bounds;
+pub mod calibration;
+pub mod caps;
+pub mod collections;
+pub mod comparison;
+pub mod configuration;
+pub mod deltas;
+pub mod ensemble;
+pub mod error;
+pub mod events;
+pub mod example;
+pub mod fingerprint;
+pub mod influence;
+pub mod initialization;
+pub mod input_summary;
+pub mod interventions;
+pub mod json;
+pub mod loader;
+pub mod memory;
+pub mod network;
+pub mod panel;
+pub mod perception;
+pub mod performance_relationships;
+pub mod population;
+pub mod precision;
+pub mod probe;
+pub mod restriction;
+#[cfg(feature = "sqlite")]
+pub mod results_db;
+pub mod runner;
+pub mod sampling;
+pub mod scoring;
+pub mod selection;
+pub mod selfcheck;
+pub mod sink;
+pub mod snapshot;
+pub mod stability;
+pub mod template;
+pub mod thresholds;
+pub mod verify;
+pub mod warnings;
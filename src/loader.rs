@@ -0,0 +1,991 @@
+//! Loading the specs of a model from readers and files.
+//!
+//! The `load_*` functions read from any [Read], such as an in-memory byte
+//! slice or stdin, while the `load_*_from_path` wrappers open a file and
+//! detect whether it is zstd compressed. [for_each_agent] streams the agents
+//! instead of collecting them, as they are the largest input.
+//!
+//! Wherever agents are loaded from a file, the file may instead be a
+//! [SimulationSnapshot] or the [ShardIndex](crate::snapshot::ShardIndex) of one, so a run can be started
+//! from the state at the end of another.
+//!
+//! The history of the agents can be cut to a [HistoryWindow] as it is read,
+//! so the activations and actions before the window are never held in
+//! memory.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use belief_spread::SimTime;
+use rand_chacha::ChaCha8Rng;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    agent_filter::{matches_all, spec_fields, AgentFilter},
+    error::ConceptError,
+    json::{
+        AgentSpec, BehaviourSpec, BeliefSpec, InterventionSpec, OutputSpecs,
+        PerformanceRelationshipSpec,
+    },
+    snapshot::{HistoryRange, Shard, SimulationSnapshot},
+};
+
+/// The first bytes of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The first bytes of a gzip member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The name given to inputs that are not files in errors.
+const READER_NAME: &str = "<input>";
+
+/// The encoding of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Plain JSON.
+    Json,
+    /// zstd compressed JSON, which can only be loaded with the `zstd`
+    /// feature.
+    JsonZstd,
+}
+
+impl InputFormat {
+    /// Detect the format of an input from its first bytes, without consuming
+    /// them.
+    ///
+    /// # Errors
+    /// If the input is gzip compressed, which is not supported, rather than
+    /// failing to parse it as JSON.
+    pub fn sniff(reader: &mut impl BufRead) -> io::Result<Self> {
+        let buf = reader.fill_buf()?;
+        if buf.starts_with(&GZIP_MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "gzip compressed inputs are not supported, only plain or zstd compressed JSON",
+            ));
+        }
+        Ok(if buf.starts_with(&ZSTD_MAGIC) {
+            InputFormat::JsonZstd
+        } else {
+            InputFormat::Json
+        })
+    }
+}
+
+/// Load [BehaviourSpec]s from a reader.
+pub fn load_behaviours(
+    reader: impl Read,
+    format: InputFormat,
+) -> Result<Vec<BehaviourSpec>, ConceptError> {
+    load(reader, format)
+}
+
+/// Load [BeliefSpec]s from a reader.
+pub fn load_beliefs(
+    reader: impl Read,
+    format: InputFormat,
+) -> Result<Vec<BeliefSpec>, ConceptError> {
+    load(reader, format)
+}
+
+/// Load [AgentSpec]s from a reader.
+pub fn load_agents(reader: impl Read, format: InputFormat) -> Result<Vec<AgentSpec>, ConceptError> {
+    load(reader, format)
+}
+
+/// Load [PerformanceRelationshipSpec]s from a reader.
+pub fn load_prs(
+    reader: impl Read,
+    format: InputFormat,
+) -> Result<Vec<PerformanceRelationshipSpec>, ConceptError> {
+    load(reader, format)
+}
+
+/// Call `f` with each [AgentSpec] as it is read from a reader, without
+/// holding every [AgentSpec] in memory at once.
+///
+/// If the input is invalid, `f` may already have been called with the
+/// [AgentSpec]s before the error.
+pub fn for_each_agent(
+    reader: impl Read,
+    format: InputFormat,
+    f: impl FnMut(AgentSpec),
+) -> Result<(), ConceptError> {
+    for_each_agent_in_window(reader, format, HistoryWindow::default(), f).map(|_| ())
+}
+
+/// Call `f` with each [AgentSpec] as it is read from a reader, as
+/// [for_each_agent], skipping the history before `window` as it is read.
+///
+/// # Returns
+/// The number of entries of history skipped.
+pub fn for_each_agent_in_window(
+    reader: impl Read,
+    format: InputFormat,
+    window: HistoryWindow,
+    f: impl FnMut(AgentSpec),
+) -> Result<SkippedHistory, ConceptError> {
+    let skipped = Cell::new(SkippedHistory::default());
+    load_seed(
+        reader,
+        format,
+        EachElement::with_seed(WindowedAgent::new(window, &skipped), f),
+    )?;
+    Ok(skipped.get())
+}
+
+/// Load [BehaviourSpec]s from a file, which may be zstd compressed.
+pub fn load_behaviours_from_path(path: &Path) -> Result<Vec<BehaviourSpec>, ConceptError> {
+    load_from_path(path)
+}
+
+/// Load [BeliefSpec]s from a file, which may be zstd compressed.
+pub fn load_beliefs_from_path(path: &Path) -> Result<Vec<BeliefSpec>, ConceptError> {
+    load_from_path(path)
+}
+
+/// Load [AgentSpec]s from a file, which may be zstd compressed. See
+/// [for_each_agent_from_path].
+pub fn load_agents_from_path(path: &Path) -> Result<Vec<AgentSpec>, ConceptError> {
+    let mut agents = Vec::new();
+    for_each_agent_from_path(path, |agent| agents.push(agent))?;
+    Ok(agents)
+}
+
+/// Load [PerformanceRelationshipSpec]s from a file, which may be zstd
+/// compressed.
+pub fn load_prs_from_path(path: &Path) -> Result<Vec<PerformanceRelationshipSpec>, ConceptError> {
+    load_from_path(path)
+}
+
+/// Load [InterventionSpec]s from a file, which may be zstd compressed, in
+/// the order they are given.
+pub fn load_interventions_from_path(path: &Path) -> Result<Vec<InterventionSpec>, ConceptError> {
+    load_from_path(path)
+}
+
+/// Load the [OutputSpecs] of a summary from a file, which may be zstd
+/// compressed.
+pub fn load_summary_from_path(path: &Path) -> Result<OutputSpecs, ConceptError> {
+    load_from_path(path)
+}
+
+/// Call `f` with each [AgentSpec] as it is read from a file, which may be
+/// zstd compressed. See [for_each_agent].
+///
+/// The file may also be a [SimulationSnapshot], whose [AgentSpec]s are all
+/// loaded before `f` is called, or a [ShardIndex](crate::snapshot::ShardIndex), whose shards are each
+/// streamed in order.
+pub fn for_each_agent_from_path(path: &Path, f: impl FnMut(AgentSpec)) -> Result<(), ConceptError> {
+    for_each_agent_in_window_from_path(path, HistoryWindow::default(), f).map(|_| ())
+}
+
+/// Call `f` with each [AgentSpec] read from a file, as
+/// [for_each_agent_from_path], skipping the history before `window` as it
+/// is read.
+///
+/// The [AgentSpec]s of a snapshot in a single file are read whole before
+/// their history is cut, as they are all loaded before `f` is called.
+///
+/// # Returns
+/// The number of entries of history skipped.
+pub fn for_each_agent_in_window_from_path(
+    path: &Path,
+    window: HistoryWindow,
+    f: impl FnMut(AgentSpec),
+) -> Result<SkippedHistory, ConceptError> {
+    let skipped = Cell::new(SkippedHistory::default());
+    if is_object(path)? {
+        load_from_path::<SnapshotFile>(path)?.for_each_agent(path, window, &skipped, f)?;
+    } else {
+        load_seed_from_path(
+            path,
+            EachElement::with_seed(WindowedAgent::new(window, &skipped), f),
+        )?;
+    }
+    Ok(skipped.get())
+}
+
+/// Call `f` with each [AgentSpec] read from a file, as
+/// [for_each_agent_from_path], and whether its fields match every one of
+/// `filters`.
+///
+/// The [AgentFilter]s are matched against the JSON object of each
+/// [AgentSpec] in an agents file, so they can match fields not used by the
+/// model. The [AgentSpec]s of a snapshot are matched against only their own
+/// fields.
+///
+/// The history before `window` is skipped as each [AgentSpec] is read, but
+/// as the whole JSON object of each is read to match the [AgentFilter]s,
+/// it is still read into memory one [AgentSpec] at a time.
+///
+/// # Returns
+/// The number of entries of history skipped.
+pub fn for_each_matching_agent_from_path(
+    path: &Path,
+    filters: &[AgentFilter],
+    window: HistoryWindow,
+    mut f: impl FnMut(AgentSpec, bool),
+) -> Result<SkippedHistory, ConceptError> {
+    if is_object(path)? {
+        for_each_agent_in_window_from_path(path, window, |spec| {
+            let matched = matches_all(filters, &spec_fields(&spec));
+            f(spec, matched)
+        })
+    } else {
+        let skipped = Cell::new(SkippedHistory::default());
+        load_seed_from_path(
+            path,
+            EachElement::with_seed(
+                AgentFieldsSeed(WindowedAgent::new(window, &skipped)),
+                |agent: AgentFields| f(agent.spec, matches_all(filters, &agent.fields)),
+            ),
+        )?;
+        Ok(skipped.get())
+    }
+}
+
+/// The earliest ticks of the history of each [AgentSpec] kept as it is
+/// loaded. Activations and actions before them are skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryWindow {
+    /// The earliest tick of activations kept, or [None] to keep them all.
+    pub activations_from: Option<SimTime>,
+    /// The earliest tick of actions kept, or [None] to keep them all.
+    pub actions_from: Option<SimTime>,
+}
+
+impl HistoryWindow {
+    /// Whether the window keeps the whole history.
+    pub fn is_empty(&self) -> bool {
+        self.activations_from.is_none() && self.actions_from.is_none()
+    }
+
+    /// The window, moved earlier if need be to keep the history at
+    /// `origin`.
+    pub fn keeping(self, origin: SimTime) -> Self {
+        HistoryWindow {
+            activations_from: self.activations_from.map(|from| from.min(origin)),
+            actions_from: self.actions_from.map(|from| from.min(origin)),
+        }
+    }
+
+    /// Remove the history before the window from an [AgentSpec] that has
+    /// already been read.
+    ///
+    /// # Returns
+    /// The number of entries of history removed.
+    pub fn retain(&self, spec: &mut AgentSpec) -> SkippedHistory {
+        fn retain_from<V>(from: Option<SimTime>, history: &mut HashMap<SimTime, V>) -> usize {
+            let Some(from) = from else { return 0 };
+            let before = history.len();
+            history.retain(|&time, _| time >= from);
+            before - history.len()
+        }
+        SkippedHistory {
+            activations: retain_from(self.activations_from, &mut spec.activations),
+            actions: retain_from(self.actions_from, &mut spec.actions),
+        }
+    }
+}
+
+/// The number of entries of history skipped by a [HistoryWindow].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkippedHistory {
+    /// The number of ticks of activations skipped, over every [AgentSpec].
+    pub activations: usize,
+    /// The number of actions skipped, over every [AgentSpec].
+    pub actions: usize,
+}
+
+impl std::ops::AddAssign for SkippedHistory {
+    fn add_assign(&mut self, other: Self) {
+        self.activations += other.activations;
+        self.actions += other.actions;
+    }
+}
+
+/// A [DeserializeSeed] for an [AgentSpec] that skips the history before a
+/// [HistoryWindow] without deserializing it, counting what it skips.
+#[derive(Clone, Copy)]
+struct WindowedAgent<'a> {
+    window: HistoryWindow,
+    skipped: &'a Cell<SkippedHistory>,
+}
+
+impl<'a> WindowedAgent<'a> {
+    fn new(window: HistoryWindow, skipped: &'a Cell<SkippedHistory>) -> Self {
+        Self { window, skipped }
+    }
+
+    fn skip(&self, skipped: SkippedHistory) {
+        let mut total = self.skipped.get();
+        total += skipped;
+        self.skipped.set(total);
+    }
+}
+
+/// The fields of an [AgentSpec].
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum AgentField {
+    Uuid,
+    Actions,
+    Activations,
+    Deltas,
+    Friends,
+    #[serde(rename = "activationFloors")]
+    ActivationFloors,
+    #[serde(rename = "activationCeilings")]
+    ActivationCeilings,
+    #[serde(other)]
+    Other,
+}
+
+const AGENT_FIELDS: &[&str] = &[
+    "uuid",
+    "actions",
+    "activations",
+    "deltas",
+    "friends",
+    "activationFloors",
+    "activationCeilings",
+];
+
+impl<'de> DeserializeSeed<'de> for WindowedAgent<'_> {
+    type Value = AgentSpec;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<AgentSpec, D::Error> {
+        deserializer.deserialize_struct("AgentSpec", AGENT_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for WindowedAgent<'_> {
+    type Value = AgentSpec;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct AgentSpec")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<AgentSpec, A::Error> {
+        let mut uuid = None;
+        let mut actions = None;
+        let mut activations = None;
+        let mut deltas = None;
+        let mut friends = None;
+        let mut floors = None;
+        let mut ceilings = None;
+        let mut skipped = SkippedHistory::default();
+        fn once<T, E: de::Error>(
+            field: &mut Option<T>,
+            name: &'static str,
+            value: T,
+        ) -> Result<(), E> {
+            match field.replace(value) {
+                Some(_) => Err(E::duplicate_field(name)),
+                None => Ok(()),
+            }
+        }
+        while let Some(key) = map.next_key()? {
+            match key {
+                AgentField::Uuid => once(&mut uuid, "uuid", map.next_value()?)?,
+                AgentField::Actions => once(
+                    &mut actions,
+                    "actions",
+                    map.next_value_seed(FromTime::new(
+                        self.window.actions_from,
+                        &mut skipped.actions,
+                    ))?,
+                )?,
+                AgentField::Activations => once(
+                    &mut activations,
+                    "activations",
+                    map.next_value_seed(FromTime::new(
+                        self.window.activations_from,
+                        &mut skipped.activations,
+                    ))?,
+                )?,
+                AgentField::Deltas => once(&mut deltas, "deltas", map.next_value()?)?,
+                AgentField::Friends => once(&mut friends, "friends", map.next_value()?)?,
+                AgentField::ActivationFloors => {
+                    once(&mut floors, "activationFloors", map.next_value()?)?
+                }
+                AgentField::ActivationCeilings => {
+                    once(&mut ceilings, "activationCeilings", map.next_value()?)?
+                }
+                AgentField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        self.skip(skipped);
+        Ok(AgentSpec {
+            uuid: uuid.unwrap_or_else(Uuid::new_v4),
+            actions: actions.unwrap_or_default(),
+            activations: activations.unwrap_or_default(),
+            deltas: deltas.unwrap_or_default(),
+            friends: friends.unwrap_or_default(),
+            activation_floors: floors.unwrap_or_default(),
+            activation_ceilings: ceilings.unwrap_or_default(),
+        })
+    }
+}
+
+/// A [DeserializeSeed] for a map keyed by [SimTime] that skips the entries
+/// before a tick without deserializing them.
+struct FromTime<'a, V> {
+    from: Option<SimTime>,
+    skipped: &'a mut usize,
+    value: PhantomData<V>,
+}
+
+impl<'a, V> FromTime<'a, V> {
+    fn new(from: Option<SimTime>, skipped: &'a mut usize) -> Self {
+        Self {
+            from,
+            skipped,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<'de, V: Deserialize<'de>> DeserializeSeed<'de> for FromTime<'_, V> {
+    type Value = HashMap<SimTime, V>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, V: Deserialize<'de>> Visitor<'de> for FromTime<'_, V> {
+    type Value = HashMap<SimTime, V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map keyed by time")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut history = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(time) = map.next_key::<SimTime>()? {
+            if self.from.is_some_and(|from| time < from) {
+                map.next_value::<IgnoredAny>()?;
+                *self.skipped += 1;
+            } else {
+                history.insert(time, map.next_value()?);
+            }
+        }
+        Ok(history)
+    }
+}
+
+/// An [AgentSpec] with every field of its JSON object, including those not
+/// used by the model.
+struct AgentFields {
+    spec: AgentSpec,
+    fields: Map<String, Value>,
+}
+
+/// A [DeserializeSeed] for [AgentFields], whose [AgentSpec] is read from
+/// the JSON object through a [WindowedAgent].
+#[derive(Clone, Copy)]
+struct AgentFieldsSeed<'a>(WindowedAgent<'a>);
+
+impl<'de> DeserializeSeed<'de> for AgentFieldsSeed<'_> {
+    type Value = AgentFields;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<AgentFields, D::Error> {
+        let value = Value::Object(Map::deserialize(deserializer)?);
+        let spec = self.0.deserialize(&value).map_err(de::Error::custom)?;
+        let Value::Object(fields) = value else {
+            unreachable!("the value is an object")
+        };
+        Ok(AgentFields { spec, fields })
+    }
+}
+
+/// Load a [SimulationSnapshot] from a file, which may be zstd compressed,
+/// or from the shards listed by a [ShardIndex](crate::snapshot::ShardIndex).
+pub fn load_snapshot_from_path(path: &Path) -> Result<SimulationSnapshot, ConceptError> {
+    let file: SnapshotFile = load_from_path(path)?;
+    let (time, rng, history) = (file.time, file.rng.clone(), file.history);
+    let mut agents = Vec::new();
+    file.for_each_agent(path, HistoryWindow::default(), &Cell::default(), |agent| {
+        agents.push(agent)
+    })?;
+    Ok(SimulationSnapshot {
+        time,
+        agents,
+        rng,
+        history,
+    })
+}
+
+/// A [SimulationSnapshot] in a single file, or the [ShardIndex](crate::snapshot::ShardIndex) of one
+/// written as shards.
+#[derive(Deserialize)]
+struct SnapshotFile {
+    time: SimTime,
+    rng: ChaCha8Rng,
+    agents: Option<Vec<AgentSpec>>,
+    shards: Option<Vec<Shard>>,
+    #[serde(default)]
+    history: Option<HistoryRange>,
+}
+
+impl SnapshotFile {
+    /// Call `f` with each [AgentSpec] of the snapshot, streaming the shards
+    /// in order if there are any.
+    ///
+    /// The history before `window` is skipped as each shard is read, and cut
+    /// from the [AgentSpec]s of a single file, adding the entries skipped to
+    /// `skipped`.
+    fn for_each_agent(
+        self,
+        path: &Path,
+        window: HistoryWindow,
+        skipped: &Cell<SkippedHistory>,
+        mut f: impl FnMut(AgentSpec),
+    ) -> Result<(), ConceptError> {
+        let seed = WindowedAgent::new(window, skipped);
+        match (self.shards, self.agents) {
+            (Some(shards), _) => {
+                for shard in shards {
+                    load_seed_from_path(
+                        &shard.resolve(path),
+                        EachElement::with_seed(seed, &mut f),
+                    )?;
+                }
+                Ok(())
+            }
+            (None, Some(agents)) => {
+                for mut agent in agents {
+                    seed.skip(window.retain(&mut agent));
+                    f(agent);
+                }
+                Ok(())
+            }
+            (None, None) => Err(ConceptError::Parse {
+                file: path.to_path_buf(),
+                json_path: ".".to_string(),
+                source: serde::de::Error::missing_field("agents"),
+            }),
+        }
+    }
+}
+
+/// A [DeserializeSeed] for a JSON array that passes each element, read with
+/// the seed `S`, to a function rather than collecting them.
+struct EachElement<S, F> {
+    seed: S,
+    f: F,
+}
+
+impl<S, F> EachElement<S, F> {
+    fn with_seed(seed: S, f: F) -> Self {
+        Self { seed, f }
+    }
+}
+
+impl<'de, S, F> DeserializeSeed<'de> for EachElement<S, F>
+where
+    S: DeserializeSeed<'de> + Copy,
+    F: FnMut(S::Value),
+{
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S, F> Visitor<'de> for EachElement<S, F>
+where
+    S: DeserializeSeed<'de> + Copy,
+    F: FnMut(S::Value),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(element) = seq.next_element_seed(self.seed)? {
+            (self.f)(element);
+        }
+        Ok(())
+    }
+}
+
+/// Parse JSON from a reader, recording the JSON path of any error.
+fn load<T: DeserializeOwned>(reader: impl Read, format: InputFormat) -> Result<T, ConceptError> {
+    load_seed(reader, format, PhantomData)
+}
+
+/// Parse JSON from a reader with a [DeserializeSeed], recording the JSON
+/// path of any error.
+fn load_seed<V, S: for<'de> DeserializeSeed<'de, Value = V>>(
+    reader: impl Read,
+    format: InputFormat,
+    seed: S,
+) -> Result<V, ConceptError> {
+    match format {
+        InputFormat::Json => parse_json(reader, seed),
+        #[cfg(not(feature = "zstd"))]
+        InputFormat::JsonZstd => Err(ConceptError::Io {
+            path: PathBuf::from(READER_NAME),
+            source: crate::sink::zstd_disabled(),
+        }),
+        #[cfg(feature = "zstd")]
+        InputFormat::JsonZstd => {
+            let decoder =
+                zstd::stream::read::Decoder::new(reader).map_err(|source| ConceptError::Io {
+                    path: PathBuf::from(READER_NAME),
+                    source,
+                })?;
+            parse_json(decoder, seed)
+        }
+    }
+}
+
+fn parse_json<V, S: for<'de> DeserializeSeed<'de, Value = V>>(
+    reader: impl Read,
+    seed: S,
+) -> Result<V, ConceptError> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut track = serde_path_to_error::Track::new();
+    seed.deserialize(serde_path_to_error::Deserializer::new(
+        &mut deserializer,
+        &mut track,
+    ))
+    .map_err(|source| ConceptError::Parse {
+        file: PathBuf::from(READER_NAME),
+        json_path: track.path().to_string(),
+        source,
+    })
+}
+
+/// Open a file, detect its format, and parse it.
+fn load_from_path<T: DeserializeOwned>(path: &Path) -> Result<T, ConceptError> {
+    load_seed_from_path(path, PhantomData)
+}
+
+/// Open a file, detect its format, and parse it with a [DeserializeSeed].
+fn load_seed_from_path<V, S: for<'de> DeserializeSeed<'de, Value = V>>(
+    path: &Path,
+    seed: S,
+) -> Result<V, ConceptError> {
+    let (reader, format) = open(path).map_err(|source| ConceptError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    load_seed(reader, format, seed).map_err(|err| in_file(err, path))
+}
+
+/// Open a file and detect its format.
+fn open(path: &Path) -> io::Result<(BufReader<File>, InputFormat)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let format = InputFormat::sniff(&mut reader)?;
+    Ok((reader, format))
+}
+
+/// Whether the JSON in a file is an object, rather than an array, judged
+/// from its first byte after decompression.
+fn is_object(path: &Path) -> Result<bool, ConceptError> {
+    let first_byte = || -> io::Result<Option<u8>> {
+        let (reader, format) = open(path)?;
+        let reader: Box<dyn Read> = match format {
+            InputFormat::Json => Box::new(reader),
+            #[cfg(feature = "zstd")]
+            InputFormat::JsonZstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+            #[cfg(not(feature = "zstd"))]
+            InputFormat::JsonZstd => return Err(crate::sink::zstd_disabled()),
+        };
+        for byte in BufReader::new(reader).bytes() {
+            let byte = byte?;
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+        }
+        Ok(None)
+    };
+    first_byte()
+        .map(|byte| byte == Some(b'{'))
+        .map_err(|source| ConceptError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Replace the name of the input in an error with the path of a file.
+fn in_file(err: ConceptError, path: &Path) -> ConceptError {
+    match err {
+        ConceptError::Io { source, .. } => ConceptError::Io {
+            path: path.to_path_buf(),
+            source,
+        },
+        ConceptError::Parse {
+            json_path, source, ..
+        } => ConceptError::Parse {
+            file: path.to_path_buf(),
+            json_path,
+            source,
+        },
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEHAVIOURS: &[u8] = br#"[
+        {"name": "b1", "uuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45"},
+        {"name": "b2"}
+    ]"#;
+
+    #[test]
+    fn load_behaviours_from_bytes_works() {
+        let behaviours = load_behaviours(BEHAVIOURS, InputFormat::Json).unwrap();
+        assert_eq!(behaviours.len(), 2);
+        assert_eq!(behaviours[0].name, "b1");
+        assert_eq!(
+            behaviours[0].uuid,
+            uuid::uuid!("98f4a478-7deb-40ef-9cb5-0f893c7a7f45")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn load_zstd_bytes_works() {
+        let compressed = zstd::encode_all(BEHAVIOURS, 3).unwrap();
+        let behaviours = load_behaviours(compressed.as_slice(), InputFormat::JsonZstd).unwrap();
+        assert_eq!(behaviours.len(), 2);
+    }
+
+    #[test]
+    fn load_agents_from_bytes_works() {
+        let json = br#"[{
+            "uuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45",
+            "activations": {"0": {"0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d": 0.5}}
+        }]"#;
+        let agents = load_agents(json.as_slice(), InputFormat::Json).unwrap();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].activations[&0].len(), 1);
+        assert!(agents[0].friends.is_empty());
+    }
+
+    #[test]
+    fn for_each_agent_streams_each_agent() {
+        let json = br#"[
+            {"uuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45"},
+            {"uuid": "0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d"}
+        ]"#;
+        let mut uuids = Vec::new();
+        for_each_agent(json.as_slice(), InputFormat::Json, |spec| {
+            uuids.push(spec.uuid)
+        })
+        .unwrap();
+        assert_eq!(
+            uuids,
+            [
+                uuid::uuid!("98f4a478-7deb-40ef-9cb5-0f893c7a7f45"),
+                uuid::uuid!("0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d")
+            ]
+        );
+    }
+
+    #[test]
+    fn for_each_agent_reports_json_path() {
+        let json = br#"[{}, {"deltas": 2}]"#;
+        let mut count = 0;
+        match for_each_agent(json.as_slice(), InputFormat::Json, |_| count += 1) {
+            Err(ConceptError::Parse { json_path, .. }) => assert_eq!(json_path, "[1].deltas"),
+            _ => panic!("expected a parse error"),
+        }
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn history_before_the_window_is_skipped() {
+        let json = br#"[
+            {
+                "uuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45",
+                "actions": {
+                    "0": "0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d",
+                    "3": "0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d"
+                },
+                "activations": {
+                    "0": {"0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d": 0.5},
+                    "1": {"0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d": 0.5},
+                    "2": {"0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d": 0.5}
+                },
+                "region": "north"
+            },
+            {"activations": {"5": {}}}
+        ]"#;
+        let window = HistoryWindow {
+            activations_from: Some(2),
+            actions_from: Some(1),
+        };
+        let mut agents = Vec::new();
+        let skipped =
+            for_each_agent_in_window(json.as_slice(), InputFormat::Json, window, |spec| {
+                agents.push(spec)
+            })
+            .unwrap();
+        assert_eq!(
+            skipped,
+            SkippedHistory {
+                activations: 2,
+                actions: 1
+            }
+        );
+        assert_eq!(agents[0].activations.keys().collect::<Vec<_>>(), [&2]);
+        assert_eq!(agents[0].actions.keys().collect::<Vec<_>>(), [&3]);
+        assert_eq!(agents[1].activations.keys().collect::<Vec<_>>(), [&5]);
+
+        // Fields are still checked as the derived deserializer does
+        let json = br#"[{"deltas": {}, "deltas": {}}]"#;
+        let result = for_each_agent_in_window(json.as_slice(), InputFormat::Json, window, |_| {});
+        assert!(
+            matches!(result, Err(ConceptError::Parse { source, .. }) if source.to_string().contains("duplicate field"))
+        );
+        let json = br#"[{"activations": {"0": {"b": 0.5}, "2": {"b": 0.5}}}]"#;
+        match for_each_agent_in_window(json.as_slice(), InputFormat::Json, window, |_| {}) {
+            Err(ConceptError::Parse { json_path, .. }) => {
+                assert_eq!(json_path, "[0].activations.2.b")
+            }
+            result => panic!("expected a parse error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn load_agents_from_snapshot_file_works() {
+        let rng: ChaCha8Rng = rand::SeedableRng::seed_from_u64(0);
+        let snapshot = format!(
+            r#"{{
+                "time": 2,
+                "agents": [
+                    {{"uuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45"}},
+                    {{"uuid": "0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d"}}
+                ],
+                "rng": {}
+            }}"#,
+            serde_json::to_string(&rng).unwrap()
+        );
+        let path = std::env::temp_dir().join(format!("concept-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, snapshot).unwrap();
+        let agents = load_agents_from_path(&path);
+        let without_agents = {
+            let rng = serde_json::to_string(&rng).unwrap();
+            std::fs::write(&path, format!(r#"{{"time": 2, "rng": {rng}}}"#)).unwrap();
+            load_agents_from_path(&path)
+        };
+        std::fs::remove_file(&path).unwrap();
+
+        let agents = agents.unwrap();
+        assert_eq!(agents.len(), 2);
+        assert_eq!(
+            agents[1].uuid,
+            uuid::uuid!("0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d")
+        );
+        assert!(
+            matches!(without_agents, Err(ConceptError::Parse { source, .. }) if source.to_string().contains("agents"))
+        );
+    }
+
+    #[test]
+    fn load_prs_from_bytes_works() {
+        let json = br#"[{
+            "behaviourUuid": "98f4a478-7deb-40ef-9cb5-0f893c7a7f45",
+            "beliefUuid": "0b0a0f41-8b4b-4a43-9a36-1a1f3c5b7c2d",
+            "value": 0.5
+        }]"#;
+        let prs = load_prs(json.as_slice(), InputFormat::Json).unwrap();
+        assert_eq!(prs[0].value, 0.5);
+    }
+
+    #[test]
+    fn load_invalid_bytes_reports_json_path() {
+        let json = br#"[{"name": "b1"}, {"name": 2}]"#;
+        match load_beliefs(json.as_slice(), InputFormat::Json) {
+            Err(ConceptError::Parse {
+                file, json_path, ..
+            }) => {
+                assert_eq!(file, PathBuf::from(READER_NAME));
+                assert_eq!(json_path, "[1].name");
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn sniff_detects_zstd() {
+        let compressed = zstd::encode_all(BEHAVIOURS, 3).unwrap();
+        assert_eq!(
+            InputFormat::sniff(&mut compressed.as_slice()).unwrap(),
+            InputFormat::JsonZstd
+        );
+        let mut plain = BEHAVIOURS;
+        assert_eq!(InputFormat::sniff(&mut plain).unwrap(), InputFormat::Json);
+        assert_eq!(
+            InputFormat::sniff(&mut &b""[..]).unwrap(),
+            InputFormat::Json
+        );
+    }
+
+    #[test]
+    fn gzip_inputs_are_rejected() {
+        let path = std::env::temp_dir().join(format!("concept-{}.json.gz", uuid::Uuid::new_v4()));
+        std::fs::write(&path, [&GZIP_MAGIC[..], &[0x08, 0x00]].concat()).unwrap();
+        let result = load_behaviours_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(ConceptError::Io { path: p, source }) => {
+                assert_eq!(p, path);
+                assert!(source.to_string().contains("gzip"), "{source}");
+            }
+            _ => panic!("expected gzip to be rejected"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn load_from_path_names_file_in_errors() {
+        let path = std::env::temp_dir().join(format!("concept-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, zstd::encode_all(&b"[{\"name\": 2}]"[..], 3).unwrap()).unwrap();
+        let result = load_behaviours_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ConceptError::Parse { file, .. }) if file == path));
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn load_zstd_without_feature_fails() {
+        let result = load_behaviours(&ZSTD_MAGIC[..], InputFormat::JsonZstd);
+        assert!(matches!(result, Err(ConceptError::Io { .. })));
+    }
+}
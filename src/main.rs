@@ -1,43 +1,496 @@
-mod json;
-mod performance_relationships;
-mod runner;
-
-use std::{collections::HashMap, fs::File, io};
-
-use anyhow::{Context, Result};
-use belief_spread::{AgentPtr, BehaviourPtr, BeliefPtr, SimTime};
-use clap::Parser;
-use json::{AgentSpec, BehaviourSpec, BeliefSpec, PerformanceRelationshipSpec};
-use performance_relationships::{vec_prs_to_performance_relationships, PerformanceRelationships};
-use runner::Runner;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use belief_spread::SimTime;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+#[cfg(feature = "sqlite")]
+use concept::results_db::ResultsDatabase;
+use concept::{
+    actions_log::ActionsLog,
+    agent_filter::AgentFilter,
+    bounds::BoundsPolicy,
+    calibration::{Calibration, CalibrationOutcome, CalibrationParam, Loss, ObservedAdoption},
+    collections::ModelIndex,
+    comparison::{Comparison, ComparisonOutcome},
+    configuration::{new_run_id, ConfigurationBuilder, FriendNormalization},
+    ensemble::{run_output_path, Ensemble, EnsembleOutcome},
+    error::ConceptError,
+    example::{ExampleOutcome, ExampleScenario},
+    fingerprint::fingerprint,
+    influence::InfluenceLog,
+    json::{OutputSpec, StatWeighting, SummaryFormat, SummaryOptions},
+    loader::{
+        load_agents_from_path, load_behaviours_from_path, load_beliefs_from_path,
+        load_prs_from_path, load_snapshot_from_path,
+    },
+    memory::PeakRss,
+    network::NetworkSnapshots,
+    panel::{self, PanelSpec},
+    performance_relationships::PrsOverride,
+    population::{
+        write_agents, FriendNetwork, PopulationOutcome, PopulationSettings, ValueDistribution,
+    },
+    precision::Precision,
+    probe::ProbeProjection,
+    runner::{RunOutcome, RunStatus, Runner, DEFAULT_SUMMARY_WINDOW},
+    sampling::OutputSampling,
+    selection::{ActionSelection, GreedySelection, LinearSelection, SoftmaxSelection},
+    selfcheck::SelfCheckReport,
+    sink::{AutoCompression, Compression, CompressionChoice, OutputSettings, DEFAULT_PLAIN_BELOW},
+    stability::{StabilityOptions, DEFAULT_STABILITY_TOLERANCE},
+    template::{check_collisions, placeholders, TemplateValues},
+    thresholds::{ThresholdMetric, ThresholdMetrics},
+    verify::{InputFile, OutputStatus, VerificationReport},
+};
+use log::{info, warn, Log, Metadata, Record, SetLoggerError};
+use serde::{ser::Error as _, Serialize, Serializer};
 use uuid::Uuid;
 
 /// The arguments of the command-line interface
-#[derive(Parser, Debug)]
+///
+/// The arguments are serialized into the
+/// [fingerprint](concept::fingerprint) of the run, except those that do not
+/// change what it does.
+#[derive(Parser, Debug, Serialize)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     /// The start time of the simulation
-    #[clap(short = 's', long = "start", value_parser, default_value_t = 1)]
+    #[clap(
+        short = 's',
+        long = "start",
+        value_parser,
+        default_value_t = 1,
+        global = true
+    )]
     start_time: SimTime,
 
     /// The end time of the simulation
-    #[clap(short = 'e', long = "end", value_parser, default_value_t = 1)]
+    #[clap(
+        short = 'e',
+        long = "end",
+        value_parser,
+        default_value_t = 1,
+        global = true
+    )]
     end_time: SimTime,
 
-    /// The output file
-    #[arg(short = 'o', long = "output", default_value = "output.json.zst")]
+    /// The time of the agents' initial activations and actions (default:
+    /// the tick before the start time); an earlier state is carried forward
+    /// to the tick before the start time
+    #[arg(long = "time-origin", value_name = "T", global = true)]
+    time_origin: Option<SimTime>,
+
+    /// The output file, of the summary statistics of the activations and
+    /// actions at each tick for every belief and behaviour. This and the
+    /// other output paths may have placeholders, such as
+    /// out/{run_id}_{seed}.json.zst, expanded from the configuration of the
+    /// run: {run_id}, {seed}, {fingerprint}, {start}, {end}, {prs_scale},
+    /// {relationship_scale}, {temperature}, {action_selection}, {precision}
+    /// and {bounds_policy}, with {{ and }} for literal braces
+    #[arg(
+        short = 'o',
+        long = "output",
+        visible_alias = "summary-output",
+        default_value = "output.json.zst"
+    )]
+    #[serde(skip)]
     output_file: std::path::PathBuf,
 
+    /// Also write the metadata of the run, the JSON printed when it ends,
+    /// to PATH
+    #[arg(long = "metadata", value_name = "PATH")]
+    #[serde(skip)]
+    metadata: Option<PathBuf>,
+
+    /// How the output file is compressed (default: zstd at level 3 if its
+    /// name ends in .zst, and plain if it ends in .json); auto chooses from
+    /// the output size estimated from the beliefs, behaviours and ticks,
+    /// adding or removing .zst from the name of the output to match
+    #[arg(long = "output-compression", value_enum)]
+    output_compression: Option<OutputCompressionMode>,
+
+    /// Compress the output file with zstd at LEVEL, overriding
+    /// --output-compression
+    #[arg(long = "output-compression-level", value_name = "LEVEL", value_parser = clap::value_parser!(i32).range(1..=22))]
+    output_compression_level: Option<i32>,
+
+    /// Leave the output plain if auto compression projects it to be smaller
+    /// than BYTES
+    #[arg(long = "auto-compression-threshold", value_name = "BYTES", default_value_t = DEFAULT_PLAIN_BELOW)]
+    #[serde(skip)]
+    auto_compression_threshold: u64,
+
+    /// Write a snapshot of the state at the end of the run, which can be
+    /// given as the agents of a later run, compressed with zstd if its name
+    /// ends in .zst and plain if it ends in .json
+    #[arg(long = "snapshot")]
+    #[serde(skip)]
+    snapshot_file: Option<std::path::PathBuf>,
+
+    /// Write the friendship network at the end of each of these ticks, given
+    /// as comma-separated times, to network_t<TIME>.csv.zst
+    #[arg(long = "network-snapshots", value_name = "TIME", value_delimiter = ',')]
+    #[serde(serialize_with = "unordered")]
+    network_snapshots: Vec<SimTime>,
+
+    /// The directory the network snapshots are written to
+    #[arg(long = "network-snapshot-dir", value_name = "DIR", default_value = ".")]
+    #[serde(skip)]
+    network_snapshot_dir: PathBuf,
+
+    /// Append the actions of every agent to an NDJSON log as each tick
+    /// completes, so they survive a run that dies, compressed with zstd if
+    /// its name ends in .zst and plain if it ends in .ndjson, .jsonl or .json
+    #[arg(long = "actions-log", value_name = "PATH")]
+    #[serde(skip)]
+    actions_log: Option<PathBuf>,
+
+    /// Count what happened to the agents at each tick, such as activations
+    /// brought within [-1, 1] and agents performing no action, and write
+    /// the counts as CSV to PATH (default: events.csv)
+    #[arg(long = "event-ledger", value_name = "PATH", num_args = 0..=1, default_missing_value = "events.csv")]
+    #[serde(skip)]
+    event_ledger: Option<PathBuf>,
+
+    /// Continue appending to the actions log of a run resumed from its
+    /// snapshot, which must end at the tick before the start time
+    #[arg(long = "resume", requires = "actions_log")]
+    resume: bool,
+
+    /// Append the activations, actions and summary of the run to a SQLite
+    /// database of results, creating it if it does not exist
+    #[cfg(feature = "sqlite")]
+    #[arg(long = "append-to", value_name = "PATH", requires = "run_label")]
+    #[serde(skip)]
+    append_to: Option<PathBuf>,
+
+    /// The label of the run in the database of results
+    #[cfg(feature = "sqlite")]
+    #[arg(long = "run-label", value_name = "NAME", requires = "append_to")]
+    #[serde(skip)]
+    run_label: Option<String>,
+
+    /// Simulate only the first N ticks, and print the runtime, output size
+    /// and memory projected for the whole run instead of writing any output
+    #[arg(long = "probe", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    #[serde(skip)]
+    probe: Option<u32>,
+
+    /// Run N replicates of the model, each from the initial state of the
+    /// agents with the seed plus its index, writing the output of each with
+    /// its index before the extensions, as output.run003.json.zst
+    #[arg(
+        long = "n-runs",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = [
+            "actions_log",
+            "snapshot_file",
+            "checkpoint_every",
+            "resume_from",
+            "event_ledger",
+            "panel_output",
+            "influence_output",
+            "network_snapshots",
+            "probe",
+            "print_fingerprint",
+        ]
+    )]
+    #[cfg_attr(feature = "sqlite", arg(conflicts_with = "append_to"))]
+    #[serde(skip)]
+    n_runs: u32,
+
+    /// Also write the output of every replicate, by its index, to PATH,
+    /// running the model as an ensemble even of one replicate
+    #[arg(long = "ensemble-output", value_name = "PATH", requires = "n_runs")]
+    #[serde(skip)]
+    ensemble_output: Option<PathBuf>,
+
+    /// Split the snapshot into this many files of agents, compressed and
+    /// written in parallel, with the snapshot file as their index
+    #[arg(long = "snapshot-shards", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    snapshot_shards: u16,
+
+    /// Write a snapshot of the state after every N ticks, so a run that
+    /// dies can be continued with --resume-from
+    #[arg(long = "checkpoint-every", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    checkpoint_every: Option<SimTime>,
+
+    /// The path the checkpoints are written to, with the tick of each
+    /// inserted before the extensions (default: checkpoint.json.zst, so
+    /// checkpoint_t<TIME>.json.zst)
+    #[arg(
+        long = "checkpoint-file",
+        value_name = "PATH",
+        requires = "checkpoint_every"
+    )]
+    #[serde(skip)]
+    checkpoint_file: Option<PathBuf>,
+
+    /// Continue the run from a snapshot or checkpoint, from the tick after
+    /// the one it was taken at
+    #[arg(long = "resume-from", value_name = "PATH")]
+    #[serde(skip)]
+    resume_from: Option<PathBuf>,
+
+    /// Write only the activations and actions of the ticks of this run to
+    /// the snapshot, leaving out the history of the agents it started from
+    #[arg(long = "output-new-only", requires = "snapshot_file")]
+    output_new_only: bool,
+
+    /// Write only the activations and actions of the last N ticks to the
+    /// snapshot
+    #[arg(long = "output-window", value_name = "N", requires = "snapshot_file", value_parser = clap::value_parser!(u32).range(1..))]
+    output_window: Option<SimTime>,
+
+    /// Keep only the activations and actions of the last N ticks before the
+    /// current one in memory, summarising each tick before it is dropped,
+    /// so the memory of a long run stays bounded. Older ticks are dropped
+    /// only every max(N, 8) ticks, so up to 2 * max(N, 8) ticks are kept
+    #[arg(
+        long = "retain-ticks",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "panel_output"
+    )]
+    #[cfg_attr(feature = "sqlite", arg(conflicts_with = "append_to"))]
+    retain_ticks: Option<SimTime>,
+
+    /// Keep only the K highest-weight friends of each agent, approximating
+    /// the model to speed up perception
+    #[arg(long = "max-friends-per-agent", value_name = "K", value_parser = clap::value_parser!(u32).range(1..), global = true)]
+    max_friends_per_agent: Option<u32>,
+
+    /// Skip the activations of each agent before tick T as the agents are
+    /// read, always keeping those at the time origin
+    #[arg(long = "load-activations-from", value_name = "T", global = true)]
+    load_activations_from: Option<SimTime>,
+
+    /// Skip the actions of each agent before tick T as the agents are read,
+    /// always keeping those at the time origin
+    #[arg(long = "load-actions-from", value_name = "T", global = true)]
+    load_actions_from: Option<SimTime>,
+
+    /// Draw the initial activation of each agent for each belief from a
+    /// normal distribution with the mean and SD of the activations at tick
+    /// --init-tick of the summary written by a prior run to PATH
+    #[arg(
+        long = "init-from-summary",
+        value_name = "PATH",
+        requires = "init_tick",
+        global = true
+    )]
+    #[serde(skip)]
+    init_from_summary: Option<std::path::PathBuf>,
+
+    /// The tick of the summary given by --init-from-summary to draw the
+    /// initial activations from
+    #[arg(
+        long = "init-tick",
+        value_name = "T",
+        requires = "init_from_summary",
+        global = true
+    )]
+    init_tick: Option<SimTime>,
+
+    /// Apply the interventions in a JSON file at the start of their ticks,
+    /// each setting the delta or forcing the activation of a belief for all
+    /// agents or a list of them
+    #[arg(long = "interventions", value_name = "PATH", global = true)]
+    #[serde(skip)]
+    interventions: Option<PathBuf>,
+
+    /// Divide the friend weights of each agent by their maximum or their
+    /// sum as they are loaded, so raw contact counts can be given
+    #[arg(long = "normalize-friend-weights", value_enum, default_value_t = FriendNormalizationMode::None, global = true)]
+    normalize_friend_weights: FriendNormalizationMode,
+
+    /// Simulate only the agents whose field FIELD in the agents file is
+    /// VALUE, dropping friendships with the others (may be repeated, and
+    /// every filter must match)
+    #[arg(long = "agent-filter", value_name = "FIELD=VALUE")]
+    #[serde(serialize_with = "unordered")]
+    agent_filter: Vec<AgentFilter>,
+
+    /// Simulate only these beliefs, given as comma-separated UUIDs, dropping
+    /// every reference to the others from the inputs
+    #[arg(
+        long = "only-beliefs",
+        value_name = "UUID",
+        value_delimiter = ',',
+        global = true
+    )]
+    #[serde(serialize_with = "unordered_option")]
+    only_beliefs: Option<Vec<Uuid>>,
+
+    /// Simulate only these behaviours, given as comma-separated UUIDs,
+    /// dropping every reference to the others from the inputs
+    #[arg(
+        long = "only-behaviours",
+        value_name = "UUID",
+        value_delimiter = ',',
+        global = true
+    )]
+    #[serde(serialize_with = "unordered_option")]
+    only_behaviours: Option<Vec<Uuid>>,
+
+    /// The delta of agents without one for a belief, which otherwise fail
+    /// validation
+    #[arg(long = "default-delta", value_name = "V", global = true)]
+    default_delta: Option<f64>,
+
+    /// Multiply every performance relationship loaded by FACTOR
+    #[arg(long = "prs-scale", value_name = "FACTOR", value_parser = non_negative)]
+    prs_scale: Option<f64>,
+
+    /// Warn of beliefs whose perception of a behaviour and performance
+    /// relationship with it have opposite signs
+    #[arg(long = "check-sign-consistency", global = true)]
+    check_sign_consistency: bool,
+
+    /// The magnitude both the perception and the performance relationship
+    /// must exceed to be warned of
+    #[arg(long = "sign-threshold", value_name = "MAG", default_value_t = 0.5, value_parser = non_negative, global = true)]
+    sign_threshold: f64,
+
+    /// Multiply every belief relationship by FACTOR, clamping the results
+    /// to [-1, 1]
+    #[arg(long = "relationship-scale", value_name = "FACTOR", value_parser = non_negative, global = true)]
+    relationship_scale: Option<f64>,
+
+    /// Replace the performance relationship of a belief and behaviour after
+    /// any scaling (may be repeated)
+    #[arg(long = "prs-override", value_name = "BELIEF:BEHAVIOUR=VALUE")]
+    prs_override: Vec<PrsOverride>,
+
+    /// Write only every Nth tick from the start time, and the last tick, to
+    /// the output and the panel
+    #[arg(long = "output-every", value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    output_every: u32,
+
+    /// Summarise and write the output this many ticks at a time, bounding
+    /// the memory used by the summary on long runs
+    #[arg(long = "summary-window", value_name = "W", default_value_t = DEFAULT_SUMMARY_WINDOW as u32, value_parser = clap::value_parser!(u32).range(1..))]
+    #[serde(skip)]
+    summary_window: u32,
+
+    /// Count only activations with an absolute value greater than EPS as
+    /// nonzero in the output
+    #[arg(long = "activation-threshold", value_name = "EPS", default_value_t = 0.0, value_parser = non_negative, global = true)]
+    activation_threshold: f64,
+
+    /// Also write the mean and SD of the activations with each agent weighted
+    /// by its number of friends or its total friend weight
+    #[arg(long = "weighted-stats", value_enum)]
+    weighted_stats: Option<WeightedStatsMode>,
+
+    /// The format the summary statistics are written to the output in: JSON,
+    /// or a long format CSV table of time,uuid,kind,statistic,value rows
+    #[arg(long = "summary-format", value_enum, default_value = "json")]
+    summary_format: SummaryFormatMode,
+
+    /// Also write the fraction of friendships whose agents performed the
+    /// same behaviour at each tick
+    #[arg(long = "action-assortativity")]
+    action_assortativity: bool,
+
+    /// Export the activations and actions of N sampled agents
+    #[arg(long = "panel-size", value_name = "N", requires = "panel_output")]
+    panel_size: Option<usize>,
+
+    /// The CSV file the panel's activations are written to, with its actions
+    /// written beside it
+    #[arg(long = "panel-output", value_name = "PATH", requires = "panel_size")]
+    #[serde(skip)]
+    panel_output: Option<std::path::PathBuf>,
+
+    /// The seed the panel is sampled with (default: the seed of the run)
+    #[arg(long = "panel-seed", requires = "panel_size")]
+    panel_seed: Option<u64>,
+
+    /// Write the name of each belief and behaviour beside its UUID in the
+    /// panel, in belief_name and behaviour_name columns
+    #[arg(long = "panel-names", requires = "panel_size")]
+    panel_names: bool,
+
+    /// Write how much of the change in each activation came from the
+    /// agent's friends and how much from its own delta as CSV to PATH, as
+    /// each tick is perceived, zstd compressed if PATH ends in .zst
+    #[arg(long = "influence-output", value_name = "PATH")]
+    #[serde(skip)]
+    influence_output: Option<PathBuf>,
+
+    /// Only write the influences on the agents of the panel, as writing
+    /// every agent writes a row per agent and belief each tick
+    #[arg(long = "influence-panel-only", requires_all = ["influence_output", "panel_size"])]
+    influence_panel_only: bool,
+
+    /// Find the first tick at which the mean activation of each belief
+    /// reaches a level, given as comma-separated UUID:LEVEL pairs
+    #[arg(
+        long = "threshold-metrics",
+        value_name = "UUID:LEVEL",
+        value_delimiter = ','
+    )]
+    #[serde(serialize_with = "unordered")]
+    threshold_metrics: Vec<ThresholdMetric>,
+
+    /// Find the first tick at which the fraction of agents performing each
+    /// behaviour reaches a level, given as comma-separated UUID:LEVEL pairs
+    #[arg(
+        long = "performer-thresholds",
+        value_name = "UUID:LEVEL",
+        value_delimiter = ','
+    )]
+    #[serde(serialize_with = "unordered")]
+    performer_thresholds: Vec<ThresholdMetric>,
+
+    /// Measure how much the mean activations and performer counts vary over
+    /// the final W ticks, and whether each converged
+    #[arg(long = "stability-window", value_name = "W", value_parser = clap::value_parser!(u32).range(1..))]
+    stability_window: Option<u32>,
+
+    /// The largest change between consecutive ticks of the stability window
+    /// for a mean activation, or fraction of agents performing a behaviour,
+    /// to have converged
+    #[arg(long = "stability-tolerance", value_name = "TOL", default_value_t = DEFAULT_STABILITY_TOLERANCE, value_parser = non_negative, requires = "stability_window")]
+    stability_tolerance: f64,
+
     /// The behaviours.json file
-    #[arg(short = 'b', long = "behaviours", default_value = "behaviours.json")]
+    #[arg(
+        short = 'b',
+        long = "behaviours",
+        default_value = "behaviours.json",
+        global = true
+    )]
+    #[serde(skip)]
     behaviours_file: std::path::PathBuf,
 
     /// The beliefs.json file
-    #[arg(short = 'c', long = "beliefs", default_value = "beliefs.json")]
+    #[arg(
+        short = 'c',
+        long = "beliefs",
+        default_value = "beliefs.json",
+        global = true
+    )]
+    #[serde(skip)]
     beliefs_file: std::path::PathBuf,
 
     /// The agents.json file
-    #[arg(short = 'a', long = "agents", default_value = "agents.json.zst")]
+    #[arg(
+        short = 'a',
+        long = "agents",
+        default_value = "agents.json.zst",
+        global = true
+    )]
+    #[serde(skip)]
     agents_file: std::path::PathBuf,
 
     /// The prs.json file
@@ -46,154 +499,1650 @@ struct Cli {
         long = "performance-relationships",
         default_value = "prs.json"
     )]
+    #[serde(skip)]
     prs_file: std::path::PathBuf,
+
+    /// How agents choose a behaviour from their behaviour scores
+    #[arg(long = "action-selection", value_enum, default_value_t = ActionSelectionMode::Linear, global = true)]
+    action_selection: ActionSelectionMode,
+
+    /// The temperature of softmax action selection
+    #[arg(long = "temperature", default_value_t = 1.0, global = true)]
+    temperature: f64,
+
+    /// The precision of the activations copied out of the agents to score
+    /// behaviours and compute the output; f32 halves their memory
+    #[arg(long = "precision", value_enum, default_value_t = PrecisionMode::F64, global = true)]
+    precision: PrecisionMode,
+
+    /// What happens to activations computed outside of [-1, 1]
+    #[arg(long = "bounds-policy", value_enum, default_value_t = BoundsMode::Clamp, global = true)]
+    bounds_policy: BoundsMode,
+
+    /// Log more (may be repeated); RUST_LOG overrides this
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, global = true)]
+    #[serde(skip)]
+    verbose: u8,
+
+    /// Log less (may be repeated); RUST_LOG overrides this
+    #[arg(short = 'q', long = "quiet", action = ArgAction::Count, conflicts_with = "verbose", global = true)]
+    #[serde(skip)]
+    quiet: u8,
+
+    /// Identify the run by ID in its logs and outputs (default: a new ULID)
+    #[arg(long = "run-id", value_name = "ID", global = true)]
+    #[serde(skip)]
+    run_id: Option<String>,
+
+    /// Use at most N threads, to simulate each tick and to load and
+    /// summarise the agents, which does not change the results (default:
+    /// one per CPU)
+    #[arg(long = "threads", value_name = "N", value_parser = clap::value_parser!(u16).range(1..), global = true)]
+    #[serde(skip)]
+    threads: Option<u16>,
+
+    /// The seed of the run (default: a random seed)
+    #[arg(long = "seed")]
+    #[serde(skip)]
+    seed: Option<u64>,
+
+    /// Print the fingerprint of the configuration of the run, a hash of the
+    /// contents of the input files, the options, the seed and the versions
+    /// of the code, and exit without running it
+    #[arg(long = "print-fingerprint", requires = "seed")]
+    #[serde(skip)]
+    print_fingerprint: bool,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
 }
 
-/// The configuration of the model.
-pub struct Configuration {
-    /// The [Behaviour]s in the model.
-    behaviours: Vec<BehaviourPtr>,
+/// The subcommands, each of which replaces the single run.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the population under two performance relationship files from the
+    /// same initial state and seed, and write the differences between them
+    Compare(CompareArgs),
+    /// Run a scenario twice with the same seed, once serially and once in
+    /// parallel, and check that the two runs are identical, exiting with 1 if
+    /// they are not
+    Selfcheck(SelfcheckArgs),
+    /// Write a small example scenario with a run.sh script that runs it, as
+    /// there is no config file format, and print the command that runs it
+    Example(ExampleArgs),
+    /// Generate a synthetic population of agents for the beliefs and
+    /// behaviours, with a random friendship network and random initial
+    /// activations and deltas
+    Generate(GenerateArgs),
+    /// Check the runs whose metadata is in a directory against the checksums
+    /// of their input files, exiting with 1 if any outputs are stale
+    VerifyOutputs(VerifyOutputsArgs),
+    /// Find the performance relationship scale whose runs best match the
+    /// observed adoption of a behaviour, by a golden-section search
+    Calibrate(CalibrateArgs),
+}
 
-    /// The [Belief]s in the model.
-    beliefs: Vec<BeliefPtr>,
+/// The arguments of the compare subcommand.
+#[derive(Args, Debug)]
+struct CompareArgs {
+    /// The performance relationships of the base variant
+    #[arg(long = "base", value_name = "PATH")]
+    base_prs_file: std::path::PathBuf,
 
-    /// The [Agent]s in the model.
-    agents: Vec<AgentPtr>,
+    /// The performance relationships of the alternative variant
+    #[arg(long = "alt", value_name = "PATH")]
+    alt_prs_file: std::path::PathBuf,
 
-    /// The performance relationships in the model.
-    prs: PerformanceRelationships,
+    /// The file the differences are written to
+    #[arg(short = 'o', long = "output", default_value = "comparison.json")]
+    output_file: std::path::PathBuf,
 
-    /// Start time.
-    start_time: SimTime,
+    /// Also write the full output of the base variant
+    #[arg(long = "base-output", value_name = "PATH")]
+    base_output_file: Option<std::path::PathBuf>,
 
-    /// End time.
-    end_time: SimTime,
+    /// Also write the full output of the alternative variant
+    #[arg(long = "alt-output", value_name = "PATH")]
+    alt_output_file: Option<std::path::PathBuf>,
 
-    /// Output file
-    output_file: File,
+    /// The seed both variants are run with (default: the seed given before
+    /// the subcommand, or a random seed)
+    #[arg(long = "seed")]
+    seed: Option<u64>,
 }
 
-fn main() -> Result<()> {
-    simple_logger::init_with_env().unwrap();
-    let args = Cli::parse();
+/// The arguments of the selfcheck subcommand.
+#[derive(Args, Debug)]
+struct SelfcheckArgs {
+    /// The directory of the scenario, which the behaviours, beliefs, agents
+    /// and performance relationships files are read from
+    #[arg(long = "scenario", value_name = "DIR", default_value = ".")]
+    scenario: PathBuf,
 
-    let mut config: Box<Configuration> = Box::new(Configuration {
-        behaviours: Vec::new(),
-        beliefs: Vec::new(),
-        agents: Vec::new(),
-        prs: HashMap::new(),
-        start_time: args.start_time,
-        end_time: args.end_time,
-        output_file: File::create(&args.output_file)
-            .with_context(|| format!("File {} doesn't exist!", &args.output_file.display()))?,
-    });
+    /// The prs.json file of the scenario
+    #[arg(
+        short = 'p',
+        long = "performance-relationships",
+        default_value = "prs.json"
+    )]
+    prs_file: PathBuf,
 
-    // Process behaviours
+    /// The seed both runs are run with (default: the seed given before the
+    /// subcommand, or a random seed)
+    #[arg(long = "seed")]
+    seed: Option<u64>,
+}
 
-    config.behaviours = read_behaviours_json(&args.behaviours_file)?;
+/// The arguments of the example subcommand.
+#[derive(Args, Debug)]
+struct ExampleArgs {
+    /// The directory the example is written to, which is created if it does
+    /// not exist
+    #[arg(long = "out", value_name = "DIR")]
+    out: PathBuf,
+}
 
-    // Process beliefs
+/// The arguments of the generate subcommand.
+#[derive(Args, Debug)]
+struct GenerateArgs {
+    /// The number of agents generated
+    #[arg(long = "n-agents", value_name = "N")]
+    n_agents: usize,
 
-    config.beliefs = read_belief_json(&args.beliefs_file, &config.behaviours)?;
+    /// How the friendships are drawn
+    #[arg(long = "network", value_enum, default_value_t = NetworkMode::ErdosRenyi)]
+    network: NetworkMode,
 
-    // Process agents
+    /// The probability that each pair of agents are friends
+    #[arg(long = "density", value_name = "P", value_parser = probability)]
+    density: f64,
 
-    config.agents = read_agent_json(&args.agents_file, &config.beliefs, &config.behaviours)?;
+    /// The weight of every friendship
+    #[arg(long = "friend-weight", value_name = "W", default_value_t = 1.0, value_parser = probability)]
+    friend_weight: f64,
 
-    // Process performance relationships
+    /// The distribution of the initial activations, as uniform:MIN,MAX or
+    /// normal:MEAN,SD, clamped to [-1, 1]
+    #[arg(
+        long = "activation-dist",
+        value_name = "DIST",
+        default_value = "uniform:-1,1"
+    )]
+    activation_dist: ValueDistribution,
 
-    config.prs = read_prs_json(&args.prs_file, &config.beliefs, &config.behaviours)?;
+    /// The distribution of the deltas, as uniform:MIN,MAX or normal:MEAN,SD,
+    /// clamped to be positive
+    #[arg(
+        long = "delta-dist",
+        value_name = "DIST",
+        default_value = "normal:1,0.1"
+    )]
+    delta_dist: ValueDistribution,
 
-    let mut run = Runner { config };
+    /// The seed of the population (default: the seed given before the
+    /// subcommand, or a random seed)
+    #[arg(long = "seed")]
+    seed: Option<u64>,
 
-    run.run()?;
+    /// The file the agents are written to, compressed with zstd if its name
+    /// ends in .zst and plain if it ends in .json
+    #[arg(short = 'o', long = "output", default_value = "agents.json.zst")]
+    output_file: PathBuf,
+}
 
-    Ok(())
+/// The friendship networks of the generate subcommand.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum NetworkMode {
+    /// Each pair of agents are friends with the same probability
+    ErdosRenyi,
 }
 
-fn read_behaviours_json(path: &std::path::Path) -> Result<Vec<BehaviourPtr>> {
-    let file = File::open(path)
-        .with_context(|| format!("Failed to read behaviours from {}", path.display()))?;
-    let reader = io::BufReader::new(file);
-    let behaviours: Vec<BehaviourSpec> =
-        serde_json::from_reader(reader).with_context(|| "behaviours.json invalid")?;
-    Ok(behaviours
-        .into_iter()
-        .map(|spec| spec.to_basic_behaviour().into())
-        .collect())
+/// The arguments of the verify-outputs subcommand.
+#[derive(Args, Debug)]
+struct VerifyOutputsArgs {
+    /// The directory of the metadata files, the run outcomes printed as
+    /// JSON, whose inputs are looked for relative to it and then to the
+    /// current directory
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
 }
 
-fn read_belief_json(path: &std::path::Path, behaviours: &[BehaviourPtr]) -> Result<Vec<BeliefPtr>> {
-    let file = File::open(path)
-        .with_context(|| format!("Failed to read beliefs from {}", path.display()))?;
-    let reader = io::BufReader::new(file);
-    let belief_specs: Vec<BeliefSpec> =
-        serde_json::from_reader(reader).with_context(|| "beliefs.json invalid")?;
-    let beliefs: Vec<BeliefPtr> = belief_specs
-        .iter()
-        .map(|spec| spec.to_basic_belief(behaviours))
-        .collect();
+/// The arguments of the calibrate subcommand.
+#[derive(Args, Debug)]
+struct CalibrateArgs {
+    /// The observed fraction of agents performing the behaviour at each
+    /// tick, as CSV with a time,fraction header
+    #[arg(long = "target", value_name = "PATH")]
+    target: PathBuf,
 
-    belief_specs
-        .iter()
-        .for_each(|spec| spec.link_belief_relationships(&beliefs));
-    Ok(beliefs)
-}
-
-fn read_agent_json(
-    path: &std::path::Path,
-    beliefs: &[BeliefPtr],
-    behaviours: &[BehaviourPtr],
-) -> Result<Vec<AgentPtr>> {
-    log::info!("Reading agents");
-    let file = File::open(path)
-        .with_context(|| format!("Failed to read agents from {}", path.display()))?;
-    let reader = io::BufReader::new(file);
-    let reader_zstd = zstd::stream::read::Decoder::new(reader)?;
-    let agent_specs: Vec<AgentSpec> =
-        serde_json::from_reader(reader_zstd).with_context(|| "agents.json invalid")?;
-    let agents: Vec<AgentPtr> = agent_specs
-        .iter()
-        .map(|spec| spec.to_basic_agent(behaviours, beliefs))
-        .collect();
-    let uuid_agents: HashMap<Uuid, AgentPtr> = agents
-        .iter()
-        .map(|a| (*a.borrow().uuid(), a.clone()))
-        .collect();
+    /// The behaviour whose adoption is matched
+    #[arg(long = "behaviour", value_name = "UUID")]
+    behaviour: Uuid,
 
-    agent_specs
-        .iter()
-        .for_each(|spec| spec.link_friends(&uuid_agents));
+    /// The parameter calibrated
+    #[arg(long = "param", value_enum, default_value_t = CalibrationParamMode::PrsScale)]
+    param: CalibrationParamMode,
+
+    /// The smallest and largest values of the parameter searched
+    #[arg(long = "range", value_name = "LOW:HIGH", value_parser = parse_range)]
+    range: (f64, f64),
+
+    /// Stop once the best value is bracketed within an interval this wide
+    #[arg(long = "tolerance", default_value_t = 0.02, value_parser = positive)]
+    tolerance: f64,
+
+    /// How the simulated adoption is compared with the observed
+    #[arg(long = "loss", value_enum, default_value_t = LossMode::Rmse)]
+    loss: LossMode,
+
+    /// The seed of every run (default: the seed given before the subcommand,
+    /// or a random seed)
+    #[arg(long = "seed")]
+    seed: Option<u64>,
+
+    /// Also write the full output of a run with the best value
+    #[arg(long = "best-output", value_name = "PATH")]
+    best_output_file: Option<PathBuf>,
+}
+
+/// The parameters that can be calibrated from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CalibrationParamMode {
+    /// The factor every performance relationship is multiplied by
+    PrsScale,
+}
+
+impl From<CalibrationParamMode> for CalibrationParam {
+    fn from(mode: CalibrationParamMode) -> Self {
+        match mode {
+            CalibrationParamMode::PrsScale => CalibrationParam::PrsScale,
+        }
+    }
+}
+
+/// The losses of a calibration available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LossMode {
+    /// The root mean square error
+    Rmse,
+    /// The mean absolute error
+    Mae,
+}
+
+impl From<LossMode> for Loss {
+    fn from(mode: LossMode) -> Self {
+        match mode {
+            LossMode::Rmse => Loss::Rmse,
+            LossMode::Mae => Loss::Mae,
+        }
+    }
+}
+
+/// The action selection strategies available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ActionSelectionMode {
+    /// Probability proportional to positive scores
+    Linear,
+    /// Always the highest score
+    Greedy,
+    /// Probability proportional to exp(score / temperature)
+    Softmax,
+}
+
+/// The weightings of the weighted statistics available from the
+/// command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum WeightedStatsMode {
+    /// The number of friends
+    Degree,
+    /// The sum of the friend weights
+    Weight,
+}
+
+impl From<WeightedStatsMode> for StatWeighting {
+    fn from(mode: WeightedStatsMode) -> Self {
+        match mode {
+            WeightedStatsMode::Degree => StatWeighting::Degree,
+            WeightedStatsMode::Weight => StatWeighting::FriendWeight,
+        }
+    }
+}
+
+/// The formats of the summary output available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SummaryFormatMode {
+    /// Statistics by tick, then by statistic, then by UUID, as JSON
+    Json,
+    /// A row for each statistic of each UUID at each tick, as CSV
+    Csv,
+}
+
+impl From<SummaryFormatMode> for SummaryFormat {
+    fn from(mode: SummaryFormatMode) -> Self {
+        match mode {
+            SummaryFormatMode::Json => SummaryFormat::Json,
+            SummaryFormatMode::Csv => SummaryFormat::Csv,
+        }
+    }
+}
 
-    Ok(agents)
+/// The compressions of the output file available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputCompressionMode {
+    /// Plain JSON
+    None,
+    /// zstd at level 3
+    Zstd,
+    /// Plain, zstd, or multithreaded zstd at a higher level, by the
+    /// projected size of the output
+    Auto,
 }
 
-fn read_prs_json(
-    path: &std::path::Path,
-    beliefs: &[BeliefPtr],
-    behaviours: &[BehaviourPtr],
-) -> Result<PerformanceRelationships> {
-    let file = File::open(path).with_context(|| {
-        format!(
-            "Failed to read performance relationships from {}",
-            path.display()
+/// The precisions available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum PrecisionMode {
+    /// Double precision
+    F64,
+    /// Single precision, with outputs rounded to f32
+    F32,
+}
+
+impl From<PrecisionMode> for Precision {
+    fn from(mode: PrecisionMode) -> Self {
+        match mode {
+            PrecisionMode::F64 => Precision::F64,
+            PrecisionMode::F32 => Precision::F32,
+        }
+    }
+}
+
+/// The friend weight normalizations available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum FriendNormalizationMode {
+    /// Divide by the largest weight of the agent
+    Max,
+    /// Divide by the sum of the weights of the agent
+    Sum,
+    /// Keep the weights as given
+    None,
+}
+
+impl From<FriendNormalizationMode> for FriendNormalization {
+    fn from(mode: FriendNormalizationMode) -> Self {
+        match mode {
+            FriendNormalizationMode::Max => FriendNormalization::Max,
+            FriendNormalizationMode::Sum => FriendNormalization::Sum,
+            FriendNormalizationMode::None => FriendNormalization::None,
+        }
+    }
+}
+
+/// The bounds policies available from the command-line.
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BoundsMode {
+    /// Saturate at the bound
+    Clamp,
+    /// Fold the excess back from the bound
+    Reflect,
+    /// Abort with the agent, belief and time
+    Error,
+}
+
+impl From<BoundsMode> for BoundsPolicy {
+    fn from(mode: BoundsMode) -> Self {
+        match mode {
+            BoundsMode::Clamp => BoundsPolicy::Clamp,
+            BoundsMode::Reflect => BoundsPolicy::Reflect,
+            BoundsMode::Error => BoundsPolicy::Error,
+        }
+    }
+}
+
+impl Cli {
+    /// The log level selected by the verbosity flags, starting from info.
+    fn log_level(&self) -> log::LevelFilter {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            i16::MIN..=-3 => log::LevelFilter::Off,
+            -2 => log::LevelFilter::Error,
+            -1 => log::LevelFilter::Warn,
+            0 => log::LevelFilter::Info,
+            1 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        }
+    }
+
+    fn action_selection(&self) -> Box<dyn ActionSelection> {
+        match self.action_selection {
+            ActionSelectionMode::Linear => Box::new(LinearSelection),
+            ActionSelectionMode::Greedy => Box::new(GreedySelection),
+            ActionSelectionMode::Softmax => Box::new(SoftmaxSelection {
+                temperature: self.temperature,
+            }),
+        }
+    }
+
+    /// The paths of the outputs of a single run that may be templates, by
+    /// their options.
+    fn output_templates(&mut self) -> Vec<(&'static str, &mut PathBuf)> {
+        let mut templates = vec![
+            ("--output", &mut self.output_file),
+            ("--network-snapshot-dir", &mut self.network_snapshot_dir),
+        ];
+        templates.extend(
+            [
+                ("--snapshot", &mut self.snapshot_file),
+                ("--checkpoint-file", &mut self.checkpoint_file),
+                ("--actions-log", &mut self.actions_log),
+                ("--event-ledger", &mut self.event_ledger),
+                ("--panel-output", &mut self.panel_output),
+                ("--influence-output", &mut self.influence_output),
+                ("--metadata", &mut self.metadata),
+                ("--ensemble-output", &mut self.ensemble_output),
+                #[cfg(feature = "sqlite")]
+                ("--append-to", &mut self.append_to),
+            ]
+            .into_iter()
+            .filter_map(|(label, path)| Some((label, path.as_mut()?))),
+        );
+        templates
+    }
+
+    /// The compression of the output file, or [None] if it is chosen
+    /// automatically. An explicit level always gives zstd at that level,
+    /// and without either option the compression follows the name of the
+    /// file.
+    fn output_compression(&self) -> Result<Option<Compression>, ConceptError> {
+        Ok(
+            match (self.output_compression_level, self.output_compression) {
+                (Some(level), _) => Some(Compression::Zstd { level, workers: 0 }),
+                (None, Some(OutputCompressionMode::None)) => Some(Compression::None),
+                (None, Some(OutputCompressionMode::Zstd)) => Some(Compression::Zstd {
+                    level: 3,
+                    workers: 0,
+                }),
+                (None, Some(OutputCompressionMode::Auto)) => None,
+                (None, None) => Some(Compression::for_path(&self.output_file)?),
+            },
         )
-    })?;
-    let reader = io::BufReader::new(file);
-    let prss: Vec<PerformanceRelationshipSpec> =
-        serde_json::from_reader(reader).with_context(|| "prs.json invalid")?;
-    let uuid_beliefs: HashMap<Uuid, BeliefPtr> = beliefs
-        .iter()
-        .map(|b| (*b.borrow().uuid(), b.clone()))
-        .collect();
+    }
+}
 
-    let uuid_behaviours: HashMap<Uuid, BehaviourPtr> = behaviours
+/// Serialize repeated arguments whose order does not matter in a canonical
+/// order, so the fingerprint of a run does not depend on the order they
+/// were given in.
+fn unordered<T: Serialize, S: Serializer>(items: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut values = items
         .iter()
-        .map(|b| (*b.borrow().uuid(), b.clone()))
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(S::Error::custom)?;
+    values.sort_by_cached_key(|value| value.to_string());
+    serializer.collect_seq(values)
+}
+
+/// [unordered], for optional arguments.
+fn unordered_option<T: Serialize, S: Serializer>(
+    items: &Option<Vec<T>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match items {
+        Some(items) => unordered(items, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Parse a finite, non-negative number.
+fn non_negative(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(v),
+        Ok(_) => Err("must be finite and non-negative".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parse a probability, a number within [0, 1].
+fn probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        Ok(_) => Err("must be within [0, 1]".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parse a finite, positive number.
+fn positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        Ok(_) => Err("must be finite and positive".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parse a range of finite numbers given as LOW:HIGH, with LOW below HIGH.
+fn parse_range(s: &str) -> Result<(f64, f64), String> {
+    let (low, high) = s
+        .split_once(':')
+        .ok_or_else(|| "must be LOW:HIGH".to_string())?;
+    let parse = |v: &str| match v.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        Ok(_) => Err("must be finite".to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    let (low, high) = (parse(low)?, parse(high)?);
+    if low < high {
+        Ok((low, high))
+    } else {
+        Err(format!("{low} must be below {high}"))
+    }
+}
+
+/// The exit code for a [ConceptError], following the BSD sysexits
+/// conventions.
+fn exit_code(err: &ConceptError) -> ExitCode {
+    ExitCode::from(match err {
+        ConceptError::Io { .. } => 66,                    // EX_NOINPUT
+        ConceptError::Parse { .. } => 65,                 // EX_DATAERR
+        ConceptError::Validation(_) => 65,                // EX_DATAERR
+        ConceptError::Simulation { .. } => 70,            // EX_SOFTWARE
+        ConceptError::ActivationOutOfBounds { .. } => 70, // EX_SOFTWARE
+        ConceptError::ActionsLogMismatch { .. } => 65,    // EX_DATAERR
+        ConceptError::MalformedTemplate { .. } => 64,     // EX_USAGE
+        ConceptError::UnknownPlaceholders { .. } => 64,   // EX_USAGE
+        ConceptError::PathCollision { .. } => 64,         // EX_USAGE
+        ConceptError::UnknownFormat { .. } => 64,         // EX_USAGE
+        ConceptError::Observations { .. } => 65,          // EX_DATAERR
+        ConceptError::Output { .. } => 74,                // EX_IOERR
+        ConceptError::Threads { .. } => 71,               // EX_OSERR
+        #[cfg(feature = "sqlite")]
+        ConceptError::Database { .. } => 74, // EX_IOERR
+        #[cfg(feature = "sqlite")]
+        ConceptError::DatabaseVersion { .. } => 65, // EX_DATAERR
+    })
+}
+
+/// A logger prefixing every message with the id of the run, so that the
+/// logs of concurrent runs can be told apart once they are interleaved.
+struct RunIdLogger {
+    inner: simple_logger::SimpleLogger,
+    run_id: String,
+}
+
+impl RunIdLogger {
+    fn init(inner: simple_logger::SimpleLogger, run_id: String) -> Result<(), SetLoggerError> {
+        log::set_max_level(inner.max_level());
+        log::set_boxed_logger(Box::new(RunIdLogger { inner, run_id }))
+    }
+}
+
+impl Log for RunIdLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(
+            &Record::builder()
+                .args(format_args!("[{}] {}", self.run_id, record.args()))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        )
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Cli::parse();
+    let run_id = args.run_id.take().unwrap_or_else(new_run_id);
+    let logger = simple_logger::SimpleLogger::new()
+        .with_level(args.log_level())
+        .env();
+    RunIdLogger::init(logger, run_id.clone()).expect("logging is only initialized once");
+
+    let result = match args.threads {
+        Some(threads) => limit_threads(threads).and_then(|_| dispatch(args, run_id)),
+        None => dispatch(args, run_id),
+    };
+    match result {
+        Ok((json, code)) => {
+            println!("{json}");
+            code
+        }
+        Err(err) => {
+            let code = exit_code(&err);
+            if let ConceptError::Validation(report) = &err {
+                report.agent_warnings().log("Validation");
+            }
+            eprintln!("Error: {:?}", anyhow::Error::from(err));
+            code
+        }
+    }
+}
+
+/// Run the subcommand, or the simulation if there is none.
+///
+/// # Returns
+/// The JSON to print, and the code to exit with.
+fn dispatch(mut args: Cli, run_id: String) -> Result<(String, ExitCode), ConceptError> {
+    match args.command.take() {
+        Some(Command::Compare(compare_args)) => {
+            compare(args, compare_args, &run_id).map(|outcome| {
+                let json = serde_json::to_string_pretty(&outcome);
+                (
+                    json.expect("comparison outcomes serialize"),
+                    ExitCode::SUCCESS,
+                )
+            })
+        }
+        Some(Command::Selfcheck(selfcheck_args)) => selfcheck(args, selfcheck_args).map(|report| {
+            let json = serde_json::to_string_pretty(&report);
+            let code = if report.passed {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            };
+            (json.expect("self-check reports serialize"), code)
+        }),
+        Some(Command::Example(example_args)) => example(example_args).map(|outcome| {
+            let json = serde_json::to_string_pretty(&outcome);
+            (json.expect("example outcomes serialize"), ExitCode::SUCCESS)
+        }),
+        Some(Command::Generate(generate_args)) => generate(&args, generate_args).map(|outcome| {
+            let json = serde_json::to_string_pretty(&outcome);
+            (
+                json.expect("population outcomes serialize"),
+                ExitCode::SUCCESS,
+            )
+        }),
+        Some(Command::VerifyOutputs(verify_args)) => verify_outputs(verify_args).map(|report| {
+            let json = serde_json::to_string_pretty(&report);
+            let code = if report.stale == 0 {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            };
+            (json.expect("verification reports serialize"), code)
+        }),
+        Some(Command::Calibrate(calibrate_args)) => {
+            calibrate(args, calibrate_args, &run_id).map(|outcome| {
+                let json = serde_json::to_string_pretty(&outcome);
+                (
+                    json.expect("calibration outcomes serialize"),
+                    ExitCode::SUCCESS,
+                )
+            })
+        }
+        None if args.print_fingerprint => {
+            print_fingerprint(&args).map(|fingerprint| (fingerprint, ExitCode::SUCCESS))
+        }
+        None if args.probe.is_some() => probe(args, run_id).map(|projection| {
+            let json = serde_json::to_string_pretty(&projection);
+            (json.expect("projections serialize"), ExitCode::SUCCESS)
+        }),
+        None if args.n_runs > 1 || args.ensemble_output.is_some() => {
+            ensemble(args, run_id).map(|outcome| {
+                let json = serde_json::to_string_pretty(&outcome);
+                (
+                    json.expect("ensemble outcomes serialize"),
+                    ExitCode::SUCCESS,
+                )
+            })
+        }
+        None => run(args, run_id).map(|outcome| {
+            let json = serde_json::to_string_pretty(&outcome);
+            let code = match outcome.status {
+                RunStatus::Completed => ExitCode::SUCCESS,
+                RunStatus::Cancelled => ExitCode::from(130), // 128 + SIGINT
+            };
+            (json.expect("run outcomes serialize"), code)
+        }),
+    }
+}
+
+/// Cap the global rayon pool at `threads` threads.
+fn limit_threads(threads: u16) -> Result<(), ConceptError> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.into())
+        .build_global()
+        .map_err(|source| ConceptError::Threads {
+            threads: threads.into(),
+            source,
+        })
+}
+
+/// Apply the options of the model shared by every subcommand.
+fn model_options(mut builder: ConfigurationBuilder, args: &Cli) -> ConfigurationBuilder {
+    builder = builder.time_range(args.start_time, args.end_time);
+    if let Some(origin) = args.time_origin {
+        builder = builder.time_origin(origin);
+    }
+    if let Some(delta) = args.default_delta {
+        builder = builder.default_delta(delta);
+    }
+    if let Some(k) = args.max_friends_per_agent {
+        builder = builder.max_friends_per_agent(k as usize);
+    }
+    if let Some(time) = args.load_activations_from {
+        builder = builder.load_activations_from(time);
+    }
+    if let Some(time) = args.load_actions_from {
+        builder = builder.load_actions_from(time);
+    }
+    if let (Some(path), Some(tick)) = (&args.init_from_summary, args.init_tick) {
+        builder = builder.init_from_summary_path(path, tick);
+    }
+    if let Some(path) = &args.interventions {
+        builder = builder.interventions_from_path(path);
+    }
+    builder = builder.normalize_friend_weights(args.normalize_friend_weights.into());
+    if let Some(factor) = args.relationship_scale {
+        builder = builder.relationship_scale(factor);
+    }
+    if args.check_sign_consistency {
+        builder = builder.check_sign_consistency(args.sign_threshold);
+    }
+    if let Some(beliefs) = &args.only_beliefs {
+        builder = builder.only_beliefs(beliefs.iter().copied());
+    }
+    if let Some(behaviours) = &args.only_behaviours {
+        builder = builder.only_behaviours(behaviours.iter().copied());
+    }
+    builder
+}
+
+/// A [ConfigurationBuilder] reading the input files given on the command
+/// line.
+fn input_builder(args: &Cli) -> ConfigurationBuilder {
+    ConfigurationBuilder::new()
+        .behaviours_from_path(&args.behaviours_file)
+        .beliefs_from_path(&args.beliefs_file)
+        .agents_from_path(&args.agents_file)
+        .prs_from_path(&args.prs_file)
+}
+
+/// Load the model and set up a [Runner] for it from the options of a single
+/// run, which writes its output with `compression`, or no output or network
+/// snapshots if it is probing and has no `compression`.
+fn runner(
+    args: &Cli,
+    run_id: String,
+    compression: Option<Compression>,
+) -> Result<Runner, ConceptError> {
+    let builder = input_builder(args).run_id(run_id);
+    let builder = match args.seed {
+        Some(seed) => builder.seed(seed),
+        None => builder,
+    };
+    let builder = match compression {
+        Some(compression) => builder.output_settings(OutputSettings {
+            path: args.output_file.clone(),
+            compression,
+        }),
+        None => builder.output(Box::new(Vec::new())),
+    };
+    let builder = if compression.is_some() && !args.network_snapshots.is_empty() {
+        builder.network_snapshots(NetworkSnapshots::new(
+            args.network_snapshots.clone(),
+            &args.network_snapshot_dir,
+        ))
+    } else {
+        builder
+    };
+    configured_runner(builder, args)
+}
+
+/// Apply the filters of the agents and the scaling and overrides of the
+/// performance relationships to `builder`.
+fn input_options(mut builder: ConfigurationBuilder, args: &Cli) -> ConfigurationBuilder {
+    for filter in &args.agent_filter {
+        builder = builder.agent_filter(filter.clone());
+    }
+    if let Some(factor) = args.prs_scale {
+        builder = builder.prs_scale(factor);
+    }
+    for prs_override in &args.prs_override {
+        builder = builder.prs_override(*prs_override);
+    }
+    builder
+}
+
+/// Apply the options of the model and of a [Runner] to `builder`, which
+/// has the inputs, seed and output of the run, and set up the [Runner].
+fn configured_runner(builder: ConfigurationBuilder, args: &Cli) -> Result<Runner, ConceptError> {
+    let builder = input_options(model_options(builder, args), args);
+    let config = builder.build()?;
+    let thresholds = ThresholdMetrics {
+        beliefs: args.threshold_metrics.clone(),
+        behaviours: args.performer_thresholds.clone(),
+    };
+    thresholds.check(config.index())?;
+
+    let mut run = Runner::new(config)
+        .with_action_selection(args.action_selection())
+        .with_precision(args.precision.into())
+        .with_bounds_policy(args.bounds_policy.into())
+        .with_summary_window(args.summary_window as usize)
+        .with_summary_format(args.summary_format.into())
+        .with_output_every(args.output_every as usize)
+        .with_activation_threshold(args.activation_threshold)
+        .with_threshold_metrics(thresholds);
+    if let Some(mode) = args.weighted_stats {
+        run = run.with_weighted_stats(mode.into());
+    }
+    if let Some(window) = args.stability_window {
+        run = run.with_stability(StabilityOptions {
+            window: window as usize,
+            tolerance: args.stability_tolerance,
+        });
+    }
+    if args.action_assortativity {
+        run = run.with_action_assortativity();
+    }
+    if let Some(ticks) = args.retain_ticks {
+        run = run.with_retained_ticks(ticks);
+    }
+    Ok(run)
+}
+
+/// Choose the compression of the output file from its uncompressed size,
+/// estimated from the beliefs and behaviours and the number of ticks
+/// written, without loading the agents or simulating anything.
+fn choose_compression(args: &Cli) -> Result<CompressionChoice, ConceptError> {
+    let kept = |uuids: Vec<Uuid>, only: &Option<Vec<Uuid>>| match only {
+        Some(only) => uuids
+            .into_iter()
+            .filter(|uuid| only.contains(uuid))
+            .collect(),
+        None => uuids,
+    };
+    let beliefs = load_beliefs_from_path(&args.beliefs_file)?;
+    let behaviours = load_behaviours_from_path(&args.behaviours_file)?;
+    let index = ModelIndex::new(
+        kept(beliefs.iter().map(|b| b.uuid).collect(), &args.only_beliefs),
+        kept(
+            behaviours.iter().map(|b| b.uuid).collect(),
+            &args.only_behaviours,
+        ),
+    );
+    let options = SummaryOptions {
+        weighting: args.weighted_stats.map(Into::into),
+        ..SummaryOptions::default()
+    };
+    let ticks = OutputSampling {
+        every: args.output_every as usize,
+        start_time: args.start_time,
+        end_time: args.end_time,
+    }
+    .times()
+    .len() as u64;
+    let estimated_bytes =
+        ticks * OutputSpec::estimated_bytes(&index, options, args.summary_format.into());
+    let thresholds = AutoCompression {
+        plain_below: args.auto_compression_threshold,
+        ..AutoCompression::default()
+    };
+    let choice = CompressionChoice {
+        estimated_bytes,
+        thresholds,
+        compression: thresholds.choose(estimated_bytes),
+    };
+    info!(
+        "Chose {:?} for an output estimated to be {} bytes uncompressed",
+        choice.compression, choice.estimated_bytes
+    );
+    Ok(choice)
+}
+
+fn run(mut args: Cli, run_id: String) -> Result<RunOutcome, ConceptError> {
+    // The seed is chosen before anything is loaded, so the output paths can
+    // be named by it
+    let seed = *args.seed.get_or_insert_with(rand::random);
+    let options =
+        serde_json::to_value(&args).map_err(|err| ConceptError::Output { source: err.into() })?;
+    let mut uses_fingerprint = false;
+    for (_, path) in args.output_templates() {
+        uses_fingerprint |= placeholders(path)?.contains(&"fingerprint");
+    }
+    let early_fingerprint = if uses_fingerprint {
+        Some(input_fingerprint(&args, &options, seed)?)
+    } else {
+        None
+    };
+    expand_output_templates(&mut args, &run_id, early_fingerprint.as_deref())?;
+    let metadata = args.metadata.take();
+
+    let output_compression = args.output_compression()?;
+    let auto_compression = match output_compression {
+        Some(_) => None,
+        None => Some(choose_compression(&args)?),
+    };
+    if let Some(choice) = auto_compression {
+        // The name of the output follows the compression chosen, so it is
+        // not read as the wrong format
+        let path = choice.compression.path_for(&args.output_file);
+        if path != args.output_file {
+            info!(
+                "Writing the output to {} to match its compression",
+                path.display()
+            );
+            args.output_file = path;
+        }
+    }
+    let compression = output_compression.or(auto_compression.map(|choice| choice.compression));
+    let mut run = runner(&args, run_id, compression)?;
+    let fingerprint = match early_fingerprint {
+        Some(fingerprint) => fingerprint,
+        None => run_fingerprint(&args, run.input_files().to_vec(), &options, seed)?,
+    };
+    info!("Fingerprint: {fingerprint}");
+    run = run.with_fingerprint(fingerprint);
+
+    // Stop at the next tick boundary on Ctrl-C, still writing the output for
+    // the ticks simulated so far
+    let token = Arc::new(AtomicBool::new(false));
+    let handler_token = token.clone();
+    if let Err(err) = ctrlc::set_handler(move || handler_token.store(true, Ordering::Relaxed)) {
+        warn!("Failed to install the Ctrl-C handler: {err}");
+    }
+
+    if let Some(path) = args.actions_log {
+        let settings = OutputSettings {
+            compression: Compression::for_path(&path)?,
+            path,
+        };
+        run = run.with_actions_log(if args.resume {
+            ActionsLog::resume(settings)?
+        } else {
+            ActionsLog::create(settings)?
+        });
+    }
+    if args.output_new_only {
+        run = run.with_new_history_only();
+    }
+    if let Some(ticks) = args.output_window {
+        run = run.with_output_window(ticks);
+    }
+    if let Some(every) = args.checkpoint_every {
+        let path = args
+            .checkpoint_file
+            .unwrap_or_else(|| PathBuf::from("checkpoint.json.zst"));
+        let settings = OutputSettings {
+            compression: Compression::for_path(&path)?,
+            path,
+        };
+        run = run.with_checkpoints(every, settings);
+    }
+    if let Some(path) = &args.resume_from {
+        let snapshot = load_snapshot_from_path(path)?;
+        info!(
+            "Resuming from {}, taken after day {}",
+            path.display(),
+            snapshot.time
+        );
+        run.restore(snapshot)?;
+    }
+    if args.event_ledger.is_some() {
+        run = run.with_event_ledger();
+    }
+    let mut influence_agents = None;
+    if let (Some(size), Some(path)) = (args.panel_size, args.panel_output) {
+        let spec = PanelSpec {
+            size,
+            seed: args.panel_seed.unwrap_or(run.seed()),
+        };
+        let settings = OutputSettings {
+            path,
+            compression: Compression::None,
+        };
+        if args.influence_panel_only {
+            influence_agents = Some(spec.sample(run.n_agents()));
+        }
+        run = run.with_panel(spec, settings);
+        if args.panel_names {
+            run = run.with_panel_names();
+        }
+    }
+    if let Some(path) = args.influence_output {
+        let settings = OutputSettings {
+            compression: if path.extension().is_some_and(|ext| ext == "zst") {
+                Compression::default()
+            } else {
+                Compression::None
+            },
+            path,
+        };
+        run = run.with_influence_log(InfluenceLog::create(&settings, influence_agents)?);
+    }
+
+    let mut outcome = run.run_with_cancel(&token)?;
+    outcome.auto_compression = auto_compression;
+    if let Some(path) = args.event_ledger {
+        let settings = OutputSettings {
+            path,
+            compression: Compression::None,
+        };
+        run.write_event_ledger(&settings)?;
+        outcome.artifacts.push(settings.path);
+    }
+    if let Some(path) = args.snapshot_file {
+        let settings = OutputSettings {
+            compression: Compression::for_path(&path)?,
+            path,
+        };
+        if args.snapshot_shards > 1 {
+            outcome
+                .artifacts
+                .extend(run.write_snapshot_shards(&settings, args.snapshot_shards.into())?);
+        } else {
+            run.write_snapshot(&settings)?;
+            outcome.artifacts.push(settings.path);
+        }
+        outcome.peak_rss_bytes = outcome
+            .peak_rss_bytes
+            .max(PeakRss::default().sample("after writing the snapshot"));
+    }
+    #[cfg(feature = "sqlite")]
+    if let (Some(path), Some(label)) = (args.append_to, args.run_label) {
+        ResultsDatabase::open(&path)?.append_run(&label, &run, &outcome)?;
+        outcome.artifacts.push(path);
+    }
+    if let Some(path) = metadata {
+        let json = serde_json::to_string_pretty(&outcome).expect("run outcomes serialize");
+        std::fs::write(&path, json + "\n").map_err(|source| ConceptError::Io { path, source })?;
+    }
+    Ok(outcome)
+}
+
+/// Run `--n-runs` replicates of the model, each from its own copy of the
+/// inputs, which are loaded once, with the seed plus its index.
+fn ensemble(mut args: Cli, run_id: String) -> Result<EnsembleOutcome, ConceptError> {
+    let base_seed = *args.seed.get_or_insert_with(rand::random);
+    expand_output_templates(&mut args, &run_id, None)?;
+    // Auto compression would probe every replicate, and they are alike
+    let compression = match args.output_compression()? {
+        Some(compression) => compression,
+        None => Compression::for_path(&args.output_file)?,
+    };
+    let behaviours = load_behaviours_from_path(&args.behaviours_file)?;
+    let beliefs = load_beliefs_from_path(&args.beliefs_file)?;
+    let agents = load_agents_from_path(&args.agents_file)?;
+    let prs = load_prs_from_path(&args.prs_file)?;
+    let ensemble = Ensemble {
+        base_seed,
+        n_runs: args.n_runs as usize,
+    };
+    let summary = match &args.ensemble_output {
+        Some(path) => Some(OutputSettings {
+            compression: Compression::for_path(path)?,
+            path: path.clone(),
+        }),
+        None => None,
+    };
+    let outcome = ensemble.run(
+        |run, seed| {
+            let builder = ConfigurationBuilder::new()
+                .with_behaviours(behaviours.clone())
+                .with_beliefs(beliefs.clone())
+                .with_agents(agents.clone())
+                .with_prs(prs.clone())
+                .run_id(format!("{run_id}-{run:03}"))
+                .seed(seed)
+                .output_settings(OutputSettings {
+                    path: run_output_path(&args.output_file, run),
+                    compression,
+                });
+            configured_runner(builder, &args)
+        },
+        summary.as_ref(),
+    )?;
+    if let Some(path) = &args.metadata {
+        let json = serde_json::to_string_pretty(&outcome).expect("ensemble outcomes serialize");
+        std::fs::write(path, json + "\n").map_err(|source| ConceptError::Io {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    Ok(outcome)
+}
+
+/// Expand the placeholders of the paths of the outputs of a single run,
+/// from its effective configuration, and check that no two outputs are
+/// written to the same path.
+fn expand_output_templates(
+    args: &mut Cli,
+    run_id: &str,
+    fingerprint: Option<&str>,
+) -> Result<(), ConceptError> {
+    let name = |mode: Option<clap::builder::PossibleValue>| {
+        mode.expect("every mode has a name").get_name().to_string()
+    };
+    let values = TemplateValues::new()
+        .with("run_id", run_id)
+        .with("seed", args.seed.expect("the seed was chosen"))
+        .with("start", args.start_time)
+        .with("end", args.end_time)
+        .with("prs_scale", args.prs_scale.unwrap_or(1.0))
+        .with("relationship_scale", args.relationship_scale.unwrap_or(1.0))
+        .with("temperature", args.temperature)
+        .with(
+            "action_selection",
+            name(args.action_selection.to_possible_value()),
+        )
+        .with("precision", name(args.precision.to_possible_value()))
+        .with(
+            "bounds_policy",
+            name(args.bounds_policy.to_possible_value()),
+        )
+        // Only found beforehand if a template has it
+        .with("fingerprint", fingerprint.unwrap_or_default());
+    for (_, path) in args.output_templates() {
+        *path = values.expand(path)?;
+    }
+
+    let mut outputs: Vec<(&str, PathBuf)> = args
+        .output_templates()
+        .into_iter()
+        .filter(|&(label, _)| label != "--network-snapshot-dir")
+        .map(|(label, path)| (label, path.clone()))
         .collect();
-    Ok(vec_prs_to_performance_relationships(
-        &prss,
-        &uuid_beliefs,
-        &uuid_behaviours,
-    ))
+    if let Some(path) = &args.panel_output {
+        outputs.push(("the panel's actions", panel::actions_path(path)));
+    }
+    check_collisions(outputs.iter().map(|(label, path)| (*label, path.as_path())))
+}
+
+/// The fingerprint of the configuration of the run, from the input files as
+/// they are now.
+fn print_fingerprint(args: &Cli) -> Result<String, ConceptError> {
+    let seed = args.seed.expect("clap requires a seed");
+    input_fingerprint(args, args, seed)
+}
+
+/// The fingerprint of a run with `options` and `seed`, reading the input
+/// files its [Configuration](concept::configuration::Configuration) would
+/// read.
+fn input_fingerprint(
+    args: &Cli,
+    options: &impl Serialize,
+    seed: u64,
+) -> Result<String, ConceptError> {
+    let builder = model_options(input_builder(args), args);
+    let inputs = builder
+        .input_paths()
+        .into_iter()
+        .map(|path| {
+            InputFile::read(path).map_err(|source| ConceptError::Io {
+                path: path.to_path_buf(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    run_fingerprint(args, inputs, options, seed)
+}
+
+/// The fingerprint of a run with `options` and `seed` from the input files
+/// of its [Configuration](concept::configuration::Configuration), followed
+/// by the snapshot it is resumed from, if it is.
+fn run_fingerprint(
+    args: &Cli,
+    mut inputs: Vec<InputFile>,
+    options: &impl Serialize,
+    seed: u64,
+) -> Result<String, ConceptError> {
+    if let Some(path) = &args.resume_from {
+        inputs.push(InputFile::read(path).map_err(|source| ConceptError::Io {
+            path: path.clone(),
+            source,
+        })?);
+    }
+    fingerprint(&inputs, options, seed).map_err(|err| ConceptError::Output { source: err.into() })
+}
+
+/// Simulate the first ticks of the run, and project the whole run from
+/// them.
+///
+/// Nothing is written: the output is measured in memory, with the
+/// compression of the output file (the default if it is chosen
+/// automatically), and the snapshot, panel, network snapshots and database
+/// of results are skipped.
+fn probe(args: Cli, run_id: String) -> Result<ProbeProjection, ConceptError> {
+    let ticks = args.probe.expect("probing was requested");
+    let mut run = runner(&args, run_id, None)?;
+    let compression = args.output_compression()?.unwrap_or_default();
+    let projection = ProbeProjection::run(&mut run, ticks, compression)?;
+    info!(
+        "Projected {:.1}s and {} bytes of output for {} ticks from {} ticks",
+        projection.projected_seconds,
+        projection.projected_output_bytes,
+        projection.total_ticks,
+        projection.probed_ticks
+    );
+    Ok(projection)
+}
+
+/// Run the scenario twice from copies of the same inputs with the same
+/// seed, and compare the runs.
+fn selfcheck(args: Cli, selfcheck: SelfcheckArgs) -> Result<SelfCheckReport, ConceptError> {
+    let scenario = &selfcheck.scenario;
+    let behaviours = load_behaviours_from_path(&scenario.join(&args.behaviours_file))?;
+    let beliefs = load_beliefs_from_path(&scenario.join(&args.beliefs_file))?;
+    let agents = load_agents_from_path(&scenario.join(&args.agents_file))?;
+    let prs = load_prs_from_path(&scenario.join(&selfcheck.prs_file))?;
+    let seed = selfcheck.seed.or(args.seed).unwrap_or_else(rand::random);
+    // The second run is simulated in parallel, so a difference between the
+    // serial and parallel paths is found
+    let parallel = match args.threads {
+        Some(threads) => threads.into(),
+        None => std::thread::available_parallelism().map_or(2, |n| n.get()),
+    };
+    let runner = |threads: usize| -> Result<Runner, ConceptError> {
+        let builder = ConfigurationBuilder::new()
+            .with_behaviours(behaviours.clone())
+            .with_beliefs(beliefs.clone())
+            .with_agents(agents.clone())
+            .with_prs(prs.clone())
+            .seed(seed)
+            .output(Box::new(Vec::new()));
+        let builder = input_options(model_options(builder, &args), &args);
+        Runner::new(builder.build()?)
+            .with_seed(seed)
+            .with_action_selection(args.action_selection())
+            .with_precision(args.precision.into())
+            .with_bounds_policy(args.bounds_policy.into())
+            .with_threads(threads)
+    };
+    let report = SelfCheckReport::run(&mut runner(1)?, &mut runner(parallel.max(2))?)?;
+    match &report.first_divergence {
+        None if report.passed => info!("Self-check passed"),
+        None => warn!("Self-check failed: the summaries differ"),
+        Some(divergence) => warn!("Self-check failed: first divergence {divergence:?}"),
+    }
+    Ok(report)
+}
+
+/// Write the example scenario, logging the command that runs it.
+fn example(example: ExampleArgs) -> Result<ExampleOutcome, ConceptError> {
+    let outcome = ExampleScenario::generate().write_to(&example.out)?;
+    info!("Run the example with: {}", outcome.run_command);
+    Ok(outcome)
+}
+
+/// Generate a population for the beliefs and behaviours, and write it to the
+/// output file.
+///
+/// The initial activations and actions are at the time origin, the tick
+/// before the start time unless it is given.
+fn generate(args: &Cli, generate: GenerateArgs) -> Result<PopulationOutcome, ConceptError> {
+    let beliefs = load_beliefs_from_path(&args.beliefs_file)?;
+    let behaviours = load_behaviours_from_path(&args.behaviours_file)?;
+    let settings = PopulationSettings {
+        n_agents: generate.n_agents,
+        network: match generate.network {
+            NetworkMode::ErdosRenyi => FriendNetwork::ErdosRenyi {
+                density: generate.density,
+            },
+        },
+        friend_weight: generate.friend_weight,
+        activations: generate.activation_dist,
+        deltas: generate.delta_dist,
+        time: args
+            .time_origin
+            .unwrap_or(args.start_time.saturating_sub(1)),
+        seed: generate.seed.or(args.seed).unwrap_or_else(rand::random),
+    };
+    info!("Generating {} agents", settings.n_agents);
+    let agents = settings.generate(&beliefs, &behaviours);
+    let friendships = agents.iter().map(|a| a.friends.len()).sum::<usize>() / 2;
+    info!(
+        "Writing {} agents with {friendships} friendships to {}",
+        agents.len(),
+        generate.output_file.display()
+    );
+    write_agents(&generate.output_file, &agents)?;
+    Ok(PopulationOutcome {
+        settings,
+        friendships,
+        artifacts: vec![generate.output_file],
+    })
+}
+
+/// Check the runs whose metadata is in a directory against the checksums of
+/// their input files.
+fn verify_outputs(verify: VerifyOutputsArgs) -> Result<VerificationReport, ConceptError> {
+    let report = VerificationReport::verify_dir(&verify.dir)?;
+    for run in &report.runs {
+        match run.status {
+            OutputStatus::Consistent => {}
+            OutputStatus::MissingInputs => warn!(
+                "Inputs of run {} not found, from {}",
+                run.run_id,
+                run.metadata.display()
+            ),
+            OutputStatus::Stale => warn!(
+                "Outputs of run {} are stale, from {}",
+                run.run_id,
+                run.metadata.display()
+            ),
+        }
+    }
+    info!(
+        "Checked {} runs: {} consistent, {} missing inputs, {} stale",
+        report.runs.len(),
+        report.consistent,
+        report.missing_inputs,
+        report.stale
+    );
+    Ok(report)
+}
+
+/// Run the base and alternative performance relationships from the same
+/// initial state with the same seed, and write the differences between
+/// them.
+///
+/// The behaviours, beliefs and agents are loaded once, and each variant
+/// starts from its own copy of them.
+fn compare(
+    args: Cli,
+    compare: CompareArgs,
+    run_id: &str,
+) -> Result<ComparisonOutcome, ConceptError> {
+    let behaviours = load_behaviours_from_path(&args.behaviours_file)?;
+    let beliefs = load_beliefs_from_path(&args.beliefs_file)?;
+    let agents = load_agents_from_path(&args.agents_file)?;
+    let seed = compare.seed.or(args.seed).unwrap_or_else(rand::random);
+    let runner = |prs_file: &Path,
+                  output: &Option<PathBuf>,
+                  variant: &str|
+     -> Result<Runner, ConceptError> {
+        let builder = ConfigurationBuilder::new()
+            .with_behaviours(behaviours.clone())
+            .with_beliefs(beliefs.clone())
+            .with_agents(agents.clone())
+            .prs_from_path(prs_file)
+            .seed(seed)
+            .run_id(format!("{run_id}-{variant}"));
+        let builder = match output {
+            Some(path) => builder.output_path(path),
+            None => builder.output(Box::new(Vec::new())),
+        };
+        let builder = input_options(model_options(builder, &args), &args);
+        Ok(Runner::new(builder.build()?)
+            .with_seed(seed)
+            .with_action_selection(args.action_selection())
+            .with_precision(args.precision.into())
+            .with_bounds_policy(args.bounds_policy.into())
+            .with_activation_threshold(args.activation_threshold))
+    };
+    let mut base = runner(&compare.base_prs_file, &compare.base_output_file, "base")?;
+    let mut alt = runner(&compare.alt_prs_file, &compare.alt_output_file, "alt")?;
+    let comparison = Comparison::run(&mut base, &mut alt)?;
+
+    let mut artifacts = Vec::new();
+    for (runner, output) in [
+        (&mut base, compare.base_output_file),
+        (&mut alt, compare.alt_output_file),
+    ] {
+        if let Some(path) = output {
+            runner.serialize_output()?;
+            artifacts.push(path);
+        }
+    }
+    let settings = OutputSettings {
+        path: compare.output_file,
+        compression: Compression::None,
+    };
+    let mut sink = settings.open().map_err(|source| ConceptError::Io {
+        path: settings.path.clone(),
+        source,
+    })?;
+    comparison
+        .to_writer(&mut sink)
+        .map_err(|err| ConceptError::Output { source: err.into() })?;
+    sink.finish()
+        .map_err(|source| ConceptError::Output { source })?;
+    artifacts.push(settings.path);
+    Ok(ComparisonOutcome {
+        run_id: run_id.to_string(),
+        seed,
+        last_tick: base.time(),
+        n_agents: base.agents().len(),
+        artifacts,
+    })
+}
+
+/// Calibrate a parameter against observed adoption, each run starting from
+/// its own copy of the inputs, which are loaded once.
+fn calibrate(
+    args: Cli,
+    calibrate: CalibrateArgs,
+    run_id: &str,
+) -> Result<CalibrationOutcome, ConceptError> {
+    let observed = ObservedAdoption::read(&calibrate.target)?;
+    let behaviours = load_behaviours_from_path(&args.behaviours_file)?;
+    let beliefs = load_beliefs_from_path(&args.beliefs_file)?;
+    let agents = load_agents_from_path(&args.agents_file)?;
+    let prs = load_prs_from_path(&args.prs_file)?;
+    let seed = calibrate.seed.or(args.seed).unwrap_or_else(rand::random);
+    let param = calibrate.param.into();
+    let runner = |value: f64, output: Option<&Path>| -> Result<Runner, ConceptError> {
+        let builder = ConfigurationBuilder::new()
+            .with_behaviours(behaviours.clone())
+            .with_beliefs(beliefs.clone())
+            .with_agents(agents.clone())
+            .with_prs(prs.clone())
+            .seed(seed)
+            .run_id(format!("{run_id}-{value}"));
+        let builder = match output {
+            Some(path) => builder.output_path(path),
+            None => builder.output(Box::new(Vec::new())),
+        };
+        // The parameter calibrated replaces any value given for it
+        let builder = input_options(model_options(builder, &args), &args);
+        let builder = match param {
+            CalibrationParam::PrsScale => builder.prs_scale(value),
+        };
+        Ok(Runner::new(builder.build()?)
+            .with_seed(seed)
+            .with_action_selection(args.action_selection())
+            .with_precision(args.precision.into())
+            .with_bounds_policy(args.bounds_policy.into())
+            .with_activation_threshold(args.activation_threshold))
+    };
+    let calibration = Calibration {
+        param,
+        behaviour: calibrate.behaviour,
+        range: calibrate.range,
+        tolerance: calibrate.tolerance,
+        loss: calibrate.loss.into(),
+    };
+    let mut outcome = calibration.run(&observed, seed, |value| runner(value, None))?;
+    if let Some(path) = calibrate.best_output_file {
+        let mut best = runner(outcome.best_value, Some(&path))?;
+        best.run()?;
+        outcome.artifacts.push(path);
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(dir: &Path, args: &[&str]) -> Cli {
+        let inputs = [
+            ("--behaviours", "behaviours.json"),
+            ("--beliefs", "beliefs.json"),
+            ("--agents", "agents.json"),
+            ("--performance-relationships", "prs.json"),
+        ]
+        .into_iter()
+        .flat_map(|(flag, file)| [flag.to_string(), dir.join(file).display().to_string()]);
+        Cli::try_parse_from(
+            ["concept".to_string()]
+                .into_iter()
+                .chain(args.iter().map(|arg| arg.to_string()))
+                .chain(inputs),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fingerprints_depend_on_the_configuration_but_not_the_order_of_flags() {
+        let dir = std::env::temp_dir().join(format!("concept-fingerprint-{}", Uuid::new_v4()));
+        ExampleScenario::generate().write_to(&dir).unwrap();
+        let fingerprint = |args: &[&str]| print_fingerprint(&parse(&dir, args)).unwrap();
+
+        let expected = fingerprint(&[
+            "--seed",
+            "3",
+            "-e",
+            "5",
+            "--network-snapshots",
+            "2,4",
+            "--agent-filter",
+            "region=north",
+            "--agent-filter",
+            "age=30",
+            "--print-fingerprint",
+        ]);
+        assert_eq!(
+            fingerprint(&[
+                "--print-fingerprint",
+                "--agent-filter",
+                "age=30",
+                "--network-snapshots",
+                "4,2",
+                "-e",
+                "5",
+                "--agent-filter",
+                "region=north",
+                "--seed",
+                "3",
+                "-vv",
+                "--run-id",
+                "another",
+            ]),
+            expected
+        );
+        // Where the outputs are written does not change the run
+        let outputs = fingerprint(&["--seed", "3", "-o", "out2.json"]);
+        assert_eq!(fingerprint(&["--seed", "3", "-o", "out3.json"]), outputs);
+        assert_eq!(
+            fingerprint(&[
+                "--seed",
+                "3",
+                "-o",
+                "out3.json",
+                "--metadata",
+                "meta.json",
+                "--summary-window",
+                "7",
+                "--snapshot",
+                "snapshot.json",
+            ]),
+            outputs
+        );
+        assert_ne!(fingerprint(&["--seed", "4", "-e", "5"]), expected);
+        assert_ne!(fingerprint(&["--seed", "3", "-e", "6"]), expected);
+
+        let base = fingerprint(&["--seed", "3"]);
+        let beliefs = dir.join("beliefs.json");
+        let mut json = std::fs::read(&beliefs).unwrap();
+        let last = json.len() - 1;
+        json[last] = if json[last] == b' ' { b'\n' } else { b' ' };
+        std::fs::write(&beliefs, json).unwrap();
+        assert_ne!(fingerprint(&["--seed", "3"]), base);
+
+        // The contents of the interventions are covered, not their path
+        let interventions = dir.join("interventions.json");
+        let belief = ExampleScenario::generate().beliefs[0].uuid;
+        let write_interventions = |value: f64| {
+            let json = format!(
+                r#"[{{"time": 1, "target": "all", "beliefUuid": "{belief}", "kind": "delta", "value": {value}}}]"#
+            );
+            std::fs::write(&interventions, json).unwrap();
+        };
+        let path = interventions.display().to_string();
+        write_interventions(0.5);
+        let before = fingerprint(&["--seed", "3", "--interventions", &path]);
+        assert_ne!(before, base);
+        write_interventions(0.75);
+        assert_ne!(
+            fingerprint(&["--seed", "3", "--interventions", &path]),
+            before
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn printed_fingerprints_are_those_written_with_the_output() {
+        let dir = std::env::temp_dir().join(format!("concept-fingerprint-{}", Uuid::new_v4()));
+        ExampleScenario::generate().write_to(&dir).unwrap();
+        let belief = ExampleScenario::generate().beliefs[0].uuid;
+        let interventions = dir.join("interventions.json");
+        std::fs::write(
+            &interventions,
+            format!(
+                r#"[{{"time": 2, "target": "all", "beliefUuid": "{belief}", "kind": "delta", "value": 1.5}}]"#
+            ),
+        )
+        .unwrap();
+        let output = dir.join("output.json");
+        let args = [
+            "--seed",
+            "3",
+            "-e",
+            "2",
+            "--interventions",
+            interventions.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        let printed = print_fingerprint(&parse(&dir, &args)).unwrap();
+        run(parse(&dir, &args), "r1".to_string()).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+        assert_eq!(written["fingerprint"], printed.as_str());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn output_templates_are_expanded_and_must_not_collide() {
+        let dir = Path::new("inputs");
+        let mut args = parse(
+            dir,
+            &[
+                "--seed",
+                "7",
+                "-o",
+                "out/{run_id}_{seed}.json",
+                "--prs-scale",
+                "0.5",
+            ],
+        );
+        args.snapshot_file = Some(PathBuf::from("out/{prs_scale}_{{s}}.json"));
+        expand_output_templates(&mut args, "r1", None).unwrap();
+        assert_eq!(args.output_file, PathBuf::from("out/r1_7.json"));
+        assert_eq!(args.snapshot_file, Some(PathBuf::from("out/0.5_{s}.json")));
+
+        let mut args = parse(dir, &["--seed", "7", "-o", "{seed}.json"]);
+        args.panel_output = Some(PathBuf::from("7.json"));
+        assert!(matches!(
+            expand_output_templates(&mut args, "r1", None),
+            Err(ConceptError::PathCollision { first, .. }) if first == "--output"
+        ));
+    }
 }
@@ -0,0 +1,98 @@
+//! Sampling the memory used by the process.
+
+use log::info;
+
+/// The resident set size of the process in bytes, read from
+/// `/proc/self/status`.
+///
+/// # Returns
+/// The resident set size, or [None] if it cannot be read, as on platforms
+/// other than Linux.
+pub fn resident_set_size() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| parse_vm_rss(&status))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Parse the `VmRSS` line of `/proc/self/status`, which is in kB.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// The largest resident set size sampled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PeakRss(Option<u64>);
+
+impl PeakRss {
+    /// Sample the [resident_set_size], logging it after `phase`.
+    ///
+    /// # Returns
+    /// The resident set size, or [None] if it cannot be read.
+    pub fn sample(&mut self, phase: &str) -> Option<u64> {
+        let rss = resident_set_size();
+        if let Some(bytes) = rss {
+            info!("RSS {phase}: {:.1} MiB", bytes as f64 / (1024.0 * 1024.0));
+        }
+        self.0 = self.0.max(rss);
+        rss
+    }
+
+    /// The largest resident set size sampled in bytes, or [None] if none
+    /// could be read.
+    pub fn peak(&self) -> Option<u64> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn resident_set_size_is_plausible_on_linux() {
+        let rss = resident_set_size().unwrap();
+        assert!(rss > 1024 * 1024, "{rss}");
+        assert!(rss < 1 << 40, "{rss}");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn resident_set_size_is_none_elsewhere() {
+        assert_eq!(resident_set_size(), None);
+    }
+
+    #[test]
+    fn parse_vm_rss_reads_kb() {
+        let status = "Name:\tconcept\nVmPeak:\t  20000 kB\nVmRSS:\t    1234 kB\n";
+        assert_eq!(parse_vm_rss(status), Some(1234 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tconcept\n"), None);
+    }
+
+    #[test]
+    fn peak_rss_keeps_the_largest_sample() {
+        let mut peak = PeakRss::default();
+        let rss = peak.sample("in a test");
+        assert_eq!(peak.peak(), rss);
+
+        let mut peak = PeakRss(Some(u64::MAX));
+        peak.sample("in a test");
+        assert_eq!(peak.peak(), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,146 @@
+//! Snapshots of the friendship network at chosen ticks.
+//!
+//! Each snapshot is an edge list of every friendship as it stood at the end
+//! of a tick, written as `source,target,weight` CSV rows ordered by the
+//! [Uuid] of the source, then of the target.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use belief_spread::{AgentPtr, SimTime};
+use uuid::Uuid;
+
+use crate::sink::{Compression, OutputSettings};
+
+/// The ticks at which the friendship network is written, and where to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkSnapshots {
+    /// The ticks, from the time origin to the end time.
+    pub ticks: BTreeSet<SimTime>,
+    /// The directory the snapshots are written to.
+    pub directory: PathBuf,
+    /// The compression applied to each snapshot.
+    pub compression: Compression,
+}
+
+impl NetworkSnapshots {
+    /// Snapshots at `ticks` written to `directory` with the default
+    /// [Compression].
+    pub fn new(ticks: impl IntoIterator<Item = SimTime>, directory: impl Into<PathBuf>) -> Self {
+        NetworkSnapshots {
+            ticks: ticks.into_iter().collect(),
+            directory: directory.into(),
+            compression: Compression::default(),
+        }
+    }
+
+    /// The settings of the snapshot at `time`, written to
+    /// `network_t<time>.csv`, with a `.zst` extension if compressed.
+    pub fn settings(&self, time: SimTime) -> OutputSettings {
+        let extension = match self.compression {
+            Compression::None => "csv",
+            Compression::Zstd { .. } => "csv.zst",
+        };
+        OutputSettings {
+            path: self.directory.join(format!("network_t{time}.{extension}")),
+            compression: self.compression,
+        }
+    }
+
+    /// Remove the ticks up to and including `time` that have not been
+    /// written yet.
+    ///
+    /// # Returns
+    /// The ticks removed, in ascending order.
+    pub(crate) fn take_due(&mut self, time: SimTime) -> Vec<SimTime> {
+        let later = match time.checked_add(1) {
+            Some(next) => self.ticks.split_off(&next),
+            None => BTreeSet::new(),
+        };
+        std::mem::replace(&mut self.ticks, later)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Write every friendship of `agents` as `source,target,weight` rows,
+/// ordered by the [Uuid] of the source, then of the target.
+///
+/// Each [Agent]'s friendships are written as they are read, so only one
+/// [Agent]'s are held at a time.
+pub fn write_edges<W: Write>(mut writer: W, agents: &[AgentPtr]) -> io::Result<()> {
+    writeln!(writer, "source,target,weight")?;
+    let mut sources: Vec<(Uuid, &AgentPtr)> = agents
+        .iter()
+        .map(|agent| (*agent.borrow().uuid(), agent))
+        .collect();
+    sources.sort_unstable_by_key(|&(uuid, _)| uuid);
+    for (source, agent) in sources {
+        let mut friends: Vec<(Uuid, f64)> = agent
+            .borrow()
+            .get_friends()
+            .iter()
+            .map(|(friend, &weight)| (*friend.borrow().uuid(), weight))
+            .collect();
+        friends.sort_unstable_by_key(|&(uuid, _)| uuid);
+        for (target, weight) in friends {
+            writeln!(writer, "{source},{target},{weight}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configuration::tests::{hub_builder, temp_path},
+        runner::Runner,
+    };
+
+    #[test]
+    fn edges_are_ordered_by_source_then_target() {
+        let config = hub_builder().build().unwrap();
+        let mut agents = config.agents.clone();
+        agents.reverse();
+        let mut csv = Vec::new();
+        write_edges(&mut csv, &agents).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        let uuid = |i: u128| Uuid::from_u128(0x300 + i);
+        assert_eq!(rows[0], "source,target,weight");
+        let hub: Vec<String> = [(1, 0.1), (2, 0.4), (3, 0.4), (4, 0.2)]
+            .iter()
+            .map(|&(i, weight)| format!("{},{},{weight}", uuid(0), uuid(i)))
+            .collect();
+        assert_eq!(rows[1..5], hub);
+        for (i, row) in (1..5).zip(&rows[5..]) {
+            assert!(row.starts_with(&format!("{},{},", uuid(i), uuid(0))));
+        }
+        assert_eq!(rows.len(), 9);
+    }
+
+    #[test]
+    fn snapshots_are_written_at_the_end_of_each_tick() {
+        let directory = temp_path("");
+        std::fs::create_dir(&directory).unwrap();
+        let mut snapshots = NetworkSnapshots::new([0, 2, 3], &directory);
+        snapshots.compression = Compression::None;
+        let config = hub_builder().network_snapshots(snapshots.clone()).build();
+        let mut runner = Runner::new(config.unwrap()).with_seed(5);
+        let outcome = runner.run().unwrap();
+
+        let mut expected = Vec::new();
+        write_edges(&mut expected, &hub_builder().build().unwrap().agents).unwrap();
+        for time in [0, 2, 3] {
+            let path = snapshots.settings(time).path;
+            assert!(outcome.artifacts.contains(&path));
+            assert_eq!(std::fs::read(&path).unwrap(), expected);
+        }
+        assert!(!snapshots.settings(1).path.exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Exporting the trajectories of a sampled panel of [Agent]s.
+//!
+//! A panel is a few [Agent]s sampled without replacement, whose every
+//! activation and action is written in long format CSV, for plotting their
+//! trajectories without reading the whole output.
+//!
+//! The name of each [Belief] and [Behaviour] may be written beside its
+//! UUID, quoted as CSV requires, but the UUID remains the key.
+
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use belief_spread::{AgentPtr, BehaviourPtr, BeliefPtr, SimTime};
+use rand::{seq::index, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::{collections::ModelIndex, json::ModelNames, sampling::OutputSampling};
+
+/// How the [Agent]s of a panel are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PanelSpec {
+    /// The number of [Agent]s sampled, or every [Agent] if there are fewer.
+    pub size: usize,
+    /// The seed of the random number generator the [Agent]s are sampled
+    /// with.
+    pub seed: u64,
+}
+
+impl PanelSpec {
+    /// Sample the positions of the [Agent]s of the panel, without
+    /// replacement, from `n_agents`.
+    ///
+    /// # Returns
+    /// The positions, in ascending order. The same seed, size and number of
+    /// [Agent]s always give the same positions.
+    pub fn sample(&self, n_agents: usize) -> Vec<usize> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut positions = index::sample(&mut rng, n_agents, self.size.min(n_agents)).into_vec();
+        positions.sort_unstable();
+        positions
+    }
+}
+
+/// The path the actions of a panel are written to, beside the activations at
+/// `path`, so `panel.csv` gives `panel_actions.csv`.
+pub fn actions_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(match name.split_once('.') {
+        Some((stem, extensions)) => format!("{stem}_actions.{extensions}"),
+        None => format!("{name}_actions"),
+    })
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break, doubling
+/// any quotes in it.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Write every activation of the [Agent]s at `positions` at the times
+/// included by `sampling` as `agent_uuid,time,belief_uuid,value` rows,
+/// ordered by [Agent], then by time, then by [Belief] in the canonical
+/// order.
+///
+/// If `names` are given, the name of the [Belief] is written in a
+/// `belief_name` column after its UUID.
+pub fn write_activations<W: Write>(
+    mut writer: W,
+    agents: &[AgentPtr],
+    beliefs: &[BeliefPtr],
+    index: &ModelIndex,
+    positions: &[usize],
+    sampling: &OutputSampling,
+    names: Option<&ModelNames>,
+) -> io::Result<()> {
+    match names {
+        Some(_) => writeln!(writer, "agent_uuid,time,belief_uuid,belief_name,value")?,
+        None => writeln!(writer, "agent_uuid,time,belief_uuid,value")?,
+    }
+    for &i in positions {
+        let agent = agents[i].borrow();
+        let mut times: Vec<SimTime> = agent
+            .get_activations()
+            .keys()
+            .copied()
+            .filter(|&time| sampling.includes(time))
+            .collect();
+        times.sort_unstable();
+        for time in times {
+            for &j in index.canonical_beliefs() {
+                if let Some(value) = agent.get_activation(time, &beliefs[j]) {
+                    let belief = index.belief_uuids()[j];
+                    write!(writer, "{},{time},{belief},", agent.uuid())?;
+                    if let Some(names) = names {
+                        let name = names.beliefs.get(&belief).map_or("", String::as_str);
+                        write!(writer, "{},", csv_field(name))?;
+                    }
+                    writeln!(writer, "{value}")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write every action of the [Agent]s at `positions` at the times included
+/// by `sampling` as `agent_uuid,time,behaviour_uuid` rows, ordered by
+/// [Agent], then by time.
+///
+/// If `names` are given, the name of the [Behaviour] is written in a
+/// `behaviour_name` column after its UUID.
+pub fn write_actions<W: Write>(
+    mut writer: W,
+    agents: &[AgentPtr],
+    positions: &[usize],
+    sampling: &OutputSampling,
+    names: Option<&ModelNames>,
+) -> io::Result<()> {
+    match names {
+        Some(_) => writeln!(writer, "agent_uuid,time,behaviour_uuid,behaviour_name")?,
+        None => writeln!(writer, "agent_uuid,time,behaviour_uuid")?,
+    }
+    for &i in positions {
+        let agent = agents[i].borrow();
+        let mut actions: Vec<(SimTime, &BehaviourPtr)> = agent
+            .get_actions()
+            .iter()
+            .filter(|(&time, _)| sampling.includes(time))
+            .map(|(&time, behaviour)| (time, behaviour))
+            .collect();
+        actions.sort_unstable_by_key(|&(time, _)| time);
+        for (time, behaviour) in actions {
+            let behaviour = *behaviour.borrow().uuid();
+            write!(writer, "{},{time},{behaviour}", agent.uuid())?;
+            if let Some(names) = names {
+                let name = names.behaviours.get(&behaviour).map_or("", String::as_str);
+                write!(writer, ",{}", csv_field(name))?;
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_is_stable_and_without_replacement() {
+        let spec = PanelSpec { size: 10, seed: 7 };
+        let positions = spec.sample(100);
+        assert_eq!(positions, spec.sample(100));
+        assert_eq!(positions.len(), 10);
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(positions.iter().all(|&i| i < 100));
+        assert_ne!(positions, PanelSpec { size: 10, seed: 8 }.sample(100));
+
+        assert_eq!(spec.sample(4), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn names_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("Vaccination"), "Vaccination");
+        assert_eq!(csv_field("Masks, indoors"), "\"Masks, indoors\"");
+        assert_eq!(
+            csv_field("The \"new\" normal"),
+            "\"The \"\"new\"\" normal\""
+        );
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn actions_path_is_beside_the_panel() {
+        assert_eq!(
+            actions_path(Path::new("out/panel.csv")),
+            PathBuf::from("out/panel_actions.csv")
+        );
+        assert_eq!(
+            actions_path(Path::new("panel")),
+            PathBuf::from("panel_actions")
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Perceiving beliefs across many threads at once.
+//!
+//! The [Agent]s cannot be shared between threads, so what perception reads
+//! is copied out of them on the calling thread: the friends of every
+//! [Agent] and the perceptions and relationships of the [Belief]s once, in a
+//! [PerceptionNetwork], and at each tick the actions of every [Agent] and
+//! the activations and deltas of a chunk of [Agent]s at a time, in a
+//! [PerceptionInputs]. The new activations of the chunk are then computed in
+//! parallel, each only from the previous tick, and set on the [Agent]s back
+//! on the calling thread.
+//!
+//! Every activation is computed with the same operations in the same order
+//! as [update_activations](crate::bounds::update_activations), so the
+//! results are identical to it, whatever the number of threads.
+
+use belief_spread::{errors::UpdateActivationError, AgentPtr, BehaviourPtr, BeliefPtr, SimTime};
+use rayon::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    collections::{ModelIndex, UuidMap},
+    configuration::Configuration,
+    error::ConceptError,
+    influence::Influence,
+    interventions::CarriedActivations,
+};
+
+/// What perception reads that does not change during a run, by position.
+#[derive(Debug, Clone)]
+pub(crate) struct PerceptionNetwork {
+    /// The UUIDs of the [Agent]s.
+    agents: Vec<Uuid>,
+    /// The UUIDs of the [Belief]s.
+    beliefs: Vec<Uuid>,
+    /// The number of [Behaviour]s.
+    n_behaviours: usize,
+    /// The position of each [Behaviour] in order of UUID.
+    behaviour_ranks: Vec<usize>,
+    /// The perception of each [Behaviour] by each [Belief], one row of
+    /// [Behaviour]s per [Belief].
+    perceptions: Vec<Option<f64>>,
+    /// The relationship of each [Belief] to each [Belief], one row per
+    /// [Belief] related from.
+    relationships: Vec<Option<f64>>,
+    /// The position and weight of the friends of each [Agent], in order of
+    /// the UUIDs of the friends.
+    friends: Vec<Vec<(usize, f64)>>,
+}
+
+impl PerceptionNetwork {
+    /// Copy what perception reads out of the [Agent]s and [Belief]s of a
+    /// [Configuration].
+    ///
+    /// # Returns
+    /// The network, or [None] if an [Agent] has a friend that is not
+    /// simulated, whose actions are only known to the [Agent]s themselves.
+    pub(crate) fn new(config: &Configuration) -> Option<Self> {
+        let index = config.index();
+        let agents: Vec<Uuid> = config.agents.iter().map(|a| *a.borrow().uuid()).collect();
+        let positions: UuidMap<usize> = agents.iter().enumerate().map(|(i, &u)| (u, i)).collect();
+        let friends = config
+            .agents
+            .iter()
+            .map(|agent| {
+                let mut friends = agent
+                    .borrow()
+                    .get_friends()
+                    .iter()
+                    .map(|(friend, &w)| {
+                        let uuid = *friend.borrow().uuid();
+                        Some((uuid, *positions.get(&uuid)?, w))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                friends.sort_unstable_by_key(|&(uuid, _, _)| uuid);
+                Some(friends.into_iter().map(|(_, i, w)| (i, w)).collect())
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut behaviour_ranks = vec![0; config.behaviours.len()];
+        for (rank, &j) in index.canonical_behaviours().iter().enumerate() {
+            behaviour_ranks[j] = rank;
+        }
+        let beliefs = &config.beliefs;
+        let perceptions = beliefs
+            .iter()
+            .flat_map(|belief| {
+                let belief = belief.borrow();
+                config
+                    .behaviours
+                    .iter()
+                    .map(move |behaviour| belief.get_perception(behaviour))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let relationships = beliefs
+            .iter()
+            .flat_map(|b1| {
+                let b1 = b1.borrow();
+                beliefs
+                    .iter()
+                    .map(move |b2| b1.get_relationship(b2))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Some(Self {
+            agents,
+            beliefs: index.belief_uuids().to_vec(),
+            n_behaviours: config.behaviours.len(),
+            behaviour_ranks,
+            perceptions,
+            relationships,
+            friends,
+        })
+    }
+
+    /// Compute the new activation of every [Belief] of the [Agent]s of
+    /// `inputs`, before they are brought within [-1, 1], in parallel.
+    ///
+    /// # Returns
+    /// The activations, one row of [Belief]s per [Agent], or the error of
+    /// an [Agent] without a delta or previous activation.
+    pub(crate) fn perceive_chunk(
+        &self,
+        inputs: &PerceptionInputs,
+        values: &mut Vec<f64>,
+    ) -> Result<(), ConceptError> {
+        let n_beliefs = self.beliefs.len();
+        values.clear();
+        values.resize(inputs.len() * n_beliefs, 0.0);
+        values
+            .par_chunks_mut(n_beliefs.max(1))
+            .enumerate()
+            .try_for_each(|(k, row)| {
+                let mut values = row.iter_mut();
+                self.perceive(inputs, k, |influence| {
+                    *values.next().expect("a value per belief") = influence.own + influence.friends
+                })
+            })
+    }
+
+    /// Compute the [Influence] on the activation of each [Belief] of the
+    /// `k`th [Agent] of `inputs`, in order, calling `f` with each, as
+    /// [update_activations](crate::bounds::update_activations) does.
+    pub(crate) fn perceive(
+        &self,
+        inputs: &PerceptionInputs,
+        k: usize,
+        mut f: impl FnMut(Influence),
+    ) -> Result<(), ConceptError> {
+        let i = inputs.first + k;
+        let previous = inputs.time - 1;
+        let n_beliefs = self.beliefs.len();
+        let simulation_error = |source| ConceptError::Simulation {
+            agent: self.agents[i],
+            time: inputs.time,
+            source,
+        };
+
+        // The summed weights of the friends that performed each behaviour,
+        // summed in order of the friends and then ordered by behaviour
+        let mut actions: Vec<(usize, f64)> = Vec::new();
+        for &(friend, w) in &self.friends[i] {
+            let Some(behaviour) = inputs.actions[friend] else {
+                continue;
+            };
+            match actions.iter_mut().find(|(b, _)| *b == behaviour) {
+                Some((_, total)) => *total += w,
+                None => actions.push((behaviour, w)),
+            }
+        }
+        actions.sort_unstable_by_key(|&(b, _)| self.behaviour_ranks[b]);
+
+        let activations = inputs.row(&inputs.activations, k, n_beliefs);
+        let deltas = inputs.row(&inputs.deltas, k, n_beliefs);
+        for b in 0..n_beliefs {
+            let delta = deltas[b].ok_or_else(|| {
+                simulation_error(UpdateActivationError::GetDeltaNone {
+                    belief: self.beliefs[b],
+                })
+            })?;
+            let activation = activations[b].ok_or_else(|| {
+                simulation_error(UpdateActivationError::GetActivationNone {
+                    time: previous,
+                    belief: self.beliefs[b],
+                })
+            })?;
+            f(Influence {
+                own: delta * activation,
+                friends: self.activation_change(i, b, activations, &actions),
+            });
+        }
+        Ok(())
+    }
+
+    /// The change in the activation of the [Agent] at position `i` for the
+    /// [Belief] at position `b` from the actions of its friends, as the
+    /// [bounds](crate::bounds) of the serial path compute it.
+    fn activation_change(
+        &self,
+        i: usize,
+        b: usize,
+        activations: &[Option<f64>],
+        actions: &[(usize, f64)],
+    ) -> f64 {
+        let n_beliefs = self.beliefs.len();
+        let pressure = match self.friends[i].len() {
+            0 => 0.0,
+            n => {
+                let perceptions = &self.perceptions[b * self.n_behaviours..][..self.n_behaviours];
+                actions
+                    .iter()
+                    .filter_map(|&(behaviour, w)| perceptions[behaviour].map(|v| w * v))
+                    .sum::<f64>()
+                    / n as f64
+            }
+        };
+        // As contextualise, the previous activation of the belief times its
+        // relationship to each belief
+        let relationships = &self.relationships[b * n_beliefs..][..n_beliefs];
+        let context = match n_beliefs {
+            0 => 0.0,
+            size => {
+                relationships
+                    .iter()
+                    .filter_map(|&r| activations[b].and_then(|x| r.map(|y| x * y)))
+                    .fold(0.0, |acc, v| acc + v)
+                    / (size as f64)
+            }
+        };
+        if pressure > 0.0 {
+            (1.0 + context) / 2.0 * pressure
+        } else {
+            (1.0 - context) / 2.0 * pressure
+        }
+    }
+}
+
+/// What perception reads at a tick, copied out of the [Agent]s: the
+/// previous actions of every [Agent], and the previous activations and the
+/// deltas of a chunk of them.
+#[derive(Debug, Default)]
+pub(crate) struct PerceptionInputs {
+    /// The tick being simulated.
+    time: SimTime,
+    /// The position of the first [Agent] of the chunk.
+    first: usize,
+    /// The number of [Agent]s in the chunk.
+    len: usize,
+    /// The position of the [Behaviour] each [Agent] performed at the
+    /// previous tick, if any.
+    actions: Vec<Option<usize>>,
+    /// The activations of the chunk at the previous tick, one row of
+    /// [Belief]s per [Agent].
+    activations: Vec<Option<f64>>,
+    /// The deltas of the chunk, one row of [Belief]s per [Agent].
+    deltas: Vec<Option<f64>>,
+}
+
+impl PerceptionInputs {
+    /// Copy the actions of every [Agent] at the tick before `time`.
+    pub(crate) fn start(&mut self, time: SimTime, agents: &[AgentPtr], index: &ModelIndex) {
+        self.time = time;
+        self.actions.clear();
+        self.actions.extend(agents.iter().map(|agent| {
+            let action = agent.borrow().get_action(time - 1)?.clone();
+            let uuid = *action.borrow().uuid();
+            index.behaviour(&uuid)
+        }));
+    }
+
+    /// Copy the activations carried into the time, those at the tick before
+    /// unless they are `carried`, and the deltas of the `chunk` of [Agent]s
+    /// starting at position `first`.
+    pub(crate) fn load_chunk(
+        &mut self,
+        first: usize,
+        chunk: &[AgentPtr],
+        beliefs: &[BeliefPtr],
+        carried: &CarriedActivations,
+    ) {
+        self.first = first;
+        self.len = chunk.len();
+        self.activations.clear();
+        self.deltas.clear();
+        for (k, agent) in chunk.iter().enumerate() {
+            let agent = agent.borrow();
+            for (b, belief) in beliefs.iter().enumerate() {
+                self.activations.push(
+                    carried
+                        .get(first + k, b)
+                        .or_else(|| agent.get_activation(self.time - 1, belief)),
+                );
+                self.deltas.push(agent.get_delta(belief));
+            }
+        }
+    }
+
+    /// The number of [Agent]s in the chunk.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The row of `values` of the `k`th [Agent] of the chunk.
+    fn row<'a>(&self, values: &'a [Option<f64>], k: usize, n_beliefs: usize) -> &'a [Option<f64>] {
+        &values[k * n_beliefs..(k + 1) * n_beliefs]
+    }
+}
+
+/// The summed weights of the friends of an [Agent] that performed each
+/// [Behaviour] at `time`, in order of the UUIDs of the [Behaviour]s.
+///
+/// The friends are summed in order of their UUIDs, unlike by
+/// [get_actions_of_friends](belief_spread::Agent::get_actions_of_friends),
+/// whose order differs between runs, so that the sums do not differ in
+/// their last bits and runs with the same seed are identical.
+pub(crate) fn actions_of_friends(
+    agent: &AgentPtr,
+    time: SimTime,
+) -> Vec<(Uuid, BehaviourPtr, f64)> {
+    let agent = agent.borrow();
+    let mut friends: Vec<(Uuid, BehaviourPtr, f64)> = agent
+        .get_friends()
+        .iter()
+        .filter_map(|(friend, &w)| {
+            let friend = friend.borrow();
+            let action = friend.get_action(time)?.clone();
+            Some((*friend.uuid(), action, w))
+        })
+        .collect();
+    friends.sort_unstable_by_key(|&(uuid, _, _)| uuid);
+
+    let mut actions: Vec<(Uuid, BehaviourPtr, f64)> = Vec::new();
+    for (_, behaviour, w) in friends {
+        let uuid = *behaviour.borrow().uuid();
+        match actions.iter_mut().find(|(b, _, _)| *b == uuid) {
+            Some((_, _, total)) => *total += w,
+            None => actions.push((uuid, behaviour, w)),
+        }
+    }
+    actions.sort_unstable_by_key(|&(uuid, _, _)| uuid);
+    actions
+}
+
+/// The change in the activation of an [Agent] for a [Belief] from the
+/// actions of its friends, given the `activation` of the [Belief] it carries
+/// into the tick, as
+/// [activation_change](belief_spread::Agent::activation_change) computes it
+/// but summed in the order of the `actions_of_friends`.
+pub(crate) fn activation_change(
+    agent: &AgentPtr,
+    activation: f64,
+    belief: &BeliefPtr,
+    beliefs: &[BeliefPtr],
+    actions_of_friends: &[(Uuid, BehaviourPtr, f64)],
+) -> f64 {
+    let agent = agent.borrow();
+    let pressure = match agent.get_friends().len() {
+        0 => 0.0,
+        n => {
+            let belief = belief.borrow();
+            actions_of_friends
+                .iter()
+                .filter_map(|(_, behaviour, w)| belief.get_perception(behaviour).map(|v| w * v))
+                .sum::<f64>()
+                / n as f64
+        }
+    };
+    // As contextualise, the activation of the belief times its relationship
+    // to each belief
+    let context = match beliefs.len() {
+        0 => 0.0,
+        size => {
+            let belief = belief.borrow();
+            beliefs
+                .iter()
+                .filter_map(|b2| belief.get_relationship(b2).map(|y| activation * y))
+                .fold(0.0, |acc, v| acc + v)
+                / (size as f64)
+        }
+    };
+    if pressure > 0.0 {
+        (1.0 + context) / 2.0 * pressure
+    } else {
+        (1.0 - context) / 2.0 * pressure
+    }
+}
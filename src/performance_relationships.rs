@@ -1,12 +1,230 @@
 use belief_spread::{BehaviourPtr, BeliefPtr};
-use std::collections::HashMap;
+use log::info;
+use serde::Serialize;
+use std::{collections::HashMap, str::FromStr};
 use uuid::Uuid;
 
-use crate::json::PerformanceRelationshipSpec;
+use crate::{
+    collections::{ModelIndex, UuidMap},
+    error::{ValidationIssue, ValidationReport},
+    json::PerformanceRelationshipSpec,
+};
 
 /// The value is how much someone holding the [Belief] would like to perform
 /// the [Behaviour].
-pub type PerformanceRelationships = HashMap<(BeliefPtr, BehaviourPtr), f64>;
+///
+/// The key is the pair of ([Belief] [Uuid], [Behaviour] [Uuid]).
+pub type PerformanceRelationships = HashMap<(Uuid, Uuid), f64>;
+
+/// [PerformanceRelationships] stored densely, indexed by the positions of the
+/// [Belief]s and [Behaviour]s in the model.
+///
+/// Missing performance relationships are stored as zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrsMatrix {
+    /// The values, with the row of each [Behaviour] holding the value for
+    /// each [Belief], so a [Behaviour]'s values are contiguous.
+    values: Vec<f64>,
+    /// The positions of the [Belief]s and [Behaviour]s.
+    index: ModelIndex,
+}
+
+impl PrsMatrix {
+    /// Create a [PrsMatrix] from [PerformanceRelationships].
+    ///
+    /// # Arguments
+    /// - `prs`: The [PerformanceRelationships].
+    /// - `beliefs`: The [Belief]s, in the order they are indexed.
+    /// - `behaviours`: The [Behaviour]s, in the order they are indexed.
+    ///
+    /// # Returns
+    /// The [PrsMatrix].
+    pub fn new(
+        prs: &PerformanceRelationships,
+        beliefs: &[BeliefPtr],
+        behaviours: &[BehaviourPtr],
+    ) -> Self {
+        Self::from_values(
+            prs.iter()
+                .map(|(&(belief, behaviour), &v)| (belief, behaviour, v)),
+            ModelIndex::from_model(beliefs, behaviours),
+        )
+    }
+
+    /// Create a [PrsMatrix] directly from [PerformanceRelationshipSpec]s,
+    /// indexed by a [ModelIndex].
+    ///
+    /// Specs referencing a [Belief] or [Behaviour] that is not in the index
+    /// are ignored. If there are several specs for a pair, the last is used.
+    pub fn from_specs(specs: &[PerformanceRelationshipSpec], index: ModelIndex) -> Self {
+        Self::from_values(
+            specs
+                .iter()
+                .map(|spec| (spec.belief_uuid, spec.behaviour_uuid, spec.value)),
+            index,
+        )
+    }
+
+    fn from_values(values: impl Iterator<Item = (Uuid, Uuid, f64)>, index: ModelIndex) -> Self {
+        let n_beliefs = index.belief_uuids().len();
+        let mut matrix = vec![0.0; n_beliefs * index.behaviour_uuids().len()];
+        for (belief, behaviour, v) in values {
+            if let (Some(i), Some(j)) = (index.belief(&belief), index.behaviour(&behaviour)) {
+                matrix[j * n_beliefs + i] = v;
+            }
+        }
+        Self {
+            values: matrix,
+            index,
+        }
+    }
+
+    /// The value for the [Belief] and [Behaviour] at the given positions.
+    pub fn get(&self, belief: usize, behaviour: usize) -> f64 {
+        self.values[behaviour * self.n_beliefs() + belief]
+    }
+
+    /// The value for each [Belief], in order, for the [Behaviour] at the
+    /// given position.
+    pub fn behaviour_row(&self, behaviour: usize) -> &[f64] {
+        let n_beliefs = self.n_beliefs();
+        &self.values[behaviour * n_beliefs..(behaviour + 1) * n_beliefs]
+    }
+
+    /// The position of the [Belief] with a [Uuid].
+    pub fn belief_index(&self, uuid: &Uuid) -> Option<usize> {
+        self.index.belief(uuid)
+    }
+
+    /// The position of the [Behaviour] with a [Uuid].
+    pub fn behaviour_index(&self, uuid: &Uuid) -> Option<usize> {
+        self.index.behaviour(uuid)
+    }
+
+    /// The [ModelIndex] the matrix is indexed by.
+    pub fn index(&self) -> &ModelIndex {
+        &self.index
+    }
+
+    /// Every nonzero value as a [PerformanceRelationshipSpec], in the
+    /// canonical order of the [ModelIndex]: by [Belief] and then by
+    /// [Behaviour].
+    pub fn to_specs(&self) -> Vec<PerformanceRelationshipSpec> {
+        let index = &self.index;
+        index
+            .canonical_beliefs()
+            .iter()
+            .flat_map(|&i| index.canonical_behaviours().iter().map(move |&j| (i, j)))
+            .filter(|&(i, j)| self.get(i, j) != 0.0)
+            .map(|(i, j)| PerformanceRelationshipSpec {
+                behaviour_uuid: index.behaviour_uuids()[j],
+                belief_uuid: index.belief_uuids()[i],
+                value: self.get(i, j),
+            })
+            .collect()
+    }
+
+    /// Apply [PrsModifications]: scale every value, and then replace the
+    /// overridden values, logging each change.
+    ///
+    /// Overrides referencing a [Belief] or [Behaviour] that is not in the
+    /// index are ignored, so they should be checked with
+    /// [PrsModifications::validate] first.
+    pub fn modify(&mut self, modifications: &PrsModifications) {
+        if let Some(factor) = modifications.scale {
+            info!("Scaling every performance relationship by {factor}");
+            self.values.iter_mut().for_each(|v| *v *= factor);
+        }
+        for o in &modifications.overrides {
+            if let (Some(i), Some(j)) = (
+                self.index.belief(&o.belief_uuid),
+                self.index.behaviour(&o.behaviour_uuid),
+            ) {
+                let n_beliefs = self.n_beliefs();
+                let v = &mut self.values[j * n_beliefs + i];
+                info!(
+                    "Overriding the performance relationship of belief {} and behaviour {}: {} -> {}",
+                    o.belief_uuid, o.behaviour_uuid, v, o.value
+                );
+                *v = o.value;
+            }
+        }
+    }
+
+    fn n_beliefs(&self) -> usize {
+        self.index.belief_uuids().len()
+    }
+}
+
+/// A value replacing the performance relationship of a [Belief] and a
+/// [Behaviour], parsed from `belief_uuid:behaviour_uuid=value`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrsOverride {
+    pub belief_uuid: Uuid,
+    pub behaviour_uuid: Uuid,
+    pub value: f64,
+}
+
+impl FromStr for PrsOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("expected belief_uuid:behaviour_uuid=value, found {s}");
+        let (uuids, value) = s.split_once('=').ok_or_else(expected)?;
+        let (belief, behaviour) = uuids.split_once(':').ok_or_else(expected)?;
+        let uuid = |u: &str| u.parse::<Uuid>().map_err(|err| format!("{u}: {err}"));
+        match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(PrsOverride {
+                belief_uuid: uuid(belief)?,
+                behaviour_uuid: uuid(behaviour)?,
+                value,
+            }),
+            Ok(_) => Err(format!("{value}: must be finite")),
+            Err(err) => Err(format!("{value}: {err}")),
+        }
+    }
+}
+
+/// Changes to the performance relationships loaded, for quick experiments
+/// without editing the inputs.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrsModifications {
+    /// The factor every value is multiplied by, if any.
+    pub scale: Option<f64>,
+    /// The values replaced after scaling, in order, so a later override of
+    /// the same pair wins.
+    pub overrides: Vec<PrsOverride>,
+}
+
+impl PrsModifications {
+    /// Whether nothing is modified.
+    pub fn is_empty(&self) -> bool {
+        self.scale.is_none() && self.overrides.is_empty()
+    }
+
+    /// Check that every override references a [Belief] and a [Behaviour] in
+    /// the model.
+    pub fn validate(&self, index: &ModelIndex) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for o in &self.overrides {
+            let unknown = |target_kind, target| ValidationIssue::UnknownTarget {
+                input: "performance relationship overrides",
+                target_kind,
+                target,
+            };
+            report.extend(
+                (!index.has_belief(&o.belief_uuid)).then(|| unknown("belief", o.belief_uuid)),
+            );
+            report.extend(
+                (!index.has_behaviour(&o.behaviour_uuid))
+                    .then(|| unknown("behaviour", o.behaviour_uuid)),
+            );
+        }
+        report
+    }
+}
 
 /// Convert [PerformanceRelationshipSpec]s to [PerformanceRelationships].
 ///
@@ -19,15 +237,15 @@ pub type PerformanceRelationships = HashMap<(BeliefPtr, BehaviourPtr), f64>;
 /// The [PerformanceRelationships].
 pub fn vec_prs_to_performance_relationships(
     prss: &[PerformanceRelationshipSpec],
-    beliefs: &HashMap<Uuid, BeliefPtr>,
-    behaviours: &HashMap<Uuid, BehaviourPtr>,
+    beliefs: &UuidMap<BeliefPtr>,
+    behaviours: &UuidMap<BehaviourPtr>,
 ) -> PerformanceRelationships {
     prss.iter()
         .map(|prs| {
             (
                 (
-                    beliefs.get(&prs.belief_uuid).unwrap().clone(),
-                    behaviours.get(&prs.behaviour_uuid).unwrap().clone(),
+                    *beliefs.get(&prs.belief_uuid).unwrap().borrow().uuid(),
+                    *behaviours.get(&prs.behaviour_uuid).unwrap().borrow().uuid(),
                 ),
                 prs.value,
             )
@@ -35,9 +253,33 @@ pub fn vec_prs_to_performance_relationships(
         .collect()
 }
 
+/// Convert [PerformanceRelationships] to [PerformanceRelationshipSpec]s.
+///
+/// # Arguments
+/// - `prs`: The [PerformanceRelationships].
+///
+/// # Returns
+/// The [PerformanceRelationshipSpec]s, ordered by [Belief] [Uuid] and then
+/// [Behaviour] [Uuid].
+pub fn performance_relationships_to_vec_prs(
+    prs: &PerformanceRelationships,
+) -> Vec<PerformanceRelationshipSpec> {
+    let mut prss: Vec<PerformanceRelationshipSpec> = prs
+        .iter()
+        .map(
+            |(&(belief_uuid, behaviour_uuid), &value)| PerformanceRelationshipSpec {
+                behaviour_uuid,
+                belief_uuid,
+                value,
+            },
+        )
+        .collect();
+    prss.sort_by_key(|prs| (prs.belief_uuid, prs.behaviour_uuid));
+    prss
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
 
     use belief_spread::{BasicBehaviour, BasicBelief, UUIDd};
     use uuid::Uuid;
@@ -46,6 +288,60 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn modify_scales_then_overrides() {
+        let (belief, behaviour) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let index = ModelIndex::new(vec![belief, Uuid::from_u128(3)], vec![behaviour]);
+        let specs = [(belief, 0.5), (Uuid::from_u128(3), -0.25)].map(|(belief_uuid, value)| {
+            PerformanceRelationshipSpec {
+                behaviour_uuid: behaviour,
+                belief_uuid,
+                value,
+            }
+        });
+        let mut prs = PrsMatrix::from_specs(&specs, index);
+        let modifications = PrsModifications {
+            scale: Some(2.0),
+            overrides: vec![format!("{belief}:{behaviour}=0.1").parse().unwrap()],
+        };
+        assert!(modifications.validate(prs.index()).is_empty());
+        prs.modify(&modifications);
+        assert_eq!(prs.behaviour_row(0), [0.1, -0.5]);
+    }
+
+    #[test]
+    fn prs_override_parses_and_validates() {
+        let o: PrsOverride =
+            "00000000-0000-0000-0000-000000000001:00000000-0000-0000-0000-000000000002=-0.5"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            o,
+            PrsOverride {
+                belief_uuid: Uuid::from_u128(1),
+                behaviour_uuid: Uuid::from_u128(2),
+                value: -0.5
+            }
+        );
+        assert!("00000000-0000-0000-0000-000000000001=0.5"
+            .parse::<PrsOverride>()
+            .is_err());
+        assert!(
+            "00000000-0000-0000-0000-000000000001:00000000-0000-0000-0000-000000000002=nan"
+                .parse::<PrsOverride>()
+                .is_err()
+        );
+
+        // The belief and behaviour are swapped
+        let index = ModelIndex::new(vec![Uuid::from_u128(2)], vec![Uuid::from_u128(1)]);
+        let report = PrsModifications {
+            scale: None,
+            overrides: vec![o],
+        }
+        .validate(&index);
+        assert_eq!(report.issues.len(), 2);
+    }
+
     #[test]
     fn test_vec_prs_to_performance_relationships_works() {
         let mut prss: Vec<PerformanceRelationshipSpec> = Vec::new();
@@ -56,16 +352,117 @@ mod tests {
             belief_uuid: *belief.uuid(),
             value: 0.2,
         });
-        let mut beliefs: HashMap<Uuid, BeliefPtr> = HashMap::new();
+        let mut beliefs: UuidMap<BeliefPtr> = UuidMap::default();
         let belief_ptr = BeliefPtr::from(belief);
         beliefs.insert(*belief_ptr.borrow().uuid(), belief_ptr.clone());
 
-        let mut behaviours: HashMap<Uuid, BehaviourPtr> = HashMap::new();
+        let mut behaviours: UuidMap<BehaviourPtr> = UuidMap::default();
         let behaviour_ptr = BehaviourPtr::from(behaviour);
         behaviours.insert(*behaviour_ptr.borrow().uuid(), behaviour_ptr.clone());
 
         let result = vec_prs_to_performance_relationships(&prss, &beliefs, &behaviours);
         assert_eq!(result.len(), 1);
-        assert_eq!(*result.get(&(belief_ptr, behaviour_ptr)).unwrap(), 0.2)
+        let belief_uuid = *belief_ptr.borrow().uuid();
+        let behaviour_uuid = *behaviour_ptr.borrow().uuid();
+        assert_eq!(*result.get(&(belief_uuid, behaviour_uuid)).unwrap(), 0.2)
+    }
+
+    #[test]
+    fn performance_relationships_round_trip() {
+        let beliefs: Vec<BeliefPtr> = (0..3)
+            .map(|i| BasicBelief::new(format!("b{i}")).into())
+            .collect();
+        let behaviours: Vec<BehaviourPtr> = (0..2)
+            .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+            .collect();
+        let mut prss: Vec<PerformanceRelationshipSpec> = Vec::new();
+        for (i, belief) in beliefs.iter().enumerate() {
+            for (j, behaviour) in behaviours.iter().enumerate() {
+                prss.push(PerformanceRelationshipSpec {
+                    behaviour_uuid: *behaviour.borrow().uuid(),
+                    belief_uuid: *belief.borrow().uuid(),
+                    value: i as f64 * 0.3 - j as f64 * 0.5,
+                });
+            }
+        }
+        prss.sort_by_key(|prs| (prs.belief_uuid, prs.behaviour_uuid));
+
+        let uuid_beliefs: UuidMap<BeliefPtr> = beliefs
+            .iter()
+            .map(|b| (*b.borrow().uuid(), b.clone()))
+            .collect();
+        let uuid_behaviours: UuidMap<BehaviourPtr> = behaviours
+            .iter()
+            .map(|b| (*b.borrow().uuid(), b.clone()))
+            .collect();
+        let prs = vec_prs_to_performance_relationships(&prss, &uuid_beliefs, &uuid_behaviours);
+
+        assert_eq!(performance_relationships_to_vec_prs(&prs), prss);
+    }
+
+    #[test]
+    fn prs_matrix_works() {
+        let beliefs: Vec<BeliefPtr> = (0..3)
+            .map(|i| BasicBelief::new(format!("b{i}")).into())
+            .collect();
+        let behaviours: Vec<BehaviourPtr> = (0..2)
+            .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+            .collect();
+        let uuid = |b: &BeliefPtr| *b.borrow().uuid();
+        let behaviour_uuid = |b: &BehaviourPtr| *b.borrow().uuid();
+        let prs = PerformanceRelationships::from([
+            ((uuid(&beliefs[0]), behaviour_uuid(&behaviours[1])), 0.5),
+            ((uuid(&beliefs[2]), behaviour_uuid(&behaviours[0])), -0.25),
+            ((Uuid::new_v4(), behaviour_uuid(&behaviours[0])), 1.0),
+        ]);
+
+        let matrix = PrsMatrix::new(&prs, &beliefs, &behaviours);
+        assert_eq!(matrix.get(0, 1), 0.5);
+        assert_eq!(matrix.get(2, 0), -0.25);
+        assert_eq!(matrix.get(1, 1), 0.0);
+        assert_eq!(matrix.behaviour_row(0), &[0.0, 0.0, -0.25]);
+        assert_eq!(matrix.belief_index(&uuid(&beliefs[2])), Some(2));
+        assert_eq!(
+            matrix.behaviour_index(&behaviour_uuid(&behaviours[1])),
+            Some(1)
+        );
+        assert_eq!(matrix.belief_index(&Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn prs_matrix_from_specs_matches_new() {
+        let beliefs: Vec<BeliefPtr> = (0..3)
+            .map(|i| BasicBelief::new(format!("b{i}")).into())
+            .collect();
+        let behaviours: Vec<BehaviourPtr> = (0..2)
+            .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+            .collect();
+        let specs: Vec<PerformanceRelationshipSpec> = beliefs
+            .iter()
+            .enumerate()
+            .map(|(i, belief)| PerformanceRelationshipSpec {
+                behaviour_uuid: *behaviours[i % 2].borrow().uuid(),
+                belief_uuid: *belief.borrow().uuid(),
+                value: 0.25 * i as f64,
+            })
+            .collect();
+        let prs: PerformanceRelationships = specs
+            .iter()
+            .map(|spec| ((spec.belief_uuid, spec.behaviour_uuid), spec.value))
+            .collect();
+
+        let index = ModelIndex::from_model(&beliefs, &behaviours);
+        let matrix = PrsMatrix::from_specs(&specs, index.clone());
+        assert_eq!(matrix, PrsMatrix::new(&prs, &beliefs, &behaviours));
+        assert_eq!(matrix.index(), &index);
+
+        // The zero value for the first belief is not listed
+        assert_eq!(
+            matrix.to_specs(),
+            performance_relationships_to_vec_prs(&prs)
+                .into_iter()
+                .filter(|spec| spec.value != 0.0)
+                .collect::<Vec<_>>()
+        );
     }
 }
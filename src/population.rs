@@ -0,0 +1,346 @@
+//! Generating synthetic populations of [Agent]s for the [Belief]s and
+//! [Behaviour]s of a model, from the same [AgentSpec]s the loaders read, so
+//! a generated file always loads.
+//!
+//! The friendships are drawn from a random network model, and the initial
+//! activations and deltas from a [ValueDistribution] for each [Agent] and
+//! [Belief]. Everything is drawn from a single generator seeded with the
+//! seed, so the same seed and model always give the same population.
+
+use std::{collections::HashMap, fmt, path::Path, path::PathBuf, str::FromStr};
+
+use belief_spread::SimTime;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::{
+    deltas::standard_normal,
+    error::ConceptError,
+    json::{AgentSpec, BehaviourSpec, BeliefSpec},
+    sink::{Compression, OutputSettings},
+};
+
+/// A distribution initial activations or deltas are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum ValueDistribution {
+    /// A uniform distribution over [`min`, `max`].
+    Uniform { min: f64, max: f64 },
+    /// A normal distribution with mean `mean` and standard deviation `sd`.
+    Normal { mean: f64, sd: f64 },
+}
+
+impl ValueDistribution {
+    /// Draw a value from the distribution.
+    pub fn sample(&self, rng: &mut impl Rng) -> f64 {
+        match *self {
+            ValueDistribution::Uniform { min, max } => min + (max - min) * rng.gen::<f64>(),
+            ValueDistribution::Normal { mean, sd } => mean + sd * standard_normal(rng),
+        }
+    }
+}
+
+impl FromStr for ValueDistribution {
+    type Err = String;
+
+    /// Parse a distribution given as `uniform:MIN,MAX` or `normal:MEAN,SD`,
+    /// with finite parameters, MIN not above MAX and SD not negative.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s
+            .split_once(':')
+            .ok_or_else(|| "must be uniform:MIN,MAX or normal:MEAN,SD".to_string())?;
+        let (a, b) = params
+            .split_once(',')
+            .ok_or_else(|| format!("{kind} takes two parameters separated by a comma"))?;
+        let parse = |v: &str| match v.trim().parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(v),
+            Ok(_) => Err("parameters must be finite".to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        let (a, b) = (parse(a)?, parse(b)?);
+        match kind {
+            "uniform" if a <= b => Ok(ValueDistribution::Uniform { min: a, max: b }),
+            "uniform" => Err(format!("{a} must not be above {b}")),
+            "normal" if b >= 0.0 => Ok(ValueDistribution::Normal { mean: a, sd: b }),
+            "normal" => Err(format!("standard deviation {b} must not be negative")),
+            _ => Err(format!(
+                "unknown distribution {kind}, expected uniform or normal"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ValueDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueDistribution::Uniform { min, max } => write!(f, "uniform:{min},{max}"),
+            ValueDistribution::Normal { mean, sd } => write!(f, "normal:{mean},{sd}"),
+        }
+    }
+}
+
+/// How the friendships of a generated population are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FriendNetwork {
+    /// Each pair of [Agent]s are friends of each other with probability
+    /// `density`, independently of every other pair.
+    ErdosRenyi { density: f64 },
+}
+
+/// The settings of a generated population.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PopulationSettings {
+    pub n_agents: usize,
+    pub network: FriendNetwork,
+    /// The weight of every friendship, in both directions.
+    pub friend_weight: f64,
+    /// The distribution of the initial activations, clamped to [-1, 1].
+    pub activations: ValueDistribution,
+    /// The distribution of the deltas, clamped to be positive.
+    pub deltas: ValueDistribution,
+    /// The tick of the initial activations and actions.
+    pub time: SimTime,
+    pub seed: u64,
+}
+
+/// What the generate subcommand wrote, printed when it finishes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PopulationOutcome {
+    pub settings: PopulationSettings,
+    /// The number of pairs of [Agent]s that are friends.
+    pub friendships: usize,
+    /// The files written.
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl PopulationSettings {
+    /// Generate a population for `beliefs` and `behaviours`.
+    ///
+    /// Each [Agent] has an activation and a delta for every [Belief], and
+    /// performs a [Behaviour] chosen uniformly at random at the tick of the
+    /// initial activations, unless there are none.
+    pub fn generate(&self, beliefs: &[BeliefSpec], behaviours: &[BehaviourSpec]) -> Vec<AgentSpec> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let uuids: Vec<_> = (0..self.n_agents)
+            .map(|_| uuid::Builder::from_random_bytes(rng.gen()).into_uuid())
+            .collect();
+        let mut friends: Vec<HashMap<_, f64>> = vec![HashMap::new(); self.n_agents];
+        match self.network {
+            FriendNetwork::ErdosRenyi { density } => {
+                for_each_erdos_renyi_pair(self.n_agents, density, &mut rng, |i, j| {
+                    friends[i].insert(uuids[j], self.friend_weight);
+                    friends[j].insert(uuids[i], self.friend_weight);
+                })
+            }
+        }
+        uuids
+            .iter()
+            .zip(friends)
+            .map(|(&uuid, friends)| AgentSpec {
+                uuid,
+                actions: behaviours
+                    .choose(&mut rng)
+                    .map(|behaviour| (self.time, behaviour.uuid))
+                    .into_iter()
+                    .collect(),
+                activations: HashMap::from([(
+                    self.time,
+                    beliefs
+                        .iter()
+                        .map(|b| (b.uuid, self.activations.sample(&mut rng).clamp(-1.0, 1.0)))
+                        .collect(),
+                )]),
+                deltas: beliefs
+                    .iter()
+                    .map(|b| {
+                        let delta = self.deltas.sample(&mut rng);
+                        (b.uuid, delta.clamp(f64::MIN_POSITIVE, f64::MAX))
+                    })
+                    .collect(),
+                friends,
+                activation_floors: HashMap::new(),
+                activation_ceilings: HashMap::new(),
+            })
+            .collect()
+    }
+}
+
+/// Call `f` with each pair of positions below `n`, the larger first, with
+/// probability `density`, in order.
+///
+/// The pairs are not each tested: the number skipped before the next is
+/// drawn from its geometric distribution, so the time taken grows with the
+/// number of pairs drawn rather than the number of pairs.
+fn for_each_erdos_renyi_pair(
+    n: usize,
+    density: f64,
+    rng: &mut impl Rng,
+    mut f: impl FnMut(usize, usize),
+) {
+    if density <= 0.0 {
+        return;
+    }
+    let log_miss = (1.0 - density).ln();
+    let (mut i, mut j) = (1, 0);
+    let mut first = true;
+    while i < n {
+        // 1 - [0, 1) is (0, 1], so the logarithm is finite
+        let skip = ((1.0 - rng.gen::<f64>()).ln() / log_miss).floor() as usize;
+        j = if first {
+            skip
+        } else {
+            (j + 1).saturating_add(skip)
+        };
+        first = false;
+        while j >= i && i < n {
+            j -= i;
+            i += 1;
+        }
+        if i < n {
+            f(i, j);
+        }
+    }
+}
+
+/// Write generated [AgentSpec]s to `path`, compressed with zstd if its name
+/// ends in .zst and plain if it ends in .json.
+pub fn write_agents(path: &Path, agents: &[AgentSpec]) -> Result<(), ConceptError> {
+    let settings = OutputSettings {
+        compression: Compression::for_path(path)?,
+        path: path.to_path_buf(),
+    };
+    let mut sink = settings.open().map_err(|source| ConceptError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::to_writer(&mut sink, agents)
+        .map_err(|err| ConceptError::Output { source: err.into() })?;
+    sink.finish()
+        .map_err(|source| ConceptError::Output { source })
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        configuration::ConfigurationBuilder, example::ExampleScenario,
+        loader::load_agents_from_path, runner::Runner,
+    };
+
+    fn settings(n_agents: usize, density: f64) -> PopulationSettings {
+        PopulationSettings {
+            n_agents,
+            network: FriendNetwork::ErdosRenyi { density },
+            friend_weight: 1.0,
+            activations: ValueDistribution::Uniform {
+                min: -1.5,
+                max: 1.5,
+            },
+            deltas: ValueDistribution::Normal { mean: 0.1, sd: 1.0 },
+            time: 0,
+            seed: 3,
+        }
+    }
+
+    #[test]
+    fn distributions_are_parsed_from_their_kind_and_parameters() {
+        assert_eq!(
+            "uniform:-1,1".parse(),
+            Ok(ValueDistribution::Uniform {
+                min: -1.0,
+                max: 1.0
+            })
+        );
+        assert_eq!(
+            "normal:1.0, 0.1".parse(),
+            Ok(ValueDistribution::Normal { mean: 1.0, sd: 0.1 })
+        );
+        for invalid in [
+            "uniform:1,-1",
+            "normal:1,-0.1",
+            "gamma:1,1",
+            "normal:1",
+            "1,1",
+        ] {
+            assert!(invalid.parse::<ValueDistribution>().is_err(), "{invalid}");
+        }
+        let distribution = ValueDistribution::Normal { mean: 1.0, sd: 0.1 };
+        assert_eq!(distribution.to_string().parse(), Ok(distribution));
+    }
+
+    #[test]
+    fn erdos_renyi_networks_have_about_the_density_asked_for() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let n = 400;
+        let mut pairs = Vec::new();
+        for_each_erdos_renyi_pair(n, 0.05, &mut rng, |i, j| pairs.push((i, j)));
+        assert!(pairs.iter().all(|&(i, j)| j < i && i < n));
+        let mut sorted = pairs.clone();
+        sorted.dedup();
+        assert_eq!(sorted, pairs, "pairs are distinct and in order");
+        let expected = 0.05 * (n * (n - 1) / 2) as f64;
+        // Five standard deviations either side
+        let sd = (expected * 0.95).sqrt();
+        assert!(
+            (pairs.len() as f64 - expected).abs() < 5.0 * sd,
+            "{}",
+            pairs.len()
+        );
+
+        let mut all = 0;
+        for_each_erdos_renyi_pair(10, 1.0, &mut rng, |_, _| all += 1);
+        assert_eq!(all, 45);
+        for_each_erdos_renyi_pair(10, 0.0, &mut rng, |_, _| panic!("no pairs"));
+    }
+
+    #[test]
+    fn generated_values_are_within_their_ranges() {
+        let example = ExampleScenario::generate();
+        let agents = settings(50, 0.1).generate(&example.beliefs, &example.behaviours);
+        assert_eq!(
+            agents,
+            settings(50, 0.1).generate(&example.beliefs, &example.behaviours)
+        );
+        for agent in &agents {
+            assert_eq!(agent.activations[&0].len(), example.beliefs.len());
+            assert!(agent.activations[&0]
+                .values()
+                .all(|v| (-1.0..=1.0).contains(v)));
+            assert!(agent.deltas.values().all(|&v| v > 0.0));
+            assert_eq!(agent.actions.len(), 1);
+            for friend in agent.friends.keys() {
+                let friend = agents.iter().find(|a| a.uuid == *friend).unwrap();
+                assert_eq!(friend.friends[&agent.uuid], 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_populations_load_and_run_end_to_end() {
+        let example = ExampleScenario::generate();
+        let agents = settings(30, 0.2).generate(&example.beliefs, &example.behaviours);
+        let path = std::env::temp_dir().join(format!("concept-agents-{}.json", Uuid::new_v4()));
+        write_agents(&path, &agents).unwrap();
+        let loaded = load_agents_from_path(&path).unwrap();
+        assert_eq!(loaded, agents);
+
+        let config = ConfigurationBuilder::new()
+            .with_behaviours(example.behaviours)
+            .with_beliefs(example.beliefs)
+            .agents_from_path(&path)
+            .with_prs(example.prs)
+            .time_range(1, 3)
+            .output(Box::new(Vec::new()))
+            .build()
+            .unwrap();
+        let outcome = Runner::new(config).with_seed(1).run().unwrap();
+        assert_eq!(outcome.last_tick, 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
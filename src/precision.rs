@@ -0,0 +1,96 @@
+//! The precision of the activations held outside of the [Agent]s.
+//!
+//! [Agent]s always store their activations as [f64], but copies of them made
+//! while simulating and summarising may be held as [f32] to halve their
+//! memory.
+
+use std::fmt::Debug;
+
+use serde::Serialize;
+
+/// The precision of the activations copied out of the [Agent]s, into the
+/// [ActivationCache](crate::scoring::ActivationCache) used to score
+/// behaviours and the buffers used to compute the summary output.
+///
+/// With [Precision::F32], each copied activation is rounded to the nearest
+/// [f32], which is within about 6e-8 of the [f64] as activations lie in
+/// [-1, 1]. Scores and summary statistics are still accumulated in [f64],
+/// so they differ from [Precision::F64] by about 1e-7, and the summary
+/// statistics are written rounded to [f32]. An [Agent] whose behaviour
+/// scores are that close to a boundary of action selection may choose
+/// differently. Perception and snapshots are unaffected, as they use the
+/// activations in the [Agent]s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Double precision.
+    #[default]
+    F64,
+    /// Single precision.
+    F32,
+}
+
+/// A floating-point type activations can be copied out of [Agent]s as.
+pub trait Activation: Copy + Default + PartialOrd + Debug + Send + Sync + 'static {
+    /// Convert an activation from an [Agent].
+    fn from_f64(value: f64) -> Self;
+
+    /// Convert back to [f64] for accumulating.
+    fn to_f64(self) -> f64;
+
+    /// Round a statistic computed from activations of this type for
+    /// writing, so that no more precision is written than they held.
+    fn round_output(value: f64) -> f64;
+}
+
+impl Activation for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn round_output(value: f64) -> f64 {
+        value
+    }
+}
+
+impl Activation for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    /// The [f64] closest to the shortest decimal that rounds to the same
+    /// [f32], so that it is written as, say, `0.1` rather than
+    /// `0.10000000149011612`.
+    fn round_output(value: f64) -> f64 {
+        (value as f32)
+            .to_string()
+            .parse()
+            .expect("f32s format as valid f64s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f32_round_output_writes_shortest_decimal() {
+        let value = <f32 as Activation>::round_output(0.1 + 1e-12);
+        assert_eq!(value, 0.1);
+        assert_eq!(serde_json::to_string(&value).unwrap(), "0.1");
+        assert_eq!(<f64 as Activation>::round_output(0.1 + 1e-12), 0.1 + 1e-12);
+    }
+
+    #[test]
+    fn precision_serializes_lowercase() {
+        assert_eq!(serde_json::to_string(&Precision::F32).unwrap(), r#""f32""#);
+    }
+}
@@ -0,0 +1,197 @@
+//! Projecting the runtime, output size and memory of a whole run from its
+//! first few ticks.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use belief_spread::SimTime;
+use serde::Serialize;
+
+use crate::{
+    error::ConceptError,
+    memory::resident_set_size,
+    runner::{PhaseTimings, Runner},
+    sampling::OutputSampling,
+    sink::{Compression, OutputSink},
+};
+
+/// What was measured from the first ticks of a run, and what that projects
+/// for the whole run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeProjection {
+    /// The identifier of the run probed.
+    pub run_id: String,
+    /// The number of ticks simulated by the probe.
+    pub probed_ticks: SimTime,
+    /// The number of ticks in the whole run.
+    pub total_ticks: SimTime,
+    /// The time spent in each phase of the probe, in seconds, where the
+    /// output is the summary of the ticks probed, written to memory.
+    pub measured: PhaseTimings,
+    /// The time projected for each phase of the whole run, in seconds.
+    pub projected: PhaseTimings,
+    /// The total time projected for the whole run, in seconds.
+    pub projected_seconds: f64,
+    /// The size of the summary output of the ticks probed, with the
+    /// [Compression] of the run.
+    pub output_bytes: u64,
+    /// The size of the summary output projected for the whole run.
+    pub projected_output_bytes: u64,
+    /// The resident set size before the probe, or [None] if it cannot be
+    /// read on this platform.
+    pub rss_before_bytes: Option<u64>,
+    /// The resident set size after the probe.
+    pub rss_after_bytes: Option<u64>,
+    /// The resident set size projected at the end of the whole run, as the
+    /// [Agent]s keep the activations and action of every tick.
+    pub projected_rss_bytes: Option<u64>,
+}
+
+impl ProbeProjection {
+    /// Simulate the first `ticks` ticks of a [Runner] that has not started,
+    /// and project the whole run from them.
+    ///
+    /// The output of the [Runner] is not written: the summary of the ticks
+    /// probed is written to memory with `compression`, to measure its time
+    /// and size. Any
+    /// network snapshots of the configuration are written as the ticks are
+    /// simulated, so a probe is best configured without them.
+    pub fn run(
+        runner: &mut Runner,
+        ticks: SimTime,
+        compression: Compression,
+    ) -> Result<Self, ConceptError> {
+        let start_time = runner.time() + 1;
+        let total_ticks = runner.end_time() + 1 - start_time;
+        let probed_ticks = ticks.clamp(1, total_ticks);
+        let rss_before_bytes = resident_set_size();
+        runner.run_until(start_time + probed_ticks - 1)?;
+        let rss_after_bytes = resident_set_size();
+
+        let started = Instant::now();
+        let output = CountingSink::default();
+        let bytes = output.0.clone();
+        let mut sink: Box<dyn OutputSink> = match compression {
+            Compression::None => Box::new(output),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level, .. } => Box::new(
+                zstd::stream::write::Encoder::new(output, level)
+                    .map_err(|source| ConceptError::Output { source })?,
+            ),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd { .. } => {
+                return Err(ConceptError::Output {
+                    source: crate::sink::zstd_disabled(),
+                })
+            }
+        };
+        runner.serialize_output_to(&mut sink)?;
+        sink.finish()
+            .map_err(|source| ConceptError::Output { source })?;
+        let output_bytes = bytes.load(Ordering::Relaxed) as f64;
+        let measured = PhaseTimings {
+            output: started.elapsed().as_secs_f64(),
+            ..runner.timings()
+        };
+
+        // Perception and action selection take the same time every tick,
+        // while the output only summarises the ticks written
+        let sampled_ticks = |end_time| {
+            OutputSampling {
+                end_time,
+                ..runner.output_sampling()
+            }
+            .times()
+            .len() as f64
+        };
+        let tick_scale = total_ticks as f64 / probed_ticks as f64;
+        let output_scale = sampled_ticks(runner.end_time()) / sampled_ticks(runner.time());
+        let projected = PhaseTimings {
+            perceive_beliefs: measured.perceive_beliefs * tick_scale,
+            perform_actions: measured.perform_actions * tick_scale,
+            output: measured.output * output_scale,
+        };
+        let growth_per_tick = rss_after_bytes
+            .zip(rss_before_bytes)
+            .map(|(after, before)| after.saturating_sub(before) as f64 / probed_ticks as f64);
+        Ok(ProbeProjection {
+            run_id: runner.run_id().to_string(),
+            probed_ticks,
+            total_ticks,
+            measured,
+            projected,
+            projected_seconds: projected.perceive_beliefs
+                + projected.perform_actions
+                + projected.output,
+            output_bytes: output_bytes as u64,
+            projected_output_bytes: (output_bytes * output_scale).round() as u64,
+            rss_before_bytes,
+            rss_after_bytes,
+            projected_rss_bytes: rss_before_bytes
+                .zip(growth_per_tick)
+                .map(|(before, growth)| before + (growth * total_ticks as f64).round() as u64),
+        })
+    }
+}
+
+/// A sink that counts the bytes written to it, and discards them.
+#[derive(Default)]
+struct CountingSink(Arc<AtomicU64>);
+
+impl io::Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl OutputSink for CountingSink {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::tests::small_builder;
+
+    #[test]
+    fn probes_project_from_the_first_ticks() {
+        let config = small_builder().time_range(1, 12).build().unwrap();
+        let mut runner = Runner::new(config).with_seed(4);
+        let projection = ProbeProjection::run(&mut runner, 3, Compression::None).unwrap();
+        assert_eq!(runner.time(), 3);
+        assert_eq!((projection.probed_ticks, projection.total_ticks), (3, 12));
+        assert_eq!(
+            projection.projected.perceive_beliefs,
+            projection.measured.perceive_beliefs * 4.0
+        );
+        assert_eq!(
+            projection.projected_output_bytes,
+            projection.output_bytes * 4
+        );
+        assert!(projection.projected_seconds > 0.0);
+
+        // The output of the runner is left unwritten
+        runner.serialize_output().unwrap();
+
+        // Only the ticks written to the output are projected for it
+        let config = small_builder().time_range(1, 12).build().unwrap();
+        let mut runner = Runner::new(config).with_output_every(4);
+        let projection = ProbeProjection::run(&mut runner, 20, Compression::None).unwrap();
+        assert_eq!(projection.probed_ticks, 12);
+        assert_eq!(projection.projected_output_bytes, projection.output_bytes);
+    }
+}
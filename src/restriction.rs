@@ -0,0 +1,195 @@
+//! Simulating a subset of the [Belief]s and [Behaviour]s of a model, by
+//! dropping the others and every reference to them from the specs before
+//! they are converted.
+
+use log::info;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    collections::UuidSet,
+    error::{ValidationIssue, ValidationReport},
+    json::{AgentSpec, BehaviourSpec, BeliefSpec, PerformanceRelationshipSpec},
+};
+
+/// The [Belief]s and [Behaviour]s kept, if only some are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelRestriction {
+    /// The [Belief]s kept, or [None] to keep every [Belief].
+    pub beliefs: Option<UuidSet>,
+    /// The [Behaviour]s kept, or [None] to keep every [Behaviour].
+    pub behaviours: Option<UuidSet>,
+}
+
+/// The number of references to the [Belief]s and [Behaviour]s that were not
+/// kept which were dropped from each kind of spec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedReferences {
+    pub beliefs: usize,
+    pub behaviours: usize,
+    pub perceptions: usize,
+    pub relationships: usize,
+    pub performance_relationships: usize,
+    pub activations: usize,
+    pub deltas: usize,
+    pub actions: usize,
+    pub activation_caps: usize,
+}
+
+/// How a model was restricted by a [ModelRestriction].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Restriction {
+    /// The [Belief]s kept, in ascending order, if only some were.
+    pub beliefs: Option<Vec<Uuid>>,
+    /// The [Behaviour]s kept, in ascending order, if only some were.
+    pub behaviours: Option<Vec<Uuid>>,
+    /// The specs and references dropped.
+    pub dropped: DroppedReferences,
+}
+
+impl ModelRestriction {
+    /// Whether every [Belief] and [Behaviour] is kept.
+    pub fn is_empty(&self) -> bool {
+        self.beliefs.is_none() && self.behaviours.is_none()
+    }
+
+    fn keeps_belief(&self, uuid: &Uuid) -> bool {
+        self.beliefs.as_ref().is_none_or(|kept| kept.contains(uuid))
+    }
+
+    fn keeps_behaviour(&self, uuid: &Uuid) -> bool {
+        self.behaviours
+            .as_ref()
+            .is_none_or(|kept| kept.contains(uuid))
+    }
+
+    /// Check that every [Belief] and [Behaviour] kept is in the specs.
+    pub fn validate(
+        &self,
+        beliefs: &[BeliefSpec],
+        behaviours: &[BehaviourSpec],
+    ) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let unknown = |kept: &Option<UuidSet>, known: UuidSet, input, kind| {
+            let mut unknown: Vec<Uuid> = kept
+                .iter()
+                .flatten()
+                .filter(|uuid| !known.contains(uuid))
+                .copied()
+                .collect();
+            unknown.sort_unstable();
+            unknown
+                .into_iter()
+                .map(move |target| ValidationIssue::UnknownTarget {
+                    input,
+                    target_kind: kind,
+                    target,
+                })
+        };
+        report.extend(unknown(
+            &self.beliefs,
+            beliefs.iter().map(|b| b.uuid).collect(),
+            "only beliefs",
+            "belief",
+        ));
+        report.extend(unknown(
+            &self.behaviours,
+            behaviours.iter().map(|b| b.uuid).collect(),
+            "only behaviours",
+            "behaviour",
+        ));
+        report
+    }
+
+    /// Drop the [Belief]s and [Behaviour]s not kept, and the perceptions,
+    /// relationships and performance relationships of those kept which
+    /// refer to them.
+    pub fn restrict_specs(
+        &self,
+        behaviours: &mut Vec<BehaviourSpec>,
+        beliefs: &mut Vec<BeliefSpec>,
+        prs: &mut Vec<PerformanceRelationshipSpec>,
+        dropped: &mut DroppedReferences,
+    ) {
+        dropped.behaviours += retain(behaviours, |b| self.keeps_behaviour(&b.uuid));
+        dropped.beliefs += retain(beliefs, |b| self.keeps_belief(&b.uuid));
+        for belief in beliefs {
+            let n = belief.perceptions.len();
+            belief.perceptions.retain(|b, _| self.keeps_behaviour(b));
+            dropped.perceptions += n - belief.perceptions.len();
+            let n = belief.relationships.len();
+            belief.relationships.retain(|b, _| self.keeps_belief(b));
+            dropped.relationships += n - belief.relationships.len();
+        }
+        dropped.performance_relationships += retain(prs, |pr| {
+            self.keeps_belief(&pr.belief_uuid) && self.keeps_behaviour(&pr.behaviour_uuid)
+        });
+    }
+
+    /// Drop the activations, deltas, actions, and activation floors and
+    /// ceilings of an [AgentSpec] which refer to the [Belief]s and
+    /// [Behaviour]s not kept.
+    pub fn restrict_agent(&self, agent: &mut AgentSpec, dropped: &mut DroppedReferences) {
+        for activations in agent.activations.values_mut() {
+            let n = activations.len();
+            activations.retain(|b, _| self.keeps_belief(b));
+            dropped.activations += n - activations.len();
+        }
+        agent
+            .activations
+            .retain(|_, activations| !activations.is_empty());
+        let n = agent.deltas.len();
+        agent.deltas.retain(|b, _| self.keeps_belief(b));
+        dropped.deltas += n - agent.deltas.len();
+        let n = agent.actions.len();
+        agent.actions.retain(|_, b| self.keeps_behaviour(b));
+        dropped.actions += n - agent.actions.len();
+        for caps in [&mut agent.activation_floors, &mut agent.activation_ceilings] {
+            let n = caps.len();
+            caps.retain(|b, _| self.keeps_belief(b));
+            dropped.activation_caps += n - caps.len();
+        }
+    }
+
+    /// Describe the restriction, having dropped `dropped`, and log it.
+    pub fn finish(&self, dropped: DroppedReferences) -> Restriction {
+        let sorted = |kept: &Option<UuidSet>| {
+            kept.as_ref().map(|kept| {
+                let mut kept: Vec<Uuid> = kept.iter().copied().collect();
+                kept.sort_unstable();
+                kept
+            })
+        };
+        info!(
+            "Restricted the model, dropping {} beliefs, {} behaviours, {} perceptions, \
+             {} relationships, {} performance relationships, {} activations, {} deltas, \
+             {} actions and {} activation floors and ceilings",
+            dropped.beliefs,
+            dropped.behaviours,
+            dropped.perceptions,
+            dropped.relationships,
+            dropped.performance_relationships,
+            dropped.activations,
+            dropped.deltas,
+            dropped.actions,
+            dropped.activation_caps
+        );
+        Restriction {
+            beliefs: sorted(&self.beliefs),
+            behaviours: sorted(&self.behaviours),
+            dropped,
+        }
+    }
+}
+
+/// Keep only the elements of `specs` for which `keep` is true.
+///
+/// # Returns
+/// The number of elements dropped.
+fn retain<T>(specs: &mut Vec<T>, keep: impl FnMut(&T) -> bool) -> usize {
+    let n = specs.len();
+    specs.retain(keep);
+    n - specs.len()
+}
@@ -0,0 +1,399 @@
+//! Accumulating the results of many runs in one SQLite database.
+//!
+//! Each run appended is a row of the `runs` table, and its activations,
+//! actions, summary and the names of its beliefs and behaviours are rows of
+//! the other tables, tagged with the id of the run. A run is appended in a
+//! single transaction, so a run that fails part way through leaves no rows
+//! behind.
+//!
+//! The `id` of a run numbers it within the database, while its `run_uid` is
+//! the identifier of the run in its logs and outputs.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, Transaction};
+use serde::Serialize;
+
+use crate::{error::ConceptError, runner::Runner};
+
+/// The statements migrating the schema from each version to the next, so
+/// the schema of version `n` is created by the first `n`.
+///
+/// The version of a database is stored in its `user_version`, where 0 is
+/// an empty database.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE runs (
+        id INTEGER PRIMARY KEY,
+        label TEXT NOT NULL,
+        seed TEXT NOT NULL,
+        metadata TEXT NOT NULL
+    );
+    CREATE TABLE activations (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        time INTEGER NOT NULL,
+        agent TEXT NOT NULL,
+        belief TEXT NOT NULL,
+        value REAL NOT NULL
+    );
+    CREATE TABLE actions (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        time INTEGER NOT NULL,
+        agent TEXT NOT NULL,
+        behaviour TEXT NOT NULL
+    );
+    CREATE TABLE belief_summary (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        time INTEGER NOT NULL,
+        belief TEXT NOT NULL,
+        mean REAL NOT NULL,
+        sd REAL,
+        median REAL,
+        nonzero_count INTEGER
+    );
+    CREATE TABLE behaviour_summary (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        time INTEGER NOT NULL,
+        behaviour TEXT NOT NULL,
+        n_performers INTEGER NOT NULL
+    );
+    CREATE INDEX activations_run ON activations (run_id);
+    CREATE INDEX actions_run ON actions (run_id);
+    CREATE INDEX belief_summary_run ON belief_summary (run_id);
+    CREATE INDEX behaviour_summary_run ON behaviour_summary (run_id);
+",
+    "
+    ALTER TABLE runs ADD COLUMN run_uid TEXT;
+",
+    "
+    CREATE TABLE belief_names (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        belief TEXT NOT NULL,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE behaviour_names (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        behaviour TEXT NOT NULL,
+        name TEXT NOT NULL
+    );
+    CREATE INDEX belief_names_run ON belief_names (run_id);
+    CREATE INDEX behaviour_names_run ON behaviour_names (run_id);
+",
+];
+
+/// The version of the schema created by this version.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// A SQLite database of the results of many runs.
+pub struct ResultsDatabase {
+    path: PathBuf,
+    connection: Connection,
+}
+
+impl ResultsDatabase {
+    /// Open the database at `path`, creating it if it does not exist and
+    /// migrating its schema to [SCHEMA_VERSION].
+    ///
+    /// # Returns
+    /// The [ResultsDatabase], or a [ConceptError::DatabaseVersion] if it was
+    /// created by a newer version.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ConceptError> {
+        let path = path.as_ref().to_path_buf();
+        let db = ResultsDatabase {
+            connection: Connection::open(&path).map_err(|source| ConceptError::Database {
+                path: path.clone(),
+                source,
+            })?,
+            path,
+        };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// The version of the schema of the database.
+    pub fn schema_version(&self) -> Result<i64, ConceptError> {
+        self.connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|source| self.error(source))
+    }
+
+    fn migrate(&self) -> Result<(), ConceptError> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            return Err(ConceptError::DatabaseVersion {
+                path: self.path.clone(),
+                version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        let tx = self.transaction()?;
+        for migration in &MIGRATIONS[version as usize..] {
+            tx.execute_batch(migration)
+                .map_err(|source| self.error(source))?;
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .and_then(|_| tx.commit())
+            .map_err(|source| self.error(source))
+    }
+
+    /// Append the activations, actions and summary written to the outputs by
+    /// a [Runner], and the names of its beliefs and behaviours, as a run
+    /// labelled `label` described by `metadata`.
+    ///
+    /// Every row is inserted in one transaction, which is rolled back if any
+    /// insert fails.
+    ///
+    /// # Returns
+    /// The id of the run in the `runs` table.
+    pub fn append_run<M: Serialize>(
+        &mut self,
+        label: &str,
+        runner: &Runner,
+        metadata: &M,
+    ) -> Result<i64, ConceptError> {
+        let metadata = serde_json::to_string(metadata).map_err(|source| ConceptError::Output {
+            source: source.into(),
+        })?;
+        let tx = self.transaction()?;
+        let run_id =
+            insert_run(&tx, label, runner, &metadata).map_err(|source| self.error(source))?;
+        tx.commit().map_err(|source| self.error(source))?;
+        log::info!("Appended run {run_id} to {}", self.path.display());
+        Ok(run_id)
+    }
+
+    fn transaction(&self) -> Result<Transaction<'_>, ConceptError> {
+        // Only the runner writes to the database while it is open, so an
+        // unchecked transaction is sufficient
+        self.connection
+            .unchecked_transaction()
+            .map_err(|source| self.error(source))
+    }
+
+    fn error(&self, source: rusqlite::Error) -> ConceptError {
+        ConceptError::Database {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+/// Insert the row of a run and every row tagged with it.
+fn insert_run(
+    tx: &Transaction,
+    label: &str,
+    runner: &Runner,
+    metadata: &str,
+) -> rusqlite::Result<i64> {
+    tx.execute(
+        "INSERT INTO runs (label, seed, metadata, run_uid) VALUES (?1, ?2, ?3, ?4)",
+        params![label, runner.seed().to_string(), metadata, runner.run_id()],
+    )?;
+    let run_id = tx.last_insert_rowid();
+    let sampling = runner.output_sampling();
+
+    let names = runner.names();
+    let mut insert =
+        tx.prepare("INSERT INTO belief_names (run_id, belief, name) VALUES (?1, ?2, ?3)")?;
+    let mut beliefs: Vec<_> = names.beliefs.iter().collect();
+    beliefs.sort_unstable_by_key(|&(uuid, _)| uuid);
+    for (belief, name) in beliefs {
+        insert.execute(params![run_id, belief.to_string(), name])?;
+    }
+    let mut insert =
+        tx.prepare("INSERT INTO behaviour_names (run_id, behaviour, name) VALUES (?1, ?2, ?3)")?;
+    let mut behaviours: Vec<_> = names.behaviours.iter().collect();
+    behaviours.sort_unstable_by_key(|&(uuid, _)| uuid);
+    for (behaviour, name) in behaviours {
+        insert.execute(params![run_id, behaviour.to_string(), name])?;
+    }
+
+    let mut insert = tx.prepare(
+        "INSERT INTO activations (run_id, time, agent, belief, value) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (agent, time, belief, value) in runner.activations_iter() {
+        if sampling.includes(time) {
+            insert.execute(params![
+                run_id,
+                time,
+                agent.to_string(),
+                belief.to_string(),
+                value
+            ])?;
+        }
+    }
+
+    let mut insert =
+        tx.prepare("INSERT INTO actions (run_id, time, agent, behaviour) VALUES (?1, ?2, ?3, ?4)")?;
+    for (agent, time, behaviour) in runner.actions_iter() {
+        if sampling.includes(time) {
+            insert.execute(params![
+                run_id,
+                time,
+                agent.to_string(),
+                behaviour.to_string()
+            ])?;
+        }
+    }
+
+    let specs = runner.output_specs();
+    let mut insert_belief = tx.prepare(
+        "INSERT INTO belief_summary (run_id, time, belief, mean, sd, median, nonzero_count) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    let mut insert_behaviour = tx.prepare(
+        "INSERT INTO behaviour_summary (run_id, time, behaviour, n_performers) \
+         VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let mut times: Vec<_> = specs.data.keys().copied().collect();
+    times.sort_unstable();
+    for time in times.into_iter().filter(|&time| sampling.includes(time)) {
+        let spec = &specs.data[&time];
+        let mut beliefs: Vec<_> = spec.mean_activation.iter().collect();
+        beliefs.sort_unstable_by_key(|&(uuid, _)| uuid);
+        for (belief, mean) in beliefs {
+            insert_belief.execute(params![
+                run_id,
+                time,
+                belief.to_string(),
+                mean,
+                spec.sd_activation.get(belief),
+                spec.median_activation.get(belief),
+                spec.nonzero_activation_count.get(belief)
+            ])?;
+        }
+        let mut behaviours: Vec<_> = spec.n_performers.iter().collect();
+        behaviours.sort_unstable_by_key(|&(uuid, _)| uuid);
+        for (behaviour, n) in behaviours {
+            insert_behaviour.execute(params![run_id, time, behaviour.to_string(), n])?;
+        }
+    }
+    Ok(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::tests::{small_builder, temp_path};
+
+    fn count(db: &ResultsDatabase, table: &str, run_id: i64) -> i64 {
+        db.connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {table} WHERE run_id = ?1"),
+                [run_id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    fn runner(seed: u64) -> Runner {
+        let mut runner = Runner::new(small_builder().build().unwrap()).with_seed(seed);
+        runner.run().unwrap();
+        runner
+    }
+
+    #[test]
+    fn runs_are_appended_with_their_rows() {
+        let path = temp_path(".sqlite");
+        let mut db = ResultsDatabase::open(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        let first = db.append_run("control", &runner(1), &"first").unwrap();
+        drop(db);
+
+        // Reopening an existing database keeps its runs
+        let mut db = ResultsDatabase::open(&path).unwrap();
+        let second = db.append_run("treatment", &runner(2), &"second").unwrap();
+        assert_ne!(first, second);
+        let (label, seed, metadata): (String, String, String) = db
+            .connection
+            .query_row(
+                "SELECT label, seed, metadata FROM runs WHERE id = ?1",
+                [second],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (label.as_str(), seed.as_str(), metadata.as_str()),
+            ("treatment", "2", "\"second\"")
+        );
+        for run_id in [first, second] {
+            // 3 agents with 2 beliefs at times 0 to 3, and 3 ticks summarised
+            assert_eq!(count(&db, "activations", run_id), 24);
+            assert_eq!(count(&db, "actions", run_id), 12);
+            assert_eq!(count(&db, "belief_summary", run_id), 6);
+            assert_eq!(count(&db, "belief_names", run_id), 2);
+            assert_eq!(count(&db, "behaviour_names", run_id), 2);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_appends_leave_no_rows() {
+        let path = temp_path(".sqlite");
+        let mut db = ResultsDatabase::open(&path).unwrap();
+        db.connection
+            .execute_batch(
+                "CREATE TRIGGER fail BEFORE INSERT ON actions \
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+        let result = db.append_run("control", &runner(1), &());
+        assert!(matches!(result, Err(ConceptError::Database { .. })));
+        for table in ["runs", "activations", "actions"] {
+            let n: i64 = db
+                .connection
+                .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(n, 0, "{table}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn older_databases_are_migrated_forward() {
+        let path = temp_path(".sqlite");
+        let connection = Connection::open(&path).unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection
+            .execute(
+                "INSERT INTO runs (label, seed, metadata) VALUES ('old', '1', 'null')",
+                [],
+            )
+            .unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        drop(connection);
+
+        let mut db = ResultsDatabase::open(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        let runner = runner(1);
+        let new = db.append_run("new", &runner, &()).unwrap();
+        let uids: Vec<Option<String>> = db
+            .connection
+            .prepare("SELECT run_uid FROM runs ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(uids, vec![None, Some(runner.run_id().to_string())]);
+        assert_eq!(count(&db, "activations", new), 24);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn databases_from_newer_versions_are_rejected() {
+        let path = temp_path(".sqlite");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(matches!(
+            ResultsDatabase::open(&path),
+            Err(ConceptError::DatabaseVersion { version, .. }) if version == SCHEMA_VERSION + 1
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,133 +1,3008 @@
-use std::collections::HashMap;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
 
-use anyhow::Result;
-use belief_spread::{update_activation_for_all_beliefs_for_agent, AgentPtr, BehaviourPtr, SimTime};
-use log::info;
-use rand::Rng;
+use belief_spread::{AgentPtr, BehaviourPtr, BeliefPtr, SimTime};
+use log::{info, warn};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use serde::Serialize;
+use uuid::Uuid;
 
-use crate::{json::OutputSpecs, Configuration};
+use crate::{
+    actions_log::ActionsLog,
+    agent_filter::AgentFiltering,
+    bounds::{set_bounded_activation, update_activations, BoundsPolicy},
+    configuration::{
+        agents_from_specs, validate_agents, Configuration, FriendNormalization, FriendPruning,
+        HistoryTrimming, RelationshipScaling,
+    },
+    deltas::DeltaGeneration,
+    error::{ConceptError, ValidationIssue, ValidationWarning},
+    events::{EventLedger, EventTotals, TickEvents},
+    influence::{Influence, InfluenceLog},
+    initialization::ActivationInitialization,
+    input_summary::InputSummary,
+    interventions::CarriedActivations,
+    json::{
+        AgentSpec, ModelNames, OutputSpec, OutputSpecs, StatWeighting, SummaryFormat,
+        SummaryOptions, SummaryResults, SummaryWriter,
+    },
+    memory::PeakRss,
+    network,
+    panel::{self, PanelSpec},
+    perception::{PerceptionInputs, PerceptionNetwork},
+    performance_relationships::{PrsMatrix, PrsModifications},
+    precision::Precision,
+    restriction::Restriction,
+    sampling::OutputSampling,
+    scoring::{compute_behaviour_scores_from, ActivationCache},
+    selection::{ActionSelection, LinearSelection},
+    sink::{CompressionChoice, OutputSettings},
+    snapshot::{
+        agent_spec, checkpoint_path, shard_file_name, HistoryRange, Shard, ShardIndex,
+        SimulationSnapshot, SnapshotRef,
+    },
+    stability::{StabilityOptions, StabilityReport},
+    thresholds::{ThresholdCrossings, ThresholdMetrics},
+    verify::InputFile,
+    warnings::{WarningCounter, WarningKind},
+};
+
+/// The number of [Agent]s converted at a time for each shard written by
+/// [Runner::write_snapshot_shards].
+const SHARD_BATCH_SIZE: usize = 1024;
+
+/// The number of batches waiting to be written to each shard.
+const SHARD_QUEUE_LEN: usize = 2;
+
+/// The number of ticks summarised at a time when writing the output, unless
+/// set by [Runner::with_summary_window].
+pub const DEFAULT_SUMMARY_WINDOW: usize = 256;
+
+/// The fewest ticks between the prunings of the history of the [Agent]s set
+/// by [Runner::with_retained_ticks], as each rebuilds every [Agent].
+pub const MIN_PRUNE_INTERVAL: SimTime = 8;
+
+/// The number of ticks between samples of the resident set size.
+const RSS_SAMPLE_INTERVAL: SimTime = 10;
+
+/// The number of agents whose activations are computed or whose behaviours
+/// are scored at a time when a tick is simulated in parallel, bounding the
+/// memory of what is copied out of them.
+const TICK_CHUNK: usize = 16_384;
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunStatus {
+    /// Every tick up to the end time was simulated.
+    Completed,
+    /// The run was cancelled before the end time.
+    Cancelled,
+}
+
+/// The time spent in each phase of a run, in seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTimings {
+    /// Updating the activations of the [Agent]s.
+    pub perceive_beliefs: f64,
+    /// Selecting the actions of the [Agent]s.
+    pub perform_actions: f64,
+    /// Computing and writing the output.
+    pub output: f64,
+}
+
+/// What happened during a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunOutcome {
+    /// The identifier of the run.
+    pub run_id: String,
+    /// The [fingerprint](crate::fingerprint) of the configuration of the
+    /// run, if it was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// How the run ended.
+    pub status: RunStatus,
+    /// The last tick simulated.
+    pub last_tick: SimTime,
+    /// The time of the initial activations of the [Agent]s, before the first
+    /// tick simulated.
+    pub time_origin: SimTime,
+    /// The number of ticks simulated during the run.
+    pub n_ticks: SimTime,
+    /// The number of [Agent]s.
+    pub n_agents: usize,
+    /// The number of [Belief]s.
+    pub n_beliefs: usize,
+    /// The number of [Behaviour]s.
+    pub n_behaviours: usize,
+    /// The seed of the random number generator.
+    pub seed: u64,
+    /// The precision of the activations copied out of the [Agent]s.
+    pub precision: Precision,
+    /// How activations computed outside of [-1, 1] were brought within it.
+    pub bounds_policy: BoundsPolicy,
+    /// The absolute value an activation had to exceed to be counted as
+    /// nonzero in the output.
+    pub activation_threshold: f64,
+    /// How the [Agent]s were weighted in the weighted statistics of the
+    /// output, if they were computed.
+    pub weighting: Option<StatWeighting>,
+    /// How the panel of [Agent]s was sampled, if one was exported.
+    pub panel: Option<PanelSpec>,
+    /// The first ticks at which the [Runner::with_threshold_metrics] were
+    /// reached, if any were set.
+    pub threshold_crossings: Option<ThresholdCrossings>,
+    /// The stability of the final ticks, if [Runner::with_stability] was
+    /// set.
+    pub stability: Option<StabilityReport>,
+    /// The interval between the ticks written to the outputs, where 1 is
+    /// every tick.
+    pub output_every: usize,
+    /// The format the output was written in.
+    pub summary_format: SummaryFormat,
+    /// The events of every tick simulated during the run, added up, if
+    /// [Runner::with_event_ledger] was set.
+    pub events: Option<EventTotals>,
+    /// How the history of the [Agent]s was cut as they were loaded, if it
+    /// was.
+    pub history_trimming: Option<HistoryTrimming>,
+    /// How the friend weights of each [Agent] were normalized.
+    pub friend_normalization: FriendNormalization,
+    /// How the friends of the [Agent]s were pruned, if they were.
+    pub friend_pruning: Option<FriendPruning>,
+    /// How the performance relationships loaded were modified, if they were.
+    pub prs_modifications: Option<PrsModifications>,
+    /// How the relationships between the [Belief]s were scaled, if they
+    /// were.
+    pub relationship_scaling: Option<RelationshipScaling>,
+    /// The warnings from the optional checks of the inputs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub validation_warnings: Vec<ValidationWarning>,
+    /// The ticks of the activations and actions written to the snapshots,
+    /// if the history before the run or outside of the output window was
+    /// left out of them.
+    pub snapshot_history: Option<HistoryRange>,
+    /// The number of ticks before the last that the [Agent]s kept the
+    /// activations and actions of, if older ticks were pruned.
+    pub retained_ticks: Option<SimTime>,
+    /// The delta given to [Agent]s without one for a [Belief], if set.
+    pub default_delta: Option<f64>,
+    /// How the compression of the output file was chosen, if it was chosen
+    /// from an estimate of its size. The [Runner] leaves this unset.
+    pub auto_compression: Option<CompressionChoice>,
+    /// How the deltas missing from the [Agent]s were generated, if any
+    /// [Belief] has a distribution of deltas.
+    pub delta_generation: Option<DeltaGeneration>,
+    /// How the initial activations of the [Agent]s were drawn from the
+    /// summary of a prior run, if they were.
+    pub activation_initialization: Option<ActivationInitialization>,
+    /// Which [Agent]s were kept by filters, if there were any.
+    pub agent_filtering: Option<AgentFiltering>,
+    /// How the model was restricted to a subset of its [Belief]s and
+    /// [Behaviour]s, if it was.
+    pub restriction: Option<Restriction>,
+    /// Summary statistics of the deltas, friendships and performance
+    /// relationships loaded.
+    pub inputs: InputSummary,
+    /// The input files read, with their checksums, so the outputs can be
+    /// checked against the inputs later.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub input_files: Vec<InputFile>,
+    /// The time spent in each phase.
+    pub timings: PhaseTimings,
+    /// The largest resident set size sampled in bytes, after loading, every
+    /// few ticks and after writing the output, or [None] if it cannot be
+    /// read on this platform.
+    pub peak_rss_bytes: Option<u64>,
+    /// The files written.
+    pub artifacts: Vec<PathBuf>,
+}
 
 pub struct Runner {
     pub config: Box<Configuration>,
+    pub action_selection: Box<dyn ActionSelection>,
+    /// The last tick simulated.
+    time: SimTime,
+    /// The seed of the random number generator.
+    seed: u64,
+    /// The fingerprint of the configuration of the run, written to the
+    /// output and the [RunOutcome].
+    fingerprint: Option<String>,
+    /// The random number generator used to select actions.
+    rng: ChaCha8Rng,
+    /// The time spent in each phase since the timings were last reset.
+    timings: PhaseTimings,
+    /// The largest resident set size sampled since the timings were last
+    /// reset.
+    rss: PeakRss,
+    /// How the output is computed, including the precision of the
+    /// activations copied out of the [Agent]s.
+    summary: SummaryOptions,
+    /// The number of ticks summarised at a time when writing the output.
+    summary_window: usize,
+    /// The format the output is written in.
+    summary_format: SummaryFormat,
+    /// The panel of [Agent]s to export with the output, and where to.
+    panel: Option<(PanelSpec, OutputSettings)>,
+    /// Whether the panel has the names of the [Belief]s and [Behaviour]s
+    /// beside their UUIDs.
+    panel_names: bool,
+    /// The levels whose crossing times are found when writing the output.
+    thresholds: ThresholdMetrics,
+    /// How the stability of the final ticks is measured when writing the
+    /// output, if it is.
+    stability: Option<StabilityOptions>,
+    /// The interval between the ticks written to the outputs.
+    output_every: usize,
+    /// How activations computed outside of [-1, 1] are brought within it.
+    bounds_policy: BoundsPolicy,
+    /// The paths of the network snapshots written so far.
+    network_snapshots_written: Vec<PathBuf>,
+    /// The interval in ticks between the checkpoints written during the
+    /// run, and where they are written, if they are.
+    checkpoints: Option<(SimTime, OutputSettings)>,
+    /// The paths of the checkpoints written so far.
+    checkpoints_written: Vec<PathBuf>,
+    /// The log the actions of each tick are appended to as it completes.
+    actions_log: Option<ActionsLog>,
+    /// The events of each tick simulated, if they are recorded.
+    event_ledger: Option<EventLedger>,
+    /// The warnings about the agents of the tick being simulated, logged at
+    /// the end of it.
+    warnings: WarningCounter,
+    /// The log the influences on the activations of each tick are written
+    /// to as they are perceived.
+    influence_log: Option<InfluenceLog>,
+    /// The buffer of the influences on the activations of each agent.
+    influences: Vec<Influence>,
+    /// Whether the snapshots leave out the activations and actions before
+    /// the start time.
+    new_history_only: bool,
+    /// The number of ticks up to the last whose activations and actions are
+    /// written to the snapshots, if not every tick is.
+    output_window: Option<SimTime>,
+    /// The number of ticks before the last whose activations and actions
+    /// the [Agent]s keep, if older ticks are pruned.
+    retained_ticks: Option<SimTime>,
+    /// The summary of each tick of the run pruned from the [Agent]s, taken
+    /// before it was pruned.
+    summarised: BTreeMap<SimTime, OutputSpec>,
+    /// The activations of every agent at the tick whose actions are being
+    /// performed.
+    activations: Activations,
+    /// The buffer used to score the behaviours of each agent.
+    scores: Vec<f64>,
+    /// The buffer of the scores in the canonical order of the behaviours,
+    /// which are passed to the [ActionSelection].
+    canonical_scores: Vec<f64>,
+    /// The threads each tick is simulated on.
+    threads: TickThreads,
+    /// What perception reads that does not change during a run, copied out
+    /// of the agents when a tick is first simulated in parallel.
+    perception: Option<PerceptionNetwork>,
+    /// The buffer of what perception reads at a tick.
+    perception_inputs: PerceptionInputs,
+    /// The buffer of the activations computed for a chunk of agents.
+    perceived: Vec<f64>,
+    /// The activations interventions replaced those carried into the tick
+    /// being simulated with.
+    carried: CarriedActivations,
+    /// The buffer of the canonical scores of a chunk of agents.
+    chunk_scores: Vec<f64>,
 }
 
 impl Runner {
-    pub fn run(&mut self) -> Result<()> {
+    /// Create a new [Runner] for a [Configuration], using [LinearSelection]
+    /// and the seed of the [Configuration].
+    pub fn new(config: Configuration) -> Self {
+        let seed = config.seed;
+        Self {
+            time: config.start_time - 1,
+            config: Box::new(config),
+            action_selection: Box::new(LinearSelection),
+            seed,
+            fingerprint: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            timings: PhaseTimings::default(),
+            rss: PeakRss::default(),
+            summary: SummaryOptions::default(),
+            summary_window: DEFAULT_SUMMARY_WINDOW,
+            summary_format: SummaryFormat::default(),
+            panel: None,
+            panel_names: false,
+            thresholds: ThresholdMetrics::default(),
+            stability: None,
+            output_every: 1,
+            bounds_policy: BoundsPolicy::default(),
+            network_snapshots_written: Vec::new(),
+            checkpoints: None,
+            checkpoints_written: Vec::new(),
+            actions_log: None,
+            event_ledger: None,
+            warnings: WarningCounter::new(),
+            influence_log: None,
+            influences: Vec::new(),
+            new_history_only: false,
+            output_window: None,
+            retained_ticks: None,
+            summarised: BTreeMap::new(),
+            activations: Activations::F64(ActivationCache::default()),
+            scores: Vec::new(),
+            canonical_scores: Vec::new(),
+            threads: TickThreads::default(),
+            perception: None,
+            perception_inputs: PerceptionInputs::default(),
+            perceived: Vec::new(),
+            carried: CarriedActivations::default(),
+            chunk_scores: Vec::new(),
+        }
+    }
+
+    /// Select actions with an [ActionSelection].
+    pub fn with_action_selection(mut self, action_selection: Box<dyn ActionSelection>) -> Self {
+        self.action_selection = action_selection;
+        self
+    }
+
+    /// Seed the random number generator, so that runs are reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// Tag the output and the [RunOutcome] with the
+    /// [fingerprint](crate::fingerprint) of the configuration of the run.
+    pub fn with_fingerprint(mut self, fingerprint: String) -> Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
+    /// Copy activations out of the [Agent]s at a [Precision], to score
+    /// behaviours and compute the output.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.summary.precision = precision;
+        self.activations = match precision {
+            Precision::F64 => Activations::F64(ActivationCache::default()),
+            Precision::F32 => Activations::F32(ActivationCache::default()),
+        };
+        self
+    }
+
+    /// Summarise and write the output `window` ticks at a time, so that
+    /// only the summaries of that many ticks are held in memory at once.
+    pub fn with_summary_window(mut self, window: usize) -> Self {
+        self.summary_window = window.max(1);
+        self
+    }
+
+    /// Write the output in a [SummaryFormat] other than JSON.
+    pub fn with_summary_format(mut self, format: SummaryFormat) -> Self {
+        self.summary_format = format;
+        self
+    }
+
+    /// Count only activations with an absolute value greater than
+    /// `threshold` as nonzero in the output, so that activations left a
+    /// rounding error away from zero by perception are not counted.
+    pub fn with_activation_threshold(mut self, threshold: f64) -> Self {
+        self.summary.activation_threshold = threshold;
+        self
+    }
+
+    /// Also write the mean and standard deviation of the activations with
+    /// each [Agent] weighted by a [StatWeighting].
+    pub fn with_weighted_stats(mut self, weighting: StatWeighting) -> Self {
+        self.summary.weighting = Some(weighting);
+        self
+    }
+
+    /// Also write the [ActionAssortativity](crate::json::ActionAssortativity)
+    /// of the friendships at each tick.
+    pub fn with_action_assortativity(mut self) -> Self {
+        self.summary.action_assortativity = true;
+        self
+    }
+
+    /// Export the activations and actions of a panel of [Agent]s sampled by a
+    /// [PanelSpec] with [Runner::write_panel] after the output is written by
+    /// [Runner::run].
+    pub fn with_panel(mut self, spec: PanelSpec, settings: OutputSettings) -> Self {
+        self.panel = Some((spec, settings));
+        self
+    }
+
+    /// Write the name of each [Belief] and [Behaviour] beside its UUID in
+    /// the panel, in `belief_name` and `behaviour_name` columns.
+    pub fn with_panel_names(mut self) -> Self {
+        self.panel_names = true;
+        self
+    }
+
+    /// Find the first ticks at which the mean activations of [Belief]s and
+    /// the fractions of [Agent]s performing [Behaviour]s reach
+    /// [ThresholdMetrics], from the summary of each tick as the output is
+    /// written.
+    pub fn with_threshold_metrics(mut self, metrics: ThresholdMetrics) -> Self {
+        self.thresholds = metrics;
+        self
+    }
+
+    /// Measure how much the mean activations of [Belief]s and the numbers of
+    /// performers of [Behaviour]s vary over the final ticks, from the summary
+    /// of each tick as the output is written, logging whether each
+    /// converged.
+    pub fn with_stability(mut self, options: StabilityOptions) -> Self {
+        self.stability = Some(options);
+        self
+    }
+
+    /// Write only every `every`th tick from the start time, and the last
+    /// tick, to the summary output and the panel. The [Agent]s keep every
+    /// tick, so snapshots are unaffected.
+    pub fn with_output_every(mut self, every: usize) -> Self {
+        self.output_every = every;
+        self
+    }
+
+    /// Bring activations computed outside of [-1, 1] within it by a
+    /// [BoundsPolicy], rather than clamping them.
+    pub fn with_bounds_policy(mut self, policy: BoundsPolicy) -> Self {
+        self.bounds_policy = policy;
+        self
+    }
+
+    /// Append the actions of each tick to an [ActionsLog] as it completes.
+    ///
+    /// A log that is not empty must end at the tick before the first tick
+    /// simulated, so a log that is resumed must be from the same run as the
+    /// state the [Runner] is [restored](Runner::restore) from.
+    pub fn with_actions_log(mut self, log: ActionsLog) -> Self {
+        self.actions_log = Some(log);
+        self
+    }
+
+    /// Record the [TickEvents] of each tick simulated in an [EventLedger].
+    pub fn with_event_ledger(mut self) -> Self {
+        self.event_ledger = Some(EventLedger::default());
+        self
+    }
+
+    /// The events of each tick simulated, if [Runner::with_event_ledger] was
+    /// set.
+    pub fn event_ledger(&self) -> Option<&EventLedger> {
+        self.event_ledger.as_ref()
+    }
+
+    /// Write the [EventLedger] as CSV to a file described by
+    /// [OutputSettings], if [Runner::with_event_ledger] was set.
+    ///
+    /// # Returns
+    /// Whether the ledger was written.
+    pub fn write_event_ledger(&self, settings: &OutputSettings) -> Result<bool, ConceptError> {
+        let Some(ledger) = &self.event_ledger else {
+            return Ok(false);
+        };
+        write_file(settings, |w| ledger.write_csv(w))?;
+        Ok(true)
+    }
+
+    /// Write how much of each activation came from each [Agent]'s friends
+    /// and from its own delta to an [InfluenceLog] as each tick is
+    /// perceived.
+    ///
+    /// The log is finished by [Runner::run], or by
+    /// [Runner::finish_influence_log].
+    pub fn with_influence_log(mut self, log: InfluenceLog) -> Self {
+        self.influence_log = Some(log);
+        self
+    }
+
+    /// Finish writing the [InfluenceLog], if there is one.
+    ///
+    /// # Returns
+    /// The path of the log, if there was one.
+    pub fn finish_influence_log(&mut self) -> Result<Option<PathBuf>, ConceptError> {
+        self.influence_log
+            .take()
+            .map(InfluenceLog::finish)
+            .transpose()
+    }
+
+    /// Leave the activations and actions before the start time out of the
+    /// snapshots, so a run continuing another only writes its own ticks.
+    ///
+    /// The deltas and friends are still written, and the snapshots note the
+    /// [HistoryRange] they include. The last tick is always included, so
+    /// the snapshot can be continued from, but a full history is only had
+    /// by combining it with the snapshot the run started from.
+    pub fn with_new_history_only(mut self) -> Self {
+        self.new_history_only = true;
+        self
+    }
+
+    /// Write only the activations and actions of the last `ticks` ticks to
+    /// the snapshots, and always the last tick, so they stay small however
+    /// long the run.
+    ///
+    /// As with [Runner::with_new_history_only], the snapshots note the
+    /// [HistoryRange] they include, and if both are set the snapshots
+    /// include the ticks in both.
+    pub fn with_output_window(mut self, ticks: SimTime) -> Self {
+        self.output_window = Some(ticks.max(1));
+        self
+    }
+
+    /// Prune the activations and actions of the [Agent]s from more than
+    /// `ticks` ticks before the last simulated, so the memory of a run does
+    /// not grow with its length.
+    ///
+    /// Perception reads the tick before, so at least 1 tick is kept. The
+    /// [Agent]s are rebuilt to free their history, so they are pruned only
+    /// every `ticks` ticks, or every [MIN_PRUNE_INTERVAL] ticks if that is
+    /// more, and hold up to that many more ticks in between.
+    ///
+    /// Each tick of the run is summarised before it is pruned, so the
+    /// output is the same as that of a run that keeps every tick. The
+    /// snapshots, the panel and the activations and actions read from the
+    /// [Runner] only have the ticks still held.
+    pub fn with_retained_ticks(mut self, ticks: SimTime) -> Self {
+        self.retained_ticks = Some(ticks.max(1));
+        self
+    }
+
+    /// Simulate each tick on a pool of `threads` threads, or on the calling
+    /// thread alone if `threads` is 1, rather than on the global rayon pool.
+    ///
+    /// The activations are computed and the behaviours scored in parallel,
+    /// and the results are identical whatever the number of threads.
+    ///
+    /// # Returns
+    /// The [Runner], or an error if the threads could not be started.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, ConceptError> {
+        self.threads = if threads <= 1 {
+            TickThreads::Serial
+        } else {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|source| ConceptError::Threads { threads, source })?;
+            TickThreads::Pool(Arc::new(pool))
+        };
+        Ok(self)
+    }
+
+    /// The number of threads each tick is simulated on.
+    pub fn threads(&self) -> usize {
+        match &self.threads {
+            TickThreads::Serial => 1,
+            TickThreads::Global => rayon::current_num_threads(),
+            TickThreads::Pool(pool) => pool.current_num_threads(),
+        }
+    }
+
+    /// Write a [SimulationSnapshot] after every tick that is a multiple of
+    /// `every`, so a run that dies can be restored from the last one.
+    ///
+    /// Each checkpoint is written with the [OutputSettings], at the
+    /// [checkpoint_path] of its tick, and records the tick it was taken
+    /// after, so it is restored at the right time.
+    pub fn with_checkpoints(mut self, every: SimTime, settings: OutputSettings) -> Self {
+        self.checkpoints = Some((every.max(1), settings));
+        self
+    }
+
+    /// The ticks of the activations and actions written to the snapshots,
+    /// if [Runner::with_new_history_only] or [Runner::with_output_window]
+    /// was set.
+    pub fn snapshot_history(&self) -> Option<HistoryRange> {
+        let new = self
+            .new_history_only
+            .then(|| self.config.start_time.min(self.time));
+        let window = self
+            .output_window
+            .map(|ticks| (self.time + 1).saturating_sub(ticks));
+        let start = match (new, window) {
+            (Some(new), Some(window)) => Some(new.max(window)),
+            (new, window) => new.or(window),
+        };
+        start.map(|start| HistoryRange {
+            start,
+            end: self.time,
+        })
+    }
+
+    /// The last tick simulated, or the tick before the start time if none
+    /// have been.
+    pub fn time(&self) -> SimTime {
+        self.time
+    }
+
+    /// The identifier of the run.
+    pub fn run_id(&self) -> &str {
+        &self.config.run_id
+    }
+
+    /// The input files of the run, as they were when it was configured.
+    pub fn input_files(&self) -> &[InputFile] {
+        &self.config.input_files
+    }
+
+    /// The seed the random number generator was created from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The number of [Agent]s simulated.
+    pub fn n_agents(&self) -> usize {
+        self.config.agents.len()
+    }
+
+    /// The [Agent]s simulated, in the order of the model.
+    ///
+    /// The [Agent]s are rebuilt when [Runner::restore] is called and as
+    /// their history is pruned by [Runner::with_retained_ticks], so they
+    /// are best found again by UUID rather than kept across ticks.
+    pub fn agents(&self) -> &[AgentPtr] {
+        &self.config.agents
+    }
+
+    /// The [Belief]s of the model, in the order of the model.
+    pub fn beliefs(&self) -> &[BeliefPtr] {
+        &self.config.beliefs
+    }
+
+    /// The [Behaviour]s of the model, in the order of the model.
+    pub fn behaviours(&self) -> &[BehaviourPtr] {
+        &self.config.behaviours
+    }
+
+    /// The first tick of the run.
+    pub fn start_time(&self) -> SimTime {
+        self.config.start_time
+    }
+
+    /// The last tick of the run.
+    pub fn end_time(&self) -> SimTime {
+        self.config.end_time
+    }
+
+    /// The time spent in each phase since the run started.
+    pub fn timings(&self) -> PhaseTimings {
+        self.timings
+    }
+
+    /// Simulate the remaining ticks up to the end time, and then write the
+    /// output, if the [Configuration] has one.
+    pub fn run(&mut self) -> Result<RunOutcome, ConceptError> {
+        self.run_with_cancel(&AtomicBool::new(false))
+    }
+
+    /// Simulate the remaining ticks up to the end time, stopping early if
+    /// `token` is set, and then write the output for the ticks simulated.
+    ///
+    /// The token is checked before each tick, so it may be set from another
+    /// thread (such as a signal handler) while the run is in progress.
+    ///
+    /// # Returns
+    /// How the run ended, or an error if a tick or the output failed.
+    pub fn run_with_cancel(&mut self, token: &AtomicBool) -> Result<RunOutcome, ConceptError> {
         info!("Starting concept");
+        info!("Run id: {}", self.config.run_id);
         info!("n beliefs: {}", self.config.beliefs.len());
         info!("n behaviours: {}", self.config.behaviours.len());
         info!("n agents: {}", self.config.agents.len());
+        info!("Time origin: {}", self.config.time_origin);
         info!("Start time: {}", self.config.start_time);
         info!("End time: {}", self.config.end_time);
-        self.tick_between(self.config.start_time, self.config.end_time);
+        self.timings = PhaseTimings::default();
+        self.rss = PeakRss::default();
+        self.rss.sample("after loading");
+        let first_tick = self.time;
+        let first_event = self.event_ledger.as_ref().map_or(0, |l| l.ticks().len());
+        let n_network_snapshots = self.network_snapshots_written.len();
+        let n_checkpoints = self.checkpoints_written.len();
+        let status = self.run_until_cancelled(self.config.end_time, token)?;
+        if status == RunStatus::Cancelled {
+            warn!("Cancelled after day {}", self.time);
+        }
         info!("Ending concept");
-        self.serialize_output()?;
-        Ok(())
+        let mut artifacts: Vec<PathBuf> = self.config.output_path.iter().cloned().collect();
+        artifacts.extend(
+            self.actions_log
+                .as_ref()
+                .map(|log| log.path().to_path_buf()),
+        );
+        artifacts.extend_from_slice(&self.network_snapshots_written[n_network_snapshots..]);
+        artifacts.extend_from_slice(&self.checkpoints_written[n_checkpoints..]);
+        artifacts.extend(self.finish_influence_log()?);
+        let results = if self.config.output.is_some() {
+            self.serialize_output()?
+        } else if !self.thresholds.is_empty() || self.stability.is_some() {
+            // The crossings and stability are found from the summary
+            self.serialize_output_to(io::sink())?
+        } else {
+            SummaryResults {
+                threshold_crossings: ThresholdCrossings::new(&self.thresholds),
+                stability: None,
+            }
+        };
+        if let Some(stability) = &results.stability {
+            stability.log();
+        }
+        if let Some((spec, settings)) = &self.panel {
+            let started = Instant::now();
+            artifacts.extend(self.write_panel(spec, settings)?);
+            self.timings.output += started.elapsed().as_secs_f64();
+        }
+        self.rss.sample("after writing the output");
+        Ok(RunOutcome {
+            run_id: self.config.run_id.clone(),
+            fingerprint: self.fingerprint.clone(),
+            status,
+            last_tick: self.time,
+            time_origin: self.config.time_origin,
+            n_ticks: self.time - first_tick,
+            n_agents: self.config.agents.len(),
+            n_beliefs: self.config.beliefs.len(),
+            n_behaviours: self.config.behaviours.len(),
+            seed: self.seed,
+            precision: self.summary.precision,
+            bounds_policy: self.bounds_policy,
+            activation_threshold: self.summary.activation_threshold,
+            weighting: self.summary.weighting,
+            panel: self.panel.as_ref().map(|(spec, _)| *spec),
+            threshold_crossings: (!self.thresholds.is_empty())
+                .then_some(results.threshold_crossings),
+            stability: results.stability,
+            output_every: self.output_every,
+            summary_format: self.summary_format,
+            events: self
+                .event_ledger
+                .as_ref()
+                .map(|ledger| EventTotals::of(&ledger.ticks()[first_event..])),
+            history_trimming: self.config.history_trimming,
+            friend_normalization: self.config.friend_normalization,
+            friend_pruning: self.config.friend_pruning,
+            prs_modifications: self.config.prs_modifications.clone(),
+            relationship_scaling: self.config.relationship_scaling,
+            validation_warnings: self.config.validation_warnings.clone(),
+            snapshot_history: self.snapshot_history(),
+            retained_ticks: self.retained_ticks,
+            default_delta: self.config.default_delta,
+            auto_compression: None,
+            delta_generation: self.config.delta_generation.clone(),
+            activation_initialization: self.config.activation_initialization.clone(),
+            agent_filtering: self.config.agent_filtering.clone(),
+            restriction: self.config.restriction.clone(),
+            inputs: self.config.input_summary.clone(),
+            input_files: self.config.input_files.clone(),
+            timings: self.timings,
+            peak_rss_bytes: self.rss.peak(),
+            artifacts,
+        })
     }
 
-    pub fn serialize_output(&mut self) -> Result<()> {
-        info!("Preparing to dump output");
-        let specs: OutputSpecs = OutputSpecs::from_agents(
-            &self.config.agents,
+    /// Simulate the tick after [Runner::time], as [Runner::run] simulates
+    /// each tick, without writing the output, so a caller can read the
+    /// [Agent]s between ticks.
+    ///
+    /// # Returns
+    /// The tick simulated, or an error if it failed.
+    pub fn step(&mut self) -> Result<SimTime, ConceptError> {
+        self.run_until(self.time + 1)?;
+        Ok(self.time)
+    }
+
+    /// Simulate every tick after [Runner::time] up to and including `end`.
+    pub fn run_until(&mut self, end: SimTime) -> Result<(), ConceptError> {
+        self.run_until_cancelled(end, &AtomicBool::new(false))
+            .map(|_| ())
+    }
+
+    fn run_until_cancelled(
+        &mut self,
+        end: SimTime,
+        token: &AtomicBool,
+    ) -> Result<RunStatus, ConceptError> {
+        self.check_actions_log()?;
+        self.write_network_snapshots()?;
+        for t in (self.time + 1)..=end {
+            if token.load(Ordering::Relaxed) {
+                return Ok(RunStatus::Cancelled);
+            }
+            self.tick(t)?;
+            self.time = t;
+            self.prune_history();
+            self.append_actions_log()?;
+            self.write_network_snapshots()?;
+            self.write_checkpoint()?;
+            if t % RSS_SAMPLE_INTERVAL == 0 {
+                self.rss.sample(&format!("after day {t}"));
+            }
+        }
+        Ok(RunStatus::Completed)
+    }
+
+    /// The first tick of the run that was not summarised as it was pruned.
+    fn first_unsummarised(&self) -> SimTime {
+        self.summarised
+            .last_key_value()
+            .map_or(self.config.start_time, |(&time, _)| time + 1)
+    }
+
+    /// Summarise and then prune the ticks the [Agent]s no longer need to
+    /// hold, if [Runner::with_retained_ticks] was set and it is time to.
+    fn prune_history(&mut self) {
+        let Some(ticks) = self.retained_ticks else {
+            return;
+        };
+        let simulated = self.time + 1 - self.config.start_time;
+        if !simulated.is_multiple_of(ticks.max(MIN_PRUNE_INTERVAL)) {
+            return;
+        }
+        let held = HistoryRange {
+            start: self.time.saturating_sub(ticks),
+            end: self.time,
+        };
+        let from = self.first_unsummarised();
+        if from < held.start {
+            let pruned = OutputSpecs::from_agents_with_options(
+                &self.config.agents,
+                &self.config.beliefs,
+                from,
+                held.start - 1,
+                self.summary,
+            );
+            self.summarised.extend(pruned.data);
+        }
+        info!(
+            "Day {} - pruning the ticks before {}",
+            self.time, held.start
+        );
+        let specs: Vec<AgentSpec> = (0..self.config.agents.len())
+            .map(|i| agent_spec(&self.config, i, Some(held)))
+            .collect();
+        let (agents, caps) = agents_from_specs(
+            &specs,
             &self.config.beliefs,
-            self.config.start_time,
-            self.config.end_time,
+            &self.config.behaviours,
+            self.config.index(),
         );
+        (self.config.agents, self.config.activation_caps) = (agents, caps);
+        self.perception = None;
+    }
+
+    /// Check that the [ActionsLog] ends at [Runner::time], unless it is
+    /// empty, when it starts from there.
+    fn check_actions_log(&self) -> Result<(), ConceptError> {
+        let Some(log) = &self.actions_log else {
+            return Ok(());
+        };
+        if log
+            .last_tick()
+            .is_some_and(|last_tick| last_tick != self.time)
+        {
+            return Err(ConceptError::ActionsLogMismatch {
+                path: log.path().to_path_buf(),
+                last_tick: log.last_tick(),
+                time: self.time,
+            });
+        }
+        Ok(())
+    }
 
-        info!("Writing output to file");
-        let writer = std::io::BufWriter::new(&mut self.config.output_file);
-        let writer_zstd = zstd::stream::write::Encoder::new(writer, 3)?.auto_finish();
-        serde_json::to_writer(writer_zstd, &specs)?;
+    /// Append the actions of [Runner::time] to the [ActionsLog], if there
+    /// is one.
+    fn append_actions_log(&mut self) -> Result<(), ConceptError> {
+        let Some(log) = &mut self.actions_log else {
+            return Ok(());
+        };
+        let started = Instant::now();
+        log.append_tick(self.time, &self.config.agents)
+            .map_err(|source| ConceptError::Io {
+                path: log.path().to_path_buf(),
+                source,
+            })?;
+        self.timings.output += started.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    /// Write a checkpoint of [Runner::time] if it is a multiple of the
+    /// interval between checkpoints.
+    fn write_checkpoint(&mut self) -> Result<(), ConceptError> {
+        let Some((every, settings)) = &self.checkpoints else {
+            return Ok(());
+        };
+        if !self.time.is_multiple_of(*every) {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let settings = OutputSettings {
+            path: checkpoint_path(&settings.path, self.time),
+            compression: settings.compression,
+        };
+        info!("Writing checkpoint to {}", settings.path.display());
+        self.write_snapshot(&settings)?;
+        self.checkpoints_written.push(settings.path);
+        self.timings.output += started.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    /// Write the friendship network for every snapshot up to [Runner::time]
+    /// that has not been written yet.
+    fn write_network_snapshots(&mut self) -> Result<(), ConceptError> {
+        let Some(snapshots) = &mut self.config.network_snapshots else {
+            return Ok(());
+        };
+        let due = snapshots.take_due(self.time);
+        if due.is_empty() {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let snapshots = self.config.network_snapshots.as_ref().unwrap();
+        for time in due {
+            let settings = snapshots.settings(time);
+            info!("Writing network snapshot to {}", settings.path.display());
+            write_file(&settings, |w| network::write_edges(w, &self.config.agents))?;
+            self.network_snapshots_written.push(settings.path);
+        }
+        self.timings.output += started.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    /// Every activation of every [Agent], as `(agent, time, belief,
+    /// activation)` records of UUIDs.
+    ///
+    /// The records are ordered by [Agent] in the order of the model, then by
+    /// time, then by [Belief] in the canonical order of the
+    /// [ModelIndex](crate::collections::ModelIndex).
+    pub fn activations_iter(&self) -> impl Iterator<Item = (Uuid, SimTime, Uuid, f64)> + '_ {
+        let beliefs = self.config.index().canonical_beliefs();
+        self.config.agents.iter().flat_map(move |agent| {
+            let borrowed = agent.borrow();
+            let agent = &*borrowed;
+            let agent_uuid = *agent.uuid();
+            let mut times: Vec<SimTime> = agent.get_activations().keys().cloned().collect();
+            times.sort_unstable();
+            times
+                .into_iter()
+                .flat_map(|time| {
+                    beliefs.iter().filter_map(move |&i| {
+                        let belief = &self.config.beliefs[i];
+                        agent
+                            .get_activation(time, belief)
+                            .map(|v| (agent_uuid, time, *belief.borrow().uuid(), v))
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Every action of every [Agent], as `(agent, time, behaviour)` records
+    /// of UUIDs.
+    ///
+    /// The records are ordered by [Agent] in the order of the model, then by
+    /// time.
+    pub fn actions_iter(&self) -> impl Iterator<Item = (Uuid, SimTime, Uuid)> + '_ {
+        self.config.agents.iter().flat_map(|agent| {
+            let agent = agent.borrow();
+            let agent_uuid = *agent.uuid();
+            let mut actions: Vec<(Uuid, SimTime, Uuid)> = agent
+                .get_actions()
+                .iter()
+                .map(|(&time, behaviour)| (agent_uuid, time, *behaviour.borrow().uuid()))
+                .collect();
+            actions.sort_unstable_by_key(|&(_, time, _)| time);
+            actions
+        })
+    }
+
+    /// Capture the state of the simulation after [Runner::time].
+    ///
+    /// Only the ticks of [Runner::snapshot_history] are included if it is
+    /// set.
+    pub fn snapshot(&self) -> SimulationSnapshot {
+        let history = self.snapshot_history();
+        SimulationSnapshot {
+            time: self.time,
+            agents: (0..self.config.agents.len())
+                .map(|i| agent_spec(&self.config, i, history))
+                .collect(),
+            rng: self.rng.clone(),
+            history,
+        }
+    }
+
+    /// Write a [SimulationSnapshot] of the state of the simulation after
+    /// [Runner::time] as JSON to a [Write].
+    ///
+    /// This gives the same JSON as serializing [Runner::snapshot], but
+    /// converts one [Agent] at a time, so the state of every [Agent] is
+    /// never copied at once.
+    pub fn write_snapshot_to<W: Write>(&self, writer: W) -> Result<(), ConceptError> {
+        let snapshot = SnapshotRef {
+            time: self.time,
+            config: &self.config,
+            rng: &self.rng,
+            history: self.snapshot_history(),
+        };
+        serde_json::to_writer(writer, &snapshot)
+            .map_err(|err| ConceptError::Output { source: err.into() })
+    }
+
+    /// Write a [SimulationSnapshot] to a file described by
+    /// [OutputSettings]. See [Runner::write_snapshot_to].
+    pub fn write_snapshot(&self, settings: &OutputSettings) -> Result<(), ConceptError> {
+        let mut sink = settings.open().map_err(|source| ConceptError::Io {
+            path: settings.path.clone(),
+            source,
+        })?;
+        self.write_snapshot_to(&mut sink)?;
+        sink.finish()
+            .map_err(|source| ConceptError::Output { source })
+    }
+
+    /// Write a [SimulationSnapshot] as `n_shards` files of contiguous
+    /// [Agent]s, each compressed and written by its own thread, and a
+    /// [ShardIndex] listing them.
+    ///
+    /// The index is written to the path of the [OutputSettings], and the
+    /// shards beside it with the number of the shard inserted before the
+    /// extensions, so `snapshot.json.zst` has shards
+    /// `snapshot_000.json.zst`, `snapshot_001.json.zst`, and so on. Every
+    /// file uses the compression of the [OutputSettings]. At least one shard
+    /// is written.
+    ///
+    /// The [Agent]s are converted to [AgentSpec]s on the calling thread, in
+    /// batches handed to the writers, so only a few batches are held in
+    /// memory at once.
+    ///
+    /// # Returns
+    /// The paths of the files written, the index first.
+    pub fn write_snapshot_shards(
+        &self,
+        settings: &OutputSettings,
+        n_shards: usize,
+    ) -> Result<Vec<PathBuf>, ConceptError> {
+        let agents = &self.config.agents;
+        let history = self.snapshot_history();
+        let n_shards = n_shards.max(1);
+        let shard_len = agents.len().div_ceil(n_shards);
+        let ranges: Vec<Range<usize>> = (0..n_shards)
+            .map(|i| {
+                let start = (i * shard_len).min(agents.len());
+                start..(start + shard_len).min(agents.len())
+            })
+            .collect();
+        let shards: Vec<Shard> = ranges
+            .iter()
+            .enumerate()
+            .map(|(i, range)| Shard {
+                path: shard_file_name(&settings.path, i),
+                n_agents: range.len(),
+            })
+            .collect();
+        let shard_settings: Vec<OutputSettings> = shards
+            .iter()
+            .map(|shard| OutputSettings {
+                path: shard.resolve(&settings.path),
+                compression: settings.compression,
+            })
+            .collect();
 
+        thread::scope(|scope| -> Result<(), ConceptError> {
+            let (senders, writers): (Vec<_>, Vec<_>) = shard_settings
+                .iter()
+                .map(|settings| {
+                    let (sender, receiver) = mpsc::sync_channel(SHARD_QUEUE_LEN);
+                    (sender, scope.spawn(move || write_shard(settings, receiver)))
+                })
+                .unzip();
+
+            // Hand out batches in turn, so every writer has work while the
+            // agents are converted
+            'convert: for offset in (0..shard_len).step_by(SHARD_BATCH_SIZE) {
+                for (range, sender) in ranges.iter().zip(&senders) {
+                    let start = (range.start + offset).min(range.end);
+                    let end = (start + SHARD_BATCH_SIZE).min(range.end);
+                    if start == end {
+                        continue;
+                    }
+                    let batch = (start..end)
+                        .map(|i| agent_spec(&self.config, i, history))
+                        .collect();
+                    if sender.send(batch).is_err() {
+                        // The writer failed, and its error is reported below
+                        break 'convert;
+                    }
+                }
+            }
+            drop(senders);
+
+            writers
+                .into_iter()
+                .try_for_each(|writer| writer.join().expect("shard writers do not panic"))
+        })?;
+
+        let index = ShardIndex {
+            time: self.time,
+            rng: self.rng.clone(),
+            shards,
+            history,
+        };
+        let mut sink = settings.open().map_err(|source| ConceptError::Io {
+            path: settings.path.clone(),
+            source,
+        })?;
+        serde_json::to_writer(&mut sink, &index)
+            .map_err(|err| ConceptError::Output { source: err.into() })?;
+        sink.finish()
+            .map_err(|source| ConceptError::Output { source })?;
+
+        Ok(std::iter::once(settings.path.clone())
+            .chain(shard_settings.into_iter().map(|settings| settings.path))
+            .collect())
+    }
+
+    /// Replace the state of the simulation with a [SimulationSnapshot].
+    ///
+    /// The [Agent]s are rebuilt from the snapshot, so the snapshot may come
+    /// from another [Runner] with the same [Belief]s and [Behaviour]s.
+    ///
+    /// # Returns
+    /// An error if the snapshot does not match the model, or cannot be
+    /// continued from, such as one taken after the end time or before the
+    /// tick before the start time.
+    pub fn restore(&mut self, snapshot: SimulationSnapshot) -> Result<(), ConceptError> {
+        let index = self.config.index();
+        let mut report = validate_agents(&snapshot.agents, index, snapshot.time);
+        let before_start = self.config.start_time - 1;
+        if !(before_start..=self.config.end_time).contains(&snapshot.time) {
+            report.extend([ValidationIssue::InvalidResumeTime {
+                time: snapshot.time,
+                before_start,
+                end: self.config.end_time,
+            }]);
+        }
+        report.into_result()?;
+
+        self.perception = None;
+        (self.config.agents, self.config.activation_caps) = agents_from_specs(
+            &snapshot.agents,
+            &self.config.beliefs,
+            &self.config.behaviours,
+            index,
+        );
+        self.time = snapshot.time;
+        self.rng = snapshot.rng;
+        self.summarised.retain(|&time, _| time <= snapshot.time);
         Ok(())
     }
 
-    fn tick_between(&mut self, start: SimTime, end: SimTime) {
-        for t in start..=end {
-            self.tick(t);
+    /// Write the output to the [OutputSink] of the configuration, and then
+    /// finish the sink.
+    ///
+    /// # Returns
+    /// What was found from the summary of each tick, or an error if there
+    /// is no output or it has already been written.
+    pub fn serialize_output(&mut self) -> Result<SummaryResults, ConceptError> {
+        let started = Instant::now();
+        let mut sink = self.config.output.take().ok_or(ConceptError::Output {
+            source: io::Error::other("there is no output, or it has already been written"),
+        })?;
+        let results = self.serialize_output_to(&mut sink)?;
+        sink.finish()
+            .map_err(|source| ConceptError::Output { source })?;
+        self.timings.output += started.elapsed().as_secs_f64();
+        Ok(results)
+    }
+
+    /// Write the activations and actions of a panel of [Agent]s, reading
+    /// only the sampled [Agent]s.
+    ///
+    /// The activations are written to the path of the [OutputSettings], and
+    /// the actions beside it, at [actions_path](panel::actions_path).
+    ///
+    /// # Returns
+    /// The paths of the activations and actions written.
+    pub fn write_panel(
+        &self,
+        spec: &PanelSpec,
+        settings: &OutputSettings,
+    ) -> Result<Vec<PathBuf>, ConceptError> {
+        let positions = spec.sample(self.config.agents.len());
+        info!("Writing a panel of {} agents", positions.len());
+        let sampling = self.output_sampling();
+        let names = self.panel_names.then(|| self.names());
+        write_file(settings, |w| {
+            panel::write_activations(
+                w,
+                &self.config.agents,
+                &self.config.beliefs,
+                self.config.index(),
+                &positions,
+                &sampling,
+                names.as_ref(),
+            )
+        })?;
+        let actions = OutputSettings {
+            path: panel::actions_path(&settings.path),
+            compression: settings.compression,
+        };
+        write_file(&actions, |w| {
+            panel::write_actions(
+                w,
+                &self.config.agents,
+                &positions,
+                &sampling,
+                names.as_ref(),
+            )
+        })?;
+        Ok(vec![settings.path.clone(), actions.path])
+    }
+
+    /// The ticks written to the outputs, up to the last tick simulated.
+    pub(crate) fn output_sampling(&self) -> OutputSampling {
+        OutputSampling {
+            every: self.output_every,
+            start_time: self.config.start_time,
+            end_time: self.time,
         }
     }
 
-    fn tick(&mut self, time: SimTime) {
+    /// The names of the [Belief]s and [Behaviour]s of the model.
+    pub fn names(&self) -> ModelNames {
+        ModelNames::from_model(&self.config.beliefs, &self.config.behaviours)
+    }
+
+    /// The summary statistics of every tick simulated so far, computed in
+    /// memory.
+    pub fn output_specs(&self) -> OutputSpecs {
+        let from = self.first_unsummarised();
+        let mut specs = OutputSpecs::from_agents_with_options(
+            &self.config.agents,
+            &self.config.beliefs,
+            from,
+            self.time,
+            self.summary,
+        );
+        specs.data.extend(
+            self.summarised
+                .iter()
+                .map(|(&time, spec)| (time, spec.clone())),
+        );
+        specs.run_id = Some(self.config.run_id.clone());
+        specs.fingerprint = self.fingerprint.clone();
+        specs.time_origin = Some(self.config.time_origin);
+        specs.names = Some(self.names());
+        specs
+    }
+
+    /// Write the output for the ticks simulated so far to a [Write].
+    ///
+    /// The output is summarised and written [Runner::with_summary_window]
+    /// ticks at a time, followed in JSON by the crossings of the
+    /// [Runner::with_threshold_metrics] if any were set.
+    ///
+    /// # Returns
+    /// What was found from the summary of each tick.
+    pub fn serialize_output_to<W: Write>(&self, writer: W) -> Result<SummaryResults, ConceptError> {
+        info!("Writing output");
+        let names = self.names();
+        SummaryWriter {
+            agents: &self.config.agents,
+            beliefs: &self.config.beliefs,
+            index: self.config.index(),
+            options: self.summary,
+            window: self.summary_window,
+            run_id: Some(&self.config.run_id),
+            fingerprint: self.fingerprint.as_deref(),
+            time_origin: Some(self.config.time_origin),
+            thresholds: &self.thresholds,
+            stability: self.stability,
+            output_every: self.output_every,
+            names: Some(&names),
+            summarised: &self.summarised,
+            format: self.summary_format,
+        }
+        .write(writer, self.config.start_time, self.time)
+        .map_err(|err| ConceptError::Output { source: err.into() })
+    }
+
+    fn tick(&mut self, time: SimTime) -> Result<(), ConceptError> {
+        self.apply_interventions(time);
         info!("Day {time} - perceiving beliefs");
-        self.perceive_beliefs(time);
+        self.warnings.reset();
+        let started = Instant::now();
+        self.perceive_beliefs(time)?;
+        self.timings.perceive_beliefs += started.elapsed().as_secs_f64();
+
         info!("Day {time} - performing actions");
+        let started = Instant::now();
         self.perform_actions(time);
+        self.activations.invalidate();
+        self.timings.perform_actions += started.elapsed().as_secs_f64();
+        if let Some(ledger) = &mut self.event_ledger {
+            ledger.record(TickEvents::from_warnings(time, &self.warnings));
+        }
+        self.warnings.log(&format!("Day {time}"));
+        Ok(())
+    }
+
+    /// Apply the interventions scheduled for `time`, if there are any, in
+    /// the order they were given.
+    fn apply_interventions(&mut self, time: SimTime) {
+        let Some(interventions) = &self.config.interventions else {
+            return;
+        };
+        let applied = interventions.apply(
+            time,
+            &self.config.agents,
+            &self.config.beliefs,
+            &mut self.carried,
+        );
+        if applied > 0 {
+            info!("Day {time} - applied {applied} interventions");
+        }
+    }
+
+    /// Update the activations of every agent at `time`, holding them to the
+    /// floors and ceilings of the agent, and fill the [ActivationCache] with
+    /// them as each agent is updated, while it is still in the CPU cache.
+    ///
+    /// Activations brought within [-1, 1] by the [BoundsPolicy] or held to a
+    /// floor or ceiling are recorded in the [WarningCounter].
+    fn perceive_beliefs(&mut self, time: SimTime) -> Result<(), ConceptError> {
+        self.activations
+            .start(time, self.config.agents.len(), self.config.beliefs.len());
+        if self.threads.parallel() && self.perception.is_none() {
+            self.perception = PerceptionNetwork::new(&self.config);
+            if self.perception.is_none() {
+                warn!("An agent has a friend that is not simulated, so ticks are simulated on one thread");
+                self.threads = TickThreads::Serial;
+            }
+        }
+        match self.perception.take() {
+            Some(network) if self.threads.parallel() => {
+                let result = self.perceive_beliefs_in_parallel(time, &network);
+                self.perception = Some(network);
+                result
+            }
+            network => {
+                self.perception = network;
+                self.perceive_beliefs_serially(time)
+            }
+        }
+    }
+
+    /// Update the activations of every agent at `time` in turn, as
+    /// [Runner::perceive_beliefs].
+    fn perceive_beliefs_serially(&mut self, time: SimTime) -> Result<(), ConceptError> {
+        for i in 0..self.config.agents.len() {
+            let logs = self.influence_log.as_ref().is_some_and(|log| log.logs(i));
+            let bounded = update_activations(
+                &self.config.agents[i],
+                time,
+                &self.config.beliefs,
+                self.bounds_policy,
+                logs.then_some(&mut self.influences),
+                |b| self.carried.get(i, b),
+            )?;
+            self.finish_perception(i, time, bounded)?;
+        }
+        Ok(())
+    }
+
+    /// Update the activations of every agent at `time`, as
+    /// [Runner::perceive_beliefs], computing those of [TICK_CHUNK] agents at
+    /// a time in parallel from a [PerceptionNetwork].
+    fn perceive_beliefs_in_parallel(
+        &mut self,
+        time: SimTime,
+        network: &PerceptionNetwork,
+    ) -> Result<(), ConceptError> {
+        let n_beliefs = self.config.beliefs.len();
+        self.perception_inputs
+            .start(time, &self.config.agents, self.config.index());
+        for first in (0..self.config.agents.len()).step_by(TICK_CHUNK) {
+            let chunk = first..self.config.agents.len().min(first + TICK_CHUNK);
+            self.perception_inputs.load_chunk(
+                first,
+                &self.config.agents[chunk.clone()],
+                &self.config.beliefs,
+                &self.carried,
+            );
+            let (inputs, values) = (&self.perception_inputs, &mut self.perceived);
+            self.threads
+                .install(|| network.perceive_chunk(inputs, values))?;
+            for (k, i) in chunk.enumerate() {
+                let mut bounded = 0;
+                for (b, belief) in self.config.beliefs.iter().enumerate() {
+                    bounded += usize::from(set_bounded_activation(
+                        &self.config.agents[i],
+                        time,
+                        belief,
+                        self.bounds_policy,
+                        self.perceived[k * n_beliefs + b],
+                    )?);
+                }
+                if self.influence_log.as_ref().is_some_and(|log| log.logs(i)) {
+                    self.influences.clear();
+                    network.perceive(&self.perception_inputs, k, |influence| {
+                        self.influences.push(influence)
+                    })?;
+                }
+                self.finish_perception(i, time, bounded)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hold the activations of the `i`th agent at `time` to its floors and
+    /// ceilings, record its warnings, log its influences and add its
+    /// activations to the [ActivationCache], once they have been updated
+    /// with `bounded` brought within [-1, 1].
+    fn finish_perception(
+        &mut self,
+        i: usize,
+        time: SimTime,
+        bounded: usize,
+    ) -> Result<(), ConceptError> {
+        let a = &self.config.agents[i];
+        let beliefs = &self.config.beliefs;
+        let capped = self
+            .config
+            .activation_caps
+            .get(i)
+            .map_or(0, |caps| caps.apply(a, time, beliefs));
+        if bounded + capped > 0 {
+            let uuid = *a.borrow().uuid();
+            self.warnings
+                .record(WarningKind::BoundedActivation, uuid, bounded);
+            self.warnings
+                .record(WarningKind::CappedActivation, uuid, capped);
+        }
+        if let Some(log) = self.influence_log.as_mut().filter(|log| log.logs(i)) {
+            log.write_agent(time, a, beliefs, self.config.index(), &self.influences)
+                .map_err(|source| ConceptError::Io {
+                    path: log.path().clone(),
+                    source,
+                })?;
+        }
+        self.activations.push(a, beliefs);
+        Ok(())
+    }
+
+    /// Select the action of every agent, from the [ActivationCache] filled
+    /// for `time`.
+    ///
+    /// The scores are passed to the [ActionSelection] in the canonical order
+    /// of the behaviours, by UUID, so that ties and sampling do not depend on
+    /// the order of the behaviours in the model. Agents that perform no
+    /// action are recorded in the [WarningCounter].
+    ///
+    /// When the ticks are simulated in parallel, the scores of [TICK_CHUNK]
+    /// agents at a time are computed in parallel, but the actions are still
+    /// selected in turn on the calling thread, drawing from the one random
+    /// number generator in the order of the agents, so the actions do not
+    /// depend on the number of threads.
+    fn perform_actions(&mut self, time: SimTime) {
+        debug_assert_eq!(self.activations.time(), Some(time));
+        let n_behaviours = self.config.behaviours.len();
+        let canonical = self.config.index().canonical_behaviours();
+        for first in (0..self.config.agents.len()).step_by(TICK_CHUNK) {
+            let chunk = first..self.config.agents.len().min(first + TICK_CHUNK);
+            let parallel = self.threads.parallel();
+            if parallel {
+                let (activations, prs) = (&self.activations, &self.config.prs);
+                let scores = &mut self.chunk_scores;
+                scores.clear();
+                scores.resize(chunk.len() * n_behaviours, 0.0);
+                self.threads.install(|| {
+                    scores
+                        .par_chunks_mut(n_behaviours.max(1))
+                        .enumerate()
+                        .for_each_init(Vec::new, |buffer, (k, row)| {
+                            activations.compute_behaviour_scores(
+                                first + k,
+                                n_behaviours,
+                                prs,
+                                buffer,
+                            );
+                            for (score, &j) in row.iter_mut().zip(canonical) {
+                                *score = buffer[j];
+                            }
+                        })
+                });
+            }
+            for (k, i) in chunk.enumerate() {
+                if !parallel {
+                    self.activations.compute_behaviour_scores(
+                        i,
+                        n_behaviours,
+                        &self.config.prs,
+                        &mut self.scores,
+                    );
+                    self.canonical_scores.clear();
+                    self.canonical_scores
+                        .extend(canonical.iter().map(|&j| self.scores[j]));
+                }
+                let scores = if parallel {
+                    &self.chunk_scores[k * n_behaviours..(k + 1) * n_behaviours]
+                } else {
+                    &self.canonical_scores[..]
+                };
+                let agent = &self.config.agents[i];
+                let action = self
+                    .action_selection
+                    .select(agent, time, scores, &mut self.rng)
+                    .map(|k| self.config.behaviours[canonical[k]].clone());
+                if action.is_none() {
+                    self.warnings
+                        .record(WarningKind::NoAction, *agent.borrow().uuid(), 1);
+                }
+                agent.borrow_mut().set_action(time, action);
+            }
+        }
+    }
+}
+
+/// The threads the phases of each tick are simulated on.
+#[derive(Debug, Clone, Default)]
+enum TickThreads {
+    /// The calling thread alone.
+    Serial,
+    /// The global rayon pool, if it has more than one thread.
+    #[default]
+    Global,
+    /// A pool of the [Runner]'s own.
+    Pool(Arc<ThreadPool>),
+}
+
+impl TickThreads {
+    /// Whether the phases are simulated in parallel.
+    fn parallel(&self) -> bool {
+        match self {
+            TickThreads::Serial => false,
+            TickThreads::Global => rayon::current_num_threads() > 1,
+            TickThreads::Pool(_) => true,
+        }
+    }
+
+    /// Run `op` where its parallel iterators use these threads.
+    fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match self {
+            TickThreads::Pool(pool) => pool.install(op),
+            TickThreads::Serial | TickThreads::Global => op(),
+        }
+    }
+}
+
+/// The [ActivationCache] of a [Runner], at its [Precision].
+#[derive(Debug, Clone)]
+enum Activations {
+    F64(ActivationCache<f64>),
+    F32(ActivationCache<f32>),
+}
+
+impl Activations {
+    fn start(&mut self, time: SimTime, n_agents: usize, n_beliefs: usize) {
+        match self {
+            Self::F64(cache) => cache.start(time, n_agents, n_beliefs),
+            Self::F32(cache) => cache.start(time, n_agents, n_beliefs),
+        }
+    }
+
+    fn push(&mut self, agent: &AgentPtr, beliefs: &[BeliefPtr]) {
+        match self {
+            Self::F64(cache) => cache.push(agent, beliefs),
+            Self::F32(cache) => cache.push(agent, beliefs),
+        }
+    }
+
+    fn invalidate(&mut self) {
+        match self {
+            Self::F64(cache) => cache.invalidate(),
+            Self::F32(cache) => cache.invalidate(),
+        }
+    }
+
+    fn time(&self) -> Option<SimTime> {
+        match self {
+            Self::F64(cache) => cache.time(),
+            Self::F32(cache) => cache.time(),
+        }
+    }
+
+    /// Score the behaviours of the `i`th [Agent] with
+    /// [compute_behaviour_scores_from].
+    fn compute_behaviour_scores(
+        &self,
+        i: usize,
+        n_behaviours: usize,
+        prs: &PrsMatrix,
+        scores: &mut Vec<f64>,
+    ) {
+        match self {
+            Self::F64(cache) => {
+                compute_behaviour_scores_from(cache.agent(i), n_behaviours, prs, scores)
+            }
+            Self::F32(cache) => {
+                compute_behaviour_scores_from(cache.agent(i), n_behaviours, prs, scores)
+            }
+        }
+    }
+}
+
+/// Open a file described by [OutputSettings], write to it, and finish it.
+fn write_file(
+    settings: &OutputSettings,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<(), ConceptError> {
+    let mut sink = settings.open().map_err(|source| ConceptError::Io {
+        path: settings.path.clone(),
+        source,
+    })?;
+    write(&mut sink)
+        .and_then(|_| sink.finish())
+        .map_err(|source| ConceptError::Output { source })
+}
+
+/// Write the batches of [AgentSpec]s received from a channel as a JSON array
+/// to a file, until the channel is closed.
+fn write_shard(
+    settings: &OutputSettings,
+    batches: Receiver<Vec<AgentSpec>>,
+) -> Result<(), ConceptError> {
+    let mut sink = settings.open().map_err(|source| ConceptError::Io {
+        path: settings.path.clone(),
+        source,
+    })?;
+    let mut write = || -> io::Result<()> {
+        sink.write_all(b"[")?;
+        let mut first = true;
+        for spec in batches.iter().flatten() {
+            if !first {
+                sink.write_all(b",")?;
+            }
+            first = false;
+            serde_json::to_writer(&mut sink, &spec)?;
+        }
+        sink.write_all(b"]")
+    };
+    write().map_err(|source| ConceptError::Output { source })?;
+    sink.finish()
+        .map_err(|source| ConceptError::Output { source })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        io,
+        path::PathBuf,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use belief_spread::AgentPtr;
+
+    use crate::{
+        configuration::{
+            tests::{hub_builder, small_builder},
+            ConfigurationBuilder,
+        },
+        error::ValidationIssue,
+        json::{
+            BehaviourSpec, BeliefSpec, InterventionKind, InterventionSpec, InterventionTarget,
+            OutputSpecs, PerformanceRelationshipSpec,
+        },
+        selection::{GreedySelection, SoftmaxSelection},
+        sink::OutputSink,
+        thresholds::{ThresholdCrossing, ThresholdMetric},
+    };
+
+    use super::*;
+
+    /// An in-memory [OutputSink] whose contents can be read after the
+    /// [Runner] has finished with it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl OutputSink for SharedBuffer {
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "config", name]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn run_writes_output_to_sink() {
+        let buffer = SharedBuffer::default();
+        let config = ConfigurationBuilder::new()
+            .behaviours_from_path(fixture("behaviours.json"))
+            .beliefs_from_path(fixture("beliefs.json"))
+            .agents_from_path(fixture("agents.json"))
+            .prs_from_path(fixture("prs.json"))
+            .time_range(1, 2)
+            .output(Box::new(buffer.clone()))
+            .build()
+            .unwrap();
+        let mut runner = Runner::new(config);
+        runner.run().unwrap();
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        let output: OutputSpecs = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(output.data.len(), 2);
+        let n_performers: usize = output.data.get(&1).unwrap().n_performers.values().sum();
+        assert_eq!(n_performers, 500);
+    }
+
+    #[test]
+    fn run_id_tags_the_outcome_and_the_output() {
+        let buffer = SharedBuffer::default();
+        let config = small_builder()
+            .run_id("cluster-7")
+            .output(Box::new(buffer.clone()))
+            .build()
+            .unwrap();
+        let mut runner = Runner::new(config);
+        let outcome = runner.run().unwrap();
+        assert_eq!(outcome.run_id, "cluster-7");
+        assert_eq!(runner.output_specs().run_id.as_deref(), Some("cluster-7"));
+        let bytes = buffer.0.lock().unwrap().clone();
+        assert!(bytes.starts_with(br#"{"runId":"cluster-7","#));
+        let output: OutputSpecs = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(output.run_id.as_deref(), Some("cluster-7"));
+
+        // Runs are identified by a new ULID by default
+        let (a, b) = (small_config(1, 3), small_config(1, 3));
+        assert_eq!(a.run_id.len(), 26);
+        assert_ne!(a.run_id, b.run_id);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn outputs_round_trip_in_the_format_of_their_name() {
+        let mut summaries = Vec::new();
+        for suffix in [".json", ".json.zst", ".zst"] {
+            let path = std::env::temp_dir().join(format!("concept-{}{suffix}", Uuid::new_v4()));
+            let config = small_builder()
+                .run_id("same")
+                .seed(1)
+                .output_path(&path)
+                .build()
+                .unwrap();
+            Runner::new(config).run().unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            let summary = crate::loader::load_summary_from_path(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let compressed = bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]);
+            assert_eq!(compressed, suffix.ends_with(".zst"), "{suffix}");
+            summaries.push(serde_json::to_value(&summary.data).unwrap());
+        }
+        assert!(summaries.windows(2).all(|w| w[0] == w[1]));
+
+        match small_builder().output_path("output.json.gz").build() {
+            Err(ConceptError::UnknownFormat { path }) => {
+                assert_eq!(path, PathBuf::from("output.json.gz"))
+            }
+            result => panic!("expected an unknown format, got {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_write_identical_output_files() {
+        // The same path each time, as the fingerprint written with the
+        // output covers it
+        let path = std::env::temp_dir().join(format!("concept-{}.json", Uuid::new_v4()));
+        let run = |seed: u64| -> Vec<u8> {
+            let config = ConfigurationBuilder::new()
+                .behaviours_from_path(fixture("behaviours.json"))
+                .beliefs_from_path(fixture("beliefs.json"))
+                .agents_from_path(fixture("agents.json"))
+                .prs_from_path(fixture("prs.json"))
+                .time_range(1, 3)
+                .run_id("same")
+                .seed(seed)
+                .output_path(&path)
+                .build()
+                .unwrap();
+            Runner::new(config).run().unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            bytes
+        };
+        let output = run(7);
+        assert_eq!(output, run(7));
+        assert_ne!(output, run(8));
     }
 
-    fn perceive_beliefs(&mut self, time: SimTime) {
-        for a in self.config.agents.iter() {
-            update_activation_for_all_beliefs_for_agent(a, time, &self.config.beliefs).unwrap();
+    #[test]
+    fn parallel_ticks_match_serial_ticks() {
+        let run = |threads: usize| -> (Vec<AgentSpec>, Vec<u8>) {
+            let buffer = SharedBuffer::default();
+            let config = ConfigurationBuilder::new()
+                .behaviours_from_path(fixture("behaviours.json"))
+                .beliefs_from_path(fixture("beliefs.json"))
+                .agents_from_path(fixture("agents.json"))
+                .prs_from_path(fixture("prs.json"))
+                .time_range(1, 4)
+                .run_id("same")
+                .output(Box::new(buffer.clone()))
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config)
+                .with_seed(11)
+                .with_action_selection(Box::new(SoftmaxSelection { temperature: 0.5 }))
+                .with_threads(threads)
+                .unwrap();
+            assert_eq!(runner.threads.parallel(), threads > 1);
+            runner.run().unwrap();
+            assert_eq!(runner.perception.is_some(), threads > 1);
+            let bytes = buffer.0.lock().unwrap().clone();
+            (agent_specs(&runner), bytes)
+        };
+        let serial = run(1);
+        assert!(serial.0.iter().any(|agent| !agent.friends.is_empty()));
+        for threads in [2, 4] {
+            assert!(run(threads) == serial, "{threads} threads");
         }
     }
 
-    fn agent_perform_action(&self, agent: &AgentPtr, time: SimTime) {
-        let mut unnormalized_probs: Vec<(BehaviourPtr, f64)> = self
+    #[test]
+    fn stepped_runs_without_an_output_match_whole_runs() {
+        let build = || small_builder().time_range(1, 3).seed(9).build().unwrap();
+        let mut stepped = Runner::new(build());
+        assert_eq!(stepped.agents().len(), 3);
+        assert_eq!(stepped.beliefs().len(), 2);
+        assert_eq!(stepped.behaviours().len(), 2);
+        assert_eq!(stepped.step().unwrap(), 1);
+        assert!(stepped.actions_iter().any(|(_, time, _)| time == 1));
+        assert!(stepped.actions_iter().all(|(_, time, _)| time <= 1));
+        stepped.run_until(2).unwrap();
+        assert_eq!(stepped.step().unwrap(), 3);
+
+        let mut whole = Runner::new(build());
+        let outcome = whole.run().unwrap();
+        assert!(outcome.artifacts.is_empty());
+        assert!(whole.serialize_output().is_err());
+        assert_agents_match(&agent_specs(&stepped), &agent_specs(&whole));
+    }
+
+    #[test]
+    fn two_runners_run_sequentially() {
+        for _ in 0..2 {
+            let config = ConfigurationBuilder::new()
+                .behaviours_from_path(fixture("behaviours.json"))
+                .beliefs_from_path(fixture("beliefs.json"))
+                .agents_from_path(fixture("agents.json"))
+                .prs_from_path(fixture("prs.json"))
+                .time_range(1, 1)
+                .output(Box::new(Vec::new()))
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config);
+            runner.run().unwrap();
+        }
+    }
+
+    #[test]
+    fn serialize_output_twice_fails() {
+        let config = small_builder().build().unwrap();
+        let mut runner = Runner::new(config);
+        assert!(runner.serialize_output().is_ok());
+        assert!(runner.serialize_output().is_err());
+    }
+
+    fn small_config(start: SimTime, end: SimTime) -> Configuration {
+        small_builder().time_range(start, end).build().unwrap()
+    }
+
+    /// The state of every agent, ordered by UUID.
+    fn agent_specs(runner: &Runner) -> Vec<AgentSpec> {
+        let mut agents = runner.snapshot().agents;
+        agents.sort_by_key(|a| a.uuid);
+        agents
+    }
+
+    fn assert_agents_match(a: &[AgentSpec], b: &[AgentSpec]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.uuid, b.uuid);
+            assert_eq!(a.actions, b.actions);
+            assert_eq!(a.deltas, b.deltas);
+            assert_eq!(a.friends, b.friends);
+            assert_eq!(a.activations, b.activations);
+        }
+    }
+
+    fn assert_snapshots_match(a: &SimulationSnapshot, b: &SimulationSnapshot) {
+        assert_eq!(a.time, b.time);
+        assert_eq!(a.agents, b.agents);
+        assert_eq!(a.rng, b.rng);
+    }
+
+    #[test]
+    fn write_snapshot_to_matches_snapshot() {
+        let mut runner = Runner::new(small_config(1, 3)).with_seed(42);
+        runner.run_until(2).unwrap();
+        let mut json = Vec::new();
+        runner.write_snapshot_to(&mut json).unwrap();
+
+        let written: SimulationSnapshot = serde_json::from_slice(&json).unwrap();
+        let expected: SimulationSnapshot =
+            serde_json::from_str(&serde_json::to_string(&runner.snapshot()).unwrap()).unwrap();
+        assert_snapshots_match(&written, &expected);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn write_snapshot_compressed_matches_snapshot() {
+        let mut runner = Runner::new(small_config(1, 3)).with_seed(42);
+        runner.run_until(2).unwrap();
+        let settings = OutputSettings {
+            path: std::env::temp_dir().join(format!("concept-{}.json.zst", Uuid::new_v4())),
+            compression: crate::sink::Compression::Zstd {
+                level: 3,
+                workers: 0,
+            },
+        };
+        runner.write_snapshot(&settings).unwrap();
+        let compressed = std::fs::read(&settings.path).unwrap();
+        std::fs::remove_file(&settings.path).unwrap();
+
+        let written: SimulationSnapshot =
+            serde_json::from_slice(&zstd::decode_all(compressed.as_slice()).unwrap()).unwrap();
+        assert_snapshots_match(&written, &runner.snapshot());
+    }
+
+    #[test]
+    fn write_snapshot_shards_loads_as_snapshot_and_agents() {
+        let mut runner = Runner::new(small_config(1, 3)).with_seed(42);
+        runner.run_until(2).unwrap();
+        let dir = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let settings = OutputSettings {
+            path: dir.join("snapshot.json.zst"),
+            compression: Default::default(),
+        };
+
+        // More shards than agents, so that some are empty
+        let n_agents = runner.config.agents.len();
+        let paths = runner
+            .write_snapshot_shards(&settings, n_agents + 1)
+            .unwrap();
+        assert_eq!(paths.len(), n_agents + 2);
+        assert_eq!(paths[0], settings.path);
+        assert_eq!(paths[1], dir.join("snapshot_000.json.zst"));
+
+        let written = crate::loader::load_snapshot_from_path(&settings.path).unwrap();
+        assert_snapshots_match(&written, &runner.snapshot());
+        let agents = crate::loader::load_agents_from_path(&settings.path).unwrap();
+        assert_eq!(agents, runner.snapshot().agents);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restored_run_matches_straight_run() {
+        let mut straight = Runner::new(small_config(1, 4)).with_seed(42);
+        straight.run_until(4).unwrap();
+
+        let mut first = Runner::new(small_config(1, 4)).with_seed(42);
+        first.run_until(2).unwrap();
+        let snapshot = first.snapshot();
+        assert_eq!(snapshot.time, 2);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        drop(first);
+
+        let mut resumed = Runner::new(small_config(1, 4)).with_seed(7);
+        resumed
+            .restore(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(resumed.time(), 2);
+        resumed.run_until(4).unwrap();
+
+        assert_agents_match(&agent_specs(&straight), &agent_specs(&resumed));
+    }
+
+    /// The state of every agent after running the small model for two
+    /// ticks with `interventions`, serially and in parallel.
+    fn run_with_interventions(interventions: Vec<InterventionSpec>) -> Vec<AgentSpec> {
+        let run = |threads| {
+            let config = small_builder()
+                .time_range(1, 2)
+                .seed(5)
+                .with_interventions(interventions.clone())
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config).with_threads(threads).unwrap();
+            runner.run_until(2).unwrap();
+            agent_specs(&runner)
+        };
+        let serial = run(1);
+        assert_agents_match(&serial, &run(2));
+        serial
+    }
+
+    /// An intervention on the first belief of the small model at tick 2.
+    fn intervention(
+        target: InterventionTarget,
+        kind: InterventionKind,
+        value: f64,
+    ) -> InterventionSpec {
+        InterventionSpec {
+            time: 2,
+            target,
+            belief_uuid: Uuid::from_u128(0x200),
+            kind,
+            value,
+        }
+    }
+
+    #[test]
+    fn interventions_change_the_subsequent_activations() {
+        let belief = Uuid::from_u128(0x200);
+        let first = InterventionTarget::Agents(vec![Uuid::from_u128(0x300)]);
+        let baseline = run_with_interventions(Vec::new());
+
+        let forced =
+            run_with_interventions(vec![intervention(first, InterventionKind::Activation, 0.9)]);
+        // The activations simulated at the tick before are left as they were
+        for (agent, before) in forced.iter().zip(&baseline) {
+            assert_eq!(agent.activations[&1], before.activations[&1]);
+        }
+        assert_ne!(
+            forced[0].activations[&2][&belief],
+            baseline[0].activations[&2][&belief]
+        );
+        // Only the first agent was targeted
+        assert_eq!(forced[1].activations[&2], baseline[1].activations[&2]);
+
+        let delta = run_with_interventions(vec![intervention(
+            InterventionTarget::All,
+            InterventionKind::Delta,
+            1.5,
+        )]);
+        for (agent, before) in delta.iter().zip(&baseline) {
+            assert_eq!(agent.deltas[&belief], 1.5);
+            assert_eq!(agent.activations[&1], before.activations[&1]);
+        }
+        assert_ne!(
+            delta[2].activations[&2][&belief],
+            baseline[2].activations[&2][&belief]
+        );
+    }
+
+    #[test]
+    fn interventions_at_the_same_tick_apply_in_order() {
+        let belief = Uuid::from_u128(0x200);
+        let activation =
+            |value| intervention(InterventionTarget::All, InterventionKind::Activation, value);
+        let forward = run_with_interventions(vec![activation(-0.5), activation(0.9)]);
+        let backward = run_with_interventions(vec![activation(0.9), activation(-0.5)]);
+        assert_ne!(forward, backward);
+        // The last intervention given is the one carried into the tick
+        assert_eq!(forward, run_with_interventions(vec![activation(0.9)]));
+        assert_eq!(backward, run_with_interventions(vec![activation(-0.5)]));
+        assert_ne!(
+            forward[0].activations[&2][&belief],
+            backward[0].activations[&2][&belief]
+        );
+    }
+
+    #[test]
+    fn run_resumed_from_a_checkpoint_matches_straight_run() {
+        let mut straight = Runner::new(small_config(1, 6)).with_seed(42);
+        straight.run_until(6).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let settings = OutputSettings {
+            path: dir.join("checkpoint.json"),
+            compression: crate::sink::Compression::None,
+        };
+        let mut died = Runner::new(small_config(1, 6))
+            .with_seed(42)
+            .with_checkpoints(2, settings.clone());
+        // The process dies after tick 5, before the next checkpoint
+        died.run_until(5).unwrap();
+        assert_eq!(
+            died.checkpoints_written,
+            [2, 4].map(|t| checkpoint_path(&settings.path, t))
+        );
+        drop(died);
+
+        let checkpoint =
+            crate::loader::load_snapshot_from_path(&dir.join("checkpoint_t4.json")).unwrap();
+        assert_eq!(checkpoint.time, 4);
+        let mut resumed = Runner::new(small_config(1, 6)).with_seed(7);
+        resumed.restore(checkpoint.clone()).unwrap();
+        assert_eq!(resumed.time(), 4);
+        resumed.run_until(6).unwrap();
+
+        let (straight, resumed) = (agent_specs(&straight), agent_specs(&resumed));
+        assert!(resumed.iter().any(|agent| !agent.friends.is_empty()));
+        assert_agents_match(&straight, &resumed);
+
+        // A checkpoint after the end of a run cannot be resumed from
+        let mut short = Runner::new(small_config(1, 3));
+        match short.restore(checkpoint) {
+            Err(ConceptError::Validation(report)) => assert_eq!(
+                report.issues,
+                [ValidationIssue::InvalidResumeTime {
+                    time: 4,
+                    before_start: 0,
+                    end: 3
+                }]
+            ),
+            result => panic!("expected an invalid resume time, got {result:?}"),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restore_links_friends_to_restored_agents() {
+        let first = Runner::new(small_config(1, 1));
+        let mut resumed = Runner::new(small_config(1, 1));
+        resumed.restore(first.snapshot()).unwrap();
+
+        let agents: HashSet<*const ()> = resumed
             .config
-            .behaviours
+            .agents
             .iter()
-            .map(|behaviour| {
+            .map(|a| Rc::as_ptr(a) as *const ())
+            .collect();
+        for agent in &resumed.config.agents {
+            for friend in agent.borrow().get_friends().keys() {
+                assert!(agents.contains(&(Rc::as_ptr(friend) as *const ())));
+            }
+        }
+    }
+
+    #[test]
+    fn restore_rejects_snapshot_without_activations() {
+        let mut runner = Runner::new(small_config(1, 2));
+        let mut snapshot = runner.snapshot();
+        snapshot.time = 5;
+        let err = runner.restore(snapshot).unwrap_err();
+        assert!(matches!(
+            err,
+            ConceptError::Validation(r)
+                if matches!(r.issues[0], ValidationIssue::MissingActivation { time: 5, .. })
+        ));
+        assert_eq!(runner.time(), 0);
+    }
+
+    #[test]
+    fn new_history_only_snapshots_continue_a_chain() {
+        let mut first = Runner::new(small_config(1, 3)).with_seed(42);
+        first.run().unwrap();
+        let first = first.snapshot();
+        assert_eq!(first.history, None);
+
+        let second = |new_history_only: bool| {
+            let config = small_builder()
+                .with_agents(first.agents.clone())
+                .time_range(4, 6)
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config).with_seed(43);
+            if new_history_only {
+                runner = runner.with_new_history_only();
+            }
+            runner.run().unwrap();
+            let mut json = Vec::new();
+            runner.write_snapshot_to(&mut json).unwrap();
+            serde_json::from_slice::<SimulationSnapshot>(&json).unwrap()
+        };
+        let (new, full) = (second(true), second(false));
+        assert_eq!(new.history, Some(HistoryRange { start: 4, end: 6 }));
+        for agent in &new.agents {
+            assert_eq!(agent.activations.keys().min(), Some(&4));
+            assert_eq!(agent.actions.keys().min(), Some(&4));
+        }
+
+        // The first and second outputs together are the whole history
+        let mut combined = new.clone();
+        for (agent, earlier) in combined.agents.iter_mut().zip(&first.agents) {
+            agent.activations.extend(earlier.activations.clone());
+            agent.actions.extend(earlier.actions.clone());
+        }
+        assert_eq!(combined.agents, full.agents);
+
+        let third = |agents: Vec<AgentSpec>| {
+            let config = small_builder()
+                .with_agents(agents)
+                .time_range(7, 8)
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config).with_seed(44);
+            runner.run().unwrap();
+            agent_specs(&runner)
+        };
+        assert_agents_match(&third(combined.agents), &third(full.agents));
+    }
+
+    /// Selects actions linearly, and sets a token once a given tick has
+    /// been reached.
+    struct CancelAt {
+        time: SimTime,
+        token: Arc<AtomicBool>,
+    }
+
+    impl ActionSelection for CancelAt {
+        fn select(
+            &self,
+            agent: &AgentPtr,
+            time: SimTime,
+            scores: &[f64],
+            rng: &mut dyn rand::RngCore,
+        ) -> Option<usize> {
+            if time >= self.time {
+                self.token.store(true, Ordering::Relaxed);
+            }
+            LinearSelection.select(agent, time, scores, rng)
+        }
+    }
+
+    #[test]
+    fn run_with_cancel_stops_at_tick_boundary() {
+        let buffer = SharedBuffer::default();
+        let config = small_builder()
+            .time_range(1, 5)
+            .output(Box::new(buffer.clone()))
+            .build()
+            .unwrap();
+        let token = Arc::new(AtomicBool::new(false));
+        let mut runner = Runner::new(config).with_action_selection(Box::new(CancelAt {
+            time: 2,
+            token: token.clone(),
+        }));
+
+        let outcome = runner.run_with_cancel(&token).unwrap();
+        assert_eq!(outcome.status, RunStatus::Cancelled);
+        assert_eq!(outcome.last_tick, 2);
+        assert_eq!(runner.time(), 2);
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        let output: OutputSpecs = serde_json::from_slice(&bytes).unwrap();
+        let mut times: Vec<SimTime> = output.data.keys().cloned().collect();
+        times.sort_unstable();
+        assert_eq!(times, vec![1, 2]);
+    }
+
+    #[test]
+    fn run_with_cancel_completes_when_not_cancelled() {
+        let mut runner = Runner::new(small_config(1, 2));
+        let outcome = runner.run_with_cancel(&AtomicBool::new(false)).unwrap();
+        assert_eq!(outcome.status, RunStatus::Completed);
+        assert_eq!(runner.time(), 2);
+    }
+
+    #[test]
+    fn run_outcome_is_populated() {
+        let output = std::env::temp_dir().join(format!("concept-{}.json", Uuid::new_v4()));
+        let config = ConfigurationBuilder::new()
+            .behaviours_from_path(fixture("behaviours.json"))
+            .beliefs_from_path(fixture("beliefs.json"))
+            .agents_from_path(fixture("agents.json"))
+            .prs_from_path(fixture("prs.json"))
+            .time_range(1, 2)
+            .output_path(&output)
+            .build()
+            .unwrap();
+        let outcome = Runner::new(config).with_seed(3).run().unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(outcome.status, RunStatus::Completed);
+        assert_eq!(outcome.last_tick, 2);
+        assert_eq!(outcome.n_ticks, 2);
+        assert_eq!(outcome.n_agents, 500);
+        assert_eq!(outcome.n_beliefs, 5);
+        assert_eq!(outcome.n_behaviours, 4);
+        assert_eq!(outcome.seed, 3);
+        assert!(outcome.timings.perceive_beliefs > 0.0);
+        assert!(outcome.timings.perform_actions > 0.0);
+        assert!(outcome.timings.output > 0.0);
+        assert_eq!(outcome.artifacts, vec![output]);
+        #[cfg(target_os = "linux")]
+        assert!(outcome.peak_rss_bytes.unwrap() > 0);
+
+        let json = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(json["status"], "completed");
+        assert_eq!(json["lastTick"], 2);
+    }
+
+    #[test]
+    fn pruning_friends_only_changes_perception_of_pruned_agents() {
+        let activations = |builder: ConfigurationBuilder| {
+            let mut runner = Runner::new(builder.build().unwrap()).with_seed(42);
+            runner.run_until(1).unwrap();
+            runner
+                .activations_iter()
+                .filter(|&(_, time, _, _)| time == 1)
+                .collect::<Vec<_>>()
+        };
+        let full = activations(hub_builder());
+        let pruned = activations(hub_builder().max_friends_per_agent(2));
+
+        let hub = Uuid::from_u128(0x300);
+        assert_eq!(full.len(), pruned.len());
+        for (full, pruned) in full.iter().zip(&pruned) {
+            assert_eq!(full.0, pruned.0);
+            if full.0 == hub {
+                assert_ne!(full.3, pruned.3);
+            } else {
+                assert_eq!(full.3, pruned.3);
+            }
+        }
+    }
+
+    #[test]
+    fn f32_precision_agrees_with_f64() {
+        let output = |precision| {
+            let mut runner = Runner::new(small_config(1, 3))
+                .with_seed(42)
+                .with_precision(precision);
+            runner.run_until(3).unwrap();
+            let mut json = Vec::new();
+            runner.serialize_output_to(&mut json).unwrap();
+            let specs: OutputSpecs = serde_json::from_slice(&json).unwrap();
+            (agent_specs(&runner), specs)
+        };
+        let (f64_agents, f64_output) = output(Precision::F64);
+        let (f32_agents, f32_output) = output(Precision::F32);
+
+        // The same actions are selected, so the agents are unaffected
+        assert_agents_match(&f64_agents, &f32_agents);
+        assert_eq!(f64_output.data.len(), f32_output.data.len());
+        for (t, expected) in &f64_output.data {
+            let actual = &f32_output.data[t];
+            for (stat, expected, actual) in [
+                ("mean", &expected.mean_activation, &actual.mean_activation),
+                ("sd", &expected.sd_activation, &actual.sd_activation),
                 (
-                    behaviour.clone(),
-                    self.config
-                        .beliefs
-                        .iter()
-                        .map(|belief| {
-                            self.config
-                                .prs
-                                .get(&(belief.clone(), behaviour.clone()))
-                                .unwrap_or(&0.0)
-                                * agent.borrow().get_activation(time, belief).unwrap_or(0.0)
-                        })
-                        .sum::<f64>(),
-                )
+                    "median",
+                    &expected.median_activation,
+                    &actual.median_activation,
+                ),
+            ] {
+                assert_eq!(expected.len(), actual.len(), "{stat} at {t}");
+                for (belief, v) in expected {
+                    assert!((v - actual[belief]).abs() < 1e-6, "{stat} at {t}");
+                }
+            }
+            assert_eq!(expected.n_performers, actual.n_performers);
+        }
+    }
+
+    #[test]
+    fn panel_holds_the_trajectories_of_the_sampled_agents() {
+        let dir = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let spec = PanelSpec { size: 2, seed: 5 };
+        let settings = OutputSettings {
+            path: dir.join("panel.csv"),
+            compression: crate::sink::Compression::None,
+        };
+        let mut runner = Runner::new(small_config(1, 2))
+            .with_seed(1)
+            .with_panel(spec, settings.clone());
+        let outcome = runner.run().unwrap();
+        let actions_path = dir.join("panel_actions.csv");
+        assert_eq!(outcome.panel, Some(spec));
+        assert_eq!(
+            outcome.artifacts,
+            vec![settings.path.clone(), actions_path.clone()]
+        );
+
+        let sampled: Vec<Uuid> = spec
+            .sample(3)
+            .into_iter()
+            .map(|i| *runner.config.agents[i].borrow().uuid())
+            .collect();
+        let expected: Vec<String> = runner
+            .activations_iter()
+            .filter(|(agent, _, _, _)| sampled.contains(agent))
+            .map(|(agent, time, belief, v)| format!("{agent},{time},{belief},{v}"))
+            .collect();
+        let activations = std::fs::read_to_string(&settings.path).unwrap();
+        let mut lines = activations.lines();
+        assert_eq!(lines.next(), Some("agent_uuid,time,belief_uuid,value"));
+        assert_eq!(lines.collect::<Vec<_>>(), expected);
+
+        let expected: Vec<String> = runner
+            .actions_iter()
+            .filter(|(agent, _, _)| sampled.contains(agent))
+            .map(|(agent, time, behaviour)| format!("{agent},{time},{behaviour}"))
+            .collect();
+        let actions = std::fs::read_to_string(&actions_path).unwrap();
+        let mut lines = actions.lines();
+        assert_eq!(lines.next(), Some("agent_uuid,time,behaviour_uuid"));
+        assert_eq!(lines.collect::<Vec<_>>(), expected);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn panels_and_summaries_name_beliefs_and_behaviours() {
+        let dir = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let settings = OutputSettings {
+            path: dir.join("panel.csv"),
+            compression: crate::sink::Compression::None,
+        };
+        let behaviours = ["Masks, \"indoors\"", "Distancing"]
+            .into_iter()
+            .zip(0x100..)
+            .map(|(name, uuid)| BehaviourSpec {
+                name: name.to_string(),
+                uuid: Uuid::from_u128(uuid),
             })
             .collect();
-        unnormalized_probs.sort_by(|(_, v1), (_, v2)| v1.partial_cmp(v2).unwrap());
+        // Without performance relationships every agent performs the
+        // behaviour with the lowest UUID
+        let config = small_builder()
+            .with_behaviours(behaviours)
+            .with_prs(Vec::<PerformanceRelationshipSpec>::new())
+            .time_range(1, 1)
+            .build()
+            .unwrap();
+        let mut runner = Runner::new(config)
+            .with_panel(PanelSpec { size: 1, seed: 5 }, settings.clone())
+            .with_panel_names();
+        runner.run().unwrap();
 
-        match unnormalized_probs.last().unwrap() {
-            (k, v) if *v <= 0.0 => agent.borrow_mut().set_action(time, Some(k.clone())),
-            _ => {
-                let filtered_probs: Vec<(BehaviourPtr, f64)> = unnormalized_probs
-                    .into_iter()
-                    .filter(|(_, x)| *x > 0.0)
-                    .collect();
-                match filtered_probs.len() {
-                    1 => agent
-                        .borrow_mut()
-                        .set_action(time, Some(filtered_probs.get(0).unwrap().0.clone())),
-                    _ => {
-                        let map_probs: HashMap<BehaviourPtr, f64> =
-                            filtered_probs.into_iter().collect();
-                        let normalizing_factor: f64 = map_probs.values().sum();
-                        let normalized_probs: Vec<(BehaviourPtr, f64)> = map_probs
-                            .into_iter()
-                            .map(|(k, v)| (k, v / normalizing_factor))
-                            .collect();
-
-                        let mut rng = rand::thread_rng();
-                        let mut rv: f64 = rng.gen();
-                        let mut chosen_behaviour = normalized_probs.last().unwrap().0.clone();
-
-                        for (behaviour, v) in normalized_probs.into_iter() {
-                            rv -= v;
-                            if rv <= 0.0 {
-                                chosen_behaviour = behaviour;
-                                break;
-                            }
-                        }
+        let activations = std::fs::read_to_string(&settings.path).unwrap();
+        let mut lines = activations.lines();
+        assert_eq!(
+            lines.next(),
+            Some("agent_uuid,time,belief_uuid,belief_name,value")
+        );
+        assert!(lines.next().unwrap().contains(",belief 0,"));
+        let actions = std::fs::read_to_string(panel::actions_path(&settings.path)).unwrap();
+        let mut lines = actions.lines();
+        assert_eq!(
+            lines.next(),
+            Some("agent_uuid,time,behaviour_uuid,behaviour_name")
+        );
+        assert!(lines
+            .next()
+            .unwrap()
+            .ends_with(",00000000-0000-0000-0000-000000000100,\"Masks, \"\"indoors\"\"\""));
 
-                        agent.borrow_mut().set_action(time, Some(chosen_behaviour))
-                    }
-                }
+        // The UUIDs remain the keys of the summary
+        let names = runner.output_specs().names.unwrap();
+        assert_eq!(names.beliefs[&Uuid::from_u128(0x201)], "belief 1");
+        assert_eq!(
+            names.behaviours[&Uuid::from_u128(0x100)],
+            "Masks, \"indoors\""
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn output_every_samples_only_the_ticks_written() {
+        let dir = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let settings = OutputSettings {
+            path: dir.join("panel.csv"),
+            compression: crate::sink::Compression::None,
+        };
+        let output = SharedBuffer::default();
+        let config = small_builder()
+            .time_range(1, 6)
+            .output(Box::new(output.clone()))
+            .build()
+            .unwrap();
+        let mut runner = Runner::new(config)
+            .with_seed(4)
+            .with_output_every(2)
+            .with_panel(PanelSpec { size: 3, seed: 1 }, settings.clone());
+        let outcome = runner.run().unwrap();
+        assert_eq!(outcome.output_every, 2);
+
+        let specs: OutputSpecs = serde_json::from_slice(&output.0.lock().unwrap()).unwrap();
+        assert_eq!(specs.output_every, Some(2));
+        let mut times: Vec<SimTime> = specs.data.keys().copied().collect();
+        times.sort();
+        assert_eq!(times, vec![1, 3, 5, 6]);
+
+        // The panel keeps the initial state and the sampled ticks
+        let actions = std::fs::read_to_string(panel::actions_path(&settings.path)).unwrap();
+        let mut times: Vec<SimTime> = actions
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().parse().unwrap())
+            .collect();
+        times.sort();
+        times.dedup();
+        assert_eq!(times, vec![0, 1, 3, 5, 6]);
+
+        // The agents, and so their snapshots, keep every tick
+        assert_eq!(runner.actions_iter().count(), 3 * 7);
+        assert!(runner
+            .snapshot()
+            .agents
+            .iter()
+            .all(|agent| agent.activations.len() == 7));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pruned_runs_end_as_unpruned_runs_do() {
+        let run = |retained: Option<SimTime>| {
+            let output = SharedBuffer::default();
+            let config = small_builder()
+                .time_range(1, 20)
+                .output(Box::new(output.clone()))
+                .build()
+                .unwrap();
+            let mut runner = Runner::new(config).with_seed(3).with_output_window(2);
+            if let Some(ticks) = retained {
+                runner = runner.with_retained_ticks(ticks);
             }
+            let outcome = runner.run().unwrap();
+            assert_eq!(outcome.retained_ticks, retained);
+            let specs: OutputSpecs = serde_json::from_slice(&output.0.lock().unwrap()).unwrap();
+            let first_held = runner.activations_iter().map(|(_, time, _, _)| time).min();
+            (
+                agent_specs(&runner),
+                runner.output_specs(),
+                specs,
+                first_held,
+            )
+        };
+        let (full, full_specs, full_output, full_first) = run(None);
+        let (pruned, pruned_specs, pruned_output, pruned_first) = run(Some(1));
+        assert_eq!(full_first, Some(0));
+        assert_eq!(pruned_first, Some(15));
+
+        // Pruned at ticks 8 and 16, so only the ticks from 15 are held, and
+        // the output window keeps only the last two in the snapshot
+        let held = |agents: &[AgentSpec]| {
+            let mut times: Vec<SimTime> = agents[0].activations.keys().copied().collect();
+            times.sort();
+            times
+        };
+        assert_eq!(held(&full), [19, 20]);
+        assert_eq!(held(&pruned), [19, 20]);
+        assert_agents_match(&full, &pruned);
+
+        // The ticks before the first pruning were summarised from the same
+        // activations
+        for specs in [&pruned_specs, &pruned_output] {
+            let mut times: Vec<SimTime> = specs.data.keys().copied().collect();
+            times.sort();
+            assert_eq!(times, (1..=20).collect::<Vec<_>>());
+            assert_eq!(
+                serde_json::to_value(&specs.data[&7]).unwrap(),
+                serde_json::to_value(&full_specs.data[&7]).unwrap()
+            );
         }
+        assert_eq!(
+            serde_json::to_value(&full_output.data[&7]).unwrap(),
+            serde_json::to_value(&full_specs.data[&7]).unwrap()
+        );
     }
 
-    fn perform_actions(&mut self, time: SimTime) {
-        self.config
+    #[test]
+    fn activations_iter_is_ordered() {
+        let mut runner = Runner::new(small_config(1, 3)).with_seed(1);
+        runner.run_until(2).unwrap();
+
+        let records: Vec<(Uuid, SimTime, Uuid, f64)> = runner.activations_iter().collect();
+        // 3 agents, with activations at times 0 to 2 for 2 beliefs
+        assert_eq!(records.len(), 3 * 3 * 2);
+
+        let agents: Vec<Uuid> = runner
+            .config
             .agents
             .iter()
-            .for_each(|agent| self.agent_perform_action(agent, time));
+            .map(|a| *a.borrow().uuid())
+            .collect();
+        let beliefs: Vec<Uuid> = runner
+            .config
+            .beliefs
+            .iter()
+            .map(|b| *b.borrow().uuid())
+            .collect();
+        let expected: Vec<(Uuid, SimTime, Uuid)> = agents
+            .iter()
+            .flat_map(|&a| {
+                let beliefs = &beliefs;
+                (0..=2).flat_map(move |t| beliefs.iter().map(move |&b| (a, t, b)))
+            })
+            .collect();
+        let keys: Vec<(Uuid, SimTime, Uuid)> =
+            records.iter().map(|&(a, t, b, _)| (a, t, b)).collect();
+        assert_eq!(keys, expected);
+
+        let first = &runner.config.agents[0];
+        let belief = &runner.config.beliefs[1];
+        assert_eq!(
+            Some(records[2 * 2 + 1].3),
+            first.borrow().get_activation(2, belief)
+        );
+    }
+
+    #[test]
+    fn actions_iter_is_ordered() {
+        let mut runner = Runner::new(small_config(1, 3));
+        runner.run_until(3).unwrap();
+
+        let records: Vec<(Uuid, SimTime, Uuid)> = runner.actions_iter().collect();
+        assert_eq!(records.len(), 3 * 4);
+        for (i, agent) in runner.config.agents.iter().enumerate() {
+            let agent = agent.borrow();
+            for t in 0..=3 {
+                let (a, time, behaviour) = records[i * 4 + t as usize];
+                assert_eq!(a, *agent.uuid());
+                assert_eq!(time, t);
+                assert_eq!(behaviour, *agent.get_action(t).unwrap().borrow().uuid());
+            }
+        }
+    }
+
+    #[test]
+    fn threshold_crossings_are_found_from_the_summary() {
+        let belief = Uuid::from_u128(0x200);
+        let behaviour = Uuid::from_u128(0x100);
+        let mut runner = Runner::new(small_config(1, 4)).with_seed(3);
+        runner.run_until(4).unwrap();
+        let specs = OutputSpecs::from_agents(&runner.config.agents, &runner.config.beliefs, 1, 4);
+        let (peak_time, peak) = (1..=4)
+            .map(|t| (t, specs.data[&t].mean_activation[&belief]))
+            .fold((0, f64::NEG_INFINITY), |max, (t, mean)| {
+                if mean > max.1 {
+                    (t, mean)
+                } else {
+                    max
+                }
+            });
+
+        let level = |uuid, level| ThresholdMetric { uuid, level };
+        runner = runner.with_threshold_metrics(ThresholdMetrics {
+            beliefs: vec![level(belief, peak), level(belief, peak + 1.0)],
+            behaviours: vec![level(behaviour, 0.0), level(behaviour, 1.1)],
+        });
+        let mut json = Vec::new();
+        let crossings = runner
+            .serialize_output_to(&mut json)
+            .unwrap()
+            .threshold_crossings;
+        let first_times = |c: &[ThresholdCrossing]| -> Vec<Option<SimTime>> {
+            c.iter().map(|c| c.first_time).collect()
+        };
+        assert_eq!(first_times(&crossings.beliefs), vec![Some(peak_time), None]);
+        assert_eq!(first_times(&crossings.behaviours), vec![Some(1), None]);
+
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let written: ThresholdCrossings =
+            serde_json::from_value(json["thresholdCrossings"].clone()).unwrap();
+        assert_eq!(
+            first_times(&written.beliefs),
+            first_times(&crossings.beliefs)
+        );
+        assert_eq!(
+            first_times(&written.behaviours),
+            first_times(&crossings.behaviours)
+        );
+    }
+
+    #[test]
+    fn stability_covers_the_final_ticks() {
+        let options = StabilityOptions {
+            window: 2,
+            tolerance: 0.0,
+        };
+        let mut runner = Runner::new(small_config(1, 4))
+            .with_seed(3)
+            .with_stability(options);
+        let outcome = runner.run().unwrap();
+        let stability = outcome.stability.unwrap();
+        assert_eq!(stability.options, options);
+        assert_eq!(
+            (stability.first_time, stability.last_time),
+            (Some(3), Some(4))
+        );
+        assert_eq!(stability.beliefs.len(), 2);
+        assert_eq!(stability.behaviours.len(), 2);
+
+        let specs = OutputSpecs::from_agents(&runner.config.agents, &runner.config.beliefs, 3, 4);
+        let belief = Uuid::from_u128(0x200);
+        let change = (specs.data[&4].mean_activation[&belief]
+            - specs.data[&3].mean_activation[&belief])
+            .abs();
+        assert_eq!(stability.beliefs[&belief].max_change, change);
+        assert_eq!(stability.beliefs[&belief].converged, change == 0.0);
+    }
+
+    #[test]
+    fn runs_from_either_time_origin_convention_agree() {
+        // The initial activations at 0 simulated from 1, and from 3
+        let mut from_1 = Runner::new(small_config(1, 2)).with_seed(9);
+        let config = small_builder()
+            .time_range(3, 4)
+            .time_origin(0)
+            .build()
+            .unwrap();
+        let mut from_3 = Runner::new(config).with_seed(9);
+        let outcome = from_3.run().unwrap();
+        from_1.run().unwrap();
+        assert_eq!(outcome.time_origin, 0);
+        assert_eq!(outcome.n_ticks, 2);
+
+        let shifted: Vec<(Uuid, SimTime, Uuid, f64)> = from_1
+            .activations_iter()
+            .filter(|&(_, time, _, _)| time > 0)
+            .map(|(agent, time, belief, v)| (agent, time + 2, belief, v))
+            .collect();
+        let simulated: Vec<(Uuid, SimTime, Uuid, f64)> = from_3
+            .activations_iter()
+            .filter(|&(_, time, _, _)| time > 2)
+            .collect();
+        assert_eq!(shifted, simulated);
+
+        let mut json = Vec::new();
+        from_3.serialize_output_to(&mut json).unwrap();
+        let specs: OutputSpecs = serde_json::from_slice(&json).unwrap();
+        assert_eq!(specs.time_origin, Some(0));
+        let mut times: Vec<SimTime> = specs.data.keys().copied().collect();
+        times.sort();
+        assert_eq!(times, vec![3, 4]);
+    }
+
+    #[test]
+    fn committed_agents_hold_their_floors_against_hostile_friends() {
+        // Both friends reject belief 0, and perform the behaviour that
+        // undermines it every tick
+        let belief_uuids = [0x200, 0x201].map(Uuid::from_u128);
+        let beliefs: Vec<BeliefSpec> = (0..2)
+            .map(|i| BeliefSpec {
+                name: format!("belief {i}"),
+                uuid: belief_uuids[i],
+                perceptions: HashMap::from([(Uuid::from_u128(0x101), [-1.0, 1.0][i])]),
+                relationships: HashMap::new(),
+                delta_distribution: None,
+            })
+            .collect();
+        let agent_uuids = [0x300, 0x301, 0x302].map(Uuid::from_u128);
+        let agents = |floor: Option<f64>| -> Vec<AgentSpec> {
+            agent_uuids
+                .iter()
+                .enumerate()
+                .map(|(i, &uuid)| AgentSpec {
+                    uuid,
+                    actions: HashMap::new(),
+                    activations: HashMap::from([(
+                        0,
+                        HashMap::from([
+                            (belief_uuids[0], if i == 0 { 0.9 } else { -1.0 }),
+                            (belief_uuids[1], if i == 0 { 0.0 } else { 1.0 }),
+                        ]),
+                    )]),
+                    deltas: belief_uuids.iter().map(|&b| (b, 1.0)).collect(),
+                    friends: if i == 0 {
+                        agent_uuids[1..].iter().map(|&f| (f, 1.0)).collect()
+                    } else {
+                        HashMap::new()
+                    },
+                    activation_floors: (i == 0)
+                        .then_some(floor)
+                        .flatten()
+                        .map(|floor| (belief_uuids[0], floor))
+                        .into_iter()
+                        .collect(),
+                    activation_ceilings: HashMap::new(),
+                })
+                .collect()
+        };
+        let run = |floor| {
+            let config = small_builder()
+                .with_beliefs(beliefs.clone())
+                .with_agents(agents(floor))
+                .time_range(1, 5)
+                .build()
+                .unwrap();
+            assert_eq!(config.input_summary.capped_agents.len(), floor.iter().len());
+            let mut runner = Runner::new(config).with_seed(7);
+            runner.run_until(5).unwrap();
+            runner
+        };
+        let committed = |runner: &Runner| -> Vec<f64> {
+            let mut activations: Vec<(SimTime, f64)> = runner
+                .activations_iter()
+                .filter(|a| a.0 == agent_uuids[0] && a.2 == belief_uuids[0] && a.1 > 0)
+                .map(|a| (a.1, a.3))
+                .collect();
+            activations.sort_by_key(|a| a.0);
+            activations.into_iter().map(|a| a.1).collect()
+        };
+
+        assert!(committed(&run(None)).iter().any(|&a| a < 0.8));
+        let runner = run(Some(0.8));
+        let held = committed(&runner);
+        assert_eq!(held.len(), 5);
+        assert!(held.iter().all(|&a| a >= 0.8));
+
+        // The floor survives a snapshot
+        let spec = &agent_specs(&runner)[0];
+        assert_eq!(
+            spec.activation_floors,
+            HashMap::from([(belief_uuids[0], 0.8)])
+        );
+        assert!(spec.activation_ceilings.is_empty());
+    }
+
+    #[test]
+    fn tied_scores_are_broken_by_behaviour_uuid() {
+        // Every behaviour has the same performance relationship `value`
+        let run =
+            |value, selection: Box<dyn ActionSelection>, reverse_behaviours, reverse_agents| {
+                let mut behaviours: Vec<BehaviourSpec> = (0..2)
+                    .map(|i| BehaviourSpec {
+                        name: format!("behaviour {i}"),
+                        uuid: Uuid::from_u128(0x100 + i),
+                    })
+                    .collect();
+                let prs = (0..2)
+                    .flat_map(|i| {
+                        behaviours.iter().map(move |b| PerformanceRelationshipSpec {
+                            behaviour_uuid: b.uuid,
+                            belief_uuid: Uuid::from_u128(0x200 + i),
+                            value,
+                        })
+                    })
+                    .collect();
+                if reverse_behaviours {
+                    behaviours.reverse();
+                }
+                let mut config = small_builder()
+                    .with_behaviours(behaviours)
+                    .with_prs(prs)
+                    .build()
+                    .unwrap();
+                if reverse_agents {
+                    config.agents.reverse();
+                }
+                let mut runner = Runner::new(config)
+                    .with_action_selection(selection)
+                    .with_seed(3);
+                runner.run_until(3).unwrap();
+                let mut actions: Vec<(Uuid, SimTime, Uuid)> =
+                    runner.actions_iter().filter(|a| a.1 > 0).collect();
+                actions.sort();
+                actions
+            };
+        let chose_lowest_uuid = |actions: Vec<(Uuid, SimTime, Uuid)>| {
+            actions.len() == 9 && actions.iter().all(|a| a.2 == Uuid::from_u128(0x100))
+        };
+
+        for reverse in [false, true] {
+            // No score is positive, so the highest is always chosen
+            assert!(chose_lowest_uuid(run(
+                0.0,
+                Box::new(LinearSelection),
+                reverse,
+                reverse
+            )));
+            assert!(chose_lowest_uuid(run(
+                -1.0,
+                Box::new(LinearSelection),
+                reverse,
+                reverse
+            )));
+            assert!(chose_lowest_uuid(run(
+                1.0,
+                Box::new(GreedySelection),
+                reverse,
+                reverse
+            )));
+        }
+
+        // Sampling between tied behaviours does not depend on their order
+        assert_eq!(
+            run(1.0, Box::new(LinearSelection), false, false),
+            run(1.0, Box::new(LinearSelection), true, false)
+        );
+    }
+
+    #[test]
+    fn artifacts_list_beliefs_in_the_same_canonical_order() {
+        // The beliefs are listed in the reverse of their UUID order
+        let belief_uuids = [0x201, 0x200].map(Uuid::from_u128);
+        let beliefs: Vec<BeliefSpec> = belief_uuids
+            .iter()
+            .enumerate()
+            .map(|(i, &uuid)| BeliefSpec {
+                name: format!("belief {i}"),
+                uuid,
+                perceptions: HashMap::from([(Uuid::from_u128(0x100 + i as u128), 0.5)]),
+                relationships: HashMap::from([(belief_uuids[1 - i], -0.2)]),
+                delta_distribution: None,
+            })
+            .collect();
+        let config = small_builder()
+            .with_beliefs(beliefs)
+            .time_range(1, 2)
+            .build()
+            .unwrap();
+        let mut sorted = belief_uuids;
+        sorted.sort();
+        assert_eq!(config.index().canonical_beliefs(), [1, 0]);
+
+        let mut runner = Runner::new(config).with_seed(42);
+        runner.run_until(2).unwrap();
+
+        // The summary, which is checked in the written text as maps parse
+        // sorted
+        let mut json = Vec::new();
+        runner.serialize_output_to(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        let mut listed: Vec<(usize, Uuid)> = sorted
+            .iter()
+            .flat_map(|&uuid| {
+                json.match_indices(&uuid.to_string())
+                    .map(|(at, _)| (at, uuid))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        listed.sort();
+        // The names, then two ticks, each with four maps of both beliefs
+        assert_eq!(listed.len(), (1 + 2 * 4) * 2);
+        for pair in listed.chunks(2) {
+            assert_eq!([pair[0].1, pair[1].1], sorted);
+        }
+
+        // The performance relationships
+        let prs: Vec<Uuid> = runner
+            .config
+            .prs
+            .to_specs()
+            .iter()
+            .map(|spec| spec.belief_uuid)
+            .collect();
+        assert_eq!(prs, sorted);
+
+        // The activations
+        let activations: Vec<Uuid> = runner
+            .activations_iter()
+            .filter(|&(agent, time, _, _)| agent == Uuid::from_u128(0x300) && time == 1)
+            .map(|(_, _, belief, _)| belief)
+            .collect();
+        assert_eq!(activations, sorted);
     }
 }
@@ -0,0 +1,59 @@
+//! Writing only some of the ticks of a run to its outputs.
+//!
+//! The ticks are chosen as the outputs are extracted from the [Agent]s, so
+//! snapshots written from the same run keep every activation and action the
+//! [Agent]s hold.
+
+use belief_spread::SimTime;
+
+/// The ticks written to the outputs: every `every`th tick from the start
+/// time, and always the last tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputSampling {
+    /// The interval between the ticks written, where 1 writes every tick.
+    pub every: usize,
+    /// The first tick of the run.
+    pub start_time: SimTime,
+    /// The last tick written.
+    pub end_time: SimTime,
+}
+
+impl OutputSampling {
+    /// Whether the activations and actions at `time` are written.
+    ///
+    /// The initial state of the [Agent]s, before the start time, is always
+    /// written.
+    pub fn includes(&self, time: SimTime) -> bool {
+        time < self.start_time
+            || time == self.end_time
+            || ((time - self.start_time) as usize).is_multiple_of(self.every.max(1))
+    }
+
+    /// The ticks from the start time to the end time that are written, in
+    /// ascending order.
+    pub fn times(&self) -> Vec<SimTime> {
+        (self.start_time..=self.end_time)
+            .filter(|&time| self.includes(time))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_keeps_every_nth_tick_and_the_last() {
+        let sampling = |every, end_time| OutputSampling {
+            every,
+            start_time: 3,
+            end_time,
+        };
+        assert_eq!(sampling(10, 25).times(), vec![3, 13, 23, 25]);
+        assert_eq!(sampling(10, 23).times(), vec![3, 13, 23]);
+        assert_eq!(sampling(1, 6).times(), vec![3, 4, 5, 6]);
+        assert_eq!(sampling(4, 2).times(), Vec::<SimTime>::new());
+        assert!(sampling(10, 25).includes(0));
+        assert!(!sampling(10, 25).includes(4));
+    }
+}
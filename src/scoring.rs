@@ -0,0 +1,375 @@
+//! The scores used to choose which behaviour an agent performs.
+
+use belief_spread::{AgentPtr, BehaviourPtr, BeliefPtr, SimTime};
+
+use crate::{
+    performance_relationships::{PerformanceRelationships, PrsMatrix},
+    precision::Activation,
+};
+
+/// Compute the score of each [Behaviour] for an [Agent].
+///
+/// The score is the sum over [Belief]s of the performance relationship
+/// multiplied by the [Agent]'s activation of the [Belief]. Missing
+/// performance relationships and activations count as zero.
+///
+/// # Arguments
+/// - `agent`: The [Agent].
+/// - `time`: The [SimTime] of the activations.
+/// - `beliefs`: The [Belief]s.
+/// - `behaviours`: The [Behaviour]s.
+/// - `prs`: The [PerformanceRelationships].
+///
+/// # Returns
+/// Each [Behaviour] with its score, in the order of `behaviours`.
+pub fn compute_behaviour_scores(
+    agent: &AgentPtr,
+    time: SimTime,
+    beliefs: &[BeliefPtr],
+    behaviours: &[BehaviourPtr],
+    prs: &PerformanceRelationships,
+) -> Vec<(BehaviourPtr, f64)> {
+    let agent = agent.borrow();
+    behaviours
+        .iter()
+        .map(|behaviour| {
+            let behaviour_uuid = *behaviour.borrow().uuid();
+            (
+                behaviour.clone(),
+                beliefs
+                    .iter()
+                    .map(|belief| {
+                        prs.get(&(*belief.borrow().uuid(), behaviour_uuid))
+                            .unwrap_or(&0.0)
+                            * agent.get_activation(time, belief).unwrap_or(0.0)
+                    })
+                    .sum::<f64>(),
+            )
+        })
+        .collect()
+}
+
+/// Compute the score of each [Behaviour] for an [Agent], using a
+/// [PrsMatrix].
+///
+/// This gives the same scores as [compute_behaviour_scores], but reads each
+/// activation once and looks up performance relationships by position
+/// rather than by hashing. See [compute_behaviour_scores_into] for a version
+/// that does not allocate.
+///
+/// # Arguments
+/// - `agent`: The [Agent].
+/// - `time`: The [SimTime] of the activations.
+/// - `beliefs`: The [Belief]s, in the order of the [PrsMatrix].
+/// - `behaviours`: The [Behaviour]s, in the order of the [PrsMatrix].
+/// - `prs`: The [PrsMatrix].
+///
+/// # Returns
+/// Each [Behaviour] with its score, in the order of `behaviours`.
+pub fn compute_behaviour_scores_dense(
+    agent: &AgentPtr,
+    time: SimTime,
+    beliefs: &[BeliefPtr],
+    behaviours: &[BehaviourPtr],
+    prs: &PrsMatrix,
+) -> Vec<(BehaviourPtr, f64)> {
+    let mut scratch = ScoreScratch::default();
+    compute_behaviour_scores_into(agent, time, beliefs, behaviours.len(), prs, &mut scratch);
+    behaviours.iter().cloned().zip(scratch.scores).collect()
+}
+
+/// Buffers reused by [compute_behaviour_scores_into] for every [Agent].
+#[derive(Debug, Default, Clone)]
+pub struct ScoreScratch {
+    /// The activation of each [Belief].
+    activations: Vec<f64>,
+    /// The score of each [Behaviour].
+    pub scores: Vec<f64>,
+}
+
+/// Compute the score of each [Behaviour] for an [Agent] into reused
+/// buffers, which only allocate if they are too small.
+///
+/// # Arguments
+/// - `agent`: The [Agent].
+/// - `time`: The [SimTime] of the activations.
+/// - `beliefs`: The [Belief]s, in the order of the [PrsMatrix].
+/// - `n_behaviours`: The number of [Behaviour]s in the [PrsMatrix].
+/// - `prs`: The [PrsMatrix].
+/// - `scratch`: The buffers, whose `scores` are set to the score of each
+///   [Behaviour] in the order of the [PrsMatrix].
+pub fn compute_behaviour_scores_into(
+    agent: &AgentPtr,
+    time: SimTime,
+    beliefs: &[BeliefPtr],
+    n_behaviours: usize,
+    prs: &PrsMatrix,
+    scratch: &mut ScoreScratch,
+) {
+    {
+        let agent = agent.borrow();
+        scratch.activations.clear();
+        scratch.activations.extend(
+            beliefs
+                .iter()
+                .map(|belief| agent.get_activation(time, belief).unwrap_or(0.0)),
+        );
+    }
+    compute_behaviour_scores_from(&scratch.activations, n_behaviours, prs, &mut scratch.scores);
+}
+
+/// Compute the score of each [Behaviour] from the activations of an
+/// [Agent], such as those held by an [ActivationCache].
+///
+/// The activations may be of any [Activation] type, but the scores are
+/// always accumulated in [f64].
+///
+/// # Arguments
+/// - `activations`: The activation of each [Belief], in the order of the
+///   [PrsMatrix].
+/// - `n_behaviours`: The number of [Behaviour]s in the [PrsMatrix].
+/// - `prs`: The [PrsMatrix].
+/// - `scores`: Set to the score of each [Behaviour] in the order of the
+///   [PrsMatrix], only allocating if it is too small.
+pub fn compute_behaviour_scores_from<T: Activation>(
+    activations: &[T],
+    n_behaviours: usize,
+    prs: &PrsMatrix,
+    scores: &mut Vec<f64>,
+) {
+    scores.clear();
+    scores.extend((0..n_behaviours).map(|j| {
+        prs.behaviour_row(j)
+            .iter()
+            .zip(activations)
+            .map(|(v, a)| v * a.to_f64())
+            .sum::<f64>()
+    }));
+}
+
+/// The activation of every [Belief] for every [Agent] at one [SimTime],
+/// held in one dense buffer so that scoring reads a slice rather than
+/// looking each activation up through the [Agent].
+///
+/// The cache is filled for a tick with [ActivationCache::fill], or with
+/// [ActivationCache::start] and [ActivationCache::push], and keeps its buffer
+/// when invalidated, so it only allocates if the population grows.
+///
+/// The activations are held as `T`, which may be [f32] to halve the memory
+/// of the cache. See [Precision](crate::precision::Precision).
+#[derive(Debug, Default, Clone)]
+pub struct ActivationCache<T = f64> {
+    /// The [SimTime] of the activations, or [None] if the cache is empty.
+    time: Option<SimTime>,
+    /// The number of [Belief]s.
+    n_beliefs: usize,
+    /// The activations, one row of `n_beliefs` per [Agent]. Missing
+    /// activations are zero.
+    values: Vec<T>,
+}
+
+impl<T: Activation> ActivationCache<T> {
+    /// Replace the contents of the cache with the activations of `agents` at
+    /// `time`.
+    pub fn fill(&mut self, agents: &[AgentPtr], beliefs: &[BeliefPtr], time: SimTime) {
+        self.start(time, agents.len(), beliefs.len());
+        for agent in agents {
+            self.push(agent, beliefs);
+        }
+    }
+
+    /// Empty the cache to be filled with activations at `time` by calling
+    /// [ActivationCache::push] for each of `n_agents` [Agent]s in turn.
+    ///
+    /// This lets the cache be filled while each [Agent] is visited for
+    /// another reason, rather than in a pass of its own.
+    pub fn start(&mut self, time: SimTime, n_agents: usize, n_beliefs: usize) {
+        self.values.clear();
+        self.values.reserve(n_agents * n_beliefs);
+        self.n_beliefs = n_beliefs;
+        self.time = Some(time);
+    }
+
+    /// Append the activations of the next [Agent], at the [SimTime] given to
+    /// [ActivationCache::start].
+    pub fn push(&mut self, agent: &AgentPtr, beliefs: &[BeliefPtr]) {
+        debug_assert_eq!(beliefs.len(), self.n_beliefs);
+        let time = self.time.expect("the cache is started before pushing");
+        let agent = agent.borrow();
+        self.values.extend(
+            beliefs
+                .iter()
+                .map(|belief| T::from_f64(agent.get_activation(time, belief).unwrap_or(0.0))),
+        );
+    }
+
+    /// Empty the cache, keeping its buffer.
+    pub fn invalidate(&mut self) {
+        self.values.clear();
+        self.time = None;
+    }
+
+    /// The [SimTime] of the activations, or [None] if the cache is empty.
+    pub fn time(&self) -> Option<SimTime> {
+        self.time
+    }
+
+    /// The activations of the [Agent] at position `i` of the [Agent]s the
+    /// cache was filled with, in the order of the [Belief]s.
+    pub fn agent(&self, i: usize) -> &[T] {
+        &self.values[i * self.n_beliefs..(i + 1) * self.n_beliefs]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use belief_spread::{Agent, BasicAgent, BasicBehaviour, BasicBelief};
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    fn uuid_of_belief(b: &BeliefPtr) -> uuid::Uuid {
+        *b.borrow().uuid()
+    }
+
+    fn uuid_of_behaviour(b: &BehaviourPtr) -> uuid::Uuid {
+        *b.borrow().uuid()
+    }
+
+    #[test]
+    fn compute_behaviour_scores_works() {
+        let b1: BeliefPtr = BasicBelief::new("b1".to_string()).into();
+        let b2: BeliefPtr = BasicBelief::new("b2".to_string()).into();
+        let beh1: BehaviourPtr = BasicBehaviour::new("beh1".to_string()).into();
+        let beh2: BehaviourPtr = BasicBehaviour::new("beh2".to_string()).into();
+
+        let mut agent = BasicAgent::new();
+        agent.set_activation(2, b1.clone(), Some(0.5)).unwrap();
+        agent.set_activation(2, b2.clone(), Some(-0.25)).unwrap();
+        let agent: AgentPtr = agent.into();
+
+        let mut prs = PerformanceRelationships::new();
+        prs.insert((uuid_of_belief(&b1), uuid_of_behaviour(&beh1)), 0.8);
+        prs.insert((uuid_of_belief(&b2), uuid_of_behaviour(&beh1)), 0.4);
+        prs.insert((uuid_of_belief(&b2), uuid_of_behaviour(&beh2)), -1.0);
+
+        let scores =
+            compute_behaviour_scores(&agent, 2, &[b1, b2], &[beh1.clone(), beh2.clone()], &prs);
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].0, beh1);
+        assert_approx_eq!(f64, scores[0].1, 0.5 * 0.8 - 0.25 * 0.4);
+        assert_eq!(scores[1].0, beh2);
+        assert_approx_eq!(f64, scores[1].1, 0.25);
+    }
+
+    #[test]
+    fn compute_behaviour_scores_missing_activation_is_zero() {
+        let b1: BeliefPtr = BasicBelief::new("b1".to_string()).into();
+        let beh1: BehaviourPtr = BasicBehaviour::new("beh1".to_string()).into();
+
+        let mut agent = BasicAgent::new();
+        agent.set_activation(1, b1.clone(), Some(1.0)).unwrap();
+        let agent: AgentPtr = agent.into();
+
+        let mut prs = PerformanceRelationships::new();
+        prs.insert((uuid_of_belief(&b1), uuid_of_behaviour(&beh1)), 0.8);
+
+        let scores = compute_behaviour_scores(&agent, 2, &[b1], &[beh1], &prs);
+        assert_approx_eq!(f64, scores[0].1, 0.0);
+    }
+
+    #[test]
+    fn compute_behaviour_scores_missing_prs_is_zero() {
+        let b1: BeliefPtr = BasicBelief::new("b1".to_string()).into();
+        let beh1: BehaviourPtr = BasicBehaviour::new("beh1".to_string()).into();
+
+        let mut agent = BasicAgent::new();
+        agent.set_activation(1, b1.clone(), Some(1.0)).unwrap();
+        let agent: AgentPtr = agent.into();
+
+        let scores =
+            compute_behaviour_scores(&agent, 1, &[b1], &[beh1], &PerformanceRelationships::new());
+        assert_approx_eq!(f64, scores[0].1, 0.0);
+    }
+
+    #[test]
+    fn compute_behaviour_scores_no_behaviours_is_empty() {
+        let b1: BeliefPtr = BasicBelief::new("b1".to_string()).into();
+        let agent: AgentPtr = BasicAgent::new().into();
+
+        assert!(
+            compute_behaviour_scores(&agent, 1, &[b1], &[], &PerformanceRelationships::new())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn compute_behaviour_scores_dense_matches_map() {
+        let beliefs: Vec<BeliefPtr> = (0..5)
+            .map(|i| BasicBelief::new(format!("b{i}")).into())
+            .collect();
+        let behaviours: Vec<BehaviourPtr> = (0..4)
+            .map(|i| BasicBehaviour::new(format!("beh{i}")).into())
+            .collect();
+
+        let mut agent = BasicAgent::new();
+        for (i, belief) in beliefs.iter().enumerate().skip(1) {
+            agent
+                .set_activation(3, belief.clone(), Some(0.9 - 0.4 * i as f64))
+                .unwrap();
+        }
+        let agent: AgentPtr = agent.into();
+
+        let mut prs = PerformanceRelationships::new();
+        for (i, belief) in beliefs.iter().enumerate() {
+            for (j, behaviour) in behaviours.iter().enumerate() {
+                if (i + j) % 3 != 0 {
+                    prs.insert(
+                        (uuid_of_belief(belief), uuid_of_behaviour(behaviour)),
+                        ((i * 7 + j * 3) % 11) as f64 / 5.5 - 1.0,
+                    );
+                }
+            }
+        }
+        let matrix = PrsMatrix::new(&prs, &beliefs, &behaviours);
+
+        let expected = compute_behaviour_scores(&agent, 3, &beliefs, &behaviours, &prs);
+        let actual = compute_behaviour_scores_dense(&agent, 3, &beliefs, &behaviours, &matrix);
+        assert_eq!(expected.len(), actual.len());
+        for ((b1, s1), (b2, s2)) in expected.iter().zip(&actual) {
+            assert_eq!(b1, b2);
+            assert_approx_eq!(f64, *s1, *s2, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn activation_cache_holds_each_agent_in_belief_order() {
+        let b1: BeliefPtr = BasicBelief::new("b1".to_string()).into();
+        let b2: BeliefPtr = BasicBelief::new("b2".to_string()).into();
+        let mut a1 = BasicAgent::new();
+        a1.set_activation(2, b1.clone(), Some(0.5)).unwrap();
+        a1.set_activation(2, b2.clone(), Some(-0.25)).unwrap();
+        a1.set_activation(3, b1.clone(), Some(1.0)).unwrap();
+        let mut a2 = BasicAgent::new();
+        a2.set_activation(2, b2.clone(), Some(0.75)).unwrap();
+        let agents: Vec<AgentPtr> = vec![a1.into(), a2.into()];
+
+        let mut cache = ActivationCache::<f64>::default();
+        cache.fill(&agents, &[b1.clone(), b2.clone()], 2);
+        assert_eq!(cache.time(), Some(2));
+        assert_eq!(cache.agent(0), [0.5, -0.25]);
+        assert_eq!(cache.agent(1), [0.0, 0.75]);
+
+        cache.fill(&agents, &[b1, b2.clone()], 3);
+        assert_eq!(cache.agent(0), [1.0, 0.0]);
+        assert_eq!(cache.agent(1), [0.0, 0.0]);
+
+        cache.invalidate();
+        assert_eq!(cache.time(), None);
+
+        let mut cache = ActivationCache::<f32>::default();
+        cache.fill(&agents, &[b2], 2);
+        assert_eq!(cache.agent(0), [-0.25f32]);
+        assert_eq!(cache.agent(1), [0.75f32]);
+    }
+}
@@ -0,0 +1,350 @@
+//! Strategies for choosing which behaviour an agent performs.
+
+use belief_spread::{AgentPtr, SimTime};
+use rand::{Rng, RngCore};
+
+/// A rule mapping the scores of each [Behaviour] for an [Agent] to the
+/// [Behaviour] the [Agent] performs.
+///
+/// The scores are computed by the [Runner](crate::runner::Runner), so
+/// strategies only differ in how they turn scores into a choice.
+pub trait ActionSelection {
+    /// Select the [Behaviour] the [Agent] performs at a [SimTime].
+    ///
+    /// This is called for every [Agent] at every tick, so implementations
+    /// should avoid allocating.
+    ///
+    /// # Arguments
+    /// - `agent`: The [Agent].
+    /// - `time`: The [SimTime].
+    /// - `scores`: The score of each [Behaviour] for the [Agent], in the
+    ///   canonical order of the [Behaviour]s, by [Uuid](uuid::Uuid).
+    /// - `rng`: The random number generator.
+    ///
+    /// # Returns
+    /// The index of the chosen [Behaviour] in `scores`, or [None] if there
+    /// are no [Behaviour]s.
+    ///
+    /// Where a strategy chooses the highest score, ties are broken by
+    /// choosing the first, which is the [Behaviour] with the lowest
+    /// [Uuid](uuid::Uuid).
+    fn select(
+        &self,
+        agent: &AgentPtr,
+        time: SimTime,
+        scores: &[f64],
+        rng: &mut dyn RngCore,
+    ) -> Option<usize>;
+}
+
+/// Choose a [Behaviour] with probability proportional to its score.
+///
+/// Only positive scores may be chosen. If no score is positive, the
+/// [Behaviour] with the highest score is always chosen, or the first of
+/// those tied for it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LinearSelection;
+
+impl LinearSelection {
+    /// The probability of choosing each score.
+    pub fn probabilities(scores: &[f64]) -> Vec<f64> {
+        match Self::total(scores) {
+            Some(total) => scores
+                .iter()
+                .map(|&v| Self::probability(v, total))
+                .collect(),
+            None => one_hot_argmax(scores).collect(),
+        }
+    }
+
+    /// The sum of the positive scores, if there are any.
+    fn total(scores: &[f64]) -> Option<f64> {
+        let total: f64 = scores.iter().filter(|&&v| v > 0.0).sum();
+        (total > 0.0).then_some(total)
+    }
+
+    fn probability(score: f64, total: f64) -> f64 {
+        if score > 0.0 {
+            score / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl ActionSelection for LinearSelection {
+    fn select(
+        &self,
+        _agent: &AgentPtr,
+        _time: SimTime,
+        scores: &[f64],
+        rng: &mut dyn RngCore,
+    ) -> Option<usize> {
+        match Self::total(scores) {
+            Some(total) => sample(scores.iter().map(|&v| Self::probability(v, total)), rng),
+            None => sample(one_hot_argmax(scores), rng),
+        }
+    }
+}
+
+/// Always choose the [Behaviour] with the highest score, or the first of
+/// those tied for it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GreedySelection;
+
+impl ActionSelection for GreedySelection {
+    fn select(
+        &self,
+        _agent: &AgentPtr,
+        _time: SimTime,
+        scores: &[f64],
+        _rng: &mut dyn RngCore,
+    ) -> Option<usize> {
+        argmax(scores)
+    }
+}
+
+/// Choose a [Behaviour] with probability proportional to
+/// `exp(score / temperature)`.
+///
+/// Every [Behaviour] may be chosen, including those with negative scores.
+/// Lower temperatures make the choice more peaked, and a temperature of
+/// zero (or below) always chooses the highest score, or the first of those
+/// tied for it.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftmaxSelection {
+    /// The temperature.
+    pub temperature: f64,
+}
+
+impl SoftmaxSelection {
+    /// The probability of choosing each score.
+    ///
+    /// The maximum score is subtracted before exponentiating, so small
+    /// temperatures tend to the one-hot argmax rather than overflowing.
+    pub fn probabilities(&self, scores: &[f64]) -> Vec<f64> {
+        match self.normalization(scores) {
+            Some((max, total)) => scores
+                .iter()
+                .map(|&v| self.weight(v, max) / total)
+                .collect(),
+            None => one_hot_argmax(scores).collect(),
+        }
+    }
+
+    /// The maximum score and the sum of the weights, or [None] if the
+    /// highest score should always be chosen.
+    fn normalization(&self, scores: &[f64]) -> Option<(f64, f64)> {
+        if self.temperature <= 0.0 {
+            return None;
+        }
+        let max = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let total: f64 = scores.iter().map(|&v| self.weight(v, max)).sum();
+        (total.is_finite() && total > 0.0).then_some((max, total))
+    }
+
+    fn weight(&self, score: f64, max: f64) -> f64 {
+        ((score - max) / self.temperature).exp()
+    }
+}
+
+impl ActionSelection for SoftmaxSelection {
+    fn select(
+        &self,
+        _agent: &AgentPtr,
+        _time: SimTime,
+        scores: &[f64],
+        rng: &mut dyn RngCore,
+    ) -> Option<usize> {
+        match self.normalization(scores) {
+            Some((max, total)) => sample(scores.iter().map(|&v| self.weight(v, max) / total), rng),
+            None => sample(one_hot_argmax(scores), rng),
+        }
+    }
+}
+
+/// The index of the highest score, the first one if there are several.
+fn argmax(scores: &[f64]) -> Option<usize> {
+    scores
+        .iter()
+        .enumerate()
+        .reduce(|best, score| if score.1 > best.1 { score } else { best })
+        .map(|(i, _)| i)
+}
+
+fn one_hot_argmax(scores: &[f64]) -> impl Iterator<Item = f64> {
+    let max = argmax(scores);
+    (0..scores.len()).map(move |i| if Some(i) == max { 1.0 } else { 0.0 })
+}
+
+/// Sample an index according to its probability.
+///
+/// A random number is always drawn, even if there is nothing to choose, so
+/// that the random number generator advances by the same amount for every
+/// [Agent].
+fn sample(probabilities: impl Iterator<Item = f64>, rng: &mut dyn RngCore) -> Option<usize> {
+    let mut rv: f64 = rng.gen();
+    let mut chosen = None;
+
+    for (i, p) in probabilities.enumerate() {
+        if p > 0.0 {
+            // Floating point error may leave a tiny remainder, in which case
+            // the last candidate is chosen
+            chosen = Some(i);
+            rv -= p;
+            if rv <= 0.0 {
+                break;
+            }
+        }
+    }
+
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use belief_spread::BasicAgent;
+    use float_cmp::assert_approx_eq;
+    use rand::{rngs::mock::StepRng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn agent() -> AgentPtr {
+        BasicAgent::new().into()
+    }
+
+    #[test]
+    fn linear_probabilities_normalizes_positive_scores() {
+        let p = LinearSelection::probabilities(&[0.2, -0.5, 0.6, 0.0]);
+        assert_approx_eq!(f64, p[0], 0.25);
+        assert_approx_eq!(f64, p[1], 0.0);
+        assert_approx_eq!(f64, p[2], 0.75);
+        assert_approx_eq!(f64, p[3], 0.0);
+    }
+
+    #[test]
+    fn linear_probabilities_picks_max_when_none_positive() {
+        assert_eq!(
+            LinearSelection::probabilities(&[-0.2, -0.1, -0.5]),
+            vec![0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn linear_select_single_positive_always_chosen() {
+        let mut rng = StepRng::new(u64::MAX, 0);
+        let chosen = LinearSelection.select(&agent(), 1, &[-0.2, 0.4, 0.0], &mut rng);
+        assert_eq!(chosen, Some(1));
+    }
+
+    #[test]
+    fn select_with_no_behaviours_is_none() {
+        let mut rng = StepRng::new(0, 1);
+        assert!(LinearSelection.select(&agent(), 1, &[], &mut rng).is_none());
+        assert!(GreedySelection.select(&agent(), 1, &[], &mut rng).is_none());
+        let softmax = SoftmaxSelection { temperature: 1.0 };
+        assert!(softmax.select(&agent(), 1, &[], &mut rng).is_none());
+    }
+
+    #[test]
+    fn greedy_select_picks_max() {
+        let mut rng = StepRng::new(0, 1);
+        let chosen = GreedySelection.select(&agent(), 1, &[0.2, 0.9, 0.4], &mut rng);
+        assert_eq!(chosen, Some(1));
+    }
+
+    #[test]
+    fn tied_highest_scores_choose_the_first() {
+        let mut rng = StepRng::new(u64::MAX, 0);
+        let scores = [-0.5, -0.1, -0.1, -0.3];
+        assert_eq!(
+            LinearSelection::probabilities(&scores),
+            vec![0.0, 1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            LinearSelection.select(&agent(), 1, &scores, &mut rng),
+            Some(1)
+        );
+        let softmax = SoftmaxSelection { temperature: 0.0 };
+        assert_eq!(softmax.select(&agent(), 1, &scores, &mut rng), Some(1));
+        let scores = [0.2, 0.9, 0.4, 0.9];
+        assert_eq!(
+            GreedySelection.select(&agent(), 1, &scores, &mut rng),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn softmax_probabilities_works() {
+        // exp(0) : exp(-ln 2) : exp(-ln 4) = 1 : 1/2 : 1/4
+        let ln2 = std::f64::consts::LN_2;
+        let softmax = SoftmaxSelection { temperature: 1.0 };
+        let p = softmax.probabilities(&[0.0, -ln2, -2.0 * ln2]);
+        assert_approx_eq!(f64, p[0], 4.0 / 7.0, epsilon = 1e-12);
+        assert_approx_eq!(f64, p[1], 2.0 / 7.0, epsilon = 1e-12);
+        assert_approx_eq!(f64, p[2], 1.0 / 7.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn softmax_probabilities_uses_temperature() {
+        let softmax = SoftmaxSelection { temperature: 2.0 };
+        let p = softmax.probabilities(&[2.0 * std::f64::consts::LN_2, 0.0]);
+        assert_approx_eq!(f64, p[0], 2.0 / 3.0, epsilon = 1e-12);
+        assert_approx_eq!(f64, p[1], 1.0 / 3.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn softmax_probabilities_tiny_temperature_is_argmax() {
+        let softmax = SoftmaxSelection {
+            temperature: 1e-300,
+        };
+        assert_eq!(
+            softmax.probabilities(&[0.1, 0.3, -0.2]),
+            vec![0.0, 1.0, 0.0]
+        );
+        let softmax = SoftmaxSelection { temperature: 0.0 };
+        assert_eq!(
+            softmax.probabilities(&[0.1, 0.3, -0.2]),
+            vec![0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn sample_follows_cumulative_probabilities() {
+        // StepRng yielding 0 gives rv = 0.0, which selects the first candidate
+        let mut rng = StepRng::new(0, 0);
+        assert_eq!(sample([0.25, 0.75].into_iter(), &mut rng), Some(0));
+        // StepRng yielding u64::MAX gives rv just below 1.0
+        let mut rng = StepRng::new(u64::MAX, 0);
+        assert_eq!(sample([0.25, 0.75].into_iter(), &mut rng), Some(1));
+    }
+
+    #[test]
+    fn select_matches_sampling_from_probabilities() {
+        let mut scores_rng = ChaCha8Rng::seed_from_u64(7);
+        let (mut rng, mut expected_rng) =
+            (ChaCha8Rng::seed_from_u64(42), ChaCha8Rng::seed_from_u64(42));
+        let softmax = SoftmaxSelection { temperature: 0.3 };
+        for n in 0..200 {
+            let scores: Vec<f64> = (0..n % 6)
+                .map(|_| scores_rng.gen_range(-1.0..1.0))
+                .collect();
+
+            let expected = sample(
+                LinearSelection::probabilities(&scores).into_iter(),
+                &mut expected_rng,
+            );
+            assert_eq!(
+                LinearSelection.select(&agent(), 1, &scores, &mut rng),
+                expected
+            );
+
+            let expected = sample(
+                softmax.probabilities(&scores).into_iter(),
+                &mut expected_rng,
+            );
+            assert_eq!(softmax.select(&agent(), 1, &scores, &mut rng), expected);
+        }
+    }
+}
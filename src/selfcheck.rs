@@ -0,0 +1,244 @@
+//! Checking that a build simulates deterministically, by running the same
+//! scenario twice with the same seed and comparing every part of the final
+//! state of the two runs.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use belief_spread::SimTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    comparison::Comparison, error::ConceptError, fingerprint::BELIEF_SPREAD_VERSION,
+    runner::Runner, snapshot::SimulationSnapshot,
+};
+
+/// The first part of the state of two runs that differs between them.
+///
+/// The deltas and friends of the [Agent]s are compared first, as they
+/// determine the rest, then the activations and actions tick by tick, and
+/// then the state of the random number generator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Divergence {
+    /// The tick of the activation or action, if it is one.
+    pub time: Option<SimTime>,
+    /// The [Agent], unless it is the random number generator.
+    pub agent: Option<Uuid>,
+    /// The part of the state: `agent`, `delta`, `friend`, `activation`,
+    /// `action` or `rng`.
+    pub field: &'static str,
+    /// The [Belief], [Behaviour] or friend the value is for, if any.
+    pub key: Option<Uuid>,
+    /// The value in the first run, or [None] if it is missing.
+    pub first: Option<String>,
+    /// The value in the second run, or [None] if it is missing.
+    pub second: Option<String>,
+}
+
+/// The position of a value in the state of a run, ordered as the values are
+/// compared.
+type StateKey = (bool, SimTime, Uuid, &'static str, Option<Uuid>);
+
+/// Every value of the state of the [Agent]s of a snapshot, formatted so that
+/// values are equal only if they are bit for bit the same.
+fn state_values(snapshot: &SimulationSnapshot) -> BTreeMap<StateKey, String> {
+    let mut values = BTreeMap::new();
+    for agent in &snapshot.agents {
+        let uuid = agent.uuid;
+        values.insert((false, 0, uuid, "agent", None), String::from("present"));
+        for (&belief, &delta) in &agent.deltas {
+            values.insert(
+                (false, 0, uuid, "delta", Some(belief)),
+                format!("{delta:?}"),
+            );
+        }
+        for (&friend, &weight) in &agent.friends {
+            values.insert(
+                (false, 0, uuid, "friend", Some(friend)),
+                format!("{weight:?}"),
+            );
+        }
+        for (&time, activations) in &agent.activations {
+            for (&belief, &activation) in activations {
+                values.insert(
+                    (true, time, uuid, "activation", Some(belief)),
+                    format!("{activation:?}"),
+                );
+            }
+        }
+        for (&time, &behaviour) in &agent.actions {
+            values.insert((true, time, uuid, "action", None), behaviour.to_string());
+        }
+    }
+    values
+}
+
+/// Find the first part of the state of two snapshots that differs.
+///
+/// # Returns
+/// The first [Divergence], or [None] if the snapshots are identical.
+pub fn first_divergence(
+    first: &SimulationSnapshot,
+    second: &SimulationSnapshot,
+) -> Option<Divergence> {
+    let first_values = state_values(first);
+    let second_values = state_values(second);
+    let keys: BTreeSet<&StateKey> = first_values.keys().chain(second_values.keys()).collect();
+    let divergence = keys
+        .into_iter()
+        .find(|key| first_values.get(key) != second_values.get(key))
+        .map(|key @ &(ticking, time, agent, field, other)| Divergence {
+            time: ticking.then_some(time),
+            agent: Some(agent),
+            field,
+            key: other,
+            first: first_values.get(key).cloned(),
+            second: second_values.get(key).cloned(),
+        })
+        .or_else(|| {
+            (first.rng != second.rng).then_some(Divergence {
+                time: None,
+                agent: None,
+                field: "rng",
+                key: None,
+                first: None,
+                second: None,
+            })
+        });
+    divergence
+}
+
+/// The optional features this build was compiled with.
+pub fn compiled_features() -> Vec<&'static str> {
+    [
+        ("cli", cfg!(feature = "cli")),
+        ("zstd", cfg!(feature = "zstd")),
+        ("sqlite", cfg!(feature = "sqlite")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+/// Whether two runs of the same scenario with the same seed were identical.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfCheckReport {
+    /// Whether the runs were identical.
+    pub passed: bool,
+    /// The version of this build.
+    pub version: &'static str,
+    /// The version of belief_spread this build is built against.
+    pub belief_spread_version: &'static str,
+    /// The optional features this build was compiled with.
+    pub features: Vec<&'static str>,
+    /// The seed both runs were run with.
+    pub seed: u64,
+    /// The number of threads each run simulated its ticks on.
+    pub threads: [usize; 2],
+    /// The last tick simulated.
+    pub last_tick: SimTime,
+    /// The number of [Agent]s in the scenario.
+    pub n_agents: usize,
+    /// The ticks whose summaries differ between the runs.
+    pub differing_ticks: Vec<SimTime>,
+    /// The first part of the state that differs, if any does.
+    pub first_divergence: Option<Divergence>,
+}
+
+impl SelfCheckReport {
+    /// Simulate the remaining ticks of two [Runner]s, and compare their
+    /// summaries and their final states.
+    ///
+    /// Each [Runner] should start from a copy of the same initial state with
+    /// the same seed, so that any difference between them is
+    /// nondeterminism, such as one simulated serially and the other in
+    /// parallel. Their outputs are not written.
+    pub fn run(first: &mut Runner, second: &mut Runner) -> Result<Self, ConceptError> {
+        let comparison = Comparison::run(first, second)?;
+        let differing_ticks: Vec<SimTime> = comparison
+            .data
+            .iter()
+            .filter(|(_, difference)| {
+                difference.mean_activation.values().any(|&d| d != 0.0)
+                    || difference.n_performers.values().any(|&d| d != 0)
+            })
+            .map(|(&time, _)| time)
+            .collect();
+        let first_divergence = first_divergence(&first.snapshot(), &second.snapshot());
+        Ok(SelfCheckReport {
+            passed: differing_ticks.is_empty() && first_divergence.is_none(),
+            version: env!("CARGO_PKG_VERSION"),
+            belief_spread_version: BELIEF_SPREAD_VERSION,
+            features: compiled_features(),
+            seed: first.seed(),
+            threads: [first.threads(), second.threads()],
+            last_tick: first.time(),
+            n_agents: first.config.agents.len(),
+            differing_ticks,
+            first_divergence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::configuration::tests::small_builder;
+
+    fn runner() -> Runner {
+        Runner::new(small_builder().build().unwrap()).with_seed(12)
+    }
+
+    #[test]
+    fn identical_runs_pass() {
+        let mut serial = runner().with_threads(1).unwrap();
+        let mut parallel = runner().with_threads(3).unwrap();
+        let report = SelfCheckReport::run(&mut serial, &mut parallel).unwrap();
+        assert!(report.passed);
+        assert_eq!(report.threads, [1, 3]);
+        assert_eq!(report.seed, 12);
+        assert_eq!(report.last_tick, 3);
+        assert_eq!(report.first_divergence, None);
+    }
+
+    #[test]
+    fn the_earliest_divergence_is_reported() {
+        let mut second = runner();
+        let agent = second.config.agents[1].clone();
+        let belief = second.config.beliefs[0].clone();
+        agent.borrow_mut().set_delta(belief, Some(0.5)).unwrap();
+        let report = SelfCheckReport::run(&mut runner(), &mut second).unwrap();
+        assert!(!report.passed);
+        assert_eq!(
+            report.first_divergence,
+            Some(Divergence {
+                time: None,
+                agent: Some(Uuid::from_u128(0x301)),
+                field: "delta",
+                key: Some(Uuid::from_u128(0x200)),
+                first: Some(String::from("1.0")),
+                second: Some(String::from("0.5")),
+            })
+        );
+
+        // Once the deltas match, the first activation that differs is found
+        let mut first = runner().snapshot();
+        let mut second = first.clone();
+        first.agents[2]
+            .activations
+            .insert(2, HashMap::from([(Uuid::from_u128(0x201), 0.25)]));
+        second.agents[0]
+            .activations
+            .insert(3, HashMap::from([(Uuid::from_u128(0x200), 0.5)]));
+        let divergence = first_divergence(&first, &second).unwrap();
+        assert_eq!(
+            (divergence.time, divergence.agent, divergence.field),
+            (Some(2), Some(Uuid::from_u128(0x302)), "activation")
+        );
+        assert_eq!(divergence.second, None);
+    }
+}
@@ -0,0 +1,375 @@
+//! Destinations for the output of a simulation.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::error::ConceptError;
+
+/// A destination the output of a simulation is written to.
+///
+/// A sink is written to as a [Write], and then [OutputSink::finish] is
+/// called once all of the output has been written.
+pub trait OutputSink: Write + Send {
+    /// Finish writing, flushing any buffered output and writing any
+    /// trailers (such as the end of a compressed frame).
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl OutputSink for Vec<u8> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> OutputSink for BufWriter<W> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write + Send> OutputSink for zstd::stream::write::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let mut inner = (*self).finish()?;
+        inner.flush()
+    }
+}
+
+/// The compression applied to an output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum Compression {
+    /// Plain, uncompressed output.
+    None,
+    /// zstd compressed output, at the given level, compressed by `workers`
+    /// threads besides the one writing, or by the writing thread if it is 0.
+    ///
+    /// Opening a file with this compression fails unless the `zstd` feature
+    /// is enabled.
+    Zstd { level: i32, workers: u32 },
+}
+
+impl Compression {
+    /// The compression implied by the name of a file: zstd at level 3 for
+    /// `.zst`, such as `output.json.zst`, and none for `.json` or `.csv`, or
+    /// for `.ndjson` and `.jsonl` logs.
+    ///
+    /// # Returns
+    /// The [Compression], or a [ConceptError::UnknownFormat] for any other
+    /// name, such as `.json.gz`, as gzip is not supported.
+    pub fn for_path(path: &Path) -> Result<Self, ConceptError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("zst") => Ok(Compression::Zstd {
+                level: 3,
+                workers: 0,
+            }),
+            Some("json" | "ndjson" | "jsonl" | "csv") => Ok(Compression::None),
+            _ => Err(ConceptError::UnknownFormat {
+                path: path.to_path_buf(),
+            }),
+        }
+    }
+
+    /// `path` with its name made to match the compression: `.zst` is added
+    /// for zstd if the name does not end in it, and removed for none if it
+    /// does, so `output.json.zst` gives `output.json`.
+    pub fn path_for(&self, path: &Path) -> PathBuf {
+        let zst = path.extension().is_some_and(|ext| ext == "zst");
+        match (self, zst) {
+            (Compression::None, true) => path.with_extension(""),
+            (Compression::Zstd { .. }, false) => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(".zst");
+                PathBuf::from(name)
+            }
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+impl Default for Compression {
+    /// zstd compression if the `zstd` feature is enabled, otherwise none.
+    fn default() -> Self {
+        if cfg!(feature = "zstd") {
+            Compression::Zstd {
+                level: 3,
+                workers: 0,
+            }
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// The settings for writing output to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSettings {
+    /// The path of the output file.
+    pub path: PathBuf,
+    /// The compression applied to the output file.
+    pub compression: Compression,
+}
+
+impl OutputSettings {
+    /// Create the output file and wrap it in the [OutputSink] for these
+    /// settings.
+    pub fn open(&self) -> io::Result<Box<dyn OutputSink>> {
+        let writer = BufWriter::new(File::create(&self.path)?);
+        Ok(match self.compression {
+            Compression::None => Box::new(writer),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level, workers } => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+                if workers > 0 {
+                    encoder.multithread(workers)?;
+                }
+                Box::new(encoder)
+            }
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd { .. } => return Err(zstd_disabled()),
+        })
+    }
+}
+
+/// The estimated size below which [AutoCompression] leaves outputs plain by
+/// default, 16 MiB.
+pub const DEFAULT_PLAIN_BELOW: u64 = 16 << 20;
+
+/// The estimated size from which [AutoCompression] compresses outputs as
+/// large by default, 1 GiB.
+pub const DEFAULT_LARGE_FROM: u64 = 1 << 30;
+
+/// Choosing the [Compression] of an output file from an estimate of its
+/// uncompressed size, so small outputs are left plain and large outputs are
+/// compressed harder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoCompression {
+    /// Outputs estimated to be smaller than this many bytes are left plain.
+    pub plain_below: u64,
+    /// Outputs estimated to be at least this many bytes are compressed at
+    /// [AutoCompression::LARGE_LEVEL] by [AutoCompression::workers] threads.
+    /// Those in between are compressed at [AutoCompression::MEDIUM_LEVEL].
+    pub large_from: u64,
+    /// The number of threads compressing large outputs.
+    pub workers: u32,
+}
+
+impl AutoCompression {
+    /// The zstd level of outputs between the thresholds.
+    pub const MEDIUM_LEVEL: i32 = 3;
+    /// The zstd level of large outputs.
+    pub const LARGE_LEVEL: i32 = 12;
+
+    /// The [Compression] of an output estimated to be `estimated_bytes`
+    /// uncompressed, which is always [Compression::None] unless the `zstd`
+    /// feature is enabled.
+    pub fn choose(&self, estimated_bytes: u64) -> Compression {
+        if !cfg!(feature = "zstd") || estimated_bytes < self.plain_below {
+            Compression::None
+        } else if estimated_bytes < self.large_from {
+            Compression::Zstd {
+                level: Self::MEDIUM_LEVEL,
+                workers: 0,
+            }
+        } else {
+            Compression::Zstd {
+                level: Self::LARGE_LEVEL,
+                workers: self.workers,
+            }
+        }
+    }
+}
+
+impl Default for AutoCompression {
+    /// Plain below [DEFAULT_PLAIN_BELOW], and large from
+    /// [DEFAULT_LARGE_FROM], compressed by a thread per CPU.
+    fn default() -> Self {
+        AutoCompression {
+            plain_below: DEFAULT_PLAIN_BELOW,
+            large_from: DEFAULT_LARGE_FROM,
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+        }
+    }
+}
+
+/// The [Compression] chosen for an output by [AutoCompression].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionChoice {
+    /// The estimated size of the uncompressed output.
+    pub estimated_bytes: u64,
+    /// The thresholds the [Compression] was chosen by.
+    pub thresholds: AutoCompression,
+    /// The [Compression] chosen.
+    pub compression: Compression,
+}
+
+/// The error for zstd compression without the `zstd` feature.
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn zstd_disabled() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "zstd support requires the zstd feature",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn write_and_read_back(compression: Compression) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4()));
+        let settings = OutputSettings {
+            path: path.clone(),
+            compression,
+        };
+        let mut sink = settings.open().unwrap();
+        sink.write_all(b"[1,2,3]").unwrap();
+        sink.finish().unwrap();
+
+        let mut bytes = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn open_uncompressed_works() {
+        assert_eq!(write_and_read_back(Compression::None), b"[1,2,3]");
+    }
+
+    #[test]
+    fn paths_are_renamed_to_match_their_compression() {
+        let zstd = Compression::Zstd {
+            level: 12,
+            workers: 4,
+        };
+        for (name, compression, expected) in [
+            ("out/output.json.zst", Compression::None, "out/output.json"),
+            ("output.json", Compression::None, "output.json"),
+            ("output.json", zstd, "output.json.zst"),
+            ("output.csv.zst", zstd, "output.csv.zst"),
+        ] {
+            let path = compression.path_for(Path::new(name));
+            assert_eq!(path, Path::new(expected));
+            assert_eq!(
+                std::mem::discriminant(&Compression::for_path(&path).unwrap()),
+                std::mem::discriminant(&compression)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn open_zstd_works() {
+        let bytes = write_and_read_back(Compression::Zstd {
+            level: 3,
+            workers: 0,
+        });
+        assert_eq!(zstd::decode_all(bytes.as_slice()).unwrap(), b"[1,2,3]");
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn open_multithreaded_zstd_works() {
+        let bytes = write_and_read_back(Compression::Zstd {
+            level: 12,
+            workers: 2,
+        });
+        assert_eq!(zstd::decode_all(bytes.as_slice()).unwrap(), b"[1,2,3]");
+    }
+
+    #[test]
+    fn compression_follows_the_file_name() {
+        let zstd = Compression::Zstd {
+            level: 3,
+            workers: 0,
+        };
+        for (name, expected) in [
+            ("output.json", Compression::None),
+            ("actions.ndjson", Compression::None),
+            ("output.csv", Compression::None),
+            ("output.json.zst", zstd),
+            ("output.csv.zst", zstd),
+            ("output.zst", zstd),
+        ] {
+            assert_eq!(Compression::for_path(Path::new(name)).unwrap(), expected);
+        }
+        for name in ["output.json.gz", "output", "output.txt"] {
+            assert!(matches!(
+                Compression::for_path(Path::new(name)),
+                Err(ConceptError::UnknownFormat { path }) if path == Path::new(name)
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn auto_compression_grows_with_the_estimate() {
+        let auto = AutoCompression {
+            plain_below: 1000,
+            large_from: 1_000_000,
+            workers: 4,
+        };
+        let medium = Compression::Zstd {
+            level: AutoCompression::MEDIUM_LEVEL,
+            workers: 0,
+        };
+        let large = Compression::Zstd {
+            level: AutoCompression::LARGE_LEVEL,
+            workers: 4,
+        };
+        for (estimate, expected) in [
+            (0, Compression::None),
+            (999, Compression::None),
+            (1000, medium),
+            (500_000, medium),
+            (999_999, medium),
+            (1_000_000, large),
+            (u64::MAX, large),
+        ] {
+            assert_eq!(auto.choose(estimate), expected, "estimate {estimate}");
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn auto_compression_without_feature_is_plain() {
+        assert_eq!(
+            AutoCompression::default().choose(u64::MAX),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn vec_sink_works() {
+        let mut sink: Box<dyn OutputSink> = Box::new(Vec::new());
+        sink.write_all(b"abc").unwrap();
+        assert!(sink.finish().is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn open_zstd_without_feature_fails() {
+        let settings = OutputSettings {
+            path: std::env::temp_dir().join(format!("concept-{}", Uuid::new_v4())),
+            compression: Compression::Zstd {
+                level: 3,
+                workers: 0,
+            },
+        };
+        let err = settings.open().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        std::fs::remove_file(settings.path).unwrap();
+    }
+}
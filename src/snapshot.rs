@@ -0,0 +1,221 @@
+//! Snapshots of the state of a simulation part way through a run.
+
+use std::path::{Path, PathBuf};
+
+use belief_spread::SimTime;
+use rand_chacha::ChaCha8Rng;
+use serde::{
+    ser::{SerializeSeq, SerializeStruct},
+    Deserialize, Serialize, Serializer,
+};
+
+use crate::{configuration::Configuration, json::AgentSpec};
+
+/// The state of a simulation after a tick, from which the simulation can be
+/// continued.
+///
+/// Taken with [Runner::snapshot](crate::runner::Runner::snapshot) and
+/// restored with [Runner::restore](crate::runner::Runner::restore). The
+/// [Belief]s, [Behaviour]s and performance relationships are not included,
+/// as they do not change during a run.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationSnapshot {
+    /// The last tick that was simulated.
+    pub time: SimTime,
+    /// The state of every [Agent], with friends referenced by UUID.
+    pub agents: Vec<AgentSpec>,
+    /// The state of the random number generator.
+    pub rng: ChaCha8Rng,
+    /// The ticks of the activations and actions included, if the history
+    /// before the run was left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryRange>,
+}
+
+/// The ticks of the activations and actions included in a snapshot, which
+/// leaves out the history a run started from.
+///
+/// The deltas and friends are always included, and the last tick is always
+/// in the range, so the snapshot can still be continued from.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRange {
+    /// The first tick included.
+    pub start: SimTime,
+    /// The last tick included.
+    pub end: SimTime,
+}
+
+impl HistoryRange {
+    /// Whether the activations and actions at `time` are included.
+    pub fn contains(&self, time: SimTime) -> bool {
+        (self.start..=self.end).contains(&time)
+    }
+}
+
+/// The [AgentSpec] of the [Agent] at position `i` of a [Configuration],
+/// with its floors and ceilings, and only the activations and actions in
+/// `history` if it is given.
+pub(crate) fn agent_spec(
+    config: &Configuration,
+    i: usize,
+    history: Option<HistoryRange>,
+) -> AgentSpec {
+    let mut spec = AgentSpec::from_agent(&config.agents[i]);
+    if let Some(caps) = config.activation_caps.get(i) {
+        caps.write_to(&mut spec, config.index());
+    }
+    if let Some(history) = history {
+        spec.activations.retain(|&time, _| history.contains(time));
+        spec.actions.retain(|&time, _| history.contains(time));
+    }
+    spec
+}
+
+/// The index of a [SimulationSnapshot] written as several shards by
+/// [Runner::write_snapshot_shards](crate::runner::Runner::write_snapshot_shards).
+///
+/// Each shard is a JSON array of [AgentSpec]s, so a shard can also be loaded
+/// as the agents of a model. The loaders in [loader](crate::loader) accept
+/// an index wherever they accept agents or a snapshot.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShardIndex {
+    /// The last tick that was simulated.
+    pub time: SimTime,
+    /// The state of the random number generator.
+    pub rng: ChaCha8Rng,
+    /// The shards, in the order of the [Agent]s.
+    pub shards: Vec<Shard>,
+    /// The ticks of the activations and actions included, if the history
+    /// before the run was left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryRange>,
+}
+
+/// A file holding some of the [Agent]s of a [SimulationSnapshot].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Shard {
+    /// The path of the shard, relative to the directory of the index.
+    pub path: PathBuf,
+    /// The number of [Agent]s in the shard.
+    pub n_agents: usize,
+}
+
+impl Shard {
+    /// The path of the shard, given the path of its index.
+    pub fn resolve(&self, index_path: &Path) -> PathBuf {
+        match index_path.parent() {
+            Some(dir) => dir.join(&self.path),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// The file name of `path` with `infix` inserted before its extensions.
+pub(crate) fn insert_before_extensions(path: &Path, infix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match name.split_once('.') {
+        Some((stem, extensions)) => format!("{stem}{infix}.{extensions}"),
+        None => format!("{name}{infix}"),
+    }
+    .into()
+}
+
+/// The file name of the shard with index `i` of an index at `path`, which
+/// inserts the number before the extensions, so `snapshot.json.zst` gives
+/// `snapshot_000.json.zst`.
+pub(crate) fn shard_file_name(path: &Path, i: usize) -> PathBuf {
+    insert_before_extensions(path, &format!("_{i:03}"))
+}
+
+/// The path of the checkpoint taken after `time` of a run checkpointing to
+/// `path`, which inserts the tick before the extensions, so
+/// `checkpoint.json.zst` gives `checkpoint_t12.json.zst` for tick 12.
+pub fn checkpoint_path(path: &Path, time: SimTime) -> PathBuf {
+    path.with_file_name(insert_before_extensions(path, &format!("_t{time}")))
+}
+
+/// A [SimulationSnapshot] of live [Agent]s, which serializes to the same
+/// format while converting one [Agent] to an [AgentSpec] at a time, so the
+/// [Agent]s are never all copied.
+pub(crate) struct SnapshotRef<'a> {
+    pub(crate) time: SimTime,
+    pub(crate) config: &'a Configuration,
+    pub(crate) rng: &'a ChaCha8Rng,
+    pub(crate) history: Option<HistoryRange>,
+}
+
+impl Serialize for SnapshotRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let n_fields = 3 + usize::from(self.history.is_some());
+        let mut state = serializer.serialize_struct("SimulationSnapshot", n_fields)?;
+        state.serialize_field("time", &self.time)?;
+        state.serialize_field("agents", &AgentsRef(self.config, self.history))?;
+        state.serialize_field("rng", self.rng)?;
+        if let Some(history) = &self.history {
+            state.serialize_field("history", history)?;
+        }
+        state.end()
+    }
+}
+
+/// The [Agent]s of a [Configuration] serialized as a sequence of
+/// [AgentSpec]s, with only the activations and actions in the
+/// [HistoryRange] if there is one.
+struct AgentsRef<'a>(&'a Configuration, Option<HistoryRange>);
+
+impl Serialize for AgentsRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.agents.len()))?;
+        for i in 0..self.0.agents.len() {
+            seq.serialize_element(&agent_spec(self.0, i, self.1))?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_file_name_inserts_number_before_extensions() {
+        assert_eq!(
+            shard_file_name(Path::new("out/snapshot.json.zst"), 7),
+            PathBuf::from("snapshot_007.json.zst")
+        );
+        assert_eq!(
+            shard_file_name(Path::new("snapshot"), 12),
+            PathBuf::from("snapshot_012")
+        );
+    }
+
+    #[test]
+    fn checkpoint_path_inserts_tick_before_extensions() {
+        assert_eq!(
+            checkpoint_path(Path::new("out/checkpoint.json.zst"), 12),
+            PathBuf::from("out/checkpoint_t12.json.zst")
+        );
+    }
+
+    #[test]
+    fn shard_resolves_relative_to_index() {
+        let shard = Shard {
+            path: PathBuf::from("snapshot_000.json"),
+            n_agents: 1,
+        };
+        assert_eq!(
+            shard.resolve(Path::new("out/snapshot.json")),
+            PathBuf::from("out/snapshot_000.json")
+        );
+        assert_eq!(
+            shard.resolve(Path::new("snapshot.json")),
+            PathBuf::from("snapshot_000.json")
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Whether the summary statistics of a run settled over its final ticks.
+//!
+//! The statistics are taken from the [OutputSpec] of each tick as the
+//! summary output is written, so the [Agent]s are not read again, and only
+//! the final window of ticks is kept.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use belief_spread::SimTime;
+use log::{info, warn};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{collections::ModelIndex, json::OutputSpec};
+
+/// The default largest change in a statistic between consecutive ticks of
+/// the window for it to have converged.
+pub const DEFAULT_STABILITY_TOLERANCE: f64 = 1e-3;
+
+/// How the stability of a run is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StabilityOptions {
+    /// The number of final ticks measured.
+    pub window: usize,
+    /// The largest change between consecutive ticks for a statistic to have
+    /// converged. For [Behaviour]s it is a fraction of the [Agent]s.
+    pub tolerance: f64,
+}
+
+/// How much a statistic varied over the window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stability {
+    /// The variance of the statistic over the ticks of the window.
+    pub variance: f64,
+    /// The largest absolute change between consecutive ticks.
+    pub max_change: f64,
+    /// Whether the largest change is within the tolerance.
+    pub converged: bool,
+}
+
+impl Stability {
+    fn new(values: &[f64], tolerance: f64) -> Self {
+        let n = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+        let max_change = values
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f64::max);
+        Stability {
+            variance,
+            max_change,
+            converged: max_change <= tolerance,
+        }
+    }
+}
+
+/// The [Stability] of the mean activation of each [Belief] and the number
+/// of performers of each [Behaviour] over the final ticks of a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StabilityReport {
+    /// How the stability was measured.
+    #[serde(flatten)]
+    pub options: StabilityOptions,
+    /// The first tick of the window, or [None] if no ticks were summarised.
+    pub first_time: Option<SimTime>,
+    /// The last tick of the window, or [None] if no ticks were summarised.
+    pub last_time: Option<SimTime>,
+    /// The [Stability] of the mean activation of each [Belief] with an
+    /// activation in the window, by [Belief].
+    pub beliefs: BTreeMap<Uuid, Stability>,
+    /// The [Stability] of the number of performers of each [Behaviour], by
+    /// [Behaviour]. Its changes are compared to the tolerance as fractions
+    /// of the [Agent]s.
+    pub behaviours: BTreeMap<Uuid, Stability>,
+}
+
+impl StabilityReport {
+    /// Whether every statistic converged.
+    pub fn converged(&self) -> bool {
+        self.beliefs
+            .values()
+            .chain(self.behaviours.values())
+            .all(|s| s.converged)
+    }
+
+    /// Log a verdict for each statistic.
+    pub fn log(&self) {
+        let verdict = |kind: &str, uuid: &Uuid, s: &Stability| {
+            if s.converged {
+                info!(
+                    "{kind} {uuid} converged: max change {:.3e}, variance {:.3e}",
+                    s.max_change, s.variance
+                );
+            } else {
+                warn!(
+                    "{kind} {uuid} not converged: max change {:.3e}, variance {:.3e}",
+                    s.max_change, s.variance
+                );
+            }
+        };
+        for (uuid, s) in &self.beliefs {
+            verdict("Mean activation of belief", uuid, s);
+        }
+        for (uuid, s) in &self.behaviours {
+            verdict("Performers of behaviour", uuid, s);
+        }
+    }
+}
+
+/// The statistics of the most recent ticks summarised, at most
+/// [StabilityOptions::window] of them.
+pub struct StabilityWindow<'a> {
+    options: StabilityOptions,
+    index: &'a ModelIndex,
+    /// The time, the mean activation of each [Belief] in the canonical
+    /// order, and the number of performers of each [Behaviour] in the
+    /// canonical order, of each tick.
+    ticks: VecDeque<(SimTime, Vec<Option<f64>>, Vec<f64>)>,
+}
+
+impl<'a> StabilityWindow<'a> {
+    /// Create an empty window, whose statistics are in the canonical order
+    /// of `index`.
+    pub fn new(options: StabilityOptions, index: &'a ModelIndex) -> Self {
+        StabilityWindow {
+            options,
+            index,
+            ticks: VecDeque::with_capacity(options.window),
+        }
+    }
+
+    /// Record the statistics of the [OutputSpec] of `time`, forgetting the
+    /// oldest tick if the window is full.
+    ///
+    /// The ticks must be observed in ascending order.
+    pub fn observe(&mut self, time: SimTime, spec: &OutputSpec) {
+        if self.ticks.len() == self.options.window.max(1) {
+            self.ticks.pop_front();
+        }
+        let beliefs = self.index.belief_uuids();
+        let behaviours = self.index.behaviour_uuids();
+        self.ticks.push_back((
+            time,
+            self.index
+                .canonical_beliefs()
+                .iter()
+                .map(|&i| spec.mean_activation.get(&beliefs[i]).copied())
+                .collect(),
+            self.index
+                .canonical_behaviours()
+                .iter()
+                .map(|&j| spec.n_performers.get(&behaviours[j]).copied().unwrap_or(0) as f64)
+                .collect(),
+        ));
+    }
+
+    /// Measure the [Stability] of the window, where there are `n_agents`
+    /// [Agent]s.
+    pub fn finish(&self, n_agents: usize) -> StabilityReport {
+        let tolerance = self.options.tolerance;
+        let beliefs = self
+            .index
+            .canonical_beliefs()
+            .iter()
+            .enumerate()
+            .filter_map(|(k, &i)| {
+                let means: Vec<f64> = self.ticks.iter().filter_map(|t| t.1[k]).collect();
+                (!means.is_empty()).then(|| {
+                    (
+                        self.index.belief_uuids()[i],
+                        Stability::new(&means, tolerance),
+                    )
+                })
+            })
+            .collect();
+        let behaviours = self
+            .index
+            .canonical_behaviours()
+            .iter()
+            .enumerate()
+            .map(|(k, &j)| {
+                let counts: Vec<f64> = self.ticks.iter().map(|t| t.2[k]).collect();
+                (
+                    self.index.behaviour_uuids()[j],
+                    Stability::new(&counts, tolerance * n_agents as f64),
+                )
+            })
+            .collect();
+        StabilityReport {
+            options: self.options,
+            first_time: self.ticks.front().map(|t| t.0),
+            last_time: self.ticks.back().map(|t| t.0),
+            beliefs,
+            behaviours,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn spec(mean: f64, n_performers: usize) -> OutputSpec {
+        OutputSpec {
+            mean_activation: HashMap::from([(Uuid::from_u128(1), mean)]),
+            sd_activation: HashMap::new(),
+            median_activation: HashMap::new(),
+            nonzero_activation_count: HashMap::new(),
+            n_performers: HashMap::from([(Uuid::from_u128(2), n_performers)]),
+            weighted_mean_activation: None,
+            weighted_sd_activation: None,
+            action_assortativity: None,
+        }
+    }
+
+    #[test]
+    fn only_the_final_window_is_measured() {
+        let index = ModelIndex::new(vec![Uuid::from_u128(1)], vec![Uuid::from_u128(2)]);
+        let options = StabilityOptions {
+            window: 3,
+            tolerance: 0.1,
+        };
+        let mut window = StabilityWindow::new(options, &index);
+        for (time, mean, n) in [
+            (1, 0.0, 0),
+            (2, 0.9, 10),
+            (3, 0.5, 4),
+            (4, 0.55, 5),
+            (5, 0.5, 5),
+        ] {
+            window.observe(time, &spec(mean, n));
+        }
+        let report = window.finish(10);
+        assert_eq!((report.first_time, report.last_time), (Some(3), Some(5)));
+
+        let belief = report.beliefs[&Uuid::from_u128(1)];
+        assert!((belief.max_change - 0.05).abs() < 1e-12);
+        assert!(belief.converged);
+
+        // A change of one performer in ten is 0.1 of the agents
+        let behaviour = report.behaviours[&Uuid::from_u128(2)];
+        assert_eq!(behaviour.max_change, 1.0);
+        assert!(behaviour.converged);
+        assert!((behaviour.variance - 2.0 / 9.0).abs() < 1e-12);
+        assert!(report.converged());
+
+        window.options.tolerance = 0.01;
+        let report = window.finish(10);
+        assert!(!report.beliefs[&Uuid::from_u128(1)].converged);
+        assert!(!report.converged());
+
+        let empty = StabilityWindow::new(options, &index).finish(10);
+        assert_eq!((empty.first_time, empty.beliefs.len()), (None, 0));
+    }
+}
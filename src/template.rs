@@ -0,0 +1,256 @@
+//! Output paths given as templates, whose placeholders are expanded from
+//! the effective configuration of a run, so the outputs of the runs of a
+//! sweep can be named by their parameters.
+//!
+//! A placeholder is a name in braces, such as `out/{run_id}_{seed}.json.zst`.
+//! Literal braces are doubled, so `{{` and `}}` expand to `{` and `}`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::error::ConceptError;
+
+/// A piece of a path template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Piece<'a> {
+    /// Text copied as it is, with any escaped braces already unescaped.
+    Literal(&'a str),
+    /// The name of a placeholder.
+    Placeholder(&'a str),
+}
+
+/// Split a template into its pieces.
+///
+/// # Returns
+/// The pieces, or a [ConceptError::MalformedTemplate] if a brace is
+/// unmatched.
+fn parse(template: &str) -> Result<Vec<Piece<'_>>, ConceptError> {
+    let malformed = |brace, position| ConceptError::MalformedTemplate {
+        template: template.to_string(),
+        brace,
+        position,
+    };
+    let mut pieces = Vec::new();
+    let mut rest = 0;
+    let mut chars = template.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                // Keep one of the two braces
+                pieces.push(Piece::Literal(&template[rest..=i]));
+                chars.next();
+                rest = i + 2;
+            }
+            '{' => {
+                let end = template[i..]
+                    .find('}')
+                    .map(|end| i + end)
+                    .filter(|&end| !template[i + 1..end].contains('{'))
+                    .ok_or_else(|| malformed('{', i))?;
+                pieces.push(Piece::Literal(&template[rest..i]));
+                pieces.push(Piece::Placeholder(&template[i + 1..end]));
+                while chars.peek().is_some_and(|&(j, _)| j <= end) {
+                    chars.next();
+                }
+                rest = end + 1;
+            }
+            '}' => return Err(malformed('}', i)),
+            _ => {}
+        }
+    }
+    pieces.push(Piece::Literal(&template[rest..]));
+    pieces.retain(|piece| *piece != Piece::Literal(""));
+    Ok(pieces)
+}
+
+/// The names of the placeholders of a path template, in order.
+pub fn placeholders(template: &Path) -> Result<Vec<&str>, ConceptError> {
+    let Some(template) = template.to_str() else {
+        return Ok(Vec::new());
+    };
+    Ok(parse(template)?
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Placeholder(name) => Some(name),
+            Piece::Literal(_) => None,
+        })
+        .collect())
+}
+
+/// The values of the placeholders of path templates, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateValues(BTreeMap<String, String>);
+
+impl TemplateValues {
+    /// Create an empty set of values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of the placeholder `name`.
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Expand the placeholders of a path template.
+    ///
+    /// A path that is not valid UTF-8 is returned as it is.
+    ///
+    /// # Returns
+    /// The path, or a [ConceptError::MalformedTemplate] if a brace is
+    /// unmatched, or a [ConceptError::UnknownPlaceholders] listing every
+    /// placeholder without a value.
+    pub fn expand(&self, template: &Path) -> Result<PathBuf, ConceptError> {
+        let Some(text) = template.to_str() else {
+            return Ok(template.to_path_buf());
+        };
+        let mut path = String::with_capacity(text.len());
+        let mut unknown: Vec<String> = Vec::new();
+        for piece in parse(text)? {
+            match piece {
+                Piece::Literal(literal) => path.push_str(literal),
+                Piece::Placeholder(name) => match self.0.get(name) {
+                    Some(value) => path.push_str(value),
+                    None if unknown.iter().any(|u| u == name) => {}
+                    None => unknown.push(name.to_string()),
+                },
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(ConceptError::UnknownPlaceholders {
+                template: text.to_string(),
+                unknown,
+                known: self.0.keys().cloned().collect(),
+            });
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// Check that no two outputs, named by their labels, are written to the
+/// same path, such as two runs of a sweep whose templates expand the same.
+///
+/// # Returns
+/// A [ConceptError::PathCollision] naming the first two outputs found with
+/// the same path.
+pub fn check_collisions<'a>(
+    outputs: impl IntoIterator<Item = (&'a str, &'a Path)>,
+) -> Result<(), ConceptError> {
+    let mut seen: HashMap<&Path, &str> = HashMap::new();
+    for (label, path) in outputs {
+        if let Some(first) = seen.insert(path, label) {
+            return Err(ConceptError::PathCollision {
+                path: path.to_path_buf(),
+                first: first.to_string(),
+                second: label.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> TemplateValues {
+        TemplateValues::new()
+            .with("run_id", "r1")
+            .with("seed", 42)
+            .with("prs_scale", 0.5)
+    }
+
+    #[test]
+    fn placeholders_are_expanded() {
+        assert_eq!(
+            values()
+                .expand(Path::new("out/{run_id}_{seed}_{prs_scale}.json.zst"))
+                .unwrap(),
+            PathBuf::from("out/r1_42_0.5.json.zst")
+        );
+        assert_eq!(
+            values().expand(Path::new("output.json")).unwrap(),
+            PathBuf::from("output.json")
+        );
+        assert_eq!(
+            placeholders(Path::new("{seed}/{{x}}{run_id}")).unwrap(),
+            ["seed", "run_id"]
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(
+            values()
+                .expand(Path::new("{{seed}}_{seed}_{{{seed}}}}}"))
+                .unwrap(),
+            PathBuf::from("{seed}_42_{42}}")
+        );
+    }
+
+    #[test]
+    fn unmatched_braces_are_malformed() {
+        for (template, brace, at) in [
+            ("out/{seed", '{', 4),
+            ("out/seed}", '}', 8),
+            ("{run_{id}", '{', 0),
+        ] {
+            match values().expand(Path::new(template)) {
+                Err(ConceptError::MalformedTemplate {
+                    brace: b, position, ..
+                }) => assert_eq!((b, position), (brace, at), "{template}"),
+                other => panic!("expected a malformed template, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn every_unknown_placeholder_is_listed() {
+        match values().expand(Path::new("{seed}_{replica}_{scale}_{replica}")) {
+            Err(err @ ConceptError::UnknownPlaceholders { .. }) => {
+                let ConceptError::UnknownPlaceholders { unknown, known, .. } = &err else {
+                    unreachable!()
+                };
+                assert_eq!(unknown, &["replica", "scale"]);
+                assert_eq!(known, &["prs_scale", "run_id", "seed"]);
+                assert!(err.to_string().contains("{replica}, {scale}"));
+            }
+            other => panic!("expected unknown placeholders, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn colliding_expansions_are_found() {
+        // Two runs of a sweep over the PRs scale that leave it out of the
+        // name
+        let template = Path::new("out/{seed}.json");
+        let runs: Vec<(String, PathBuf)> = [0.5, 1.0]
+            .iter()
+            .map(|&scale| {
+                let values = values().with("prs_scale", scale);
+                (format!("run {scale}"), values.expand(template).unwrap())
+            })
+            .collect();
+        match check_collisions(runs.iter().map(|(l, p)| (l.as_str(), p.as_path()))) {
+            Err(ConceptError::PathCollision {
+                path,
+                first,
+                second,
+            }) => {
+                assert_eq!(path, PathBuf::from("out/42.json"));
+                assert_eq!((first.as_str(), second.as_str()), ("run 0.5", "run 1"));
+            }
+            other => panic!("expected a collision, got {other:?}"),
+        }
+
+        let template = Path::new("out/{seed}_{prs_scale}.json");
+        let paths: Vec<PathBuf> = [0.5, 1.0]
+            .iter()
+            .map(|&scale| values().with("prs_scale", scale).expand(template).unwrap())
+            .collect();
+        assert!(check_collisions([("a", paths[0].as_path()), ("b", paths[1].as_path())]).is_ok());
+    }
+}
@@ -0,0 +1,211 @@
+//! The first ticks at which summary statistics cross configured levels.
+//!
+//! The crossings are found from the [OutputSpec] of each tick as the summary
+//! output is written, so the [Agent]s are not read again.
+
+use std::str::FromStr;
+
+use belief_spread::SimTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    collections::ModelIndex,
+    error::{ConceptError, ValidationIssue, ValidationReport},
+    json::OutputSpec,
+};
+
+/// A level for the statistic of a [Belief] or [Behaviour], parsed from
+/// `uuid:level`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThresholdMetric {
+    /// The UUID of the [Belief] or [Behaviour].
+    pub uuid: Uuid,
+    /// The level the statistic must reach.
+    pub level: f64,
+}
+
+impl FromStr for ThresholdMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (uuid, level) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected uuid:level, found {s}"))?;
+        let uuid = uuid.parse().map_err(|err| format!("{uuid}: {err}"))?;
+        match level.parse::<f64>() {
+            Ok(level) if level.is_finite() => Ok(ThresholdMetric { uuid, level }),
+            Ok(_) => Err(format!("{level}: must be finite")),
+            Err(err) => Err(format!("{level}: {err}")),
+        }
+    }
+}
+
+/// The levels whose crossing times are found.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThresholdMetrics {
+    /// Levels of the mean activation of [Belief]s.
+    pub beliefs: Vec<ThresholdMetric>,
+    /// Levels of the fraction of [Agent]s performing [Behaviour]s.
+    pub behaviours: Vec<ThresholdMetric>,
+}
+
+impl ThresholdMetrics {
+    /// Whether there are no levels.
+    pub fn is_empty(&self) -> bool {
+        self.beliefs.is_empty() && self.behaviours.is_empty()
+    }
+
+    /// Check that every [Belief] and [Behaviour] is in the model.
+    ///
+    /// # Returns
+    /// A [ConceptError::Validation] listing every unknown UUID.
+    pub fn check(&self, index: &ModelIndex) -> Result<(), ConceptError> {
+        let mut report = ValidationReport::default();
+        report.extend(
+            self.beliefs
+                .iter()
+                .filter(|m| !index.has_belief(&m.uuid))
+                .map(|m| ValidationIssue::UnknownTarget {
+                    input: "threshold metrics",
+                    target_kind: "belief",
+                    target: m.uuid,
+                }),
+        );
+        report.extend(
+            self.behaviours
+                .iter()
+                .filter(|m| !index.has_behaviour(&m.uuid))
+                .map(|m| ValidationIssue::UnknownTarget {
+                    input: "threshold metrics",
+                    target_kind: "behaviour",
+                    target: m.uuid,
+                }),
+        );
+        report.into_result()
+    }
+}
+
+/// The first tick at which a statistic reached a level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThresholdCrossing {
+    /// The UUID of the [Belief] or [Behaviour].
+    pub uuid: Uuid,
+    /// The level.
+    pub level: f64,
+    /// The first tick at which the statistic was at least the level, or
+    /// [None] if it never was.
+    pub first_time: Option<SimTime>,
+}
+
+/// The first ticks at which each of the [ThresholdMetrics] was reached.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThresholdCrossings {
+    /// The crossings of the mean activation of [Belief]s.
+    pub beliefs: Vec<ThresholdCrossing>,
+    /// The crossings of the fraction of [Agent]s performing [Behaviour]s.
+    pub behaviours: Vec<ThresholdCrossing>,
+}
+
+impl ThresholdCrossings {
+    /// Start looking for the crossings of `metrics`, none of which have been
+    /// reached.
+    pub fn new(metrics: &ThresholdMetrics) -> Self {
+        let unreached = |m: &ThresholdMetric| ThresholdCrossing {
+            uuid: m.uuid,
+            level: m.level,
+            first_time: None,
+        };
+        ThresholdCrossings {
+            beliefs: metrics.beliefs.iter().map(unreached).collect(),
+            behaviours: metrics.behaviours.iter().map(unreached).collect(),
+        }
+    }
+
+    /// Record the levels reached by the [OutputSpec] of `time`, where there
+    /// are `n_agents` [Agent]s.
+    ///
+    /// The ticks must be observed in ascending order.
+    pub fn observe(&mut self, time: SimTime, spec: &OutputSpec, n_agents: usize) {
+        let unreached = |c: &&mut ThresholdCrossing| c.first_time.is_none();
+        for crossing in self.beliefs.iter_mut().filter(unreached) {
+            if spec
+                .mean_activation
+                .get(&crossing.uuid)
+                .is_some_and(|&mean| mean >= crossing.level)
+            {
+                crossing.first_time = Some(time);
+            }
+        }
+        for crossing in self.behaviours.iter_mut().filter(unreached) {
+            let n_performers = spec.n_performers.get(&crossing.uuid).copied().unwrap_or(0);
+            if n_agents > 0 && n_performers as f64 / n_agents as f64 >= crossing.level {
+                crossing.first_time = Some(time);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn spec(mean: f64, n_performers: usize) -> OutputSpec {
+        OutputSpec {
+            mean_activation: HashMap::from([(Uuid::from_u128(1), mean)]),
+            sd_activation: HashMap::new(),
+            median_activation: HashMap::new(),
+            nonzero_activation_count: HashMap::new(),
+            n_performers: HashMap::from([(Uuid::from_u128(2), n_performers)]),
+            weighted_mean_activation: None,
+            weighted_sd_activation: None,
+            action_assortativity: None,
+        }
+    }
+
+    #[test]
+    fn parse_threshold_metric() {
+        assert_eq!(
+            "00000000-0000-0000-0000-000000000001:0.5".parse(),
+            Ok(ThresholdMetric {
+                uuid: Uuid::from_u128(1),
+                level: 0.5
+            })
+        );
+        assert!("00000000-0000-0000-0000-000000000001"
+            .parse::<ThresholdMetric>()
+            .is_err());
+        assert!("not-a-uuid:0.5".parse::<ThresholdMetric>().is_err());
+        assert!("00000000-0000-0000-0000-000000000001:inf"
+            .parse::<ThresholdMetric>()
+            .is_err());
+    }
+
+    #[test]
+    fn crossings_keep_the_first_tick_reached() {
+        let level = |uuid, level| ThresholdMetric {
+            uuid: Uuid::from_u128(uuid),
+            level,
+        };
+        let metrics = ThresholdMetrics {
+            beliefs: vec![level(1, 0.5), level(1, 0.9), level(3, 0.0)],
+            behaviours: vec![level(2, 0.5)],
+        };
+        let mut crossings = ThresholdCrossings::new(&metrics);
+        crossings.observe(1, &spec(0.2, 1), 4);
+        crossings.observe(2, &spec(0.5, 2), 4);
+        crossings.observe(3, &spec(0.3, 4), 4);
+
+        let first_times = |c: &[ThresholdCrossing]| -> Vec<Option<SimTime>> {
+            c.iter().map(|c| c.first_time).collect()
+        };
+        assert_eq!(first_times(&crossings.beliefs), vec![Some(2), None, None]);
+        assert_eq!(first_times(&crossings.behaviours), vec![Some(2)]);
+    }
+}
@@ -0,0 +1,354 @@
+//! Checking that the outputs of runs still correspond to the inputs beside
+//! them, by the checksums of the input files recorded in the metadata of
+//! each run.
+//!
+//! The metadata of a run is the [RunOutcome](crate::runner::RunOutcome) it
+//! prints, saved as a JSON file. Any JSON file without the input files of a
+//! run is skipped, so the metadata can be kept beside the inputs and
+//! outputs.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConceptError;
+
+/// An input file of a run, as it was when the run was configured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputFile {
+    /// The path of the file, as it was given to the run.
+    pub path: PathBuf,
+    /// The size of the file.
+    pub bytes: u64,
+    /// When the file was last modified, in seconds since the Unix epoch, if
+    /// the platform records it.
+    pub modified: Option<u64>,
+    /// The 64-bit FNV-1a checksum of the file, in hexadecimal.
+    pub checksum: String,
+}
+
+impl InputFile {
+    /// Read the file at `path`, and record it with its checksum.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let modified = file
+            .metadata()?
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_secs());
+        let (bytes, checksum) = checksum(BufReader::new(file))?;
+        Ok(InputFile {
+            path: path.to_path_buf(),
+            bytes,
+            modified,
+            checksum: format!("{checksum:016x}"),
+        })
+    }
+}
+
+/// The size and 64-bit FNV-1a hash of everything read from `reader`.
+///
+/// FNV-1a is not cryptographic, but it is enough to tell whether an input
+/// was changed since a run read it.
+pub(crate) fn checksum(mut reader: impl Read) -> io::Result<(u64, u64)> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let (mut bytes, mut hash) = (0, OFFSET_BASIS);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok((bytes, hash)),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        for &byte in &buffer[..n] {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+        }
+        bytes += n as u64;
+    }
+}
+
+/// The part of the metadata of a run needed to verify its outputs.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedRun {
+    run_id: String,
+    input_files: Vec<InputFile>,
+}
+
+/// How an input file compares to the one a run read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InputStatus {
+    /// The file has the checksum it had when the run read it.
+    Unchanged,
+    /// The file has changed since the run read it.
+    Changed,
+    /// The file was not found, as it was moved, renamed or deleted.
+    NotFound,
+}
+
+/// An input file of a run, checked against the one the run read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputCheck {
+    /// The path of the file, as it was given to the run.
+    pub path: PathBuf,
+    /// Where the file was found, if it was.
+    pub found: Option<PathBuf>,
+    /// How the file compares to the one the run read.
+    pub status: InputStatus,
+    /// Whether the file was modified after the run read it, even if its
+    /// contents are unchanged.
+    pub modified_since: bool,
+}
+
+/// Whether the outputs of a run correspond to its inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputStatus {
+    /// Every input file is unchanged.
+    Consistent,
+    /// No input file found has changed, but some were not found.
+    MissingInputs,
+    /// An input file has changed since the run, so its outputs are stale.
+    Stale,
+}
+
+/// The input files of a run, checked against those it read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputCheck {
+    /// The metadata file of the run.
+    pub metadata: PathBuf,
+    /// The identifier of the run.
+    pub run_id: String,
+    /// Whether the outputs of the run correspond to its inputs.
+    pub status: OutputStatus,
+    /// Each input file of the run.
+    pub inputs: Vec<InputCheck>,
+}
+
+impl OutputCheck {
+    /// Check the input files of the run whose metadata is at `metadata`.
+    ///
+    /// A relative path is looked for relative to the directory of the
+    /// metadata first, and then relative to the current directory. If the
+    /// file is not at its path, it is looked for by name beside the
+    /// metadata, in case the inputs were moved together.
+    fn new(metadata: PathBuf, run: RecordedRun) -> Self {
+        let dir = metadata.parent().unwrap_or(Path::new(""));
+        let inputs: Vec<InputCheck> = run
+            .input_files
+            .into_iter()
+            .map(|recorded| {
+                let mut candidates = if recorded.path.is_absolute() {
+                    vec![recorded.path.clone()]
+                } else {
+                    vec![dir.join(&recorded.path), recorded.path.clone()]
+                };
+                candidates.extend(recorded.path.file_name().map(|name| dir.join(name)));
+                let current = candidates.into_iter().find_map(|candidate| {
+                    InputFile::read(&candidate)
+                        .ok()
+                        .map(|current| (candidate, current))
+                });
+                match current {
+                    Some((found, current)) => InputCheck {
+                        status: if current.checksum == recorded.checksum
+                            && current.bytes == recorded.bytes
+                        {
+                            InputStatus::Unchanged
+                        } else {
+                            InputStatus::Changed
+                        },
+                        modified_since: current.modified > recorded.modified,
+                        found: Some(found),
+                        path: recorded.path,
+                    },
+                    None => InputCheck {
+                        path: recorded.path,
+                        found: None,
+                        status: InputStatus::NotFound,
+                        modified_since: false,
+                    },
+                }
+            })
+            .collect();
+        let has = |status| inputs.iter().any(|input| input.status == status);
+        let status = if has(InputStatus::Changed) {
+            OutputStatus::Stale
+        } else if has(InputStatus::NotFound) {
+            OutputStatus::MissingInputs
+        } else {
+            OutputStatus::Consistent
+        };
+        OutputCheck {
+            metadata,
+            run_id: run.run_id,
+            status,
+            inputs,
+        }
+    }
+}
+
+/// The runs whose metadata was found in a directory, and whether their
+/// outputs correspond to their inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    /// Each run, in order of the path of its metadata.
+    pub runs: Vec<OutputCheck>,
+    /// The number of runs whose outputs are consistent with their inputs.
+    pub consistent: usize,
+    /// The number of runs with inputs that were not found.
+    pub missing_inputs: usize,
+    /// The number of runs whose outputs are stale.
+    pub stale: usize,
+}
+
+impl VerificationReport {
+    /// Check the runs of every metadata file directly in `dir`.
+    ///
+    /// # Returns
+    /// The [VerificationReport], or a [ConceptError::Io] if `dir` cannot be
+    /// listed. Files that cannot be read or are not the metadata of a run
+    /// are skipped.
+    pub fn verify_dir(dir: &Path) -> Result<Self, ConceptError> {
+        let io_error = |source| ConceptError::Io {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().is_some_and(|ext| ext == "json") && path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort_unstable();
+
+        let runs: Vec<OutputCheck> = paths
+            .into_iter()
+            .filter_map(|path| {
+                let run = File::open(&path)
+                    .map(BufReader::new)
+                    .map_err(|err| err.to_string())
+                    .and_then(|reader| {
+                        serde_json::from_reader::<_, RecordedRun>(reader)
+                            .map_err(|err| err.to_string())
+                    });
+                match run {
+                    Ok(run) => Some(OutputCheck::new(path, run)),
+                    Err(err) => {
+                        log::debug!("Skipping {}: {err}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect();
+        let count = |status| runs.iter().filter(|run| run.status == status).count();
+        Ok(VerificationReport {
+            consistent: count(OutputStatus::Consistent),
+            missing_inputs: count(OutputStatus::MissingInputs),
+            stale: count(OutputStatus::Stale),
+            runs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        configuration::ConfigurationBuilder,
+        example::ExampleScenario,
+        runner::Runner,
+        sink::{Compression, OutputSettings},
+    };
+
+    #[test]
+    fn checksums_are_fnv1a() {
+        assert_eq!(checksum(&b""[..]).unwrap(), (0, 0xcbf2_9ce4_8422_2325));
+        assert_eq!(checksum(&b"a"[..]).unwrap(), (1, 0xaf63_dc4c_8601_ec8c));
+        assert_eq!(
+            checksum(&b"foobar"[..]).unwrap(),
+            (6, 0x8594_4171_f739_67e8)
+        );
+    }
+
+    #[test]
+    fn stale_outputs_and_missing_inputs_are_reported() {
+        let dir = std::env::temp_dir().join(format!("concept-verify-{}", Uuid::new_v4()));
+        ExampleScenario::generate().write_to(&dir).unwrap();
+        let config = ConfigurationBuilder::new()
+            .behaviours_from_path(dir.join("behaviours.json"))
+            .beliefs_from_path(dir.join("beliefs.json"))
+            .agents_from_path(dir.join("agents.json"))
+            .prs_from_path(dir.join("prs.json"))
+            .time_range(1, 2)
+            .output_settings(OutputSettings {
+                path: dir.join("output.json"),
+                compression: Compression::None,
+            })
+            .build()
+            .unwrap();
+        let outcome = Runner::new(config).with_seed(1).run().unwrap();
+        assert_eq!(outcome.input_files.len(), 4);
+        std::fs::write(dir.join("run.json"), serde_json::to_vec(&outcome).unwrap()).unwrap();
+
+        // The inputs and output beside the metadata are skipped
+        let report = VerificationReport::verify_dir(&dir).unwrap();
+        assert_eq!(report.runs.len(), 1);
+        assert_eq!(report.runs[0].run_id, outcome.run_id);
+        assert_eq!(report.runs[0].status, OutputStatus::Consistent);
+
+        // Inputs moved with the metadata are found beside it
+        let moved = dir.with_extension("moved");
+        std::fs::rename(&dir, &moved).unwrap();
+        let report = VerificationReport::verify_dir(&moved).unwrap();
+        assert_eq!(report.runs[0].status, OutputStatus::Consistent);
+        assert_eq!(
+            report.runs[0].inputs[0].found,
+            Some(moved.join("behaviours.json"))
+        );
+        std::fs::rename(&moved, &dir).unwrap();
+
+        std::fs::rename(dir.join("prs.json"), dir.join("prs-old.json")).unwrap();
+        let report = VerificationReport::verify_dir(&dir).unwrap();
+        assert_eq!(report.runs[0].status, OutputStatus::MissingInputs);
+        assert_eq!(report.missing_inputs, 1);
+        assert_eq!(report.runs[0].inputs[3].status, InputStatus::NotFound);
+
+        let beliefs = dir.join("beliefs.json");
+        let mut json = std::fs::read(&beliefs).unwrap();
+        json.push(b'\n');
+        std::fs::write(&beliefs, json).unwrap();
+        let report = VerificationReport::verify_dir(&dir).unwrap();
+        assert_eq!((report.stale, report.consistent), (1, 0));
+        assert_eq!(
+            report.runs[0]
+                .inputs
+                .iter()
+                .map(|input| input.status)
+                .collect::<Vec<_>>(),
+            [
+                InputStatus::Unchanged,
+                InputStatus::Changed,
+                InputStatus::Unchanged,
+                InputStatus::NotFound
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+//! Counting the warnings about individual [Agent]s, so that a run of
+//! millions of [Agent]s logs one line per kind of warning rather than one
+//! per [Agent].
+//!
+//! A [WarningCounter] is shared by reference with whatever updates the
+//! [Agent]s, which may be many threads at once: each warning increments an
+//! atomic counter, and the first few [Agent]s warned of are kept as
+//! examples. Nothing is locked or allocated unless a warning is recorded.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The number of [Agent]s kept as examples of each [WarningKind].
+pub const EXAMPLES: usize = 3;
+
+/// A kind of warning about an [Agent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WarningKind {
+    /// An activation computed outside of [-1, 1] was brought within it by
+    /// the [BoundsPolicy](crate::bounds::BoundsPolicy).
+    BoundedActivation,
+    /// An activation was held to the floor or ceiling of its [Agent].
+    CappedActivation,
+    /// An [Agent] performed no action.
+    NoAction,
+    /// An [Agent] has no initial activation for a [Belief].
+    MissingActivation,
+    /// An [Agent] has no delta for a [Belief].
+    MissingDelta,
+}
+
+impl WarningKind {
+    /// Every kind, in order.
+    pub const ALL: [WarningKind; 5] = [
+        WarningKind::BoundedActivation,
+        WarningKind::CappedActivation,
+        WarningKind::NoAction,
+        WarningKind::MissingActivation,
+        WarningKind::MissingDelta,
+    ];
+
+    /// What is counted, following the count in a log line.
+    pub fn description(self) -> &'static str {
+        match self {
+            WarningKind::BoundedActivation => "activations brought within [-1, 1]",
+            WarningKind::CappedActivation => "activations held to a floor or ceiling",
+            WarningKind::NoAction => "agents performed no action",
+            WarningKind::MissingActivation => "initial activations missing",
+            WarningKind::MissingDelta => "deltas missing",
+        }
+    }
+}
+
+/// The number of each [WarningKind] recorded, with examples of the
+/// [Agent]s warned of.
+#[derive(Debug, Default)]
+pub struct WarningCounter {
+    counts: [AtomicUsize; WarningKind::ALL.len()],
+    examples: [Mutex<Vec<Uuid>>; WarningKind::ALL.len()],
+    /// Whether each kind has all its examples.
+    full: [AtomicBool; WarningKind::ALL.len()],
+}
+
+impl WarningCounter {
+    /// Create a counter with nothing recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `n` warnings of `kind` about `agent`.
+    ///
+    /// The [Agent] is kept as an example if it is among the first
+    /// [EXAMPLES] [Agent]s recorded for `kind`.
+    pub fn record(&self, kind: WarningKind, agent: Uuid, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let k = kind as usize;
+        self.counts[k].fetch_add(n, Ordering::Relaxed);
+        // The lock is only taken until the examples are full
+        if !self.full[k].load(Ordering::Relaxed) {
+            let mut examples = self.examples[k]
+                .lock()
+                .expect("no thread panics holding it");
+            if examples.len() < EXAMPLES && !examples.contains(&agent) {
+                examples.push(agent);
+            }
+            if examples.len() == EXAMPLES {
+                self.full[k].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The number of warnings of `kind` recorded.
+    pub fn count(&self, kind: WarningKind) -> usize {
+        self.counts[kind as usize].load(Ordering::Relaxed)
+    }
+
+    /// The kinds of warning recorded, in order, with their counts and
+    /// examples.
+    pub fn summaries(&self) -> Vec<WarningSummary> {
+        WarningKind::ALL
+            .into_iter()
+            .filter(|&kind| self.count(kind) > 0)
+            .map(|kind| {
+                let mut examples = self.examples[kind as usize]
+                    .lock()
+                    .expect("no thread panics holding it")
+                    .clone();
+                // Threads may have recorded them in any order
+                examples.sort_unstable();
+                WarningSummary {
+                    kind,
+                    count: self.count(kind),
+                    examples,
+                }
+            })
+            .collect()
+    }
+
+    /// Log a line for each kind of warning recorded, prefixed with
+    /// `context`.
+    pub fn log(&self, context: &str) {
+        for summary in self.summaries() {
+            log::warn!("{context} - {summary}");
+        }
+    }
+
+    /// Forget everything recorded, such as at the end of a tick.
+    pub fn reset(&mut self) {
+        for count in &mut self.counts {
+            *count.get_mut() = 0;
+        }
+        for examples in &mut self.examples {
+            examples
+                .get_mut()
+                .expect("no thread panics holding it")
+                .clear();
+        }
+        for full in &mut self.full {
+            *full.get_mut() = false;
+        }
+    }
+}
+
+/// The count of a [WarningKind], with examples of the [Agent]s warned of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WarningSummary {
+    pub kind: WarningKind,
+    pub count: usize,
+    /// Up to [EXAMPLES] of the [Agent]s warned of, in order of UUID.
+    pub examples: Vec<Uuid>,
+}
+
+impl fmt::Display for WarningSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.count, self.kind.description())?;
+        if !self.examples.is_empty() {
+            let examples: Vec<String> = self.examples.iter().map(Uuid::to_string).collect();
+            write!(f, ", including agents {}", examples.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ValidationIssue, ValidationReport};
+
+    use super::*;
+
+    #[test]
+    fn warnings_from_many_threads_are_counted_with_a_few_examples() {
+        let counter = WarningCounter::new();
+        std::thread::scope(|scope| {
+            for t in 0..4 {
+                let counter = &counter;
+                scope.spawn(move || {
+                    for i in 0..1_000 {
+                        let agent = Uuid::from_u128(t * 1_000 + i);
+                        counter.record(WarningKind::BoundedActivation, agent, 2);
+                        counter.record(WarningKind::NoAction, agent, usize::from(i == 0));
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.count(WarningKind::BoundedActivation), 8_000);
+        assert_eq!(counter.count(WarningKind::NoAction), 4);
+        assert_eq!(counter.count(WarningKind::CappedActivation), 0);
+
+        let summaries = counter.summaries();
+        assert_eq!(
+            summaries.iter().map(|s| s.kind).collect::<Vec<_>>(),
+            [WarningKind::BoundedActivation, WarningKind::NoAction]
+        );
+        for summary in &summaries {
+            assert_eq!(summary.examples.len(), EXAMPLES);
+            assert!(summary.examples.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn summaries_name_the_count_and_examples() {
+        let mut counter = WarningCounter::new();
+        let agent = Uuid::from_u128(0x300);
+        counter.record(WarningKind::CappedActivation, agent, 2);
+        counter.record(WarningKind::CappedActivation, agent, 1);
+        assert_eq!(
+            counter.summaries()[0].to_string(),
+            format!("3 activations held to a floor or ceiling, including agents {agent}")
+        );
+        counter.reset();
+        assert!(counter.summaries().is_empty());
+        counter.record(WarningKind::CappedActivation, Uuid::from_u128(0x301), 1);
+        assert_eq!(counter.summaries()[0].examples, [Uuid::from_u128(0x301)]);
+    }
+
+    #[test]
+    fn validation_reports_count_their_issues_by_agent() {
+        let mut report = ValidationReport::default();
+        report.extend((0..10).map(|i| ValidationIssue::MissingActivation {
+            agent: Uuid::from_u128(0x300 + i),
+            belief: Uuid::from_u128(0x200),
+            time: 0,
+        }));
+        report.extend([
+            ValidationIssue::MissingDelta {
+                agent: Uuid::from_u128(0x300),
+                belief: Uuid::from_u128(0x200),
+            },
+            ValidationIssue::MissingInput { input: "agents" },
+        ]);
+        let summaries = report.agent_warnings().summaries();
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|s| (s.kind, s.count))
+                .collect::<Vec<_>>(),
+            [
+                (WarningKind::MissingActivation, 10),
+                (WarningKind::MissingDelta, 1)
+            ]
+        );
+        assert_eq!(
+            summaries[0].examples,
+            [0x300, 0x301, 0x302].map(Uuid::from_u128)
+        );
+    }
+}